@@ -0,0 +1,366 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use data_encoding::HEXLOWER;
+
+use hickory_proto::{
+    op::{Message, ResponseCode},
+    rr::{DNSClass, RData, Record, RecordType},
+};
+use hickory_resolver::Name;
+
+use crate::dns::serde::DnssecMode;
+
+/// Identifies a cached response: the question that was asked, who it was
+/// asked of, the DO and CD bits, which change what comes back, and how it
+/// was validated, so that a response let through without validation never
+/// answers a query that demands it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub name: Name,
+    pub qtype: RecordType,
+    pub class: DNSClass,
+    pub server: SocketAddr,
+    pub dnssec_ok: bool,
+    pub checking_disabled: bool,
+    pub dnssec_mode: DnssecMode,
+}
+
+impl CacheKey {
+    pub fn new(
+        query: &hickory_proto::op::Query,
+        server: SocketAddr,
+        dnssec_ok: bool,
+        checking_disabled: bool,
+        dnssec_mode: DnssecMode,
+    ) -> Self {
+        Self {
+            // names are compared case insensitively
+            name: query.name().to_lowercase(),
+            qtype: query.query_type(),
+            class: query.query_class(),
+            server,
+            dnssec_ok,
+            checking_disabled,
+            dnssec_mode,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    message: Message,
+    inserted: Instant,
+    ttl: Duration,
+}
+
+impl CacheEntry {
+    /// Serializes the entry as a single line of the cache file:
+    ///
+    /// ```text
+    /// <inserted, unix seconds> <ttl seconds> <server> <DNSSEC mode> <wire format message in hex>
+    /// ```
+    fn to_line(&self, key: &CacheKey) -> Option<String> {
+        let inserted = SystemTime::now()
+            .checked_sub(self.inserted.elapsed())?
+            .duration_since(UNIX_EPOCH)
+            .ok()?;
+
+        Some(format!(
+            "{} {} {} {} {}",
+            inserted.as_secs(),
+            self.ttl.as_secs(),
+            key.server,
+            key.dnssec_mode,
+            HEXLOWER.encode(&self.message.to_vec().ok()?)
+        ))
+    }
+
+    /// Parses a line of the cache file, returning `None` if it is malformed
+    /// or has already expired.
+    fn from_line(line: &str) -> Option<(CacheKey, Self)> {
+        let mut fields = line.split_whitespace();
+        let inserted = UNIX_EPOCH + Duration::from_secs(fields.next()?.parse().ok()?);
+        let ttl = Duration::from_secs(fields.next()?.parse().ok()?);
+        let server: SocketAddr = fields.next()?.parse().ok()?;
+        let dnssec_mode: DnssecMode = fields.next()?.parse().ok()?;
+        let message = Message::from_vec(&HEXLOWER.decode(fields.next()?.as_bytes()).ok()?).ok()?;
+
+        let age = SystemTime::now().duration_since(inserted).ok()?;
+
+        if age >= ttl {
+            return None;
+        }
+
+        // servers copy the DO and CD bits of the query into their response
+        let dnssec_ok = message
+            .extensions()
+            .as_ref()
+            .is_some_and(|edns| edns.dnssec_ok());
+        let key = CacheKey::new(
+            message.queries().first()?,
+            server,
+            dnssec_ok,
+            message.checking_disabled(),
+            dnssec_mode,
+        );
+
+        Some((
+            key,
+            Self {
+                message,
+                inserted: Instant::now().checked_sub(age)?,
+                ttl,
+            },
+        ))
+    }
+}
+
+/// In-memory response cache that lives as long as the plugin process and
+/// honors the TTLs of the records it holds. It can optionally be backed by a
+/// file so that it survives plugin restarts.
+#[derive(Debug, Default)]
+pub struct Cache {
+    entries: Mutex<HashMap<CacheKey, CacheEntry>>,
+    file: Mutex<Option<PathBuf>>,
+}
+
+/// A snapshot of a live cache entry.
+#[derive(Debug)]
+pub struct CacheEntryInfo {
+    pub key: CacheKey,
+    pub remaining: Duration,
+    pub message: Message,
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Looks up a response. The records' TTLs are reduced by the time spent
+    /// in the cache, just as a caching resolver would do. Expired entries are
+    /// evicted.
+    pub fn get(&self, key: &CacheKey) -> Option<Message> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+        let age = entry.inserted.elapsed();
+
+        if age >= entry.ttl {
+            tracing::debug!(cache.phase = "expired", ?key);
+            entries.remove(key);
+            return None;
+        }
+
+        tracing::debug!(cache.phase = "hit", ?key);
+
+        let mut message = entry.message.clone();
+        let age = age.as_secs() as u32;
+
+        let age_records = |records: &mut Vec<Record>| {
+            for record in records.iter_mut() {
+                record.set_ttl(record.ttl().saturating_sub(age));
+            }
+        };
+
+        age_records(message.answers_mut());
+        age_records(message.name_servers_mut());
+        age_records(message.additionals_mut());
+
+        Some(message)
+    }
+
+    /// Caches a response for as long as the lowest TTL of its answers, or
+    /// for negative responses, as long as the SOA in the authority section
+    /// says the negative answer may be cached, raised to `min_ttl` and
+    /// lowered to `max_ttl` if given. Responses that are neither are not
+    /// cached.
+    pub fn insert(
+        &self,
+        key: CacheKey,
+        message: &Message,
+        min_ttl: Option<Duration>,
+        max_ttl: Option<Duration>,
+    ) {
+        let Some(mut ttl) = message
+            .answers()
+            .iter()
+            .map(|record| record.ttl())
+            .min()
+            .or_else(|| negative_ttl(message))
+        else {
+            return;
+        };
+
+        let secs = |bound: Duration| u32::try_from(bound.as_secs()).unwrap_or(u32::MAX);
+        if let Some(min_ttl) = min_ttl {
+            ttl = ttl.max(secs(min_ttl));
+        }
+        if let Some(max_ttl) = max_ttl {
+            ttl = ttl.min(secs(max_ttl));
+        }
+
+        if ttl == 0 {
+            return;
+        }
+
+        tracing::debug!(cache.phase = "insert", ?key, cache.ttl = ttl);
+
+        let entry = CacheEntry {
+            message: message.clone(),
+            inserted: Instant::now(),
+            ttl: Duration::from_secs(ttl.into()),
+        };
+
+        // the file is an append-only log; later lines for the same key win
+        // when it is loaded again
+        if let Some(path) = &*self.file.lock().unwrap() {
+            let appended = entry.to_line(&key).map_or(Ok(()), |line| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| writeln!(file, "{line}"))
+            });
+
+            if let Err(err) = appended {
+                tracing::warn!(cache.phase = "persist", cache.file = ?path, cache.error = %err);
+            }
+        }
+
+        self.entries.lock().unwrap().insert(key, entry);
+    }
+
+    /// Backs the cache with the given file, loading any unexpired entries it
+    /// already holds. Does nothing if the cache is already backed by this
+    /// file.
+    pub fn persist_to(&self, path: &Path) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+
+        if file.as_deref() == Some(path) {
+            return Ok(());
+        }
+
+        match File::open(path) {
+            Ok(existing) => {
+                let mut entries = self.entries.lock().unwrap();
+
+                for line in BufReader::new(existing).lines() {
+                    if let Some((key, entry)) = CacheEntry::from_line(&line?) {
+                        entries.insert(key, entry);
+                    }
+                }
+
+                tracing::debug!(cache.phase = "load", cache.file = ?path, cache.size = entries.len());
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        *file = Some(path.into());
+        drop(file);
+
+        // compact the log, dropping expired and superseded lines
+        self.rewrite()
+    }
+
+    /// Lists the entries that have not yet expired.
+    pub fn entries(&self) -> Vec<CacheEntryInfo> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.inserted.elapsed() < entry.ttl);
+
+        entries
+            .iter()
+            .map(|(key, entry)| CacheEntryInfo {
+                key: key.clone(),
+                remaining: entry.ttl - entry.inserted.elapsed().min(entry.ttl),
+                message: entry.message.clone(),
+            })
+            .collect()
+    }
+
+    /// Removes every entry for the given name, or all entries if no name is
+    /// given. Returns how many entries were removed.
+    pub fn clear(&self, name: Option<&Name>) -> io::Result<usize> {
+        let removed = {
+            let mut entries = self.entries.lock().unwrap();
+            let before = entries.len();
+
+            match name {
+                Some(name) => entries.retain(|key, _| key.name != name.to_lowercase()),
+                None => entries.clear(),
+            }
+
+            before - entries.len()
+        };
+
+        self.rewrite()?;
+
+        Ok(removed)
+    }
+
+    /// Rewrites the backing file, if any, with the current live entries.
+    fn rewrite(&self) -> io::Result<()> {
+        let Some(path) = self.file.lock().unwrap().clone() else {
+            return Ok(());
+        };
+
+        let entries = self.entries.lock().unwrap();
+        let mut file = File::create(path)?;
+
+        for (key, entry) in entries.iter() {
+            if entry.inserted.elapsed() >= entry.ttl {
+                continue;
+            }
+
+            if let Some(line) = entry.to_line(key) {
+                writeln!(file, "{line}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How long a negative response (NXDOMAIN, or NOERROR without any answers,
+/// i.e. NODATA) may be cached, which per [RFC 2308 section
+/// 5](https://www.rfc-editor.org/rfc/rfc2308#section-5) is the lesser of the
+/// TTL of the SOA record in the authority section and its MINIMUM field.
+/// Returns `None` for positive responses, or if there is no SOA.
+pub fn negative_ttl(message: &Message) -> Option<u32> {
+    negative_kind(message)?;
+
+    let soa = negative_soa(message)?;
+
+    match soa.data() {
+        Some(RData::SOA(rdata)) => Some(soa.ttl().min(rdata.minimum())),
+        _ => None,
+    }
+}
+
+/// What kind of negative response this is, if any: `NXDOMAIN` if the name
+/// does not exist, or `NODATA` if it does but has no records of the type
+/// asked for.
+pub fn negative_kind(message: &Message) -> Option<&'static str> {
+    match message.response_code() {
+        ResponseCode::NXDomain => Some("NXDOMAIN"),
+        ResponseCode::NoError if message.answers().is_empty() => Some("NODATA"),
+        _ => None,
+    }
+}
+
+/// The SOA record in the authority section, which comes with negative
+/// responses.
+pub fn negative_soa(message: &Message) -> Option<&Record> {
+    message
+        .name_servers()
+        .iter()
+        .find(|record| matches!(record.data(), Some(RData::SOA(_))))
+}
@@ -1,4 +1,10 @@
-use std::{pin::Pin, sync::Arc, time::Duration};
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use futures_util::{future, Stream, StreamExt};
 use hickory_client::client::{AsyncClient, AsyncDnssecClient};
@@ -6,22 +12,39 @@ use hickory_proto::{
     error::{ProtoError, ProtoErrorKind},
     h2::HttpsClientStreamBuilder,
     iocompat::AsyncIoTokioAsStd,
-    op::NoopMessageFinalizer,
+    op::{Message, NoopMessageFinalizer},
     quic::QuicClientStream,
+    rr::{
+        dnssec::{PublicKeyBuf, TrustAnchor},
+        Name, Record, RecordType,
+    },
     tcp::TcpClientStream,
     udp::UdpClientStream,
-    xfer::DnsResponse,
+    xfer::{DnsRequest, DnsRequestOptions, DnsResponse, FirstAnswer},
     DnsHandle, DnsMultiplexer,
 };
 use hickory_resolver::config::Protocol;
-use nu_protocol::{LabeledError, Span};
+use nu_protocol::LabeledError;
 use rustls::{OwnedTrustAnchor, RootCertStore};
-use tokio::{net::UdpSocket, task::JoinSet};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+    task::JoinSet,
+};
 
-use super::{config::Config, serde::DnssecMode};
+use super::{
+    config::Config,
+    constants, dnssec,
+    error::DnsError,
+    querylog,
+    serde::{self, DnssecMode},
+    stats::{Outcome, Stats},
+    transport::Transport,
+    wire,
+};
 
 type DnsHandleResponse =
-    Pin<Box<(dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send + 'static)>>;
+    Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send + 'static>>;
 
 /// Client struct that wraps both a secure and non-secure client. This is a hack
 /// to allow falling back to unverified responses when the record is not signed.
@@ -34,22 +57,73 @@ type DnsHandleResponse =
 pub struct DnsClient {
     async_client: Option<AsyncClient>,
     dnssec_client: Option<AsyncDnssecClient>,
+    transport: Option<Arc<dyn Transport>>,
+    stats: Arc<Stats>,
 }
 
 type TokioTcpConnect = AsyncIoTokioAsStd<tokio::net::TcpStream>;
 
+/// Loads the root trust anchors saved by `dns dnssec update-anchors`, if the
+/// config names a file for them. Until the file has been written, the anchors
+/// built into hickory are used.
+fn load_trust_anchor(config: &Config) -> Result<Option<TrustAnchor>, LabeledError> {
+    let Some(path) = &config.trust_anchors else {
+        return Ok(None);
+    };
+
+    let contents = match std::fs::read_to_string(&path.item) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            tracing::debug!(trust_anchors.phase = "missing", trust_anchors.path = ?path.item);
+            return Ok(None);
+        }
+        Err(err) => {
+            return Err(LabeledError::new("invalid trust anchors").with_label(
+                format!("could not read {}: {err}", path.item.display()),
+                path.span,
+            ))
+        }
+    };
+
+    let mut trust_anchor = TrustAnchor::new();
+    for key in dnssec::parse_anchor_file(&contents, path.span)? {
+        trust_anchor.insert_trust_anchor(&PublicKeyBuf::new(key.public_key));
+    }
+
+    Ok(Some(trust_anchor))
+}
+
+/// A TLS client config that trusts the web PKI's root certificates.
+pub(crate) fn tls_client_config() -> rustls::ClientConfig {
+    let mut root_store = RootCertStore::empty();
+    root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+
+    rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store)
+        .with_no_client_auth()
+}
+
 impl DnsClient {
     pub async fn new(
         config: &Config,
+        stats: Arc<Stats>,
     ) -> Result<(Self, JoinSet<Result<(), ProtoError>>), LabeledError> {
-        let connect_err = |err| {
-            LabeledError::new("connection error").with_label(
-                format!("Error creating client connection: {}", err),
-                Span::unknown(),
-            )
+        let connect_err = |err: ProtoError| -> LabeledError {
+            DnsError::from_proto(&err, config.server.item, config.server.span).into()
         };
 
         let mut join_set = JoinSet::new();
+        let trust_anchor = match config.dnssec_mode.item {
+            DnssecMode::None => None,
+            _ => load_trust_anchor(config)?,
+        };
 
         macro_rules! make_clients {
             ($conn:expr) => {{
@@ -63,9 +137,11 @@ impl DnsClient {
                 };
 
                 let dnssec_client = if config.dnssec_mode.item != DnssecMode::None {
-                    let (dnssec_client, bg) = AsyncDnssecClient::connect($conn)
-                        .await
-                        .map_err(connect_err)?;
+                    let mut builder = AsyncDnssecClient::builder($conn);
+                    if let Some(trust_anchor) = &trust_anchor {
+                        builder = builder.trust_anchor(trust_anchor.clone());
+                    }
+                    let (dnssec_client, bg) = builder.build().await.map_err(connect_err)?;
                     join_set.spawn(bg);
                     Some(dnssec_client)
                 } else {
@@ -103,19 +179,7 @@ impl DnsClient {
                 })
             }
             proto @ (Protocol::Https | Protocol::Tls | Protocol::Quic) => {
-                let mut root_store = RootCertStore::empty();
-                root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
-                    OwnedTrustAnchor::from_subject_spki_name_constraints(
-                        ta.subject,
-                        ta.spki,
-                        ta.name_constraints,
-                    )
-                }));
-
-                let client_config = rustls::ClientConfig::builder()
-                    .with_safe_defaults()
-                    .with_root_certificates(root_store)
-                    .with_no_client_auth();
+                let client_config = tls_client_config();
 
                 match proto {
                     Protocol::Tls => {
@@ -172,10 +236,418 @@ impl DnsClient {
             Self {
                 async_client,
                 dnssec_client,
+                transport: None,
+                stats,
             },
             join_set,
         ))
     }
+
+    /// A client that sends everything over the given transport. No DNSSEC
+    /// validation is performed on the responses.
+    pub fn with_transport(transport: Arc<dyn Transport>, stats: Arc<Stats>) -> Self {
+        Self {
+            async_client: None,
+            dnssec_client: None,
+            transport: Some(transport),
+            stats,
+        }
+    }
+}
+
+/// Identifies which pooled connection a config calls for.
+#[derive(Clone, Debug, PartialEq)]
+struct PoolKey {
+    server: SocketAddr,
+    protocol: Protocol,
+    dns_name: Option<String>,
+    dnssec_mode: DnssecMode,
+    trust_anchors: Option<PathBuf>,
+}
+
+impl PoolKey {
+    fn new(config: &Config) -> Self {
+        Self {
+            server: config.server.item,
+            protocol: config.protocol.item,
+            dns_name: config.dns_name.as_ref().map(|name| name.item.clone()),
+            dnssec_mode: config.dnssec_mode.item.clone(),
+            trust_anchors: config.trust_anchors.as_ref().map(|path| path.item.clone()),
+        }
+    }
+}
+
+type PooledClient = (DnsClient, JoinSet<Result<(), ProtoError>>);
+
+/// Open connections to nameservers, one per server and protocol, kept for as
+/// long as the plugin process lives. This saves redoing the TCP, TLS, or
+/// QUIC handshakes on every command invocation.
+#[derive(Default)]
+pub struct ClientPool {
+    // there will only ever be a handful of servers, so a linear scan is fine
+    clients: tokio::sync::Mutex<Vec<(PoolKey, PooledClient)>>,
+    transport: Option<Arc<dyn Transport>>,
+    pub stats: Arc<Stats>,
+}
+
+impl ClientPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A pool whose clients all send over the given transport rather than
+    /// connecting to anything.
+    pub fn with_transport(transport: Arc<dyn Transport>) -> Self {
+        Self {
+            transport: Some(transport),
+            ..Self::default()
+        }
+    }
+
+    /// Closes every pooled connection, so that the next command connects
+    /// afresh, e.g. to pick up new trust anchors.
+    pub async fn clear(&self) {
+        self.clients.lock().await.clear();
+    }
+
+    /// Returns a client for the server and protocol in the given config,
+    /// connecting if there is no open connection yet. Connecting gives up
+    /// after the configured timeout.
+    pub async fn get(&self, config: &Config) -> Result<DnsClient, LabeledError> {
+        if let Some(transport) = &self.transport {
+            return Ok(DnsClient::with_transport(
+                transport.clone(),
+                self.stats.clone(),
+            ));
+        }
+
+        let key = PoolKey::new(config);
+        let mut clients = self.clients.lock().await;
+
+        if let Some(idx) = clients.iter().position(|(pooled, _)| *pooled == key) {
+            let (_, (client, bg)) = &mut clients[idx];
+
+            // the background tasks exit when the connection is closed, e.g.
+            // by the server timing out an idle TCP connection
+            if bg.try_join_next().is_none() {
+                tracing::debug!(client.phase = "reuse", client.addr = ?key.server, client.protocol = ?key.protocol);
+                return Ok(client.clone());
+            }
+
+            tracing::debug!(client.phase = "reconnect", client.addr = ?key.server, client.protocol = ?key.protocol);
+            clients.swap_remove(idx);
+        }
+
+        let (client, bg) = tokio::time::timeout(
+            config.timeout.item,
+            DnsClient::new(config, self.stats.clone()),
+        )
+        .await
+        .map_err(|_| DnsError::Timeout {
+            server: config.server.item,
+            span: config.server.span,
+        })??;
+        tracing::info!(client.addr = ?key.server, client.protocol = ?key.protocol);
+
+        clients.push((key, (client.clone(), bg)));
+
+        Ok(client)
+    }
+}
+
+impl DnsClient {
+    /// Sends a fully formed request message and waits for the first answer,
+    /// giving up after the configured timeout. Every exchange is counted in
+    /// the session stats.
+    pub async fn send_message(
+        &self,
+        config: &Config,
+        message: Message,
+    ) -> Result<DnsResponse, ProtoError> {
+        let bytes = message.to_vec().unwrap_or_default();
+        let sent = Instant::now();
+
+        if config.verbose.item {
+            log_message("sent", config, &message, &bytes);
+        }
+
+        let logged = config.log_file.is_some().then(|| message.clone());
+        let resp = tokio::time::timeout(config.timeout.item, self.exchange(config, message))
+            .await
+            .unwrap_or_else(|_| Err(ProtoErrorKind::Timeout.into()));
+
+        let outcome = match &resp {
+            Ok(resp) => Outcome::Response {
+                rcode: resp.response_code(),
+                size: resp.as_buffer().len(),
+                rtt: sent.elapsed(),
+            },
+            Err(err) if matches!(err.kind(), ProtoErrorKind::Timeout) => Outcome::Timeout,
+            Err(_) => Outcome::Error,
+        };
+
+        self.stats.record(config.server.item, bytes.len(), outcome);
+
+        if let Some(request) = &logged {
+            querylog::append(config, request, &resp, sent.elapsed());
+        }
+
+        if config.verbose.item {
+            if let Ok(resp) = &resp {
+                log_message("received", config, resp, resp.as_buffer());
+            }
+        }
+
+        resp
+    }
+
+    /// Asks one question, built from the config like any other, and returns
+    /// the records in the answer section, for commands that need nothing else
+    /// from the response.
+    pub async fn answers(
+        &self,
+        config: &Config,
+        name: &Name,
+        qtype: RecordType,
+    ) -> Result<Vec<Record>, ProtoError> {
+        let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+        query.set_query_class(config.class.item);
+
+        let request = serde::Query(query).into_message(config);
+        config.throttle().await;
+
+        Ok(self
+            .send_message(config, request)
+            .await?
+            .into_message()
+            .take_answers())
+    }
+
+    async fn exchange(&self, config: &Config, message: Message) -> Result<DnsResponse, ProtoError> {
+        if let Some(transport) = &self.transport {
+            let buffer = transport
+                .exchange(config.server.item, message.to_vec()?)
+                .await?;
+            return Ok(DnsResponse::new(
+                wire::parse_response(&buffer, config.lenient.item)?,
+                buffer,
+            ));
+        }
+
+        // hickory's own exchanges drop responses that don't parse, so
+        // salvaging them means reading them off the socket here
+        if config.id.is_some() || config.lenient.item {
+            return exchange_raw(
+                config.server.item,
+                config.protocol.item,
+                message,
+                config.lenient.item,
+            )
+            .await;
+        }
+
+        let mut options = DnsRequestOptions::default();
+        options.use_edns = message.extensions().is_some();
+        options.recursion_desired = message.recursion_desired();
+
+        self.send(DnsRequest::new(message, options))
+            .first_answer()
+            .await
+    }
+}
+
+impl DnsClient {
+    /// Asks for a zone transfer over a fresh TCP connection, whatever the
+    /// configured protocol, and yields each message of the response as soon
+    /// as it arrives. The stream ends when the server closes the connection
+    /// or after the first error; it is up to the caller to stop reading once
+    /// the transfer is complete. Only the first message is counted in the
+    /// session stats.
+    pub async fn zone_transfer(
+        &self,
+        config: &Config,
+        message: Message,
+    ) -> Result<impl Stream<Item = Result<Message, ProtoError>>, ProtoError> {
+        let request = message.to_vec()?;
+        let len = u16::try_from(request.len())
+            .map_err(|_| ProtoError::from("message too long for TCP"))?;
+
+        if config.verbose.item {
+            log_message("sent", config, &message, &request);
+        }
+
+        let mut stream =
+            tokio::time::timeout(config.timeout.item, TcpStream::connect(config.server.item))
+                .await
+                .map_err(|_| ProtoError::from(ProtoErrorKind::Timeout))??;
+
+        stream.write_all(&len.to_be_bytes()).await?;
+        stream.write_all(&request).await?;
+
+        let state = Transfer {
+            stream,
+            config: config.clone(),
+            stats: self.stats.clone(),
+            first: Some((request.len(), Instant::now())),
+            done: false,
+        };
+
+        Ok(futures_util::stream::unfold(
+            state,
+            |mut state| async move {
+                if state.done {
+                    return None;
+                }
+
+                let read = tokio::time::timeout(state.config.timeout.item, state.read())
+                    .await
+                    .unwrap_or_else(|_| Err(ProtoErrorKind::Timeout.into()));
+
+                let resp = match read {
+                    Ok(None) => return None,
+                    Ok(Some(buffer)) => Message::from_vec(&buffer).map(|message| (message, buffer)),
+                    Err(err) => Err(err),
+                };
+
+                if let Some((sent, at)) = state.first.take() {
+                    let outcome = match &resp {
+                        Ok((message, buffer)) => Outcome::Response {
+                            rcode: message.response_code(),
+                            size: buffer.len(),
+                            rtt: at.elapsed(),
+                        },
+                        Err(err) if matches!(err.kind(), ProtoErrorKind::Timeout) => {
+                            Outcome::Timeout
+                        }
+                        Err(_) => Outcome::Error,
+                    };
+
+                    state.stats.record(state.config.server.item, sent, outcome);
+                }
+
+                if let Ok((message, buffer)) = &resp {
+                    if state.config.verbose.item {
+                        log_message("received", &state.config, message, buffer);
+                    }
+                }
+
+                state.done = resp.is_err();
+
+                Some((resp.map(|(message, _)| message), state))
+            },
+        ))
+    }
+}
+
+/// The connection a zone transfer is read from.
+struct Transfer {
+    stream: TcpStream,
+    config: Config,
+    stats: Arc<Stats>,
+    /// The size of the request and when it was sent, until the first message
+    /// of the response arrives.
+    first: Option<(usize, Instant)>,
+    done: bool,
+}
+
+impl Transfer {
+    /// Reads the next length-prefixed message, or `None` if the server
+    /// closed the connection in between messages.
+    async fn read(&mut self) -> Result<Option<Vec<u8>>, ProtoError> {
+        let len = match self.stream.read_u16().await {
+            Ok(len) => len,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut buffer = vec![0; len as usize];
+        self.stream.read_exact(&mut buffer).await?;
+
+        Ok(Some(buffer))
+    }
+}
+
+/// Logs a message as `dig` would render it, followed by a hex dump of its
+/// wire format with `--debug`.
+fn log_message(direction: &str, config: &Config, message: &Message, bytes: &[u8]) {
+    let hex = if config.debug.item {
+        serde::util::hexdump(bytes)
+    } else {
+        String::new()
+    };
+
+    tracing::info!(
+        target: constants::WIRE_LOG_TARGET,
+        direction,
+        server = %config.server.item,
+        protocol = %config.protocol.item,
+        size = bytes.len(),
+        "\n{message}{hex}"
+    );
+}
+
+/// Sends a message over a fresh UDP or TCP connection without any of the
+/// hickory machinery in between. The hickory transports all assign their own
+/// random message ID, so this is the only way to send a message exactly as
+/// given. No DNSSEC validation is performed on the response.
+pub async fn exchange_raw(
+    server: SocketAddr,
+    protocol: Protocol,
+    message: Message,
+    lenient: bool,
+) -> Result<DnsResponse, ProtoError> {
+    let request = message.to_vec()?;
+
+    let buffer = match protocol {
+        Protocol::Udp => {
+            let bind_addr: SocketAddr = match server {
+                SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+                SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+            };
+
+            let socket = UdpSocket::bind(bind_addr).await?;
+            socket.connect(server).await?;
+            socket.send(&request).await?;
+
+            let mut buffer = vec![0; u16::MAX as usize];
+
+            // ignore any stray datagrams that do not belong to this exchange
+            loop {
+                let len = socket.recv(&mut buffer).await?;
+
+                match wire::parse_response(&buffer[..len], lenient) {
+                    Ok(resp) if resp.id() == message.id() => {
+                        buffer.truncate(len);
+                        break buffer;
+                    }
+                    _ => continue,
+                }
+            }
+        }
+        Protocol::Tcp => {
+            let mut stream = TcpStream::connect(server).await?;
+            let len = u16::try_from(request.len())
+                .map_err(|_| ProtoError::from("message too long for TCP"))?;
+
+            stream.write_all(&len.to_be_bytes()).await?;
+            stream.write_all(&request).await?;
+
+            let len = stream.read_u16().await?;
+            let mut buffer = vec![0; len as usize];
+            stream.read_exact(&mut buffer).await?;
+            buffer
+        }
+        proto => {
+            return Err(ProtoError::from(format!(
+                "raw exchange not supported over {proto}"
+            )))
+        }
+    };
+
+    Ok(DnsResponse::new(
+        wire::parse_response(&buffer, lenient)?,
+        buffer,
+    ))
 }
 
 impl DnsHandle for DnsClient {
@@ -189,6 +661,8 @@ impl DnsHandle for DnsClient {
         let request = request.into();
 
         match (&self.async_client, &self.dnssec_client) {
+            // only clients with a transport have neither, and those never
+            // go through here
             (None, None) => unreachable!(),
             (Some(async_client), None) => Box::pin(async_client.send(request)),
             (None, Some(dnssec_client)) => Box::pin(dnssec_client.send(request)),
@@ -0,0 +1,211 @@
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{RData, Record, RecordType},
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, ListStream, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        config::Config,
+        constants,
+        error::DnsError,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsAxfr;
+
+impl DnsAxfr {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let metadata = config.metadata(self.name());
+        let zone: Spanned<String> = call.req(0)?;
+        let span = zone.span;
+        let mut zone = Name::from_utf8(&zone.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), zone.span)
+        })?;
+        zone.set_fqdn(true);
+
+        let client = plugin.dns_client(&config).await?;
+
+        // a small buffer, so that a large zone is held back by a slow
+        // consumer rather than piling up in memory
+        let (row_tx, mut row_rx) = mpsc::channel(config.tasks.item);
+
+        let transfer_config = config.clone();
+        plugin.spawn(async move {
+            if let Err(err) = transfer(&transfer_config, zone, &client, span, &row_tx).await {
+                let _ = row_tx.send(Err(err)).await;
+            }
+
+            Ok(())
+        });
+
+        Ok(PipelineData::ListStream(
+            ListStream::new(
+                std::iter::from_fn(move || {
+                    tokio::task::block_in_place(|| {
+                        row_rx.blocking_recv().map(|row| {
+                            row.and_then(|record| serde::Record(record).into_value(&config))
+                                .unwrap_or_else(|err| Value::error(err.into(), Span::unknown()))
+                        })
+                    })
+                }),
+                call.head,
+                engine.signals().clone(),
+            ),
+            metadata,
+        ))
+    }
+}
+
+/// Transfers the zone, sending each record on as soon as the message it came
+/// in has arrived. The transfer starts with the zone's SOA record and ends when
+/// that same SOA comes around again; the closing copy is not sent on.
+pub(crate) async fn transfer(
+    config: &Config,
+    zone: Name,
+    client: &DnsClient,
+    span: Span,
+    row_tx: &mpsc::Sender<Result<Record, LabeledError>>,
+) -> Result<(), LabeledError> {
+    let mut query = hickory_proto::op::Query::query(zone.clone(), RecordType::AXFR);
+    query.set_query_class(config.class.item);
+
+    let mut request = serde::Query(query).into_message(config);
+    request.set_recursion_desired(false);
+
+    let messages = client
+        .zone_transfer(config, request)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+    let mut messages = std::pin::pin!(messages);
+
+    let mut start: Option<hickory_proto::rr::rdata::SOA> = None;
+
+    while let Some(message) = futures_util::StreamExt::next(&mut messages).await {
+        let message =
+            message.map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+        if message.response_code() != ResponseCode::NoError {
+            return Err(LabeledError::new("zone transfer failed").with_label(
+                format!(
+                    "{} answered {} for {zone}",
+                    config.server.item,
+                    RCode(message.response_code()).mnemonic()
+                ),
+                span,
+            ));
+        }
+
+        for record in message.into_parts().answers {
+            match (&start, record.data()) {
+                (None, Some(RData::SOA(soa))) if *record.name() == zone => {
+                    start = Some(soa.clone());
+                }
+                (None, _) => {
+                    return Err(LabeledError::new("zone transfer failed").with_label(
+                        format!("the transfer of {zone} did not start with its SOA record"),
+                        span,
+                    ))
+                }
+                (Some(first), Some(RData::SOA(soa)))
+                    if *record.name() == zone && soa.serial() == first.serial() =>
+                {
+                    tracing::debug!(axfr.phase = "finish", axfr.serial = soa.serial());
+                    return Ok(());
+                }
+                _ => {}
+            }
+
+            // the receiving end goes away when the user stops consuming the
+            // stream, which is our cue to stop
+            if row_tx.send(Ok(record)).await.is_err() {
+                tracing::debug!(axfr.phase = "exit");
+                return Ok(());
+            }
+        }
+    }
+
+    Err(LabeledError::new("zone transfer failed").with_label(
+        format!("the connection closed before the transfer of {zone} was complete"),
+        span,
+    ))
+}
+
+impl PluginCommand for DnsAxfr {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::AXFR
+    }
+
+    fn description(&self) -> &str {
+        "Transfer a whole zone from its nameserver (AXFR)"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Records are streamed as each message of the transfer arrives, so even very large zones are never held in memory all at once. The transfer always goes over TCP. The zone's SOA record comes first; the copy of it that marks the end of the transfer is left out. Most public nameservers refuse transfers, so point --server at one that allows them."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .required(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone to transfer",
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Zone class",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns axfr --server 192.0.2.53 example.com | save example.com.nuon",
+                description: "transfer a zone from a nameserver that allows it",
+                result: None,
+            },
+            Example {
+                example: "dns axfr --server 192.0.2.53 example.com | where type == A | first 10",
+                description: "stop the transfer once enough records have arrived",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "network", "axfr", "zone", "transfer", "dump"]
+    }
+}
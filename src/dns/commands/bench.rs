@@ -0,0 +1,300 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use futures_util::{future, StreamExt};
+use hickory_proto::error::ProtoErrorKind;
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsBench;
+
+/// The outcome of a single query fired during the benchmark.
+enum Probe {
+    Response(Duration, RCode),
+    Error,
+    Timeout,
+}
+
+impl DnsBench {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let name: Spanned<String> = call.req(0)?;
+        let name = Name::from_utf8(&name.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), name.span)
+        })?;
+
+        let count = match call.get_flag::<Spanned<i64>>(constants::flags::COUNT)? {
+            Some(count) => Some(usize::try_from(count.item).map_err(|err| {
+                LabeledError::new("invalid input")
+                    .with_label(format!("should be positive int: {err}"), count.span)
+            })?),
+            None => None,
+        };
+
+        let duration = match call.get_flag_value(constants::flags::DURATION) {
+            Some(val @ Value::Duration { .. }) => Some(Duration::from_nanos(
+                val.as_duration()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), val.span())
+                })?,
+            )),
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("benchmark duration should be a duration", val.span()))
+            }
+            None => None,
+        };
+
+        // with neither a count nor a duration, fire a fixed number of queries
+        let count = match (count, duration) {
+            (None, None) => Some(constants::config::default::BENCH_COUNT),
+            (count, _) => count,
+        };
+
+        let client = plugin.dns_client(&config).await?;
+
+        let signals = engine.signals().clone();
+        let start = Instant::now();
+        let deadline = duration.map(|duration| start + duration);
+
+        let probes: Vec<Probe> = futures_util::stream::iter(0..)
+            .take_while(|i| {
+                future::ready(
+                    count.is_none_or(|count| *i < count)
+                        && deadline.is_none_or(|deadline| Instant::now() < deadline)
+                        && !signals.interrupted(),
+                )
+            })
+            .map(|i| {
+                let qtypes = &config.qtypes.item;
+                let mut query =
+                    hickory_proto::op::Query::query(name.clone(), qtypes[i % qtypes.len()].item);
+                query.set_query_class(config.class.item);
+
+                let request = serde::Query(query).into_message(&config);
+                let client = client.clone();
+                let config = &config;
+
+                async move {
+                    config.throttle().await;
+                    let sent = Instant::now();
+
+                    match client.send_message(config, request).await {
+                        Ok(resp) => Probe::Response(sent.elapsed(), RCode(resp.response_code())),
+                        Err(err) if matches!(err.kind(), ProtoErrorKind::Timeout) => Probe::Timeout,
+                        Err(err) => {
+                            tracing::debug!(bench.error = ?err);
+                            Probe::Error
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(config.tasks.item)
+            .collect()
+            .await;
+
+        let elapsed = start.elapsed();
+
+        let mut latencies = Vec::with_capacity(probes.len());
+        let mut rcodes = BTreeMap::<String, i64>::new();
+        let (mut errors, mut timeouts) = (0, 0);
+
+        for probe in probes.iter() {
+            match probe {
+                Probe::Response(latency, rcode) => {
+                    latencies.push(*latency);
+                    *rcodes.entry(rcode.mnemonic()).or_default() += 1;
+                }
+                Probe::Error => errors += 1,
+                Probe::Timeout => timeouts += 1,
+            }
+        }
+
+        latencies.sort();
+
+        if call.has_flag(constants::flags::HISTOGRAM)? {
+            return Ok(PipelineData::Value(
+                serde::util::latency_histogram(&latencies).with_span(call.head),
+                config.metadata(self.name()),
+            ));
+        }
+
+        let duration_or_nothing = |latency: Option<Duration>| {
+            latency
+                .map(serde::util::std_to_duration)
+                .unwrap_or(Value::nothing(Span::unknown()))
+        };
+
+        let mean = (!latencies.is_empty())
+            .then(|| latencies.iter().sum::<Duration>() / latencies.len() as u32);
+
+        let latency = Value::record(
+            record![
+                "min"  => duration_or_nothing(latencies.first().copied()),
+                "mean" => duration_or_nothing(mean),
+                "p50"  => duration_or_nothing(percentile(&latencies, 50.0)),
+                "p90"  => duration_or_nothing(percentile(&latencies, 90.0)),
+                "p99"  => duration_or_nothing(percentile(&latencies, 99.0)),
+                "max"  => duration_or_nothing(latencies.last().copied()),
+            ],
+            Span::unknown(),
+        );
+
+        let rcodes = Value::record(
+            rcodes
+                .into_iter()
+                .map(|(rcode, count)| (rcode, Value::int(count, Span::unknown())))
+                .collect(),
+            Span::unknown(),
+        );
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "name"       => Value::string(config.display_name(&name), Span::unknown()),
+                    "server"     => Value::string(config.server.item.to_string(), Span::unknown()),
+                    "protocol"   => Value::string(config.protocol.item.to_string(), Span::unknown()),
+                    "queries"    => Value::int(probes.len() as i64, Span::unknown()),
+                    "successes"  => Value::int(latencies.len() as i64, Span::unknown()),
+                    "errors"     => Value::int(errors, Span::unknown()),
+                    "timeouts"   => Value::int(timeouts, Span::unknown()),
+                    "elapsed"    => serde::util::std_to_duration(elapsed),
+                    "throughput" => Value::float(
+                        probes.len() as f64 / elapsed.as_secs_f64(),
+                        Span::unknown(),
+                    ),
+                    "rcodes"     => rcodes,
+                    "latency"    => latency,
+                ],
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// Nearest-rank percentile of an already sorted list of samples.
+fn percentile(sorted: &[Duration], pct: f64) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+impl PluginCommand for DnsBench {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::BENCH
+    }
+
+    fn description(&self) -> &str {
+        "Benchmark a nameserver by firing many queries at it"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Sends queries for the given name, cycling through the query types, until either --count queries have been sent or --duration has elapsed, whichever comes first. Without either, sends 100 queries. Returns throughput in queries per second, success and error counts, counts per response code, and latency percentiles. With --histogram, returns instead a row per latency bucket, with the bucket's bounds and how many answers arrived within them, ready for charting."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .required(constants::flags::NAME, SyntaxShape::String, "DNS record name")
+            .named(constants::flags::TYPE, SyntaxShape::Any, "Query type(s)", Some('t'))
+            .named(constants::flags::CLASS, SyntaxShape::Any, "Query class", None)
+            .named(
+                constants::flags::COUNT,
+                SyntaxShape::Int,
+                format!(
+                    "Number of queries to send. Default: {}",
+                    constants::config::default::BENCH_COUNT
+                ),
+                None,
+            )
+            .named(
+                constants::flags::DURATION,
+                SyntaxShape::Duration,
+                "Keep sending queries for this long",
+                None,
+            )
+            .named(
+                constants::flags::TASKS,
+                SyntaxShape::Int,
+                format!(
+                    "Number of queries in flight at once. Please be mindful not to overwhelm your nameserver! Default: {}",
+                    constants::config::default::TASKS
+                ),
+                Some('j'),
+            )
+            .named(
+                constants::flags::RATE,
+                SyntaxShape::Number,
+                "Send at most this many queries per second, to go easy on nameservers you don't run",
+                None,
+            )
+            .switch(
+                constants::flags::HISTOGRAM,
+                "Return a table of latency buckets and their counts instead of the summary",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns bench --server 127.0.0.1 --type A example.com",
+                description: "send 100 A queries to a local resolver",
+                result: None,
+            },
+            Example {
+                example: "dns bench --server 127.0.0.1 --duration 10sec -j 32 example.com",
+                description: "send queries for 10 seconds with 32 in flight at once",
+                result: None,
+            },
+            Example {
+                example: "dns bench --server 127.0.0.1 --histogram example.com | where count > 0",
+                description: "count the answers in each latency bucket",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "network", "benchmark", "load", "latency", "dnsperf"]
+    }
+}
@@ -0,0 +1,407 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use futures_util::StreamExt;
+use hickory_proto::{
+    multicast::{MdnsQueryType, MdnsStream, MDNS_IPV4, MDNS_IPV6},
+    op::MessageType,
+    xfer::{DnsStreamHandle, SerialMessage},
+    BufDnsStreamHandle,
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, ListStream, PipelineData, Signature, Span, Spanned, SyntaxShape,
+    Value,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    dns::{
+        config, constants,
+        mdns::{self, Instance},
+        serde::util,
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsBrowse;
+
+/// The interface to browse on: for IPv4, by its address, and for IPv6, by
+/// its index.
+enum Interface {
+    V4(Option<Ipv4Addr>),
+    V6(u32),
+}
+
+impl DnsBrowse {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let service = match call.opt::<Spanned<String>>(0)? {
+            Some(service) => mdns::service_name(&service.item).map_err(|err| {
+                LabeledError::new("invalid service type").with_label(err, service.span)
+            })?,
+            None => Name::from_ascii(mdns::SERVICE_TYPES).expect("should be a valid name"),
+        };
+
+        let continuous = call.has_flag(constants::flags::CONTINUOUS)?;
+
+        let wait = match call.get_flag_value(constants::flags::WAIT) {
+            Some(val) if continuous => {
+                return Err(LabeledError::new("invalid config combination").with_label(
+                    "--continuous browses until interrupted, so there is nothing to wait for",
+                    val.span(),
+                ))
+            }
+            Some(val @ Value::Duration { .. }) => {
+                Duration::from_nanos(val.as_duration()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), val.span())
+                })?)
+            }
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("wait should be a duration", val.span()))
+            }
+            None => constants::config::default::BROWSE_WAIT,
+        };
+
+        let interface = interface(
+            call.get_flag_value(constants::flags::INTERFACE),
+            call.has_flag(constants::flags::IPV6)?,
+            call.head,
+        )?;
+
+        let (group, (stream, handle)) = match interface {
+            // joining the group, rather than just asking, is what hears the
+            // services that announce themselves or say goodbye later on
+            Interface::V4(addr) => (
+                *MDNS_IPV4,
+                MdnsStream::new_ipv4(MdnsQueryType::OneShotJoin, None, addr),
+            ),
+            Interface::V6(index) => (
+                *MDNS_IPV6,
+                MdnsStream::new_ipv6(MdnsQueryType::OneShotJoin, None, Some(index)),
+            ),
+        };
+        let stream = stream.await.map_err(|err| {
+            LabeledError::new("could not join multicast group")
+                .with_label(format!("could not join {group}: {err}"), call.head)
+        })?;
+
+        let deadline = (!continuous).then(|| Instant::now() + wait);
+        let (row_tx, mut row_rx) = mpsc::channel(16);
+
+        plugin.spawn(browse(stream, handle, group, service, deadline, row_tx));
+
+        Ok(PipelineData::ListStream(
+            ListStream::new(
+                std::iter::from_fn(move || {
+                    tokio::task::block_in_place(|| {
+                        row_rx.blocking_recv().map(|row| {
+                            row.unwrap_or_else(|err| Value::error(err.into(), Span::unknown()))
+                        })
+                    })
+                }),
+                call.head,
+                engine.signals().clone(),
+            ),
+            None,
+        ))
+    }
+}
+
+/// Reads `--interface`: an interface name or index, or, for IPv4, an
+/// address. IPv6 multicast has no default interface to fall back on.
+fn interface(value: Option<Value>, ipv6: bool, head: Span) -> Result<Interface, LabeledError> {
+    let Some(value) = value else {
+        return match ipv6 {
+            false => Ok(Interface::V4(None)),
+            true => Err(LabeledError::new("need interface").with_label(
+                "IPv6 multicast needs --interface to say which link to browse",
+                head,
+            )),
+        };
+    };
+
+    let span = value.span();
+    let invalid = |msg: String| LabeledError::new("invalid interface").with_label(msg, span);
+
+    let index = match value {
+        Value::Int { val, .. } => {
+            u32::try_from(val).map_err(|err| invalid(format!("invalid interface index: {err}")))?
+        }
+        Value::String { val, .. } => match (val.parse::<Ipv4Addr>(), ipv6) {
+            (Ok(addr), false) => return Ok(Interface::V4(Some(addr))),
+            (Ok(_), true) => {
+                return Err(invalid(
+                    "IPv6 interfaces are given by name or index, not address".into(),
+                ))
+            }
+            (Err(_), _) => config::zone_index(&val).map_err(invalid)?,
+        },
+        _ => return Err(invalid("should be an interface name or index".into())),
+    };
+
+    match ipv6 {
+        true => Ok(Interface::V6(index)),
+        false => Ok(Interface::V4(Some(
+            mdns::interface_ipv4(index).map_err(invalid)?,
+        ))),
+    }
+}
+
+/// Asks for the instances of a service and streams a row each time one
+/// appears or goes away, until the deadline, if any. Queries are repeated
+/// with the interval doubling each time (RFC 6762 section 5.2), and an
+/// instance that is neither refreshed nor said goodbye to by the time its
+/// TTL runs out is taken to be gone.
+async fn browse(
+    mut stream: MdnsStream,
+    mut handle: BufDnsStreamHandle,
+    group: SocketAddr,
+    service: Name,
+    deadline: Option<Instant>,
+    row_tx: mpsc::Sender<Result<Value, LabeledError>>,
+) -> Result<(), LabeledError> {
+    let query = mdns::query(&service).map_err(|err| {
+        LabeledError::new("could not encode query").with_label(err.to_string(), Span::unknown())
+    })?;
+
+    // what was last heard of each instance, and when that expires
+    let mut known = HashMap::<Name, (Instant, Instance)>::new();
+    let mut interval = Duration::from_secs(1);
+    let mut next_query = Instant::now();
+    let mut next_sweep = Instant::now() + Duration::from_secs(1);
+
+    loop {
+        let now = Instant::now();
+        if deadline.is_some_and(|deadline| deadline <= now) {
+            return Ok(());
+        }
+
+        let mut rows = Vec::new();
+
+        if next_query <= now {
+            handle
+                .send(SerialMessage::new(query.clone(), group))
+                .map_err(|err| {
+                    LabeledError::new("could not send query")
+                        .with_label(err.to_string(), Span::unknown())
+                })?;
+            next_query = now + interval;
+            interval = (interval * 2).min(constants::config::default::BROWSE_MAX_INTERVAL);
+        }
+
+        if next_sweep <= now {
+            let expired: Vec<Name> = known
+                .iter()
+                .filter(|(_, (expires, _))| *expires <= now)
+                .map(|(name, _)| name.clone())
+                .collect();
+            for name in expired {
+                if let Some((_, instance)) = known.remove(&name) {
+                    rows.push(row("removed", &service, &instance, None));
+                }
+            }
+            next_sweep = now + Duration::from_secs(1);
+        }
+
+        let wake = [Some(next_query), Some(next_sweep), deadline]
+            .into_iter()
+            .flatten()
+            .min()
+            .expect("there is always a next query");
+
+        match tokio::time::timeout_at(wake.into(), stream.next()).await {
+            Err(_) => {}
+            Ok(None) => return Ok(()),
+            Ok(Some(Err(err))) => tracing::debug!(mdns.error = %err),
+            Ok(Some(Ok(msg))) => {
+                let from = msg.addr();
+                let message = match msg.to_message() {
+                    Ok(message) if message.message_type() == MessageType::Response => message,
+                    // queries from others on the link, and the ones sent
+                    // here, come back through the group too
+                    Ok(_) => continue,
+                    Err(err) => {
+                        tracing::debug!(mdns.from = %from, mdns.error = %err);
+                        continue;
+                    }
+                };
+
+                let now = Instant::now();
+                for instance in mdns::instances(&message, &service) {
+                    match instance.ttl {
+                        // a goodbye only names the instance, so the row says
+                        // what was known of it
+                        0 => {
+                            if let Some((_, mut gone)) = known.remove(&instance.name) {
+                                gone.ttl = 0;
+                                rows.push(row("removed", &service, &gone, Some(from)));
+                            }
+                        }
+                        ttl => {
+                            let expires = now + Duration::from_secs(ttl.into());
+                            if !known.contains_key(&instance.name) {
+                                rows.push(row("added", &service, &instance, Some(from)));
+                            }
+                            known.insert(instance.name.clone(), (expires, instance));
+                        }
+                    }
+                }
+            }
+        }
+
+        // the receiving end goes away when the user stops consuming the
+        // stream, which is our cue to stop
+        for row in rows {
+            if row_tx.send(Ok(row)).await.is_err() {
+                tracing::debug!(browse.phase = "exit");
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn row(event: &str, service: &Name, instance: &Instance, from: Option<SocketAddr>) -> Value {
+    let name = |name: &Name| Value::string(util::escape_name(name, true), Span::unknown());
+    let nothing = || Value::nothing(Span::unknown());
+
+    Value::record(
+        record![
+            "event"     => Value::string(event, Span::unknown()),
+            "time"      => Value::date(chrono::Local::now().fixed_offset(), Span::unknown()),
+            "service"   => name(service),
+            "instance"  => Value::string(&instance.label, Span::unknown()),
+            "name"      => name(&instance.name),
+            "host"      => instance.host.as_ref().map_or_else(nothing, name),
+            "port"      => instance.port.map_or_else(nothing, |port| Value::int(port.into(), Span::unknown())),
+            "txt"       => Value::list(
+                instance
+                    .txt
+                    .iter()
+                    .map(|txt| Value::string(txt, Span::unknown()))
+                    .collect(),
+                Span::unknown(),
+            ),
+            "addresses" => Value::list(
+                instance
+                    .addrs
+                    .iter()
+                    .map(|addr| Value::string(addr.to_string(), Span::unknown()))
+                    .collect(),
+                Span::unknown(),
+            ),
+            "ttl"       => util::sec_to_duration(instance.ttl),
+            "from"      => from.map_or_else(nothing, |from| {
+                Value::string(from.ip().to_string(), Span::unknown())
+            }),
+        ],
+        Span::unknown(),
+    )
+}
+
+impl PluginCommand for DnsBrowse {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::BROWSE
+    }
+
+    fn description(&self) -> &str {
+        "Browse the DNS-SD services on the local link over multicast DNS"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks the local link over multicast DNS (RFC 6762) for the instances of a DNS-SD service type (RFC 6763), like _http._tcp, or, without one, for the service types on the link. Emits a row for each instance as it is found, with its host, port, TXT strings, and addresses where the response included them, and listens for --wait before stopping. With --continuous, it keeps listening, and asking again at growing intervals, until interrupted, and also emits a row when an instance goes away, either by saying goodbye or by not being refreshed before its TTL runs out. Queries go out on the default IPv4 interface, or the one given with --interface; --ipv6 browses over IPv6, which needs --interface."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        Signature::build(self.name())
+            .optional(
+                constants::flags::SERVICE,
+                SyntaxShape::String,
+                "Service type to browse, e.g. _http._tcp. Default: the service types on the link",
+            )
+            .named(
+                constants::flags::WAIT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to listen for responses. Default: {}sec",
+                    constants::config::default::BROWSE_WAIT.as_secs()
+                ),
+                Some('w'),
+            )
+            .named(
+                constants::flags::INTERFACE,
+                SyntaxShape::Any,
+                "Interface to browse on, by name or index, or, for IPv4, by address",
+                Some('i'),
+            )
+            .switch(
+                constants::flags::IPV6,
+                "Browse over IPv6 rather than IPv4",
+                Some('6'),
+            )
+            .switch(
+                constants::flags::CONTINUOUS,
+                "Keep browsing until interrupted, streaming services as they appear and disappear",
+                Some('c'),
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns browse",
+                description: "list the service types on the local link",
+                result: None,
+            },
+            Example {
+                example: "dns browse _ipp._tcp --wait 5sec --interface eth0",
+                description: "find the printers on a given interface",
+                result: None,
+            },
+            Example {
+                example: "dns browse --continuous _airplay._tcp",
+                description: "watch AirPlay receivers come and go",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "mdns",
+            "multicast",
+            "dns-sd",
+            "bonjour",
+            "zeroconf",
+            "avahi",
+            "service discovery",
+        ]
+    }
+}
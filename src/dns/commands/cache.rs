@@ -0,0 +1,156 @@
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCacheList;
+
+impl PluginCommand for DnsCacheList {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        plugin.load_cache(&config)?;
+
+        let mut entries = plugin.cache.entries();
+        entries.sort_by(|a, b| {
+            (&a.key.name, u16::from(a.key.qtype), a.key.server).cmp(&(
+                &b.key.name,
+                u16::from(b.key.qtype),
+                b.key.server,
+            ))
+        });
+
+        let rows = entries
+            .into_iter()
+            .map(|entry| {
+                let answers = entry
+                    .message
+                    .answers()
+                    .iter()
+                    .filter_map(|record| record.data())
+                    .map(|rdata| Value::string(rdata.to_string(), Span::unknown()))
+                    .collect();
+
+                Value::record(
+                    record![
+                        "name"      => Value::string(config.display_name(&entry.key.name), Span::unknown()),
+                        "type"      => Value::string(entry.key.qtype.to_string(), Span::unknown()),
+                        "class"     => Value::string(entry.key.class.to_string(), Span::unknown()),
+                        "server"    => Value::string(entry.key.server.to_string(), Span::unknown()),
+                        "remaining" => serde::util::std_to_duration(entry.remaining),
+                        "answers"   => Value::list(answers, Span::unknown()),
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        Ok(PipelineData::Value(Value::list(rows, call.head), None))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CACHE_LIST
+    }
+
+    fn description(&self) -> &str {
+        "List the responses held in the plugin's cache"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            example: "dns cache list | where remaining < 1min",
+            description: "show cached responses that are about to expire",
+            result: None,
+        }]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "cache", "ttl"]
+    }
+}
+
+#[derive(Debug)]
+pub struct DnsCacheClear;
+
+impl PluginCommand for DnsCacheClear {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin.load_cache(&Config::from_nu(engine, call)?)?;
+
+        let name = match call.opt::<Spanned<String>>(0)? {
+            Some(name) => Some(Name::from_utf8(&name.item).map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), name.span)
+            })?),
+            None => None,
+        };
+
+        let removed = plugin.cache.clear(name.as_ref()).map_err(|err| {
+            LabeledError::new("cache error")
+                .with_label(format!("could not rewrite cache file: {}", err), call.head)
+        })?;
+        tracing::debug!(cache.phase = "clear", ?name, cache.removed = removed);
+
+        Ok(PipelineData::Empty)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CACHE_CLEAR
+    }
+
+    fn description(&self) -> &str {
+        "Remove responses from the plugin's cache"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name()).optional(
+            constants::flags::NAME,
+            SyntaxShape::String,
+            "Only remove responses for this name (default: remove everything)",
+        )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns cache clear",
+                description: "empty the cache",
+                result: None,
+            },
+            Example {
+                example: "dns cache clear example.com",
+                description: "forget all cached responses for a single name",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "cache", "flush", "purge"]
+    }
+}
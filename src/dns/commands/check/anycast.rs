@@ -0,0 +1,211 @@
+use std::time::{Duration, Instant};
+
+use futures_util::future;
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{
+        rdata::opt::{EdnsCode, EdnsOption},
+        DNSClass, Name, RData, RecordType,
+    },
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, error::DnsError, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckAnycast;
+
+impl DnsCheckAnycast {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine, call)?;
+
+        if let Some(server) = call.opt::<Value>(0)? {
+            config = config.with_server(&server)?;
+        }
+
+        let client = plugin.dns_client(&config).await?;
+
+        // the NSID option rides along on the first question, so the instance
+        // it names is the one that answered it
+        let (id_server, hostname_bind) = future::join(
+            identify(&config, &client, "id.server.", true, call.head),
+            identify(&config, &client, "hostname.bind.", false, call.head),
+        )
+        .await;
+        let (id_server, hostname_bind) = (id_server?, hostname_bind?);
+
+        let rtt = id_server.rtt.min(hostname_bind.rtt);
+        let text = |text: Option<String>| {
+            text.map_or(Value::nothing(Span::unknown()), |text| {
+                Value::string(text, Span::unknown())
+            })
+        };
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "server"        => Value::string(config.server.item.to_string(), Span::unknown()),
+                    "nsid"          => id_server.nsid.unwrap_or(Value::nothing(Span::unknown())),
+                    "id_server"     => text(id_server.txt),
+                    "hostname_bind" => text(hostname_bind.txt),
+                    "rtt"           => serde::util::std_to_duration(rtt),
+                ],
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// What one identity question revealed.
+struct Identity {
+    txt: Option<String>,
+    nsid: Option<Value>,
+    rtt: Duration,
+}
+
+/// Asks a CHAOS class TXT question that servers conventionally answer with
+/// their own identity, optionally asking for the NSID (RFC 5001) as well.
+/// Servers that don't say who they are answer with an error code, which is
+/// not an error here.
+async fn identify(
+    config: &Config,
+    client: &DnsClient,
+    name: &str,
+    nsid: bool,
+    span: Span,
+) -> Result<Identity, LabeledError> {
+    let mut query = hickory_proto::op::Query::query(
+        Name::from_ascii(name).expect("identity name should parse"),
+        RecordType::TXT,
+    );
+    query.set_query_class(DNSClass::CH);
+
+    let mut request = serde::Query(query).into_message(config);
+    request.set_recursion_desired(false);
+
+    if nsid {
+        request
+            .extensions_mut()
+            .get_or_insert_with(hickory_proto::op::Edns::new)
+            .options_mut()
+            .insert(EdnsOption::Unknown(u16::from(EdnsCode::NSID), vec![]));
+    }
+
+    config.throttle().await;
+    let sent = Instant::now();
+    let resp = client
+        .send_message(config, request)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+    let rtt = sent.elapsed();
+
+    let txt = (resp.response_code() == ResponseCode::NoError)
+        .then(|| {
+            resp.answers()
+                .iter()
+                .find_map(|record| match record.data() {
+                    Some(RData::TXT(txt)) => Some(
+                        txt.iter()
+                            .map(|part| String::from_utf8_lossy(part))
+                            .collect::<String>(),
+                    ),
+                    _ => None,
+                })
+        })
+        .flatten();
+
+    let nsid = resp
+        .extensions()
+        .as_ref()
+        .and_then(|edns| edns.options().get(EdnsCode::NSID))
+        .and_then(|opt| match opt {
+            EdnsOption::Unknown(_, data) if !data.is_empty() => Some(nsid_value(data)),
+            _ => None,
+        });
+
+    Ok(Identity { txt, nsid, rtt })
+}
+
+/// NSIDs are opaque bytes, but they are usually readable text; anything else
+/// is shown in hex.
+fn nsid_value(data: &[u8]) -> Value {
+    match std::str::from_utf8(data) {
+        Ok(text) if text.chars().all(|c| !c.is_control()) => Value::string(text, Span::unknown()),
+        _ => Value::string(data_encoding::HEXLOWER.encode(data), Span::unknown()),
+    }
+}
+
+impl PluginCommand for DnsCheckAnycast {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_ANYCAST
+    }
+
+    fn description(&self) -> &str {
+        "Identify which instance of an anycast nameserver is answering"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks the server who it is in the ways servers conventionally answer: the NSID EDNS option (RFC 5001), and the id.server and hostname.bind TXT records in the CHAOS class. Returns them along with the round trip time in a single record. Any the server won't answer are empty."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name())).optional(
+            constants::flags::NAMESERVER,
+            SyntaxShape::String,
+            "Nameserver to identify. Default: --server",
+        )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check anycast 1.1.1.1",
+                description: "see which Cloudflare location answers you",
+                result: None,
+            },
+            Example {
+                example: "[198.41.0.4 199.9.14.201 192.33.4.12] | each { dns check anycast $in }",
+                description: "identify the nearest instances of several root servers",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "network",
+            "anycast",
+            "nsid",
+            "id.server",
+            "hostname.bind",
+        ]
+    }
+}
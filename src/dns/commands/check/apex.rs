@@ -0,0 +1,327 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::{IpAddr, SocketAddr},
+};
+
+use futures_util::future;
+use hickory_proto::rr::{Name, RData, Record, RecordType};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, error::DnsError},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckApex;
+
+impl DnsCheckApex {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let zone: Spanned<String> = call.req(0)?;
+        let span = zone.span;
+        let zone = Name::from_utf8(&zone.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), zone.span)
+        })?;
+
+        let client = plugin.dns_client(&config).await?;
+
+        let (soa, ns, cname) = future::join3(
+            answers(&config, &client, &zone, RecordType::SOA, span),
+            answers(&config, &client, &zone, RecordType::NS, span),
+            answers(&config, &client, &zone, RecordType::CNAME, span),
+        )
+        .await;
+        let (soa, ns, cname) = (soa?, ns?, cname?);
+
+        let mut rows = vec![
+            check(
+                "soa",
+                !soa.is_empty(),
+                match soa.len() {
+                    0 => format!("there is no SOA record at {}", config.display_name(&zone)),
+                    1 => "the apex has an SOA record".into(),
+                    n => format!("the apex has {n} SOA records, where there should be one"),
+                },
+                call.head,
+            ),
+            check(
+                "ns",
+                !ns.is_empty(),
+                match ns.is_empty() {
+                    true => format!("there are no NS records at {}", config.display_name(&zone)),
+                    false => format!(
+                        "the apex has NS records for {}",
+                        targets(&config, &ns).join(", ")
+                    ),
+                },
+                call.head,
+            ),
+            // RFC 1034 section 3.6.2: a CNAME can't share its name with other
+            // data, and the apex always has SOA and NS records
+            check(
+                "cname",
+                cname.is_empty(),
+                match cname.first().and_then(Record::data) {
+                    Some(RData::CNAME(target)) => format!(
+                        "the apex is a CNAME to {}, which leaves no room for its SOA and NS records",
+                        config.display_name(target)
+                    ),
+                    _ => "the apex is not a CNAME".into(),
+                },
+                call.head,
+            ),
+        ];
+
+        let nameservers = nameservers(&config, &client, &ns_targets(&ns)).await;
+        rows.push(consistency(plugin, &config, &zone, nameservers, call.head).await);
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+fn check(name: &str, pass: bool, detail: String, span: Span) -> Value {
+    Value::record(
+        record![
+            "check"  => Value::string(name, Span::unknown()),
+            "status" => Value::string(if pass { "pass" } else { "fail" }, Span::unknown()),
+            "detail" => Value::string(detail, Span::unknown()),
+        ],
+        span,
+    )
+}
+
+/// Asks for the records of one type at the apex itself, leaving out any that
+/// belong to another name, like the SOA of a parent zone.
+async fn answers(
+    config: &Config,
+    client: &DnsClient,
+    zone: &Name,
+    qtype: RecordType,
+    span: Span,
+) -> Result<Vec<Record>, LabeledError> {
+    Ok(client
+        .answers(config, zone, qtype)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?
+        .into_iter()
+        .filter(|record| record.record_type() == qtype && record.name() == zone)
+        .collect())
+}
+
+fn ns_targets(ns: &[Record]) -> Vec<Name> {
+    ns.iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::NS(target)) => Some(target.0.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn targets(config: &Config, ns: &[Record]) -> Vec<String> {
+    ns_targets(ns)
+        .iter()
+        .map(|target| config.display_name(target))
+        .collect()
+}
+
+/// Looks up the addresses of each nameserver, in the families allowed by
+/// `--ipv4` and `--ipv6`. A nameserver whose addresses can't be found has
+/// none.
+async fn nameservers(
+    config: &Config,
+    client: &DnsClient,
+    targets: &[Name],
+) -> Vec<(Name, Vec<IpAddr>)> {
+    future::join_all(targets.iter().map(|target| async move {
+        let mut addrs = Vec::new();
+
+        for qtype in config.address_types() {
+            match client.answers(config, target, *qtype).await {
+                Ok(records) => {
+                    addrs.extend(records.iter().filter_map(|record| match record.data() {
+                        Some(RData::A(addr)) => Some(IpAddr::V4(addr.0)),
+                        Some(RData::AAAA(addr)) => Some(IpAddr::V6(addr.0)),
+                        _ => None,
+                    }))
+                }
+                Err(err) => {
+                    tracing::debug!(check.apex.nameserver = %target, check.apex.error = ?err);
+                }
+            }
+        }
+
+        (target.clone(), addrs)
+    }))
+    .await
+}
+
+/// Asks each of the zone's nameservers for the apex A and AAAA records, and
+/// checks that they all give the same ones.
+async fn consistency(
+    plugin: &Dns,
+    config: &Config,
+    zone: &Name,
+    nameservers: Vec<(Name, Vec<IpAddr>)>,
+    span: Span,
+) -> Value {
+    let port = config.server.item.port();
+    let servers: Vec<(String, SocketAddr)> = nameservers
+        .iter()
+        .flat_map(|(target, addrs)| {
+            addrs.iter().map(move |addr| {
+                (
+                    format!("{} ({addr})", config.display_name(target)),
+                    SocketAddr::new(*addr, port),
+                )
+            })
+        })
+        .collect();
+
+    if servers.is_empty() {
+        return check(
+            "consistent-addresses",
+            false,
+            "none of the zone's nameservers could be found to ask".into(),
+            span,
+        );
+    }
+
+    let answers = future::join_all(servers.iter().map(|(_, addr)| async move {
+        let config = config.with_addr(*addr);
+        let client = plugin.dns_client(&config).await.map_err(|err| err.msg)?;
+        let mut rdata = BTreeSet::new();
+
+        for qtype in [RecordType::A, RecordType::AAAA] {
+            let records = client
+                .answers(&config, zone, qtype)
+                .await
+                .map_err(|err| err.to_string())?;
+            rdata.extend(
+                records
+                    .iter()
+                    .filter(|record| record.record_type() == qtype)
+                    .filter_map(|record| record.data().map(|data| data.to_string())),
+            );
+        }
+
+        Ok::<_, String>(rdata)
+    }))
+    .await;
+
+    let mut unreachable = Vec::new();
+    let mut groups: BTreeMap<BTreeSet<String>, Vec<&str>> = BTreeMap::new();
+
+    for ((server, _), answer) in servers.iter().zip(answers) {
+        match answer {
+            Ok(rdata) => groups.entry(rdata).or_default().push(server),
+            Err(err) => unreachable.push(format!("{server}: {err}")),
+        }
+    }
+
+    let detail = match (groups.len(), unreachable.is_empty()) {
+        (0, _) => format!("no nameserver could be asked: {}", unreachable.join("; ")),
+        (1, true) => format!(
+            "all {} nameserver addresses give the same apex addresses",
+            servers.len()
+        ),
+        (1, false) => format!(
+            "the nameservers that answered agree, but some could not be asked: {}",
+            unreachable.join("; ")
+        ),
+        _ => {
+            let mut detail = groups
+                .iter()
+                .map(|(rdata, servers)| {
+                    let rdata = match rdata.is_empty() {
+                        true => "none".into(),
+                        false => rdata.iter().cloned().collect::<Vec<_>>().join(" "),
+                    };
+                    format!("{} answer {rdata}", servers.join(", "))
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+
+            if !unreachable.is_empty() {
+                detail.push_str(&format!("; could not ask {}", unreachable.join("; ")));
+            }
+
+            detail
+        }
+    };
+
+    check(
+        "consistent-addresses",
+        groups.len() == 1 && unreachable.is_empty(),
+        detail,
+        span,
+    )
+}
+
+impl PluginCommand for DnsCheckApex {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_APEX
+    }
+
+    fn description(&self) -> &str {
+        "Check that a zone's apex is set up sanely"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Returns a row per check with its status, pass or fail, and what was found: soa, that the apex has an SOA record; ns, that it has NS records; cname, that it isn't a CNAME, which can't coexist with them (RFC 1034 section 3.6.2); and consistent-addresses, that every address of every nameserver in the NS set gives the same apex A and AAAA records. The nameservers are asked on the port of --server."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name())).required(
+            constants::flags::ZONE,
+            SyntaxShape::String,
+            "Zone to check",
+        )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check apex example.com",
+                description: "check the apex of a zone",
+                result: None,
+            },
+            Example {
+                example: "dns check apex example.com | where status == fail",
+                description: "list only the problems",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "apex", "cname", "ns", "soa", "lint", "zone"]
+    }
+}
@@ -0,0 +1,296 @@
+use std::net::IpAddr;
+
+use futures_util::future;
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{Name, RData},
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{record, Example, LabeledError, PipelineData, Signature, Span, Value};
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        config::Config,
+        constants,
+        error::DnsError,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckCaptive;
+
+/// What a probe name should resolve to when nothing is in the way.
+enum Expect {
+    /// Addresses reachable from anywhere, not ones on the local network.
+    Public,
+    /// These addresses and no others.
+    Exactly(Vec<IpAddr>),
+    /// Nothing, because the name doesn't exist.
+    NxDomain,
+}
+
+impl DnsCheckCaptive {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let client = plugin.dns_client(&config).await?;
+
+        let mut probes: Vec<(Name, Expect)> = constants::captive::PROBES
+            .iter()
+            .map(|(name, addrs)| {
+                let expect = match addrs.is_empty() {
+                    true => Expect::Public,
+                    false => Expect::Exactly(
+                        addrs
+                            .iter()
+                            .map(|addr| addr.parse().expect("probe address should parse"))
+                            .collect(),
+                    ),
+                };
+
+                (
+                    Name::from_ascii(name).expect("probe name should parse"),
+                    expect,
+                )
+            })
+            .collect();
+
+        // portals often answer for every name, including ones that can't
+        // exist
+        let label: String = (0..16)
+            .map(|_| char::from(b'a' + rand::random::<u8>() % 26))
+            .collect();
+        probes.push((
+            Name::from_ascii(format!("{label}.com.")).expect("random name should parse"),
+            Expect::NxDomain,
+        ));
+
+        let rows = future::join_all(
+            probes
+                .iter()
+                .map(|(name, expect)| probe(&config, &client, name, expect, call.head)),
+        )
+        .await;
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+async fn probe(
+    config: &Config,
+    client: &DnsClient,
+    name: &Name,
+    expect: &Expect,
+    span: Span,
+) -> Value {
+    let (rcode, addrs, status, detail) = match resolve(config, client, name, span).await {
+        Ok((rcode, addrs)) => {
+            let (status, detail) = verdict(expect, rcode, &addrs);
+            (RCode(rcode).into_value(), addrs, status, detail)
+        }
+        Err(err) => (Value::nothing(Span::unknown()), vec![], "error", err.msg),
+    };
+
+    let expect = match expect {
+        Expect::Public => "public addresses".into(),
+        Expect::Exactly(addrs) => list(addrs),
+        Expect::NxDomain => "NXDOMAIN".into(),
+    };
+
+    Value::record(
+        record![
+            "name"      => Value::string(config.display_name(name), Span::unknown()),
+            "expect"    => Value::string(expect, Span::unknown()),
+            "rcode"     => rcode,
+            "addresses" => Value::list(
+                addrs
+                    .iter()
+                    .map(|addr| Value::string(addr.to_string(), Span::unknown()))
+                    .collect(),
+                Span::unknown(),
+            ),
+            "status"    => Value::string(status, Span::unknown()),
+            "detail"    => Value::string(detail, Span::unknown()),
+        ],
+        span,
+    )
+}
+
+/// Looks up a name's addresses, in the families allowed by `--ipv4` and
+/// `--ipv6`, along with the response code, which is the first that isn't
+/// NOERROR.
+async fn resolve(
+    config: &Config,
+    client: &DnsClient,
+    name: &Name,
+    span: Span,
+) -> Result<(ResponseCode, Vec<IpAddr>), LabeledError> {
+    let mut rcode = ResponseCode::NoError;
+    let mut addrs = Vec::new();
+
+    for qtype in config.address_types() {
+        let mut query = hickory_proto::op::Query::query(name.clone(), *qtype);
+        query.set_query_class(config.class.item);
+        let request = serde::Query(query).into_message(config);
+
+        config.throttle().await;
+        let resp = client
+            .send_message(config, request)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+        if rcode == ResponseCode::NoError {
+            rcode = resp.response_code();
+        }
+
+        addrs.extend(
+            resp.answers()
+                .iter()
+                .filter_map(|record| match record.data() {
+                    Some(RData::A(addr)) => Some(IpAddr::V4(addr.0)),
+                    Some(RData::AAAA(addr)) => Some(IpAddr::V6(addr.0)),
+                    _ => None,
+                }),
+        );
+    }
+
+    Ok((rcode, addrs))
+}
+
+fn list(addrs: &[IpAddr]) -> String {
+    addrs
+        .iter()
+        .map(IpAddr::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn verdict(expect: &Expect, rcode: ResponseCode, addrs: &[IpAddr]) -> (&'static str, String) {
+    match expect {
+        Expect::NxDomain if addrs.is_empty() => ("ok", "the made up name doesn't resolve".into()),
+        Expect::NxDomain => (
+            "intercepted",
+            format!("a name that can't exist resolved to {}", list(addrs)),
+        ),
+        _ if rcode != ResponseCode::NoError || addrs.is_empty() => (
+            "intercepted",
+            format!(
+                "the name answered {} with no addresses, so lookups are being blocked",
+                RCode(rcode).mnemonic()
+            ),
+        ),
+        Expect::Exactly(expected) => {
+            match addrs
+                .iter()
+                .copied()
+                .filter(|addr| !expected.contains(addr))
+                .collect::<Vec<_>>()
+            {
+                unexpected if unexpected.is_empty() => {
+                    ("ok", "the name resolved to its known addresses".into())
+                }
+                unexpected => (
+                    "intercepted",
+                    format!(
+                        "the name resolved to {}, not its known addresses",
+                        list(&unexpected)
+                    ),
+                ),
+            }
+        }
+        Expect::Public => match addrs.iter().copied().filter(is_local).collect::<Vec<_>>() {
+            local if local.is_empty() => ("ok", "the name resolved to public addresses".into()),
+            local => (
+                "intercepted",
+                format!(
+                    "the name resolved to {}, on the local network rather than the internet",
+                    list(&local)
+                ),
+            ),
+        },
+    }
+}
+
+/// Whether an address is one a portal would send visitors to: private,
+/// shared, loopback, link-local, or unspecified.
+fn is_local(addr: &IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(v4) => {
+            v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                // the shared address space of carrier-grade NAT (RFC 6598)
+                || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64)
+        }
+        IpAddr::V6(v6) => {
+            v6.is_loopback()
+                || v6.is_unspecified()
+                // unique local (RFC 4193) and link-local
+                || (v6.segments()[0] & 0xfe00) == 0xfc00
+                || (v6.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+impl PluginCommand for DnsCheckCaptive {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_CAPTIVE
+    }
+
+    fn description(&self) -> &str {
+        "Check whether DNS is being intercepted by a captive portal"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Resolves the names Firefox, Android, Apple, and Windows use to detect captive portals, and a made up name that can't exist. A portal that intercepts DNS typically answers with an address on the local network, answers for names that don't exist, or blocks lookups until you log in. Returns a row per name with what was expected, the addresses it resolved to, and a status of ok, intercepted, or error if the query failed."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name()))
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check captive",
+                description: "see whether the network's resolver is behind a captive portal",
+                result: None,
+            },
+            Example {
+                example: "dns check captive | any { $in.status == intercepted }",
+                description: "get a yes or no answer",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "captive", "portal", "wifi", "hotspot", "intercept"]
+    }
+}
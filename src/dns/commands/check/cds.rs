@@ -0,0 +1,327 @@
+use std::collections::BTreeMap;
+
+use futures_util::future;
+use hickory_proto::rr::{
+    dnssec::{rdata::DNSSECRData, Algorithm, DigestType},
+    Name, RData, Record, RecordType,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, dnssec::DnsKey, error::DnsError, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckCds;
+
+/// A DS or CDS record, reduced to what is compared.
+#[derive(Clone, PartialEq)]
+struct Digest {
+    key_tag: u16,
+    algorithm: u8,
+    digest_type: DigestType,
+    digest: Vec<u8>,
+}
+
+/// Everything known about one key, as identified by its tag and algorithm.
+#[derive(Default)]
+struct KeyState {
+    ds: Vec<Digest>,
+    cds: Vec<Digest>,
+    cdnskey: bool,
+    dnskey: Option<DnsKey>,
+}
+
+impl DnsCheckCds {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let zone: Spanned<String> = call.req(0)?;
+        let span = zone.span;
+        let zone = Name::from_utf8(&zone.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), zone.span)
+        })?;
+
+        let client = plugin.dns_client(&config).await?;
+
+        let ((ds, cds), (cdnskey, dnskey)) = future::join(
+            future::join(
+                answers(&config, &client, &zone, RecordType::DS, span),
+                answers(&config, &client, &zone, RecordType::CDS, span),
+            ),
+            future::join(
+                answers(&config, &client, &zone, RecordType::CDNSKEY, span),
+                answers(&config, &client, &zone, RecordType::DNSKEY, span),
+            ),
+        )
+        .await;
+        let (ds, cds, cdnskey, dnskey) = (ds?, cds?, cdnskey?, dnskey?);
+
+        let mut keys: BTreeMap<(u16, u8), KeyState> = BTreeMap::new();
+        let mut delete = false;
+
+        for record in &dnskey {
+            if let Some(RData::DNSSEC(DNSSECRData::DNSKEY(key))) = record.data() {
+                let key = DnsKey::from(key);
+                let id = (key.key_tag(), key.algorithm);
+                keys.entry(id).or_default().dnskey = Some(key);
+            }
+        }
+
+        for record in &ds {
+            if let Some(RData::DNSSEC(DNSSECRData::DS(ds))) = record.data() {
+                let ds = digest(ds);
+                keys.entry((ds.key_tag, ds.algorithm))
+                    .or_default()
+                    .ds
+                    .push(ds);
+            }
+        }
+
+        for record in &cds {
+            if let Some(RData::DNSSEC(DNSSECRData::CDS(cds))) = record.data() {
+                // RFC 8078 section 4: algorithm 0 asks for the DS to be removed
+                if u8::from(cds.algorithm()) == 0 {
+                    delete = true;
+                    continue;
+                }
+
+                let cds = digest(cds);
+                keys.entry((cds.key_tag, cds.algorithm))
+                    .or_default()
+                    .cds
+                    .push(cds);
+            }
+        }
+
+        for record in &cdnskey {
+            if let Some(RData::DNSSEC(DNSSECRData::CDNSKEY(key))) = record.data() {
+                let key = DnsKey::from(&**key);
+
+                if key.algorithm == 0 {
+                    delete = true;
+                    continue;
+                }
+
+                keys.entry((key.key_tag(), key.algorithm))
+                    .or_default()
+                    .cdnskey = true;
+            }
+        }
+
+        let publishes_cds = keys.values().any(|key| !key.cds.is_empty());
+        let publishes_cdnskey = keys.values().any(|key| key.cdnskey);
+
+        let mut rows: Vec<Value> = keys
+            .into_iter()
+            // keys the parent knows nothing about and that the child isn't
+            // asking to have added, like most ZSKs, are of no interest
+            .filter(|(_, key)| !key.ds.is_empty() || !key.cds.is_empty() || key.cdnskey)
+            .map(|((key_tag, algorithm), key)| {
+                let (status, detail) = status(&zone, &key, publishes_cds, publishes_cdnskey);
+                let digest_types = |digests: &[Digest]| {
+                    Value::list(
+                        digests
+                            .iter()
+                            .map(|digest| {
+                                Value::string(
+                                    serde::util::digest_type_name(digest.digest_type),
+                                    Span::unknown(),
+                                )
+                            })
+                            .collect(),
+                        Span::unknown(),
+                    )
+                };
+
+                Value::record(
+                    record![
+                        "key_tag"   => Value::int(key_tag.into(), Span::unknown()),
+                        "algorithm" => Value::string(Algorithm::from_u8(algorithm).to_string(), Span::unknown()),
+                        "ds"        => digest_types(&key.ds),
+                        "cds"       => digest_types(&key.cds),
+                        "cdnskey"   => Value::bool(key.cdnskey, Span::unknown()),
+                        "dnskey"    => Value::bool(key.dnskey.is_some(), Span::unknown()),
+                        "status"    => Value::string(status, Span::unknown()),
+                        "detail"    => Value::string(detail, Span::unknown()),
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        if delete {
+            rows.push(Value::record(
+                record![
+                    "key_tag"   => Value::int(0, Span::unknown()),
+                    "algorithm" => Value::string(Algorithm::from_u8(0).to_string(), Span::unknown()),
+                    "ds"        => Value::list(vec![], Span::unknown()),
+                    "cds"       => Value::list(vec![], Span::unknown()),
+                    "cdnskey"   => Value::bool(false, Span::unknown()),
+                    "dnskey"    => Value::bool(false, Span::unknown()),
+                    "status"    => Value::string("delete", Span::unknown()),
+                    "detail"    => Value::string("the child asks for all DS records to be removed (RFC 8078)", Span::unknown()),
+                ],
+                call.head,
+            ));
+        }
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+async fn answers(
+    config: &Config,
+    client: &DnsClient,
+    zone: &Name,
+    qtype: RecordType,
+    span: Span,
+) -> Result<Vec<Record>, LabeledError> {
+    Ok(client
+        .answers(config, zone, qtype)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?
+        .into_iter()
+        .filter(|record| record.record_type() == qtype)
+        .collect())
+}
+
+fn digest(ds: &hickory_proto::rr::dnssec::rdata::DS) -> Digest {
+    Digest {
+        key_tag: ds.key_tag(),
+        algorithm: ds.algorithm().into(),
+        digest_type: ds.digest_type(),
+        digest: ds.digest().to_vec(),
+    }
+}
+
+/// Works out where a key stands in the delegation, from the parent's DS
+/// records and the child's CDS and CDNSKEY records (RFC 7344), with a word of
+/// explanation.
+fn status(
+    zone: &Name,
+    key: &KeyState,
+    publishes_cds: bool,
+    publishes_cdnskey: bool,
+) -> (&'static str, String) {
+    let wants = !key.cds.is_empty() || key.cdnskey;
+
+    if let Some(dnskey) = &key.dnskey {
+        let wrong = key.ds.iter().chain(&key.cds).find(|digest| {
+            dnskey
+                .ds_digest(zone, digest.digest_type)
+                .is_some_and(|expected| expected != digest.digest)
+        });
+
+        if let Some(wrong) = wrong {
+            return (
+                "mismatch",
+                format!(
+                    "the {} digest does not match the DNSKEY",
+                    serde::util::digest_type_name(wrong.digest_type)
+                ),
+            );
+        }
+    } else if wants {
+        return (
+            "mismatch",
+            "the child asks for a key it does not publish in its DNSKEY set".into(),
+        );
+    }
+
+    if publishes_cds && publishes_cdnskey && key.cds.is_empty() == key.cdnskey {
+        return (
+            "mismatch",
+            "the key is in only one of the CDS and CDNSKEY sets, which must agree".into(),
+        );
+    }
+
+    match (!key.ds.is_empty(), wants) {
+        (true, true) => (
+            "current",
+            "the parent's DS matches what the child asks for".into(),
+        ),
+        (true, false) if publishes_cds || publishes_cdnskey => (
+            "pending-remove",
+            "the child no longer asks for this key; the parent should remove its DS".into(),
+        ),
+        (true, false) => (
+            "unmanaged",
+            "the child publishes no CDS or CDNSKEY records".into(),
+        ),
+        (false, _) => (
+            "pending-add",
+            "the child asks for this key; the parent has no DS for it yet".into(),
+        ),
+    }
+}
+
+impl PluginCommand for DnsCheckCds {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_CDS
+    }
+
+    fn description(&self) -> &str {
+        "Compare a zone's CDS and CDNSKEY records with the DS records at its parent"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Children publish CDS and CDNSKEY records (RFC 7344) to tell the parent which DS records to hold. Returns a row per key with its status: current, pending-add, or pending-remove while a rollover is under way, unmanaged if the child publishes neither, or mismatch if the records disagree with each other or with the zone's DNSKEY set. A delete row means the child asks for the DS to be removed (RFC 8078)."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name())).required(
+            constants::flags::ZONE,
+            SyntaxShape::String,
+            "Zone to check",
+        )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check cds example.com",
+                description: "see whether a zone's DS records are in sync",
+                result: None,
+            },
+            Example {
+                example: "dns check cds example.com | where status != current",
+                description: "list keys with a rollover pending or a problem",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns", "dnssec", "cds", "cdnskey", "ds", "rollover", "rfc7344",
+        ]
+    }
+}
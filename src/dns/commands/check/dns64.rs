@@ -0,0 +1,131 @@
+use std::str::FromStr;
+
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, nat64},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckDns64;
+
+impl DnsCheckDns64 {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+
+        let (name, span) = match call.opt::<Spanned<String>>(0)? {
+            Some(name) => (
+                Name::from_utf8(&name.item).map_err(|err| {
+                    LabeledError::new("invalid name")
+                        .with_label(format!("Error parsing name: {}", err), name.span)
+                })?,
+                name.span,
+            ),
+            None => (Name::from_str(nat64::WELL_KNOWN_NAME).unwrap(), call.head),
+        };
+
+        let client = plugin.dns_client(&config).await?;
+        let discovery = nat64::discover(&config, &client, &name, span).await?;
+
+        let prefixes: Vec<Value> = discovery
+            .prefixes
+            .iter()
+            .map(|prefix| Value::string(prefix.to_string(), Span::unknown()))
+            .collect();
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "name"        => Value::string(config.display_name(&name), Span::unknown()),
+                    "server"      => Value::string(config.server.item.to_string(), Span::unknown()),
+                    "synthesized" => Value::bool(!discovery.synthesized.is_empty(), Span::unknown()),
+                    "prefix"      => prefixes.first().cloned().unwrap_or(Value::nothing(Span::unknown())),
+                    "prefixes"    => Value::list(prefixes, Span::unknown()),
+                    "addresses"   => Value::list(
+                        discovery
+                            .synthesized
+                            .iter()
+                            .map(|addr| Value::string(addr.to_string(), Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                ],
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl PluginCommand for DnsCheckDns64 {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_DNS64
+    }
+
+    fn description(&self) -> &str {
+        "Check whether the resolver synthesizes AAAA records (DNS64), and with which NAT64 prefix"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks for the AAAA records of a name that only has A records, ipv4only.arpa unless another is given. Any AAAA records in the answer must have been synthesized. The NAT64 prefix is worked out by finding the name's IPv4 address embedded in them, per RFC 7050 and RFC 6052."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name()))
+            .optional(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "A name with only A records. Default: ipv4only.arpa",
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Query class",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check dns64",
+                description: "check whether the system resolver does DNS64",
+                result: None,
+            },
+            Example {
+                example: "dns check dns64 --server 2001:db8::53 example.com",
+                description: "check a given resolver with a name of your own",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "network", "dns64", "nat64", "ipv6", "pref64"]
+    }
+}
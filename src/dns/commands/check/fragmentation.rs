@@ -0,0 +1,210 @@
+use hickory_proto::{
+    op::Query,
+    rr::{Name, RecordType},
+    xfer::DnsResponse,
+};
+use hickory_resolver::config::Protocol;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckFragmentation;
+
+impl DnsCheckFragmentation {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine, call)?;
+
+        if let Some(server) = call.opt::<Value>(0)? {
+            config = config.with_server(&server)?;
+        }
+
+        let name = match call.get_flag::<Spanned<String>>(constants::flags::NAME)? {
+            Some(name) => serde::util::parse_name(&name.item).map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), name.span)
+            })?,
+            None => Name::from_ascii(constants::fragmentation::NAME).expect("name should parse"),
+        };
+
+        // the test response is a signed one, to make it big
+        let mut udp = config.clone();
+        udp.protocol.item = Protocol::Udp;
+        udp.dnssec_ok.item = true;
+        let mut tcp = udp.clone();
+        tcp.protocol.item = Protocol::Tcp;
+
+        let mut sizes = Vec::new();
+        let mut largest_intact = None;
+        let mut lost_within_flag_day = false;
+
+        // one size at a time, from the smallest, so that a lost fragment
+        // doesn't cost the smaller sizes their answers
+        for bufsize in constants::fragmentation::BUFSIZES {
+            let resp = ask(plugin, &udp, &name, Some(*bufsize)).await;
+
+            let (status, size) = match &resp {
+                Ok(resp) if resp.truncated() => ("truncated", Some(resp.as_buffer().len())),
+                Ok(resp) => ("intact", Some(resp.as_buffer().len())),
+                Err(_) => ("lost", None),
+            };
+
+            match status {
+                "intact" => largest_intact = largest_intact.max(size),
+                "lost" => {
+                    lost_within_flag_day |= *bufsize <= constants::config::default::EDNS_PAYLOAD
+                }
+                _ => (),
+            }
+
+            sizes.push(Value::record(
+                record![
+                    "bufsize" => Value::filesize(*bufsize as i64, Span::unknown()),
+                    "status"  => Value::string(status, Span::unknown()),
+                    "size"    => size.map_or(Value::nothing(Span::unknown()), |size| {
+                        Value::filesize(size as i64, Span::unknown())
+                    }),
+                    "error"   => resp.err().map_or(Value::nothing(Span::unknown()), |err| {
+                        Value::string(err, Span::unknown())
+                    }),
+                ],
+                Span::unknown(),
+            ));
+        }
+
+        let tcp_resp = ask(plugin, &tcp, &name, None).await;
+        let tcp_works = tcp_resp.is_ok();
+
+        // DNS Flag Day 2020: responses of up to 1232 bytes should get through
+        // over UDP, and anything bigger should be retried over TCP
+        let flag_day = largest_intact.is_some() && !lost_within_flag_day && tcp_works;
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "server"         => Value::string(config.server.item.to_string(), Span::unknown()),
+                    "name"           => Value::string(config.display_name(&name), Span::unknown()),
+                    "largest_intact" => largest_intact.map_or(Value::nothing(Span::unknown()), |size| {
+                        Value::filesize(size as i64, Span::unknown())
+                    }),
+                    "tcp"            => Value::bool(tcp_works, Span::unknown()),
+                    "tcp_size"       => tcp_resp.as_ref().map_or(Value::nothing(Span::unknown()), |resp| {
+                        Value::filesize(resp.as_buffer().len() as i64, Span::unknown())
+                    }),
+                    "flag_day"       => Value::bool(flag_day, Span::unknown()),
+                    "sizes"          => Value::list(sizes, Span::unknown()),
+                ],
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// Asks for the test name's DNSKEY records, advertising `bufsize` as the
+/// largest UDP response that can be taken, or the usual size if none.
+async fn ask(
+    plugin: &Dns,
+    config: &Config,
+    name: &Name,
+    bufsize: Option<u16>,
+) -> Result<DnsResponse, String> {
+    let client = plugin.dns_client(config).await.map_err(|err| err.msg)?;
+
+    let mut query = Query::query(name.clone(), RecordType::DNSKEY);
+    query.set_query_class(config.class.item);
+    let mut request = serde::Query(query).into_message(config);
+
+    if let (Some(bufsize), Some(edns)) = (bufsize, request.extensions_mut()) {
+        edns.set_max_payload(bufsize);
+    }
+
+    config.throttle().await;
+    client
+        .send_message(config, request)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+impl PluginCommand for DnsCheckFragmentation {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_FRAGMENTATION
+    }
+
+    fn description(&self) -> &str {
+        "Find the largest UDP response that gets through from a server, and whether TCP works"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Follows the DNS Flag Day 2020 methodology: asks for the signed DNSKEY records of a name, the root by default, over UDP with the EDNS buffer size advertised as 512, 1232, 1432, 2048, and then 4096 bytes, and reports for each whether the response arrived intact, arrived truncated, or was lost, as big responses that are fragmented often are. Then asks again over TCP, where truncated responses are retried. The result has the largest response that arrived intact over UDP, whether TCP works, and flag_day, which is true when no response was lost at a buffer size of 1232 bytes or less and TCP works. Use --name with a zone that has large DNSKEY records to test bigger responses."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name()))
+            .optional(
+                constants::flags::NAMESERVER,
+                SyntaxShape::String,
+                "Server to test. Default: --server",
+            )
+            .named(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Name whose DNSKEY records make the test response. Default: the root",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check fragmentation",
+                description: "test the path to the system resolver",
+                result: None,
+            },
+            Example {
+                example: "dns check fragmentation 9.9.9.9 | get sizes",
+                description: "see how each buffer size fared with a public resolver",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "fragmentation",
+            "flag day",
+            "edns",
+            "bufsize",
+            "mtu",
+            "udp",
+            "tcp",
+        ]
+    }
+}
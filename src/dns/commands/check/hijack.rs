@@ -0,0 +1,323 @@
+use std::{collections::BTreeSet, net::SocketAddr};
+
+use futures_util::future;
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{Name, RecordType},
+};
+use hickory_resolver::config::Protocol;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        error::DnsError,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckHijack;
+
+/// What one resolver said about a name and type.
+struct Answer {
+    rcode: ResponseCode,
+    rdata: BTreeSet<String>,
+}
+
+impl DnsCheckHijack {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let name: Spanned<String> = call.req(0)?;
+        let span = name.span;
+        let name = serde::util::parse_name(&name.item)
+            .map(|name| config.input_name(name))
+            .map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), span)
+            })?;
+
+        let public: Vec<_> = constants::config::default::DOH_RESOLVERS
+            .iter()
+            .map(|(resolver, host, addr)| (*resolver, doh_config(&config, host, *addr)))
+            .collect();
+
+        let mut rows = Vec::new();
+
+        for qtype in config.qtypes.item.iter().map(|qtype| qtype.item) {
+            let (local, public_answers) = future::join(
+                ask(plugin, &config, &name, qtype, span),
+                future::join_all(
+                    public
+                        .iter()
+                        .map(|(_, doh)| ask(plugin, doh, &name, qtype, span)),
+                ),
+            )
+            .await;
+            let local = local?;
+
+            let (status, detail) = verdict(&local, &public_answers);
+
+            rows.push(Value::record(
+                record![
+                    "name"   => Value::string(config.display_name(&name), Span::unknown()),
+                    "type"   => Value::string(serde::util::rtype_mnemonic(qtype), Span::unknown()),
+                    "status" => Value::string(status, Span::unknown()),
+                    "detail" => Value::string(detail, Span::unknown()),
+                    "local"  => answer_value("local", config.server.item, &Ok(local)),
+                    "public" => Value::list(
+                        public
+                            .iter()
+                            .zip(&public_answers)
+                            .map(|((resolver, doh), answer)| {
+                                answer_value(resolver, doh.server.item, answer)
+                            })
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                ],
+                call.head,
+            ));
+        }
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// The config for asking a public resolver over HTTPS, with everything but
+/// how to reach the server kept as given.
+fn doh_config(config: &Config, host: &str, addr: SocketAddr) -> Config {
+    let mut doh = config.with_addr(addr);
+    doh.protocol.item = Protocol::Https;
+    doh.server_addrs = vec![addr];
+    doh.server_host = Some(host.into());
+    doh.dns_name = Some(Spanned {
+        item: host.into(),
+        span: Span::unknown(),
+    });
+    doh
+}
+
+async fn ask(
+    plugin: &Dns,
+    config: &Config,
+    name: &Name,
+    qtype: RecordType,
+    span: Span,
+) -> Result<Answer, LabeledError> {
+    let client = plugin.dns_client(config).await?;
+
+    let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+    query.set_query_class(config.class.item);
+    let request = serde::Query(query).into_message(config);
+
+    config.throttle().await;
+    let resp = client
+        .send_message(config, request)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+    // only the records of the type asked for; CNAMEs on the way there
+    // differ between resolvers for innocent reasons, like CDNs
+    Ok(Answer {
+        rcode: resp.response_code(),
+        rdata: resp
+            .answers()
+            .iter()
+            .filter(|record| record.record_type() == qtype)
+            .filter_map(|record| record.data().map(|data| data.to_string()))
+            .collect(),
+    })
+}
+
+/// Compares the local resolver's answer with the public ones. Content
+/// delivery networks give different addresses to different resolvers, so the
+/// local answer only needs to have something in common with one of them; an
+/// answer that shares nothing with any, or a name that exists only locally,
+/// is what interference looks like.
+fn verdict(local: &Answer, public: &[Result<Answer, LabeledError>]) -> (&'static str, String) {
+    let public: Vec<_> = public
+        .iter()
+        .filter_map(|answer| answer.as_ref().ok())
+        .collect();
+
+    if public.is_empty() {
+        return (
+            "inconclusive",
+            "none of the public resolvers could be reached".into(),
+        );
+    }
+
+    let rcodes: BTreeSet<_> = public
+        .iter()
+        .map(|answer| u16::from(answer.rcode))
+        .collect();
+    if !rcodes.contains(&u16::from(local.rcode)) {
+        let public_rcodes = rcodes
+            .iter()
+            .map(|rcode| RCode(<ResponseCode as From<u16>>::from(*rcode)).mnemonic())
+            .collect::<Vec<_>>()
+            .join(" or ");
+
+        return (
+            "mismatch",
+            format!(
+                "the local resolver answered {} with {} record(s), where the public resolvers answered {public_rcodes}",
+                RCode(local.rcode).mnemonic(),
+                local.rdata.len(),
+            ),
+        );
+    }
+
+    let public_rdata: BTreeSet<_> = public.iter().flat_map(|answer| &answer.rdata).collect();
+
+    match (local.rdata.is_empty(), public_rdata.is_empty()) {
+        (true, true) => ("ok", "no resolver has records of this type".into()),
+        (true, false) => (
+            "mismatch",
+            "the local resolver has no records where the public resolvers do".into(),
+        ),
+        (false, true) => (
+            "mismatch",
+            "the local resolver has records where the public resolvers have none".into(),
+        ),
+        (false, false) if local.rdata.iter().any(|rdata| public_rdata.contains(rdata)) => (
+            "ok",
+            "the local resolver's records agree with the public resolvers'".into(),
+        ),
+        (false, false) => (
+            "mismatch",
+            format!(
+                "none of the local resolver's records ({}) were given by any public resolver",
+                local.rdata.iter().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        ),
+    }
+}
+
+fn answer_value(
+    resolver: &str,
+    server: SocketAddr,
+    answer: &Result<Answer, LabeledError>,
+) -> Value {
+    let (rcode, rdata, error) = match answer {
+        Ok(answer) => (
+            RCode(answer.rcode).into_value(),
+            Value::list(
+                answer
+                    .rdata
+                    .iter()
+                    .map(|rdata| Value::string(rdata, Span::unknown()))
+                    .collect(),
+                Span::unknown(),
+            ),
+            Value::nothing(Span::unknown()),
+        ),
+        Err(err) => (
+            Value::nothing(Span::unknown()),
+            Value::list(vec![], Span::unknown()),
+            Value::string(err.msg.clone(), Span::unknown()),
+        ),
+    };
+
+    Value::record(
+        record![
+            "resolver" => Value::string(resolver, Span::unknown()),
+            "server"   => Value::string(server.to_string(), Span::unknown()),
+            "rcode"    => rcode,
+            "rdata"    => rdata,
+            "error"    => error,
+        ],
+        Span::unknown(),
+    )
+}
+
+impl PluginCommand for DnsCheckHijack {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_HIJACK
+    }
+
+    fn description(&self) -> &str {
+        "Compare the local resolver's answers with those of public resolvers over HTTPS"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks the local resolver, the system's or the one given with --server, and Cloudflare, Google, and Quad9 over DNS over HTTPS, which an ISP or middlebox can't tamper with, then compares the answers for each type. The status is mismatch if the response codes differ, as when a name that doesn't exist gets an answer anyway, or if the local records have nothing in common with any public resolver's; ok if they agree; and inconclusive if no public resolver could be reached. Names served by content delivery networks may legitimately get different addresses from each resolver, so a mismatch is worth a closer look rather than proof of interference."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name()))
+            .required(constants::flags::NAME, SyntaxShape::String, "Name to check")
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Record type(s) to compare",
+                Some('t'),
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat the name as fully qualified even without a trailing dot",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check hijack example.com",
+                description: "see whether the local resolver's answers agree with public ones",
+                result: None,
+            },
+            Example {
+                example: "dns check hijack this-name-does-not-exist.example.com",
+                description: "catch a resolver that answers for names that don't exist",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "hijack",
+            "doh",
+            "interference",
+            "middlebox",
+            "isp",
+            "compare",
+        ]
+    }
+}
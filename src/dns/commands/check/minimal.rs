@@ -0,0 +1,211 @@
+use hickory_proto::rr::{Record, RecordType};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, error::DnsError, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckMinimal;
+
+impl DnsCheckMinimal {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine, call)?;
+
+        if let Some(server) = call.get_flag::<Value>(constants::flags::NAMESERVER)? {
+            config = config.with_server(&server)?;
+        }
+
+        let parse = |name: Spanned<String>| {
+            serde::util::parse_name(&name.item)
+                .map(|parsed| config.input_name(parsed))
+                .map_err(|err| {
+                    LabeledError::new("invalid name")
+                        .with_label(format!("Error parsing name: {}", err), name.span)
+                })
+        };
+        let name: Spanned<String> = call.req(0)?;
+        let span = name.span;
+        let name = parse(name)?;
+        let zone = call
+            .get_flag::<Spanned<String>>(constants::flags::ZONE)?
+            .map(parse)
+            .transpose()?;
+
+        let client = plugin.dns_client(&config).await?;
+        let mut rows = Vec::new();
+
+        for qtype in config.qtypes.item.iter().map(|qtype| qtype.item) {
+            let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+            query.set_query_class(config.class.item);
+            let request = serde::Query(query).into_message(&config);
+
+            config.throttle().await;
+            let resp = client
+                .send_message(&config, request)
+                .await
+                .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+            // the bailiwick is the zone the answer came from: as given, or
+            // as named by the authority section, or else the name itself
+            let zone = zone
+                .clone()
+                .or_else(|| {
+                    resp.name_servers()
+                        .iter()
+                        .find(|record| {
+                            matches!(record.record_type(), RecordType::SOA | RecordType::NS)
+                        })
+                        .map(|record| record.name().clone())
+                })
+                .unwrap_or_else(|| name.clone());
+
+            let out_of_bailiwick: Vec<&Record> = resp
+                .additionals()
+                .iter()
+                .filter(|record| !zone.zone_of(record.name()))
+                .collect();
+            let minimal = resp.name_servers().is_empty() && resp.additionals().is_empty();
+
+            let names = |records: &[&Record]| {
+                Value::list(
+                    records
+                        .iter()
+                        .map(|record| {
+                            Value::string(
+                                format!(
+                                    "{} {}",
+                                    config.display_name(record.name()),
+                                    record.record_type()
+                                ),
+                                Span::unknown(),
+                            )
+                        })
+                        .collect(),
+                    Span::unknown(),
+                )
+            };
+
+            rows.push(Value::record(
+                record![
+                    "name"             => Value::string(config.display_name(&name), Span::unknown()),
+                    "type"             => Value::string(serde::util::rtype_mnemonic(qtype), Span::unknown()),
+                    "zone"             => Value::string(config.display_name(&zone), Span::unknown()),
+                    "minimal"          => Value::bool(minimal, Span::unknown()),
+                    "authority"        => Value::int(resp.name_servers().len() as i64, Span::unknown()),
+                    "additional"       => Value::int(resp.additionals().len() as i64, Span::unknown()),
+                    "in_bailiwick"     => Value::bool(out_of_bailiwick.is_empty(), Span::unknown()),
+                    "out_of_bailiwick" => names(&out_of_bailiwick),
+                ],
+                call.head,
+            ));
+        }
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl PluginCommand for DnsCheckMinimal {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_MINIMAL
+    }
+
+    fn description(&self) -> &str {
+        "Check whether a server sends minimal responses, and keeps additional records in bailiwick"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks the server for the name, for each type, and reports how many records it put in the authority and additional sections, and minimal, which is true when it put none, as servers configured with minimal-responses do. Additional records are in bailiwick when they are at or under the zone the answer came from: the one given with --zone, or else the owner of the SOA or NS records in the authority section, or else the name itself. Records out of bailiwick are listed; resolvers won't trust them, so they only make the response bigger. Most useful when pointed at an authoritative server with --nameserver."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name()))
+            .required(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Name to ask for",
+            )
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Record type(s) to ask for",
+                Some('t'),
+            )
+            .named(
+                constants::flags::NAMESERVER,
+                SyntaxShape::String,
+                "Server to check. Default: --server",
+                None,
+            )
+            .named(
+                constants::flags::ZONE,
+                SyntaxShape::String,
+                "Zone that additional records should be in. Default: from the authority section",
+                Some('z'),
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat the name as fully qualified even without a trailing dot",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check minimal example.com --nameserver a.iana-servers.net",
+                description: "see whether an authoritative server sends minimal responses",
+                result: None,
+            },
+            Example {
+                example: "dns check minimal example.com --type [A MX NS] | where not in_bailiwick",
+                description: "find responses with additional records from outside the zone",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "minimal",
+            "minimal-responses",
+            "additional",
+            "authority",
+            "bailiwick",
+            "authoritative",
+        ]
+    }
+}
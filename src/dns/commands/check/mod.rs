@@ -0,0 +1,15 @@
+//! The `dns check` commands, which each look into one aspect of how DNS is
+//! set up and report what they found.
+
+pub mod anycast;
+pub mod apex;
+pub mod captive;
+pub mod cds;
+pub mod dns64;
+pub mod fragmentation;
+pub mod hijack;
+pub mod minimal;
+pub mod rrsig;
+pub mod soa;
+pub mod transport;
+pub mod zonemd;
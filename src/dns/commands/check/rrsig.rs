@@ -0,0 +1,319 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures_util::{stream, StreamExt};
+use hickory_proto::rr::{
+    dnssec::rdata::{DNSSECRData, SIG},
+    Name, RData, RecordType,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        config::Config,
+        constants,
+        error::DnsError,
+        serde::{self, DnssecMode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckRrsig;
+
+/// What was found for one RRset: the signatures over it, or none if it is
+/// unsigned. Empty RRsets are left out entirely.
+struct Signatures {
+    name: Name,
+    rtype: RecordType,
+    sigs: Vec<SIG>,
+}
+
+impl DnsCheckRrsig {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine, call)?;
+
+        // the signatures are the point, so they mustn't be validated away
+        config.dnssec_mode.item = DnssecMode::None;
+
+        let names = call
+            .rest::<Spanned<String>>(0)?
+            .into_iter()
+            .map(|name| {
+                Name::from_utf8(&name.item).map_err(|err| {
+                    LabeledError::new("invalid name")
+                        .with_label(format!("Error parsing name: {}", err), name.span)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if names.is_empty() {
+            return Err(LabeledError::new("no names")
+                .with_label("Give a zone or names to check", call.head));
+        }
+
+        let qtypes: Vec<RecordType> = match call.get_flag_value(constants::flags::TYPE) {
+            Some(_) => config.qtypes.item.iter().map(|qtype| qtype.item).collect(),
+            None => constants::config::default::RRSIG_TYPES.to_vec(),
+        };
+
+        let warn_within = match call.get_flag_value(constants::flags::WARN_WITHIN) {
+            Some(val @ Value::Duration { .. }) => {
+                Duration::from_nanos(val.as_duration()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), val.span())
+                })?)
+            }
+            Some(val) => {
+                return Err(LabeledError::new("invalid duration")
+                    .with_label("should be a duration", val.span()))
+            }
+            None => Duration::ZERO,
+        };
+
+        let client = plugin.dns_client(&config).await?;
+
+        let questions: Vec<_> = names
+            .iter()
+            .flat_map(|name| qtypes.iter().map(move |qtype| (name, *qtype)))
+            .collect();
+
+        let results: Vec<_> = stream::iter(questions)
+            .map(|(name, qtype)| {
+                let config = &config;
+                let client = &client;
+                async move { signatures(config, client, name, qtype, call.head).await }
+            })
+            .buffered(config.tasks.item)
+            .collect()
+            .await;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        let mut rows = Vec::new();
+
+        for result in results {
+            let found = match result {
+                Ok(Some(found)) => found,
+                Ok(None) => continue,
+                Err(err) => {
+                    rows.push(Value::error(err.into(), call.head));
+                    continue;
+                }
+            };
+
+            if found.sigs.is_empty() {
+                rows.push(Value::record(
+                    record![
+                        "name"       => Value::string(config.display_name(&found.name), Span::unknown()),
+                        "type"       => Value::string(found.rtype.to_string(), Span::unknown()),
+                        "key_tag"    => Value::nothing(Span::unknown()),
+                        "algorithm"  => Value::nothing(Span::unknown()),
+                        "signer"     => Value::nothing(Span::unknown()),
+                        "inception"  => Value::nothing(Span::unknown()),
+                        "expiration" => Value::nothing(Span::unknown()),
+                        "expires_in" => Value::nothing(Span::unknown()),
+                        "status"     => Value::string("unsigned", Span::unknown()),
+                        "ok"         => Value::bool(false, Span::unknown()),
+                    ],
+                    call.head,
+                ));
+                continue;
+            }
+
+            for sig in &found.sigs {
+                // signature times are serial numbers (RFC 4034 section 3.1.5),
+                // so they are taken as whichever time is closest to now
+                let from_now = |time: u32| i64::from(time.wrapping_sub(now as u32) as i32);
+                let expires_in = from_now(sig.sig_expiration());
+
+                let status = if expires_in <= 0 {
+                    "expired"
+                } else if from_now(sig.sig_inception()) > 0 {
+                    "not-yet-valid"
+                } else if (expires_in as u64) <= warn_within.as_secs() {
+                    "expiring"
+                } else {
+                    "ok"
+                };
+
+                rows.push(Value::record(
+                    record![
+                        "name"       => Value::string(config.display_name(&found.name), Span::unknown()),
+                        "type"       => Value::string(found.rtype.to_string(), Span::unknown()),
+                        "key_tag"    => Value::int(sig.key_tag().into(), Span::unknown()),
+                        "algorithm"  => Value::string(sig.algorithm().to_string(), Span::unknown()),
+                        "signer"     => Value::string(config.display_name(sig.signer_name()), Span::unknown()),
+                        "inception"  => serde::util::sec_to_date(now + from_now(sig.sig_inception()), call.head)?,
+                        "expiration" => serde::util::sec_to_date(now + expires_in, call.head)?,
+                        "expires_in" => Value::duration(expires_in * 1_000_000_000, Span::unknown()),
+                        "status"     => Value::string(status, Span::unknown()),
+                        "ok"         => Value::bool(status == "ok", Span::unknown()),
+                    ],
+                    call.head,
+                ));
+            }
+        }
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// Asks for an RRset with the DO bit set, so that the signatures over it come
+/// along. Returns `None` if there is no such RRset.
+async fn signatures(
+    config: &Config,
+    client: &DnsClient,
+    name: &Name,
+    qtype: RecordType,
+    span: Span,
+) -> Result<Option<Signatures>, LabeledError> {
+    let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+    query.set_query_class(config.class.item);
+
+    let mut request = serde::Query(query).into_message(config);
+    if let Some(edns) = request.extensions_mut() {
+        edns.set_dnssec_ok(true);
+    }
+
+    config.throttle().await;
+    let resp = client
+        .send_message(config, request)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+    if !resp
+        .answers()
+        .iter()
+        .any(|record| record.record_type() == qtype)
+    {
+        return Ok(None);
+    }
+
+    let sigs = resp
+        .answers()
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::RRSIG(sig))) if sig.type_covered() == qtype => {
+                Some((**sig).clone())
+            }
+            _ => None,
+        })
+        .collect();
+
+    Ok(Some(Signatures {
+        name: name.clone(),
+        rtype: qtype,
+        sigs,
+    }))
+}
+
+impl PluginCommand for DnsCheckRrsig {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_RRSIG
+    }
+
+    fn description(&self) -> &str {
+        "Report how long until the DNSSEC signatures on a zone's RRsets expire"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks for each name's SOA, NS, DNSKEY, A, AAAA, and MX RRsets (or the --type given) with their signatures, and returns a row per signature with its validity period and the time left until it expires. The status is ok, expiring if it expires within --warn-within, expired, not-yet-valid, or unsigned for an RRset without signatures; `ok` is false for anything but ok, which makes for easy monitoring. Types the name has no records of are left out."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name()))
+            .rest(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Zone or names to check",
+            )
+            .named(
+                constants::flags::WARN_WITHIN,
+                SyntaxShape::Duration,
+                "Fail signatures that expire within this long",
+                Some('w'),
+            )
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "RRset type(s) to check. Default: SOA, NS, DNSKEY, A, AAAA, and MX",
+                Some('t'),
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Query class",
+                None,
+            )
+            .named(
+                constants::flags::TASKS,
+                SyntaxShape::Int,
+                format!(
+                    "Number of queries to have in flight at once. Default: {}",
+                    constants::config::default::TASKS
+                ),
+                Some('j'),
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check rrsig example.com",
+                description: "see when a zone's signatures expire",
+                result: None,
+            },
+            Example {
+                example: "dns check rrsig --warn-within 7day example.com www.example.com | where not ok",
+                description: "list signatures that expire within a week",
+                result: None,
+            },
+            Example {
+                example: "if (dns check rrsig --warn-within 7day example.com | any { not $in.ok }) { exit 1 }",
+                description: "fail a cron job when signatures are about to expire",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "dnssec",
+            "rrsig",
+            "signature",
+            "expiration",
+            "monitoring",
+        ]
+    }
+}
@@ -0,0 +1,323 @@
+use std::{collections::BTreeSet, time::Instant};
+
+use futures_util::future;
+use hickory_proto::rr::{Name, RData, RecordType};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        error::DnsError,
+        health::Health,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckSoa;
+
+impl DnsCheckSoa {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let zone: Spanned<String> = call.req(0)?;
+        let span = zone.span;
+        let zone = serde::util::parse_name(&zone.item)
+            .map(|zone| config.input_name(zone))
+            .map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), span)
+            })?;
+
+        let slow = match call.get_flag_value(constants::flags::SLOW) {
+            Some(val @ Value::Duration { .. }) => {
+                std::time::Duration::from_nanos(val.as_duration()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), val.span())
+                })?)
+            }
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("slow should be a duration", val.span()))
+            }
+            None => constants::config::default::SLOW_RTT,
+        };
+
+        // the zone's nameservers, as --server knows them
+        let client = plugin.dns_client(&config).await?;
+        let mut query = hickory_proto::op::Query::query(zone.clone(), RecordType::NS);
+        query.set_query_class(config.class.item);
+        let request = serde::Query(query).into_message(&config);
+
+        config.throttle().await;
+        let resp = client
+            .send_message(&config, request)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+        let nameservers: BTreeSet<Name> = resp
+            .answers()
+            .iter()
+            .filter_map(|record| match record.data()? {
+                RData::NS(ns) => Some(ns.0.clone()),
+                _ => None,
+            })
+            .collect();
+
+        if nameservers.is_empty() {
+            return Err(LabeledError::new("no nameservers").with_label(
+                format!(
+                    "{} answered {} with no NS records for {zone}",
+                    config.server.item,
+                    RCode(resp.response_code()).mnemonic()
+                ),
+                span,
+            ));
+        }
+
+        let mut health = Health::default();
+        let mut rows = Vec::new();
+
+        for nameserver in &nameservers {
+            let display = config.display_name(nameserver);
+            let ns_config =
+                match config.with_server(&Value::string(nameserver.to_ascii(), Span::unknown())) {
+                    Ok(ns_config) => ns_config,
+                    Err(err) => {
+                        health.failed(&display, "network");
+                        rows.push(row(&display, None, Err(err.msg)));
+                        continue;
+                    }
+                };
+
+            let answers = future::join_all(
+                ns_config
+                    .server_addrs
+                    .iter()
+                    .map(|addr| ask(plugin, ns_config.with_addr(*addr), &zone, span)),
+            )
+            .await;
+
+            for (addr, answer) in ns_config.server_addrs.iter().zip(answers) {
+                let server = format!("{display} ({})", addr.ip());
+                match &answer {
+                    Ok(soa) => health.answered(&server, soa.rtt),
+                    Err((kind, _)) => health.failed(&server, kind),
+                }
+                rows.push(row(
+                    &display,
+                    Some(addr.ip().to_string()),
+                    answer.map_err(|(_, err)| err),
+                ));
+            }
+        }
+
+        let serials: BTreeSet<i64> = rows
+            .iter()
+            .filter_map(|row| row.get_data_by_key("serial")?.as_int().ok())
+            .collect();
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "zone"        => Value::string(config.display_name(&zone), Span::unknown()),
+                    "consistent"  => Value::bool(serials.len() == 1, Span::unknown()),
+                    "serials"     => Value::list(
+                        serials
+                            .into_iter()
+                            .map(|serial| Value::int(serial, Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                    "nameservers" => Value::list(rows, Span::unknown()),
+                    "health"      => health.summary(slow),
+                ],
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// What one nameserver address said the zone's SOA is.
+struct Soa {
+    serial: u32,
+    authoritative: bool,
+    rtt: std::time::Duration,
+}
+
+async fn ask(
+    plugin: &Dns,
+    config: Config,
+    zone: &Name,
+    span: Span,
+) -> Result<Soa, (&'static str, String)> {
+    // the kind of failure goes into the health summary, and what happened
+    // into the row
+    let failure = |err: LabeledError| {
+        let kind = DnsError::kind(err.code.as_deref());
+        let message = err
+            .labels
+            .first()
+            .map_or(err.msg, |label| label.text.clone());
+        (kind, message)
+    };
+    let client = plugin.dns_client(&config).await.map_err(failure)?;
+
+    let mut query = hickory_proto::op::Query::query(zone.clone(), RecordType::SOA);
+    query.set_query_class(config.class.item);
+    let request = serde::Query(query).into_message(&config);
+
+    config.throttle().await;
+    let sent = Instant::now();
+    let resp = client
+        .send_message(&config, request)
+        .await
+        .map_err(|err| failure(DnsError::from_proto(&err, config.server.item, span).into()))?;
+    let rtt = sent.elapsed();
+
+    resp.answers()
+        .iter()
+        .find_map(|record| match record.data()? {
+            RData::SOA(soa) => Some(Soa {
+                serial: soa.serial(),
+                authoritative: resp.authoritative(),
+                rtt,
+            }),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            let kind = match resp.response_code() {
+                hickory_proto::op::ResponseCode::ServFail => "servfail",
+                hickory_proto::op::ResponseCode::Refused => "refused",
+                _ => "other",
+            };
+            (
+                kind,
+                format!(
+                    "answered {} with no SOA record",
+                    RCode(resp.response_code()).mnemonic()
+                ),
+            )
+        })
+}
+
+fn row(nameserver: &str, address: Option<String>, answer: Result<Soa, String>) -> Value {
+    let nothing = || Value::nothing(Span::unknown());
+    let (serial, authoritative, rtt, error) = match answer {
+        Ok(soa) => (
+            Value::int(soa.serial.into(), Span::unknown()),
+            Value::bool(soa.authoritative, Span::unknown()),
+            serde::util::std_to_duration(soa.rtt),
+            nothing(),
+        ),
+        Err(err) => (
+            nothing(),
+            nothing(),
+            nothing(),
+            Value::string(err, Span::unknown()),
+        ),
+    };
+
+    Value::record(
+        record![
+            "nameserver"    => Value::string(nameserver, Span::unknown()),
+            "address"       => address.map_or(nothing(), |address| Value::string(address, Span::unknown())),
+            "serial"        => serial,
+            "authoritative" => authoritative,
+            "rtt"           => rtt,
+            "error"         => error,
+        ],
+        Span::unknown(),
+    )
+}
+
+impl PluginCommand for DnsCheckSoa {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_SOA
+    }
+
+    fn description(&self) -> &str {
+        "Check that all of a zone's nameservers serve the same SOA serial"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Looks up the zone's NS records with --server, then asks every address of every nameserver for the zone's SOA record. Returns a row per address with the serial it serves, whether it answered authoritatively, and its round trip time, or its error. consistent is true when every nameserver that answered serves the same serial; a secondary that is behind its primary serves an older one. health sums up how the nameservers fared: a row for each that didn't answer, failed some of the time, or took longer than --slow on average, with how many times each kind of failure happened, so problems don't have to be picked out of the rows."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name()))
+            .required(constants::flags::ZONE, SyntaxShape::String, "Zone to check")
+            .named(
+                constants::flags::SLOW,
+                SyntaxShape::Duration,
+                format!(
+                    "How long a nameserver can take on average before it counts as slow. Default: {}ms",
+                    constants::config::default::SLOW_RTT.as_millis()
+                ),
+                None,
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat the name as fully qualified even without a trailing dot",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check soa example.com",
+                description: "see whether every nameserver has the latest version of the zone",
+                result: None,
+            },
+            Example {
+                example: "dns check soa example.com --slow 100ms | get health",
+                description: "list the nameservers that are down, failing, or slow",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "soa",
+            "serial",
+            "nameserver",
+            "secondary",
+            "propagation",
+            "consistency",
+            "health",
+        ]
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::BTreeSet;
+
+use futures_util::future;
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{Name, RecordType},
+};
+use hickory_resolver::config::Protocol;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckTransport;
+
+/// What came back over one transport.
+struct Answer {
+    rcode: ResponseCode,
+    flags: BTreeSet<&'static str>,
+    records: BTreeSet<String>,
+    size: usize,
+}
+
+impl DnsCheckTransport {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let name: Spanned<String> = call.req(0)?;
+        let span = name.span;
+        let name = serde::util::parse_name(&name.item)
+            .map(|name| config.input_name(name))
+            .map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), span)
+            })?;
+
+        let mut udp = config.clone();
+        udp.protocol.item = Protocol::Udp;
+        let mut tcp = config.clone();
+        tcp.protocol.item = Protocol::Tcp;
+
+        let mut rows = Vec::new();
+
+        for qtype in config.qtypes.item.iter().map(|qtype| qtype.item) {
+            let (udp_answer, tcp_answer) = future::join(
+                ask(plugin, &udp, &name, qtype),
+                ask(plugin, &tcp, &name, qtype),
+            )
+            .await;
+
+            let (status, differences) = compare(&udp_answer, &tcp_answer);
+
+            rows.push(Value::record(
+                record![
+                    "name"        => Value::string(config.display_name(&name), Span::unknown()),
+                    "type"        => Value::string(serde::util::rtype_mnemonic(qtype), Span::unknown()),
+                    "status"      => Value::string(status, Span::unknown()),
+                    "differences" => Value::list(
+                        differences
+                            .into_iter()
+                            .map(|difference| Value::string(difference, Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                    "udp"         => answer_value(&udp_answer),
+                    "tcp"         => answer_value(&tcp_answer),
+                ],
+                call.head,
+            ));
+        }
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+async fn ask(
+    plugin: &Dns,
+    config: &Config,
+    name: &Name,
+    qtype: RecordType,
+) -> Result<Answer, String> {
+    let client = plugin.dns_client(config).await.map_err(|err| err.msg)?;
+
+    let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+    query.set_query_class(config.class.item);
+    let request = serde::Query(query).into_message(config);
+
+    config.throttle().await;
+    let resp = client
+        .send_message(config, request)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let flags = [
+        ("aa", resp.authoritative()),
+        ("tc", resp.truncated()),
+        ("rd", resp.recursion_desired()),
+        ("ra", resp.recursion_available()),
+        ("ad", resp.authentic_data()),
+        ("cd", resp.checking_disabled()),
+    ]
+    .into_iter()
+    .filter_map(|(flag, set)| set.then_some(flag))
+    .collect();
+
+    // TTLs count down between the two answers, so records are compared
+    // without them
+    let records = resp
+        .answers()
+        .iter()
+        .map(|record| {
+            format!(
+                "{} {} {}",
+                record.name(),
+                record.record_type(),
+                record.data().map_or(String::new(), |data| data.to_string())
+            )
+        })
+        .collect();
+
+    Ok(Answer {
+        rcode: resp.response_code(),
+        flags,
+        records,
+        size: resp.as_buffer().len(),
+    })
+}
+
+/// Lists what differs between the UDP and TCP answers. A UDP answer that was
+/// truncated is expected to be missing records and to be smaller, so only
+/// its response code and other flags are compared.
+fn compare(
+    udp: &Result<Answer, String>,
+    tcp: &Result<Answer, String>,
+) -> (&'static str, Vec<String>) {
+    let (udp, tcp) = match (udp, tcp) {
+        (Ok(udp), Ok(tcp)) => (udp, tcp),
+        (Err(udp), Err(tcp)) => {
+            return (
+                "inconclusive",
+                vec![format!("neither transport answered: UDP {udp}; TCP {tcp}")],
+            )
+        }
+        (Err(err), Ok(_)) => return ("mismatch", vec![format!("only TCP answered; UDP {err}")]),
+        (Ok(_), Err(err)) => return ("mismatch", vec![format!("only UDP answered; TCP {err}")]),
+    };
+
+    let mut differences = Vec::new();
+    let truncated = udp.flags.contains("tc");
+
+    if udp.rcode != tcp.rcode {
+        differences.push(format!(
+            "rcode: {} over UDP, {} over TCP",
+            RCode(udp.rcode).mnemonic(),
+            RCode(tcp.rcode).mnemonic()
+        ));
+    }
+
+    let flags = |flags: &BTreeSet<&'static str>| {
+        flags
+            .iter()
+            .filter(|flag| **flag != "tc")
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    if flags(&udp.flags) != flags(&tcp.flags) {
+        differences.push(format!(
+            "flags: [{}] over UDP, [{}] over TCP",
+            flags(&udp.flags).join(" "),
+            flags(&tcp.flags).join(" ")
+        ));
+    }
+
+    if !truncated {
+        for record in udp.records.difference(&tcp.records) {
+            differences.push(format!("only over UDP: {record}"));
+        }
+        for record in tcp.records.difference(&udp.records) {
+            differences.push(format!("only over TCP: {record}"));
+        }
+
+        if udp.size != tcp.size {
+            differences.push(format!(
+                "size: {} bytes over UDP, {} bytes over TCP",
+                udp.size, tcp.size
+            ));
+        }
+    }
+
+    match differences.is_empty() {
+        true => ("ok", differences),
+        false => ("mismatch", differences),
+    }
+}
+
+fn answer_value(answer: &Result<Answer, String>) -> Value {
+    match answer {
+        Ok(answer) => Value::record(
+            record![
+                "rcode"   => RCode(answer.rcode).into_value(),
+                "flags"   => Value::list(
+                    answer
+                        .flags
+                        .iter()
+                        .map(|flag| Value::string(*flag, Span::unknown()))
+                        .collect(),
+                    Span::unknown(),
+                ),
+                "records" => Value::list(
+                    answer
+                        .records
+                        .iter()
+                        .map(|record| Value::string(record, Span::unknown()))
+                        .collect(),
+                    Span::unknown(),
+                ),
+                "size"    => Value::filesize(answer.size as i64, Span::unknown()),
+                "error"   => Value::nothing(Span::unknown()),
+            ],
+            Span::unknown(),
+        ),
+        Err(err) => Value::record(
+            record![
+                "rcode"   => Value::nothing(Span::unknown()),
+                "flags"   => Value::list(vec![], Span::unknown()),
+                "records" => Value::list(vec![], Span::unknown()),
+                "size"    => Value::nothing(Span::unknown()),
+                "error"   => Value::string(err, Span::unknown()),
+            ],
+            Span::unknown(),
+        ),
+    }
+}
+
+impl PluginCommand for DnsCheckTransport {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_TRANSPORT
+    }
+
+    fn description(&self) -> &str {
+        "Compare the answers to the same question over UDP and TCP"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks --server the same question over UDP and over TCP, for each type, and lists the differences in the response code, the header flags, the answer records, and the response size. A middlebox that rewrites or filters DNS often only handles one transport, so the answers differing is a sign of one. A truncated UDP answer is expected to be smaller and missing records, so only its response code and flags are compared. The status is ok if nothing differs, mismatch if something does, and inconclusive if neither transport answered."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name()))
+            .required(constants::flags::NAME, SyntaxShape::String, "Name to check")
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Record type(s) to compare",
+                Some('t'),
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat the name as fully qualified even without a trailing dot",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check transport example.com",
+                description: "see whether UDP and TCP give the same answer",
+                result: None,
+            },
+            Example {
+                example: "dns check transport example.com --type [A AAAA TXT] | where status != ok",
+                description: "list the types whose answers differ between transports",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "transport",
+            "udp",
+            "tcp",
+            "compare",
+            "middlebox",
+            "interference",
+        ]
+    }
+}
@@ -0,0 +1,312 @@
+use std::path::PathBuf;
+
+use futures_util::future;
+use hickory_proto::{
+    rr::{Name, RData, Record},
+    serialize::txt::Parser,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    dns::{
+        commands::axfr,
+        config::Config,
+        constants,
+        zonemd::{self, ZoneMd},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCheckZonemd;
+
+/// A zone to check, with the ZONEMD records at its apex.
+struct Zone {
+    apex: Name,
+    serial: u32,
+    records: Vec<Record>,
+    zonemds: Vec<ZoneMd>,
+}
+
+impl DnsCheckZonemd {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let zone = match (
+            call.opt::<Spanned<String>>(0)?,
+            call.get_flag::<Spanned<PathBuf>>(constants::flags::ZONE)?,
+        ) {
+            (None, Some(path)) => read_zone(engine, call, path)?,
+            (Some(name), None) => transfer_zone(plugin, &config, name).await?,
+            (Some(_), Some(path)) => {
+                return Err(LabeledError::new("conflicting arguments").with_label(
+                    "give either a zone to transfer or a zone file, not both",
+                    path.span,
+                ))
+            }
+            (None, None) => {
+                return Err(LabeledError::new("no zone").with_label(
+                    "Give a zone to transfer or a zone file with --zone",
+                    call.head,
+                ))
+            }
+        };
+
+        if zone.zonemds.is_empty() {
+            return Err(LabeledError::new("no ZONEMD record").with_label(
+                format!("{} has no ZONEMD record at its apex", zone.apex),
+                call.head,
+            ));
+        }
+
+        let rows = zone
+            .zonemds
+            .iter()
+            .map(|zonemd| {
+                let computed = match (zonemd.scheme, zonemd.digest_type()) {
+                    (zonemd::SCHEME_SIMPLE, Some(digest_type)) => {
+                        zonemd::simple_digest(&zone.apex, &zone.records, digest_type)
+                    }
+                    _ => None,
+                };
+
+                let (valid, detail) = match &computed {
+                    _ if zonemd.serial != zone.serial => (
+                        Some(false),
+                        format!(
+                            "the ZONEMD serial {} does not match the SOA serial {}",
+                            zonemd.serial, zone.serial
+                        ),
+                    ),
+                    Some(computed) if *computed == zonemd.digest => {
+                        (Some(true), "the digest matches the zone".into())
+                    }
+                    Some(_) => (Some(false), "the digest does not match the zone".into()),
+                    None => (
+                        None,
+                        format!(
+                            "scheme {} with hash algorithm {} is not supported",
+                            zonemd.scheme_name(),
+                            zonemd.algorithm_name()
+                        ),
+                    ),
+                };
+
+                Value::record(
+                    record![
+                        "zone"      => Value::string(config.display_name(&zone.apex), Span::unknown()),
+                        "serial"    => Value::int(zonemd.serial.into(), Span::unknown()),
+                        "scheme"    => Value::string(zonemd.scheme_name(), Span::unknown()),
+                        "algorithm" => Value::string(zonemd.algorithm_name(), Span::unknown()),
+                        "digest"    => Value::string(data_encoding::HEXLOWER.encode(&zonemd.digest), Span::unknown()),
+                        "computed"  => computed.map_or(Value::nothing(Span::unknown()), |computed| {
+                            Value::string(data_encoding::HEXLOWER.encode(&computed), Span::unknown())
+                        }),
+                        "valid"     => valid.map_or(Value::nothing(Span::unknown()), |valid| {
+                            Value::bool(valid, Span::unknown())
+                        }),
+                        "detail"    => Value::string(detail, Span::unknown()),
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// Transfers the whole zone and holds on to it, since the digest can only be
+/// computed once every record is in.
+async fn transfer_zone(
+    plugin: &Dns,
+    config: &Config,
+    name: Spanned<String>,
+) -> Result<Zone, LabeledError> {
+    let mut apex = Name::from_utf8(&name.item).map_err(|err| {
+        LabeledError::new("invalid name")
+            .with_label(format!("Error parsing name: {}", err), name.span)
+    })?;
+    apex.set_fqdn(true);
+
+    let client = plugin.dns_client(config).await?;
+    let (record_tx, mut record_rx) = mpsc::channel(config.tasks.item);
+
+    let transfer = async {
+        let result = axfr::transfer(config, apex.clone(), &client, name.span, &record_tx).await;
+        drop(record_tx);
+        result
+    };
+    let collect = async {
+        let mut records = Vec::new();
+        while let Some(record) = record_rx.recv().await {
+            records.push(record);
+        }
+        records
+    };
+
+    let (result, records) = future::join(transfer, collect).await;
+    result?;
+    let records = records.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let zonemds = records
+        .iter()
+        .filter(|record| *record.name() == apex)
+        .filter_map(ZoneMd::from_record)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| LabeledError::new("invalid ZONEMD record").with_label(err, name.span))?;
+
+    Ok(Zone {
+        serial: soa_serial(&apex, &records).unwrap_or_default(),
+        apex,
+        records,
+        zonemds,
+    })
+}
+
+fn read_zone(
+    engine: &EngineInterface,
+    call: &EvaluatedCall,
+    path: Spanned<PathBuf>,
+) -> Result<Zone, LabeledError> {
+    let zone_err = |msg: String| LabeledError::new("invalid zone file").with_label(msg, path.span);
+
+    let full_path = PathBuf::from(engine.get_current_dir()?).join(&path.item);
+    let contents = std::fs::read_to_string(&full_path)
+        .map_err(|err| zone_err(format!("could not read {}: {err}", path.item.display())))?;
+
+    let origin = match call.get_flag::<Spanned<String>>(constants::flags::ORIGIN)? {
+        Some(origin) => Some(Name::from_utf8(&origin.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing origin: {}", err), origin.span)
+        })?),
+        None => None,
+    };
+
+    let extracted = zonemd::extract(&contents).map_err(zone_err)?;
+    let (_, rrsets) = Parser::new(extracted.rest, Some(full_path), origin)
+        .parse()
+        .map_err(|err| zone_err(err.to_string()))?;
+
+    let mut records: Vec<Record> = rrsets
+        .into_values()
+        .flat_map(|rrset| rrset.records_without_rrsigs().cloned().collect::<Vec<_>>())
+        .collect();
+
+    // the TTL goes into the digest, so the SOA needs the one it was given
+    if let Some(ttl) = extracted.soa_ttl {
+        for record in &mut records {
+            if let Some(RData::SOA(_)) = record.data() {
+                record.set_ttl(ttl);
+            }
+        }
+    }
+
+    let (apex, serial) = records
+        .iter()
+        .find_map(|record| match record.data() {
+            Some(RData::SOA(soa)) => Some((record.name().clone(), soa.serial())),
+            _ => None,
+        })
+        .ok_or_else(|| zone_err("the zone file has no SOA record".into()))?;
+
+    Ok(Zone {
+        apex,
+        serial,
+        records,
+        zonemds: extracted.zonemds,
+    })
+}
+
+fn soa_serial(apex: &Name, records: &[Record]) -> Option<u32> {
+    records.iter().find_map(|record| match record.data() {
+        Some(RData::SOA(soa)) if record.name() == apex => Some(soa.serial()),
+        _ => None,
+    })
+}
+
+impl PluginCommand for DnsCheckZonemd {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CHECK_ZONEMD
+    }
+
+    fn description(&self) -> &str {
+        "Verify a zone's ZONEMD digest"
+    }
+
+    fn extra_description(&self) -> &str {
+        "A ZONEMD record (RFC 8976) at the apex holds a digest of the whole zone, so that a copy of it can be checked for completeness and tampering. The zone is transferred from --server, or read from a zone file with --zone, and a row is returned per ZONEMD record with its scheme and hash algorithm, the digest it holds and the one computed, and whether they match. Only the SIMPLE scheme with SHA-384 or SHA-512 can be checked. Zone files are read with the same parser as `dns serve`, which can't read DNSSEC records, so transfer signed zones instead."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::super::connection_flags(Signature::build(self.name()))
+            .optional(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Zone to transfer and check",
+            )
+            .named(
+                constants::flags::ZONE,
+                SyntaxShape::Filepath,
+                "Zone file to check instead",
+                Some('z'),
+            )
+            .named(
+                constants::flags::ORIGIN,
+                SyntaxShape::String,
+                "Origin for relative names in the zone file, if it has no $ORIGIN",
+                None,
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Zone class",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns check zonemd --server 192.0.2.53 example.com",
+                description: "transfer a zone and verify its digest",
+                result: None,
+            },
+            Example {
+                example: "dns check zonemd --zone root.zone",
+                description: "verify a copy of the root zone",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "zonemd", "digest", "zone", "rfc8976", "axfr"]
+    }
+}
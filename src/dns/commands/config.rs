@@ -0,0 +1,241 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{record, Example, LabeledError, PipelineData, Signature, Span, Value};
+
+use crate::{
+    dns::{
+        config::{Config, SystemOptions},
+        constants::{self, flags},
+        serde::{util, DnssecMode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsConfigShow;
+
+impl DnsConfigShow {
+    fn run_impl(
+        &self,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+    ) -> Result<PipelineData, LabeledError> {
+        let (config, given) = Config::from_nu_with_sources(engine, call)?;
+        let source = |key: &str| {
+            given
+                .iter()
+                .find(|(name, _, _)| name == key)
+                .map(|(_, _, source)| source.clone())
+        };
+
+        // what fills in for settings that weren't given: the system's
+        // resolver config when there is no --server, or the plugin's defaults
+        let server_given = source(flags::SERVER).is_some();
+        let system_servers = !server_given
+            && hickory_resolver::system_conf::read_system_conf()
+                .is_ok_and(|(system, _)| !system.name_servers().is_empty());
+        let system_options = !server_given && SystemOptions::read().is_some();
+        let fallback = |key: &str, from_system: bool| match source(key) {
+            Some(source) => source.to_string(),
+            None if from_system => "system".into(),
+            None => "default".into(),
+        };
+
+        let row = |key: &str, value: Value, source: String| {
+            Value::record(
+                record![
+                    "key"    => Value::string(key, Span::unknown()),
+                    "value"  => value,
+                    "source" => Value::string(source, Span::unknown()),
+                ],
+                Span::unknown(),
+            )
+        };
+        let string = |val: String| Value::string(val, Span::unknown());
+        let bool = |val: bool| Value::bool(val, Span::unknown());
+        let path = |val: &Option<nu_protocol::Spanned<std::path::PathBuf>>| {
+            val.as_ref()
+                .map_or(Value::nothing(Span::unknown()), |path| {
+                    string(path.item.display().to_string())
+                })
+        };
+
+        let mut rows = vec![
+            row(
+                flags::SERVER,
+                string(config.server.item.to_string()),
+                fallback(flags::SERVER, system_servers),
+            ),
+            row(
+                "server-addresses",
+                Value::list(
+                    config
+                        .server_addrs
+                        .iter()
+                        .map(|addr| string(addr.to_string()))
+                        .collect(),
+                    Span::unknown(),
+                ),
+                fallback(flags::SERVER, system_servers),
+            ),
+            row(
+                flags::PROTOCOL,
+                string(config.protocol.item.to_string()),
+                fallback(flags::PROTOCOL, system_servers),
+            ),
+            row(
+                flags::DNS_NAME,
+                config
+                    .dns_name
+                    .as_ref()
+                    .map_or(Value::nothing(Span::unknown()), |name| {
+                        string(name.item.clone())
+                    }),
+                fallback(flags::DNS_NAME, false),
+            ),
+            row(
+                flags::DNSSEC,
+                string(
+                    match config.dnssec_mode.item {
+                        DnssecMode::None => "none",
+                        DnssecMode::Strict => "strict",
+                        DnssecMode::Opportunistic => "opportunistic",
+                    }
+                    .into(),
+                ),
+                fallback(flags::DNSSEC, false),
+            ),
+            row(
+                flags::TIMEOUT,
+                util::std_to_duration(config.timeout.item),
+                fallback(flags::TIMEOUT, system_options),
+            ),
+            row(
+                flags::ATTEMPTS,
+                Value::int(config.attempts.item as i64, Span::unknown()),
+                fallback(flags::ATTEMPTS, system_options),
+            ),
+            row(
+                flags::ROTATE,
+                bool(config.rotate.item),
+                fallback(flags::ROTATE, system_options),
+            ),
+            row(
+                flags::TASKS,
+                Value::int(config.tasks.item as i64, Span::unknown()),
+                fallback(flags::TASKS, false),
+            ),
+            row(
+                flags::IPV4,
+                bool(config.ipv4.item),
+                fallback(flags::IPV4, false),
+            ),
+            row(
+                flags::IPV6,
+                bool(config.ipv6.item),
+                fallback(flags::IPV6, false),
+            ),
+            row(
+                flags::CACHE,
+                bool(config.cache.item),
+                fallback(flags::CACHE, false),
+            ),
+            row(
+                flags::CACHE_FILE,
+                path(&config.cache_file),
+                fallback(flags::CACHE_FILE, false),
+            ),
+            row(
+                flags::LOG_FILE,
+                path(&config.log_file),
+                fallback(flags::LOG_FILE, false),
+            ),
+            row(
+                flags::LOG_LEVEL,
+                config
+                    .log_level
+                    .as_ref()
+                    .map_or(Value::nothing(Span::unknown()), |level| {
+                        string(level.item.clone())
+                    }),
+                fallback(flags::LOG_LEVEL, false),
+            ),
+        ];
+
+        // anything else that was set, as it was given
+        for (key, value, source) in &given {
+            let shown = rows.iter().any(|row| {
+                row.get_data_by_key("key")
+                    .is_some_and(|shown| shown.as_str().is_ok_and(|shown| shown == key))
+            });
+
+            // a secret is only shown as having been given
+            let value = match key.as_str() {
+                flags::TSIG_SECRET => string("(redacted)".into()),
+                _ => value.clone(),
+            };
+
+            if !shown {
+                rows.push(row(key, value, source.to_string()));
+            }
+        }
+
+        Ok(PipelineData::Value(Value::list(rows, call.head), None))
+    }
+}
+
+impl PluginCommand for DnsConfigShow {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.run_impl(engine, call)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::CONFIG_SHOW
+    }
+
+    fn description(&self) -> &str {
+        "Show the configuration queries would use, and where each setting came from"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Merges the command's flags, the NU_PLUGIN_DNS_* environment variables, and the plugin config, in that order of precedence, the same way every other command does, and returns a row per setting with its effective value and its source: flag, env with the variable's name, plugin config, system for what comes from the system's resolver config without --server, or default. The main settings are always listed; any others are listed when they are set. Takes the connection flags, so the effect of one can be tried out."
+    }
+
+    fn signature(&self) -> Signature {
+        super::connection_flags(Signature::build(self.name()))
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns config show",
+                description: "see which server queries go to, and why",
+                result: None,
+            },
+            Example {
+                example: "dns config show | where source != default",
+                description: "list the settings that were changed from their defaults",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "config",
+            "settings",
+            "debug",
+            "server",
+            "env",
+            "resolv.conf",
+        ]
+    }
+}
@@ -0,0 +1,201 @@
+use hickory_proto::rr::{
+    rdata::svcb::{Alpn, IpHint, SvcParamValue, Unknown},
+    RData, RecordType,
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, error::DnsError, serde, special},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsDdr;
+
+/// The SvcParamKey of the URI template a DoH server takes queries at (RFC
+/// 9461 section 5), which hickory doesn't know by name.
+const DOHPATH_KEY: u16 = 7;
+
+impl DnsDdr {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine, call)?;
+
+        if let Some(server) = call.opt::<Value>(0)? {
+            config = config.with_server(&server)?;
+        }
+
+        let name = Name::from_ascii(special::DDR_NAME).expect("DDR name should parse");
+        let mut query = hickory_proto::op::Query::query(name, RecordType::SVCB);
+        query.set_query_class(config.class.item);
+        let request = serde::Query(query).into_message(&config);
+
+        let client = plugin.dns_client(&config).await?;
+        config.throttle().await;
+        let resp = client
+            .send_message(&config, request)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, call.head))?;
+
+        let mut rows: Vec<(u16, Value)> = resp
+            .answers()
+            .iter()
+            .filter_map(|record| match record.data()? {
+                RData::SVCB(svcb) => Some(svcb),
+                _ => None,
+            })
+            .map(|svcb| {
+                let mut alpn = Vec::new();
+                let mut port = None;
+                let mut dohpath = None;
+                let mut addresses = Vec::new();
+
+                for (key, value) in svcb.svc_params() {
+                    match value {
+                        SvcParamValue::Alpn(Alpn(ids)) => alpn.clone_from(ids),
+                        SvcParamValue::Port(num) => port = Some(*num),
+                        SvcParamValue::Ipv4Hint(IpHint(ips)) => {
+                            addresses.extend(ips.iter().map(|ip| ip.to_string()))
+                        }
+                        SvcParamValue::Ipv6Hint(IpHint(ips)) => {
+                            addresses.extend(ips.iter().map(|ip| ip.to_string()))
+                        }
+                        SvcParamValue::Unknown(Unknown(bytes))
+                            if u16::from(*key) == DOHPATH_KEY =>
+                        {
+                            dohpath = Some(String::from_utf8_lossy(bytes).into_owned())
+                        }
+                        _ => {}
+                    }
+                }
+
+                // an ALPN names the transport; a port not given is that
+                // transport's default
+                let transports: Vec<(&str, u16)> = alpn
+                    .iter()
+                    .filter_map(|id| match id.as_str() {
+                        "dot" => Some(("tls", 853)),
+                        "doq" => Some(("quic", 853)),
+                        "h2" | "h3" | "http/1.1" => Some(("https", 443)),
+                        _ => None,
+                    })
+                    .fold(Vec::new(), |mut transports, transport| {
+                        if !transports.contains(&transport) {
+                            transports.push(transport);
+                        }
+                        transports
+                    });
+
+                let strings = |vals: Vec<String>| {
+                    Value::list(
+                        vals.into_iter()
+                            .map(|val| Value::string(val, Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    )
+                };
+                let optional = |val: Option<Value>| val.unwrap_or(Value::nothing(Span::unknown()));
+
+                let row = Value::record(
+                    record![
+                        "priority"   => Value::int(svcb.svc_priority() as i64, Span::unknown()),
+                        "target"     => Value::string(config.display_name(svcb.target_name()), Span::unknown()),
+                        "transports" => strings(transports.iter().map(|(name, _)| (*name).into()).collect()),
+                        "alpn"       => strings(alpn),
+                        "port"       => optional(
+                            port.or(transports.first().map(|(_, port)| *port))
+                                .map(|port| Value::int(port as i64, Span::unknown())),
+                        ),
+                        "dohpath"    => optional(dohpath.map(|path| Value::string(path, Span::unknown()))),
+                        "addresses"  => strings(addresses),
+                    ],
+                    call.head,
+                );
+
+                (svcb.svc_priority(), row)
+            })
+            .collect();
+
+        // most preferred first, as clients are to try them
+        rows.sort_by_key(|(priority, _)| *priority);
+
+        Ok(PipelineData::Value(
+            Value::list(rows.into_iter().map(|(_, row)| row).collect(), call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl PluginCommand for DnsDdr {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::DDR
+    }
+
+    fn description(&self) -> &str {
+        "Discover the encrypted resolvers a resolver designates (DDR, RFC 9462)"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks the resolver for the SVCB records of _dns.resolver.arpa, which a resolver answers itself with the DNS over TLS, HTTPS, or QUIC servers that stand in for it. Returns a row per designated resolver, most preferred first, with its name, the transports its ALPN values stand for, the port, which defaults to the transport's, the URI path a DoH server takes queries at, and any address hints. An empty list means the resolver designates none. RFC 9462 only has a client trust a designation once the designated resolver's certificate covers the address of the resolver that was asked, which this doesn't check."
+    }
+
+    fn signature(&self) -> Signature {
+        super::connection_flags(Signature::build(self.name())).optional(
+            constants::flags::NAMESERVER,
+            SyntaxShape::String,
+            "Resolver to ask. Default: --server",
+        )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns ddr 1.1.1.1",
+                description: "find the encrypted transports a public resolver offers",
+                result: None,
+            },
+            Example {
+                example: "dns ddr | where 'https' in transports",
+                description: "find a DNS over HTTPS server to use instead of the system resolver",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "ddr",
+            "designated",
+            "resolver.arpa",
+            "svcb",
+            "doh",
+            "dot",
+            "doq",
+            "encrypted",
+        ]
+    }
+}
@@ -0,0 +1,208 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, SyntaxShape, Value};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants, serde,
+        wire::{self, Layout},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsDecode;
+
+impl DnsDecode {
+    fn decode(config: &Config, offsets: bool, value: &Value) -> Result<Value, LabeledError> {
+        let span = value.span();
+        let bytes = match value {
+            Value::Binary { val, .. } => val.clone(),
+            value => {
+                return Err(LabeledError::new("invalid input").with_label(
+                    format!("expected a DNS message as binary, not {}", value.get_type()),
+                    span,
+                ))
+            }
+        };
+
+        // a message that is too broken to lay out may still be partly
+        // salvageable, just without the offsets
+        let layout = match (offsets, Layout::read(&bytes)) {
+            (false, _) => None,
+            (true, Ok(layout)) => Some(layout),
+            (true, Err(_)) if config.lenient.item => None,
+            (true, Err(err)) => {
+                return Err(LabeledError::new("malformed message").with_label(err, span))
+            }
+        };
+
+        let problems = match config.lenient.item {
+            true => wire::problems(&bytes),
+            false => Vec::new(),
+        };
+
+        let message = serde::Message::from_bytes(bytes, config.lenient.item)
+            .map_err(|err| {
+                LabeledError::new("malformed message")
+                    .with_label(format!("Error decoding message: {err}"), span)
+            })?
+            .into_value(config)?;
+        let message = wire::insert_problems(message, problems, config)?;
+
+        match layout {
+            Some(layout) => annotate(message, layout),
+            None => Ok(message),
+        }
+    }
+}
+
+/// Adds a `wire` column to the question and to each record, saying where it
+/// is in the message and how its names were encoded. The records have to be
+/// in the order they came in, so this relies on `--sort`, `--dedup`, and
+/// `--group-rrsets` being off.
+fn annotate(message: Value, layout: Layout) -> Result<Value, LabeledError> {
+    let span = message.span();
+    let mut message = message.into_record()?;
+
+    if let (Some(Value::Record { val, .. }), Some(query)) = (
+        message.get_mut("question"),
+        layout.queries.into_iter().last(),
+    ) {
+        val.to_mut().push("wire", query.into_value());
+    }
+
+    for (section, records) in [
+        ("answer", layout.answers),
+        ("authority", layout.name_servers),
+        ("additional", layout.additionals),
+    ] {
+        if let Some(Value::List { vals, .. }) = message.get_mut(section) {
+            for (row, record) in vals.iter_mut().zip(records) {
+                if let Value::Record { val, .. } = row {
+                    val.to_mut().push("wire", record.into_value());
+                }
+            }
+        }
+    }
+
+    Ok(Value::record(message, span))
+}
+
+impl PluginCommand for DnsDecode {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine, call)?;
+        let offsets = call.has_flag(constants::flags::OFFSETS)?;
+
+        if offsets {
+            config.sort.item = false;
+            config.dedup.item = false;
+            config.group_rrsets.item = false;
+        }
+
+        let message: Option<Value> = call.opt(0)?;
+
+        match (message, input) {
+            (
+                Some(message),
+                PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _),
+            ) => Ok(PipelineData::Value(
+                Self::decode(&config, offsets, &message)?,
+                None,
+            )),
+            (None, PipelineData::Value(list @ Value::List { .. }, _)) => {
+                let span = list.span();
+                let messages = list
+                    .into_list()?
+                    .iter()
+                    .map(|message| Self::decode(&config, offsets, message))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(PipelineData::Value(Value::list(messages, span), None))
+            }
+            (None, PipelineData::Value(message, _)) => Ok(PipelineData::Value(
+                Self::decode(&config, offsets, &message)?,
+                None,
+            )),
+            (None, PipelineData::ByteStream(stream, _)) => {
+                let span = stream.span();
+                let message = Value::binary(stream.into_bytes()?, span);
+
+                Ok(PipelineData::Value(
+                    Self::decode(&config, offsets, &message)?,
+                    None,
+                ))
+            }
+            (None, _) => Err(LabeledError::new("no message")
+                .with_label("Give a DNS message as binary or pipe it in", call.head)),
+            (Some(_), input) => Err(LabeledError::new("ambiguous input").with_label(
+                "Input should either be a positional arg or piped, but not both",
+                input.span().unwrap_or(call.head),
+            )),
+        }
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::DECODE
+    }
+
+    fn description(&self) -> &str {
+        "Decode a DNS message from its wire format"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Takes a message as binary, e.g. a UDP payload from a packet capture, and returns it in the same shape as the responses from `dns query`. With --offsets, the question and each record get a wire column with their byte offset and length, and, for their owner name and any names in their rdata, where the name is, whether it was compressed, and the offsets the compression pointers pointed to. OPT, SIG, and TSIG records, which are shown apart from the additional section, aren't annotated. With --lenient, a malformed message is parsed as far as it can be: records whose rdata doesn't parse are shown with it in the generic \\# format and an error column, and where parsing had to stop, e.g. because the message is shorter than its header says, a row with just the error marks the spot."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional("message", SyntaxShape::Binary, "DNS message in wire format")
+            .switch(
+                constants::flags::OFFSETS,
+                "Annotate the question and records with where they are in the message and how their names were compressed",
+                Some('o'),
+            )
+            .switch(
+                constants::flags::WITH_QUESTION,
+                "Add the question to every record, so each still says what was asked after the sections are flattened",
+                None,
+            )
+            .switch(
+                constants::flags::LENIENT,
+                "Salvage what can be parsed from a malformed message instead of failing, with an error column on each record that couldn't be",
+                None,
+            )
+            .switch(
+                constants::flags::CODE,
+                "Return code fields with both string and numeric representations",
+                Some('c'),
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "open --raw response.bin | dns decode",
+                description: "decode a message saved to a file",
+                result: None,
+            },
+            Example {
+                example: "open --raw response.bin | dns decode --offsets | get answer.wire.name",
+                description: "see which owner names in the answer were compressed",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "decode", "wire", "packet", "compression", "parse"]
+    }
+}
@@ -0,0 +1,358 @@
+use std::path::PathBuf;
+
+use hickory_proto::rr::{
+    dnssec::{rdata::DNSSECRData, Algorithm, DigestType},
+    Name, RData, RecordType,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        dnssec::{self, DnsKey},
+        error::DnsError,
+        http,
+        serde::{util, DnssecMode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsDnssecKeytag;
+
+#[derive(Debug)]
+pub struct DnsDnssecUpdateAnchors;
+
+impl PluginCommand for DnsDnssecKeytag {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut keys: Vec<Value> = call.rest(0)?;
+
+        match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => {}
+            input if keys.is_empty() => keys.extend(input),
+            input => {
+                return Err(LabeledError::new("ambiguous input").with_label(
+                    "Input should either be positional args or piped, but not both",
+                    input.span().unwrap_or(call.head),
+                ))
+            }
+        }
+
+        let mut keys = keys
+            .iter()
+            .map(DnsKey::try_from_value)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(flags) = call.get_flag_value(constants::flags::FLAGS) {
+            keys.push(DnsKey::try_from_value(&Value::record(
+                record![
+                    "flags"      => flags,
+                    "algorithm"  => call.get_flag_value(constants::flags::ALGORITHM).unwrap_or(Value::nothing(call.head)),
+                    "public_key" => call.get_flag_value(constants::flags::PUBLIC_KEY).unwrap_or(Value::nothing(call.head)),
+                ],
+                call.head,
+            ))?);
+        }
+
+        let rows = keys
+            .into_iter()
+            .map(|key| {
+                Value::record(
+                    record![
+                        "name"               => key
+                            .name
+                            .as_ref()
+                            .map_or(Value::nothing(Span::unknown()), |name| {
+                                Value::string(name, Span::unknown())
+                            }),
+                        "flags"              => Value::int(key.flags.into(), Span::unknown()),
+                        "algorithm"          => Value::int(key.algorithm.into(), Span::unknown()),
+                        "secure_entry_point" => Value::bool(key.secure_entry_point(), Span::unknown()),
+                        "key_tag"            => Value::int(key.key_tag().into(), Span::unknown()),
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        Ok(PipelineData::Value(Value::list(rows, call.head), None))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::DNSSEC_KEYTAG
+    }
+
+    fn description(&self) -> &str {
+        "Calculate the key tags of DNSKEY records"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Key tags are how DS and RRSIG records refer to the DNSKEY they belong to, computed per RFC 4034 appendix B. Keys can be DNSKEY rows as `dns query` returns them, presentation format strings such as zone file lines, or given with --flags, --algorithm, and --public-key."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        Signature::build(self.name())
+            .rest(
+                constants::flags::KEY,
+                SyntaxShape::Any,
+                "DNSKEY records, in presentation format or as records",
+            )
+            .named(
+                constants::flags::FLAGS,
+                SyntaxShape::Int,
+                "Flags of a key given by its fields, e.g. 257 for a KSK",
+                None,
+            )
+            .named(
+                constants::flags::ALGORITHM,
+                SyntaxShape::Any,
+                "Algorithm of a key given by its fields, by number or name",
+                None,
+            )
+            .named(
+                constants::flags::PUBLIC_KEY,
+                SyntaxShape::Any,
+                "Public key of a key given by its fields, as base64 or binary",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns query --type DNSKEY example.com | get 0.answer | where type == DNSKEY | dns dnssec keytag",
+                description: "key tags of the keys a zone publishes",
+                result: None,
+            },
+            Example {
+                example: "open example.com.zone | lines | find DNSKEY | dns dnssec keytag",
+                description: "key tags of the keys in a zone file",
+                result: None,
+            },
+            Example {
+                example: "dns dnssec keytag --flags 257 --algorithm 8 --public-key AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=",
+                description: "key tag of the root zone's 2017 KSK, 20326",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "dnssec", "dnskey", "ds", "key tag", "keytag"]
+    }
+}
+
+impl DnsDnssecUpdateAnchors {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine, call)?;
+        let Some(path) = config.trust_anchors.clone() else {
+            return Err(LabeledError::new("no trust anchor file").with_label(
+                "Give a file to save the anchors to with --trust-anchors, or set trust-anchors in the plugin config",
+                call.head,
+            ));
+        };
+        let full_path = PathBuf::from(engine.get_current_dir()?).join(&path.item);
+
+        let url = call
+            .get_flag::<Spanned<String>>(constants::flags::URL)?
+            .unwrap_or_else(|| Spanned {
+                item: constants::config::default::ROOT_ANCHORS_URL.into(),
+                span: call.head,
+            });
+
+        let xml = http::get(&url.item, config.timeout.item)
+            .await
+            .map_err(|err| {
+                LabeledError::new("could not fetch trust anchors").with_label(err, url.span)
+            })?;
+        let anchors = dnssec::parse_root_anchors(&String::from_utf8_lossy(&xml))
+            .map_err(|err| LabeledError::new("invalid trust anchors").with_label(err, url.span))?;
+
+        // the anchors are only digests, so the keys themselves are taken from
+        // the root's DNSKEY set, with its signatures, which the validator
+        // can't check yet since it would need the very anchors being fetched.
+        // The set is checked here instead, against the keys that match.
+        config.dnssec_mode.item = DnssecMode::None;
+        config.dnssec_ok.item = true;
+        let client = plugin.dns_client(&config).await?;
+        let root_records = client
+            .answers(&config, &Name::root(), RecordType::DNSKEY)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, call.head))?;
+        let root_keys: Vec<DnsKey> = root_records
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::DNSSEC(DNSSECRData::DNSKEY(key))) => Some(DnsKey::from(key)),
+                _ => None,
+            })
+            .collect();
+
+        let now = chrono::DateTime::<chrono::Utc>::from(std::time::SystemTime::now());
+        let mut trusted: Vec<DnsKey> = Vec::new();
+
+        let rows = anchors
+            .iter()
+            .map(|anchor| {
+                let valid = anchor.valid_at(now);
+                let key = root_keys.iter().find(|key| anchor.matches(key));
+
+                if let (true, Some(key)) = (valid, key) {
+                    if !trusted.contains(key) {
+                        trusted.push(key.clone());
+                    }
+                }
+
+                let date = |date: Option<chrono::DateTime<chrono::FixedOffset>>| {
+                    date.map_or(Value::nothing(Span::unknown()), |date| {
+                        Value::date(date, Span::unknown())
+                    })
+                };
+
+                Value::record(
+                    record![
+                        "id"          => Value::string(&anchor.id, Span::unknown()),
+                        "key_tag"     => Value::int(anchor.key_tag.into(), Span::unknown()),
+                        "algorithm"   => Value::string(Algorithm::from_u8(anchor.algorithm).to_string(), Span::unknown()),
+                        "digest_type" => Value::string(
+                            DigestType::from_u8(anchor.digest_type)
+                                .map_or_else(|_| anchor.digest_type.to_string(), |digest_type| {
+                                    util::digest_type_name(digest_type).into()
+                                }),
+                            Span::unknown(),
+                        ),
+                        "digest"      => Value::string(data_encoding::HEXLOWER.encode(&anchor.digest), Span::unknown()),
+                        "valid_from"  => date(anchor.valid_from),
+                        "valid_until" => date(anchor.valid_until),
+                        "valid"       => Value::bool(valid, Span::unknown()),
+                        "published"   => Value::bool(key.is_some(), Span::unknown()),
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        if trusted.is_empty() {
+            return Err(LabeledError::new("no usable trust anchors").with_label(
+                format!(
+                    "none of the currently valid anchors from {} match a key in the root's DNSKEY set",
+                    url.item
+                ),
+                url.span,
+            ));
+        }
+
+        // a key whose digest matches is only the key IANA published if the
+        // set it came in is signed with it, and not made up along the way
+        let now_secs = now.timestamp() as u32;
+        dnssec::verify_dnskey_set(&Name::root(), &root_records, &trusted, now_secs).map_err(
+            |err| {
+                LabeledError::new("could not validate root keys").with_label(
+                    format!("{err}; {} was not changed", path.item.display()),
+                    call.head,
+                )
+            },
+        )?;
+
+        let save_err = |err: std::io::Error| {
+            LabeledError::new("could not save trust anchors").with_label(
+                format!("could not write {}: {err}", path.item.display()),
+                path.span,
+            )
+        };
+
+        if let Some(dir) = full_path.parent() {
+            std::fs::create_dir_all(dir).map_err(save_err)?;
+        }
+        std::fs::write(&full_path, dnssec::format_anchor_file(&trusted, &url.item))
+            .map_err(save_err)?;
+
+        // validating clients were set up with the old anchors
+        plugin.pool.clear().await;
+
+        Ok(PipelineData::Value(Value::list(rows, call.head), None))
+    }
+}
+
+impl PluginCommand for DnsDnssecUpdateAnchors {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::DNSSEC_UPDATE_ANCHORS
+    }
+
+    fn description(&self) -> &str {
+        "Fetch the root zone's DNSSEC trust anchors from IANA and save them for validation"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Downloads IANA's trust anchor file (RFC 7958) over HTTPS and looks up the root's DNSKEY set with its signatures. Keys whose digest matches a currently valid anchor are saved to the --trust-anchors file, once the set is found to be signed by one of them, and validation uses them from then on instead of the anchors built into the plugin. The file is trusted because of the HTTPS connection it came over; its detached S/MIME signature (root-anchors.p7s) is not checked. Set trust-anchors in the plugin config so that every command picks the file up. Returns a row per anchor in the file, saying whether it is valid now and whether the root publishes its key."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .named(
+                constants::flags::TRUST_ANCHORS,
+                SyntaxShape::Filepath,
+                "File to save the anchors to. Default: trust-anchors from the plugin config",
+                None,
+            )
+            .named(
+                constants::flags::URL,
+                SyntaxShape::String,
+                format!(
+                    "Where to fetch the trust anchor file from. Default: {}",
+                    constants::config::default::ROOT_ANCHORS_URL
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns dnssec update-anchors --trust-anchors ~/.config/nushell/root-anchors.zone",
+                description: "fetch the root trust anchors and save them",
+                result: None,
+            },
+            Example {
+                example: "$env.config.plugins.dns.trust-anchors = '~/.config/nushell/root-anchors.zone'; dns dnssec update-anchors",
+                description: "keep the anchors where every command will use them",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "dnssec", "trust anchor", "root", "ksk", "rfc7958"]
+    }
+}
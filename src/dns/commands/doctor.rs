@@ -0,0 +1,440 @@
+use std::net::SocketAddr;
+
+use futures_util::future;
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{Name, RecordType},
+    xfer::DnsResponse,
+};
+use hickory_resolver::config::Protocol;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{record, Example, LabeledError, PipelineData, Signature, Span, Value};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsDoctor;
+
+/// How a check came out.
+#[derive(Clone, Copy)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// What one check found, and what to do about it if anything.
+struct Finding {
+    check: &'static str,
+    status: Status,
+    detail: String,
+    suggestion: Option<&'static str>,
+}
+
+impl Finding {
+    fn pass(check: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Pass,
+            detail: detail.into(),
+            suggestion: None,
+        }
+    }
+
+    fn warn(check: &'static str, detail: impl Into<String>, suggestion: &'static str) -> Self {
+        Self {
+            check,
+            status: Status::Warn,
+            detail: detail.into(),
+            suggestion: Some(suggestion),
+        }
+    }
+
+    fn fail(check: &'static str, detail: impl Into<String>, suggestion: &'static str) -> Self {
+        Self {
+            check,
+            status: Status::Fail,
+            detail: detail.into(),
+            suggestion: Some(suggestion),
+        }
+    }
+
+    fn into_value(self, span: Span) -> Value {
+        let status = match self.status {
+            Status::Pass => "pass",
+            Status::Warn => "warn",
+            Status::Fail => "fail",
+        };
+
+        Value::record(
+            record![
+                "check"      => Value::string(self.check, Span::unknown()),
+                "status"     => Value::string(status, Span::unknown()),
+                "detail"     => Value::string(self.detail, Span::unknown()),
+                "suggestion" => self.suggestion.map_or(
+                    Value::nothing(Span::unknown()),
+                    |suggestion| Value::string(suggestion, Span::unknown()),
+                ),
+            ],
+            span,
+        )
+    }
+}
+
+impl DnsDoctor {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+
+        // the checks are of plain DNS, so a server reached over TLS, HTTPS,
+        // or QUIC is checked on port 53
+        let port = match config.protocol.item {
+            Protocol::Udp | Protocol::Tcp => config.server.item.port(),
+            _ => constants::config::default_port(Protocol::Udp),
+        };
+        let addr = SocketAddr::new(config.server.item.ip(), port);
+
+        let mut udp = config.with_addr(addr);
+        udp.protocol.item = Protocol::Udp;
+        let mut tcp = udp.clone();
+        tcp.protocol.item = Protocol::Tcp;
+        let mut dnssec = udp.clone();
+        dnssec.dnssec_ok.item = true;
+
+        let root = Name::root();
+        let known_good =
+            Name::from_ascii(constants::doctor::KNOWN_GOOD).expect("name should parse");
+        let signed = Name::from_ascii(constants::doctor::SIGNED).expect("name should parse");
+        let bogus = Name::from_ascii(constants::doctor::BOGUS).expect("name should parse");
+
+        let ((udp_root, tcp_root, large), (known_good, signed, bogus)) = future::join(
+            future::join3(
+                ask(plugin, &udp, &root, RecordType::NS),
+                ask(plugin, &tcp, &root, RecordType::NS),
+                ask(plugin, &dnssec, &root, RecordType::DNSKEY),
+            ),
+            future::join3(
+                ask(plugin, &udp, &known_good, RecordType::A),
+                ask(plugin, &dnssec, &signed, RecordType::A),
+                ask(plugin, &dnssec, &bogus, RecordType::A),
+            ),
+        )
+        .await;
+
+        let findings = vec![
+            reachable(&udp, &udp_root, &tcp_root),
+            transport(&udp_root, &tcp_root),
+            edns(&udp_root),
+            large_response(&large, tcp_root.is_ok()),
+            root_query(&udp_root),
+            known_good_name(&known_good),
+            validation(&signed, &bogus),
+        ];
+
+        Ok(PipelineData::Value(
+            Value::list(
+                findings
+                    .into_iter()
+                    .map(|finding| finding.into_value(call.head))
+                    .collect(),
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+async fn ask(
+    plugin: &Dns,
+    config: &Config,
+    name: &Name,
+    qtype: RecordType,
+) -> Result<DnsResponse, String> {
+    let client = plugin.dns_client(config).await.map_err(|err| err.msg)?;
+
+    let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+    query.set_query_class(config.class.item);
+    let request = serde::Query(query).into_message(config);
+
+    config.throttle().await;
+    client
+        .send_message(config, request)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+fn reachable(
+    config: &Config,
+    udp: &Result<DnsResponse, String>,
+    tcp: &Result<DnsResponse, String>,
+) -> Finding {
+    const CHECK: &str = "reachable";
+
+    match (udp, tcp) {
+        (Err(udp), Err(_)) => Finding::fail(
+            CHECK,
+            format!("{} didn't answer over UDP or TCP: {udp}", config.server.item),
+            "check the network connection and the resolver's address, or try another resolver with --server",
+        ),
+        _ => Finding::pass(CHECK, format!("{} answers queries", config.server.item)),
+    }
+}
+
+fn transport(udp: &Result<DnsResponse, String>, tcp: &Result<DnsResponse, String>) -> Finding {
+    const CHECK: &str = "udp-and-tcp";
+
+    match (udp, tcp) {
+        (Ok(_), Ok(_)) => Finding::pass(CHECK, "the resolver answers over both UDP and TCP"),
+        (Ok(_), Err(err)) => Finding::warn(
+            CHECK,
+            format!("the resolver answers over UDP but not TCP: {err}"),
+            "allow TCP port 53 to the resolver; responses too big for UDP are retried over TCP",
+        ),
+        (Err(err), Ok(_)) => Finding::warn(
+            CHECK,
+            format!("the resolver answers over TCP but not UDP: {err}"),
+            "allow UDP port 53 to the resolver, or use --protocol tcp",
+        ),
+        (Err(_), Err(_)) => Finding::fail(
+            CHECK,
+            "the resolver answers over neither UDP nor TCP",
+            "check that a firewall isn't blocking port 53",
+        ),
+    }
+}
+
+fn edns(udp: &Result<DnsResponse, String>) -> Finding {
+    const CHECK: &str = "edns";
+
+    match udp {
+        Err(_) => Finding::fail(
+            CHECK,
+            "couldn't be checked, since the resolver didn't answer over UDP",
+            "fix reaching the resolver over UDP first",
+        ),
+        Ok(resp) => match resp.extensions() {
+            None => Finding::warn(
+                CHECK,
+                "the response has no EDNS, so it is limited to 512 bytes over UDP",
+                "the resolver or a middlebox strips EDNS; DNSSEC and large answers will need TCP",
+            ),
+            Some(edns) if edns.max_payload() < constants::config::default::EDNS_PAYLOAD => {
+                Finding::warn(
+                    CHECK,
+                    format!(
+                        "the resolver accepts UDP responses of up to {} bytes, less than the usual {}",
+                        edns.max_payload(),
+                        constants::config::default::EDNS_PAYLOAD
+                    ),
+                    "larger answers will be truncated and retried over TCP",
+                )
+            }
+            Some(edns) => Finding::pass(
+                CHECK,
+                format!(
+                    "EDNS version {} with UDP responses of up to {} bytes",
+                    edns.version(),
+                    edns.max_payload()
+                ),
+            ),
+        },
+    }
+}
+
+fn large_response(large: &Result<DnsResponse, String>, tcp_works: bool) -> Finding {
+    const CHECK: &str = "large-udp-response";
+
+    match large {
+        Ok(resp) if resp.truncated() && tcp_works => Finding::pass(
+            CHECK,
+            "large responses are truncated over UDP and can be retried over TCP",
+        ),
+        Ok(resp) if resp.truncated() => Finding::fail(
+            CHECK,
+            "large responses are truncated over UDP, and TCP doesn't work to retry them",
+            "allow TCP port 53 to the resolver",
+        ),
+        Ok(resp) => Finding::pass(
+            CHECK,
+            format!(
+                "a {} byte response to a DNSSEC query arrived over UDP",
+                resp.as_buffer().len()
+            ),
+        ),
+        Err(err) => Finding::fail(
+            CHECK,
+            format!("a large response over UDP never arrived: {err}"),
+            "fragmented UDP is probably being dropped; lower the EDNS buffer size on the resolver, allow IP fragments, or use --protocol tcp",
+        ),
+    }
+}
+
+fn root_query(udp: &Result<DnsResponse, String>) -> Finding {
+    const CHECK: &str = "root";
+
+    match udp {
+        Ok(resp) if resp.response_code() == ResponseCode::NoError && resp.answer_count() > 0 => {
+            Finding::pass(
+                CHECK,
+                format!("the root zone has {} nameservers", resp.answer_count()),
+            )
+        }
+        Ok(resp) => Finding::fail(
+            CHECK,
+            format!(
+                "asking for the root nameservers got {} with {} answers",
+                RCode(resp.response_code()).mnemonic(),
+                resp.answer_count()
+            ),
+            "the resolver can't resolve from the root; check its upstream configuration",
+        ),
+        Err(err) => Finding::fail(
+            CHECK,
+            format!("asking for the root nameservers failed: {err}"),
+            "fix reaching the resolver first",
+        ),
+    }
+}
+
+fn known_good_name(resp: &Result<DnsResponse, String>) -> Finding {
+    const CHECK: &str = "known-good-name";
+
+    match resp {
+        Ok(resp) if resp.response_code() == ResponseCode::NoError && resp.answer_count() > 0 => {
+            Finding::pass(CHECK, format!("{} resolves", constants::doctor::KNOWN_GOOD))
+        }
+        Ok(resp) => Finding::fail(
+            CHECK,
+            format!(
+                "{} got {} with {} answers",
+                constants::doctor::KNOWN_GOOD,
+                RCode(resp.response_code()).mnemonic(),
+                resp.answer_count()
+            ),
+            "the resolver may be filtering or misconfigured; compare with dns check hijack",
+        ),
+        Err(err) => Finding::fail(
+            CHECK,
+            format!(
+                "{} couldn't be resolved: {err}",
+                constants::doctor::KNOWN_GOOD
+            ),
+            "fix reaching the resolver first",
+        ),
+    }
+}
+
+/// A validating resolver sets AD on answers for signed names, and answers
+/// SERVFAIL for names whose signatures are broken on purpose.
+fn validation(
+    signed: &Result<DnsResponse, String>,
+    bogus: &Result<DnsResponse, String>,
+) -> Finding {
+    const CHECK: &str = "dnssec-validation";
+
+    let (Ok(signed), Ok(bogus)) = (signed, bogus) else {
+        return Finding::fail(
+            CHECK,
+            "couldn't be checked, since the test queries failed",
+            "fix reaching the resolver first",
+        );
+    };
+
+    let bogus_rejected = bogus.response_code() == ResponseCode::ServFail;
+
+    match (signed.authentic_data(), bogus_rejected) {
+        (true, true) => Finding::pass(
+            CHECK,
+            format!(
+                "the resolver validates: {} is authenticated and {} is rejected",
+                constants::doctor::SIGNED,
+                constants::doctor::BOGUS
+            ),
+        ),
+        (true, false) => Finding::fail(
+            CHECK,
+            format!(
+                "the resolver authenticates {} but also answers for {}, whose signatures are broken",
+                constants::doctor::SIGNED,
+                constants::doctor::BOGUS
+            ),
+            "the resolver's DNSSEC validation is inconsistent; check its trust anchors",
+        ),
+        (false, _) => Finding::warn(
+            CHECK,
+            "the resolver doesn't validate DNSSEC",
+            "use a validating resolver, or validate locally with --dnssec strict",
+        ),
+    }
+}
+
+impl PluginCommand for DnsDoctor {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::DOCTOR
+    }
+
+    fn description(&self) -> &str {
+        "Diagnose common problems with the resolver in one go"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Checks that the resolver, the system's or the one given with --server, answers at all; that it answers over both UDP and TCP; that EDNS gets through; that a large DNSSEC response arrives over UDP or can be retried over TCP; that the root nameservers and a well-known name resolve; and whether it validates DNSSEC, by asking for a signed name and one whose signatures are deliberately broken. Returns a row per check with a status of pass, warn, or fail, what was found, and a suggestion for anything that isn't a pass. The checks are of plain DNS on port 53, whatever --protocol is."
+    }
+
+    fn signature(&self) -> Signature {
+        super::connection_flags(Signature::build(self.name()))
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns doctor",
+                description: "check the system resolver",
+                result: None,
+            },
+            Example {
+                example: "dns doctor --server 192.168.1.1 | where status != pass",
+                description: "list the problems with the router's resolver",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "doctor",
+            "diagnose",
+            "troubleshoot",
+            "health",
+            "edns",
+            "dnssec",
+        ]
+    }
+}
@@ -0,0 +1,341 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use futures_util::{stream, StreamExt};
+use hickory_proto::rr::Record;
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, ListStream, PipelineData, Signature, Span, Spanned, SyntaxShape,
+    Value,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, error::DnsError, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsEnumerate;
+
+impl DnsEnumerate {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let metadata = config.metadata(self.name());
+        let domain: Spanned<String> = call.req(0)?;
+        let span = domain.span;
+        let domain = serde::util::parse_name(&domain.item)
+            .map(|domain| config.input_name(domain))
+            .map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), span)
+            })?;
+
+        let words: Vec<String> = match (
+            call.get_flag::<Spanned<PathBuf>>(constants::flags::WORDLIST)?,
+            input,
+        ) {
+            (Some(path), PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _)) => {
+                std::fs::read_to_string(&path.item)
+                    .map_err(|err| {
+                        LabeledError::new("could not read wordlist").with_label(
+                            format!("could not read {}: {err}", path.item.display()),
+                            path.span,
+                        )
+                    })?
+                    .lines()
+                    .map(str::trim)
+                    // wordlists often have comments
+                    .filter(|word| !word.is_empty() && !word.starts_with('#'))
+                    .map(String::from)
+                    .collect()
+            }
+            (None, PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _)) => {
+                return Err(LabeledError::new("no wordlist")
+                    .with_label("Give a --wordlist or pipe in the labels to try", call.head))
+            }
+            (None, input) => input
+                .into_iter()
+                .map(|word| word.into_string())
+                .collect::<Result<_, _>>()?,
+            (Some(path), _) => {
+                return Err(LabeledError::new("ambiguous input").with_label(
+                    "Labels should either come from --wordlist or be piped, but not both",
+                    path.span,
+                ))
+            }
+        };
+
+        let candidates = words
+            .iter()
+            .map(|word| {
+                Name::from_utf8(word)
+                    .and_then(|label| label.append_domain(&domain))
+                    .map_err(|err| {
+                        LabeledError::new("invalid label")
+                            .with_label(format!("{word} can't go under {domain}: {err}"), call.head)
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let client = plugin.dns_client(&config).await?;
+        let wildcard = wildcard_answers(&config, &client, &domain, call.head).await?;
+
+        if !wildcard.is_empty() {
+            tracing::info!(enumerate.wildcard = ?wildcard, "domain has a wildcard");
+        }
+
+        let (row_tx, mut row_rx) = mpsc::channel(config.tasks.item);
+
+        plugin.spawn(enumerate(config, client, candidates, wildcard, row_tx));
+
+        Ok(PipelineData::ListStream(
+            ListStream::new(
+                std::iter::from_fn(move || {
+                    tokio::task::block_in_place(|| {
+                        row_rx.blocking_recv().map(|row| {
+                            row.unwrap_or_else(|err| Value::error(err.into(), Span::unknown()))
+                        })
+                    })
+                }),
+                call.head,
+                engine.signals().clone(),
+            ),
+            metadata,
+        ))
+    }
+}
+
+/// How many made up names to ask about when looking for a wildcard. A
+/// wildcard that rotates through a pool of addresses may give a different
+/// answer each time, so one isn't enough to know them all.
+const WILDCARD_PROBES: usize = 3;
+
+/// Asks about names under the domain that surely don't exist, and returns
+/// whatever records they got anyway, which a wildcard must have made up.
+async fn wildcard_answers(
+    config: &Config,
+    client: &DnsClient,
+    domain: &Name,
+    span: Span,
+) -> Result<HashSet<String>, LabeledError> {
+    let mut answers = HashSet::new();
+
+    for _ in 0..WILDCARD_PROBES {
+        let label: String = (0..16)
+            .map(|_| char::from(b'a' + rand::random::<u8>() % 26))
+            .collect();
+        let name = Name::from_ascii(&label)
+            .and_then(|label| label.append_domain(domain))
+            .map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("can't make names under {domain}: {err}"), span)
+            })?;
+
+        answers.extend(
+            lookup(config, client, &name, span)
+                .await?
+                .iter()
+                .map(fingerprint),
+        );
+    }
+
+    Ok(answers)
+}
+
+/// What a record says, regardless of its owner name, which is the one
+/// asked about even when a wildcard made it up.
+fn fingerprint(record: &Record) -> String {
+    format!(
+        "{} {}",
+        record.record_type(),
+        record
+            .data()
+            .map(|rdata| rdata.to_string())
+            .unwrap_or_default()
+    )
+}
+
+async fn lookup(
+    config: &Config,
+    client: &DnsClient,
+    name: &Name,
+    span: Span,
+) -> Result<Vec<Record>, LabeledError> {
+    let mut records = Vec::new();
+
+    for qtype in config.qtypes.item.iter().map(|qtype| qtype.item) {
+        records.extend(
+            client
+                .answers(config, name, qtype)
+                .await
+                .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?,
+        );
+    }
+
+    Ok(records)
+}
+
+async fn enumerate(
+    config: Config,
+    client: DnsClient,
+    candidates: Vec<Name>,
+    wildcard: HashSet<String>,
+    row_tx: mpsc::Sender<Result<Value, LabeledError>>,
+) -> Result<(), LabeledError> {
+    let mut results = stream::iter(candidates)
+        .map(|name| {
+            let client = client.clone();
+            let config = &config;
+
+            async move {
+                let records = lookup(config, &client, &name, Span::unknown()).await?;
+                Ok::<_, LabeledError>((name, records))
+            }
+        })
+        .buffer_unordered(config.tasks.item);
+
+    while let Some(result) = results.next().await {
+        let row = match result {
+            Err(err) => Err(err),
+            Ok((_, records)) if records.is_empty() => continue,
+            // every record could have come from the wildcard, so there's
+            // no telling whether the name really exists
+            Ok((_, records))
+                if records
+                    .iter()
+                    .all(|record| wildcard.contains(&fingerprint(record))) =>
+            {
+                continue
+            }
+            Ok((name, records)) => records
+                .into_iter()
+                .map(|record| serde::Record(record).into_value(&config))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|records| {
+                    Value::record(
+                        record![
+                            "name"    => Value::string(config.display_name(&name), Span::unknown()),
+                            "records" => Value::list(records, Span::unknown()),
+                        ],
+                        Span::unknown(),
+                    )
+                }),
+        };
+
+        // the receiving end goes away when the user stops consuming the
+        // stream, which is our cue to stop
+        if row_tx.send(row).await.is_err() {
+            tracing::debug!(enumerate.phase = "exit");
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+impl PluginCommand for DnsEnumerate {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::ENUMERATE
+    }
+
+    fn description(&self) -> &str {
+        "Find names under a domain by trying the labels in a wordlist"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks about each label from the wordlist, or piped in, under the domain, and streams the names that exist with their records as they are found. Before starting, a few made up names are asked about to detect a wildcard; names whose records all match what the wildcard answered are left out, since there is no telling whether they really exist. The record types asked for are the same as dns query's, AAAA and A unless --type is given."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .required(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Domain to enumerate",
+            )
+            .named(
+                constants::flags::WORDLIST,
+                SyntaxShape::Filepath,
+                "File with a label to try on each line. Empty lines and lines starting with # are skipped",
+                Some('w'),
+            )
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Record type(s) to ask for",
+                Some('t'),
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat the domain as fully qualified even without a trailing dot",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot",
+                None,
+            )
+            .named(
+                constants::flags::TASKS,
+                SyntaxShape::Int,
+                format!(
+                    "Number of names to ask about at once. Default: {}",
+                    constants::config::default::TASKS
+                ),
+                Some('j'),
+            )
+            .named(
+                constants::flags::RATE,
+                SyntaxShape::Number,
+                "Send at most this many queries per second, to go easy on nameservers you don't run",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns enumerate example.com --wordlist subdomains.txt --rate 50",
+                description: "try every label in a wordlist, at most 50 queries per second",
+                result: None,
+            },
+            Example {
+                example: "[www mail vpn] | dns enumerate example.com | get name",
+                description: "try a few labels given inline",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "enumerate",
+            "subdomain",
+            "wordlist",
+            "brute",
+            "recon",
+        ]
+    }
+}
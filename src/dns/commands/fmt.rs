@@ -0,0 +1,287 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature, Span, Value};
+
+use crate::{
+    dns::{constants, serde::util},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsFmt;
+
+impl DnsFmt {
+    /// Renders one message the way dig prints a response.
+    fn message(message: &Value) -> Result<String, LabeledError> {
+        if !matches!(message, Value::Record { .. }) {
+            return Err(LabeledError::new("invalid message").with_label(
+                "should be a message record, as returned by `dns query`",
+                message.span(),
+            ));
+        }
+
+        let column = |name: &str| util::column(message, name);
+        let header = column("header");
+        let flag = |name: &str| {
+            header
+                .as_ref()
+                .and_then(|header| util::column(header, name))
+                .and_then(|val| val.as_bool().ok())
+                .unwrap_or(false)
+        };
+        let count = |section: &str| {
+            column(section)
+                .and_then(|records| records.as_list().map(<[Value]>::len).ok())
+                .unwrap_or(0)
+        };
+
+        let mut out = Vec::new();
+
+        out.push(format!(
+            ";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+            column("opcode").map_or("QUERY".into(), |val| code(&val)),
+            column("rcode").map_or("NOERROR".into(), |val| code(&val)),
+            column("id").map_or(String::new(), |val| field(&val)),
+        ));
+
+        let is_response = header
+            .as_ref()
+            .and_then(|header| util::column(header, "message_type"))
+            .is_none_or(|val| code(&val) == "Response");
+        let flags: Vec<_> = [
+            ("qr", is_response),
+            ("aa", flag("authoritative")),
+            ("tc", flag("truncated")),
+            ("rd", flag("recursion_desired")),
+            ("ra", flag("recursion_available")),
+            ("ad", flag("authentic_data")),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect();
+        out.push(format!(
+            ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+            flags.join(" "),
+            usize::from(column("question").is_some()),
+            count("answer"),
+            count("authority"),
+            count("additional"),
+        ));
+
+        if let Some(edns) = column("edns") {
+            let dnssec_ok = util::column(&edns, "dnssec_ok")
+                .and_then(|val| val.as_bool().ok())
+                .unwrap_or(false);
+
+            out.push(String::new());
+            out.push(";; OPT PSEUDOSECTION:".into());
+            out.push(format!(
+                "; EDNS: version: {}, flags:{}; udp: {}",
+                util::column(&edns, "version").map_or("0".into(), |val| field(&val)),
+                if dnssec_ok { " do" } else { "" },
+                util::column(&edns, "max_payload").map_or(String::new(), |val| field(&val)),
+            ));
+
+            if let Some(Value::Record { val: opts, .. }) = util::column(&edns, "opts") {
+                for (code, option) in opts.iter() {
+                    out.push(format!("; {code}: {}", field(option)));
+                }
+            }
+        }
+
+        if let Some(question) = column("question").filter(|q| !q.is_empty()) {
+            let text =
+                |name: &str| util::column(&question, name).map_or(String::new(), |val| code(&val));
+
+            out.push(String::new());
+            out.push(";; QUESTION SECTION:".into());
+            out.push(format!(
+                ";{}\t\t\t{}\t{}",
+                text("name"),
+                text("class"),
+                text("type")
+            ));
+        }
+
+        for (section, title) in [
+            ("answer", "ANSWER"),
+            ("authority", "AUTHORITY"),
+            ("additional", "ADDITIONAL"),
+        ] {
+            let Some(records) = column(section) else {
+                continue;
+            };
+            let records = records.as_list()?;
+            if records.is_empty() {
+                continue;
+            }
+
+            out.push(String::new());
+            out.push(format!(";; {title} SECTION:"));
+
+            for record in records {
+                out.extend(Self::record(record)?);
+            }
+        }
+
+        out.push(String::new());
+        if let Some(server) = column("server") {
+            out.push(format!(";; SERVER: {}", field(&server)));
+        }
+        if let Some(size) = column("size") {
+            out.push(format!(";; MSG SIZE  rcvd: {}", field(&size)));
+        }
+
+        Ok(out.join("\n"))
+    }
+
+    /// A record as zone file lines: one, or one per record of an RRset from
+    /// `--group-rrsets`.
+    fn record(record: &Value) -> Result<Vec<String>, LabeledError> {
+        let text = |name: &str| util::column(record, name).map_or(String::new(), |val| code(&val));
+        let (name, rtype, class) = (text("name"), text("type"), text("class"));
+        let ttl = util::column(record, "ttl").map_or(String::new(), |val| field(&val));
+
+        let rdatas = match util::column(record, "records") {
+            Some(records) => records.into_list()?,
+            None => vec![util::column(record, "rdata").unwrap_or(Value::nothing(Span::unknown()))],
+        };
+
+        Ok(rdatas
+            .iter()
+            .map(|rdata| {
+                format!(
+                    "{name}\t\t{ttl}\t{class}\t{rtype}\t{}",
+                    Self::rdata(&rtype, rdata)
+                )
+            })
+            .collect())
+    }
+
+    /// Rdata in presentation format. Structured rdata has its columns in the
+    /// order of the type's presentation format, so they are written out in
+    /// turn.
+    fn rdata(rtype: &str, rdata: &Value) -> String {
+        match rdata {
+            // an address record stands for just its address
+            Value::Record { val, .. } if val.contains("ip") => {
+                val.get("ip").map_or(String::new(), field)
+            }
+            Value::Record { val, .. } => val
+                .values()
+                .map(|val| Self::rdata(rtype, val))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Value::List { vals, .. } => vals
+                .iter()
+                .map(|val| match val {
+                    Value::String { val, .. } if matches!(rtype, "TXT" | "SPF") => {
+                        format!("\"{val}\"")
+                    }
+                    val => Self::rdata(rtype, val),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            val => field(val),
+        }
+    }
+
+    fn run_impl(call: &EvaluatedCall, input: PipelineData) -> Result<PipelineData, LabeledError> {
+        let text = match input {
+            PipelineData::Value(Value::List { vals, .. }, _) => vals
+                .iter()
+                .map(Self::message)
+                .collect::<Result<Vec<_>, _>>()?
+                .join("\n\n"),
+            PipelineData::Value(message @ Value::Record { .. }, _) => Self::message(&message)?,
+            PipelineData::ListStream(stream, _) => stream
+                .into_iter()
+                .map(|message| Self::message(&message))
+                .collect::<Result<Vec<_>, _>>()?
+                .join("\n\n"),
+            input => {
+                return Err(LabeledError::new("invalid input").with_label(
+                    "pipe in messages, as returned by `dns query`",
+                    input.span().unwrap_or(call.head),
+                ))
+            }
+        };
+
+        Ok(PipelineData::Value(Value::string(text, call.head), None))
+    }
+}
+
+/// The name of a code, whether it came as a `{name, code}` record with
+/// `--code` or as a plain string.
+fn code(val: &Value) -> String {
+    match val {
+        Value::Record { val, .. } => val.get("name").map_or(String::new(), field),
+        val => field(val),
+    }
+}
+
+/// A single value as it would appear in presentation format. Durations are
+/// TTLs and timers, written in seconds.
+fn field(val: &Value) -> String {
+    match val {
+        Value::String { val, .. } => val.clone(),
+        Value::Int { val, .. } => val.to_string(),
+        Value::Bool { val, .. } => u8::from(*val).to_string(),
+        Value::Duration { val, .. } => (val / 1_000_000_000).to_string(),
+        Value::Filesize { val, .. } => val.get().to_string(),
+        Value::Binary { val, .. } => data_encoding::HEXUPPER.encode(val),
+        Value::Nothing { .. } => String::new(),
+        Value::Custom { val: custom, .. } => custom
+            .to_base_value(val.span())
+            .map_or(String::new(), |val| field(&val)),
+        val => val.to_expanded_string(" ", &nu_protocol::Config::default()),
+    }
+}
+
+impl PluginCommand for DnsFmt {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        Self::run_impl(call, input)
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::FMT
+    }
+
+    fn description(&self) -> &str {
+        "Render messages from dns query as dig-style text"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Takes the message records `dns query` returns, e.g. saved with `to json` or `to nuon` and opened again later, and writes them out the way dig prints a response: a header with the status and flags, the EDNS options, then the question and each section with a record per line in zone file format. Messages made with --code or --group-rrsets are rendered the same. Durations are written as seconds."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns query example.com | dns fmt",
+                description: "see a response the way dig shows it",
+                result: None,
+            },
+            Example {
+                example: "dns query example.com | save answers.nuon; open answers.nuon | dns fmt",
+                description: "display saved results again later",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "format", "dig", "pretty", "print", "text", "render"]
+    }
+}
@@ -0,0 +1,335 @@
+use std::str::FromStr;
+
+use futures_util::{stream, StreamExt};
+use hickory_proto::rr::rdata::opt::{ClientSubnet, EdnsCode, EdnsOption};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        config::Config,
+        constants,
+        error::DnsError,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsGeo;
+
+/// A client subnet to ask on behalf of, and the region it stands for, if
+/// known.
+struct Subnet {
+    region: Option<String>,
+    subnet: ClientSubnet,
+    text: String,
+}
+
+impl DnsGeo {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let name: Spanned<String> = call.req(0)?;
+        let name = Name::from_utf8(&name.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), name.span)
+        })?;
+
+        let subnets = match call.get_flag_value(constants::flags::SUBNET) {
+            Some(val) => parse_subnets(&val)?,
+            None => constants::config::default::GEO_SUBNETS
+                .iter()
+                .map(|(region, subnet)| Subnet {
+                    region: Some((*region).into()),
+                    subnet: ClientSubnet::from_str(subnet).expect("built-in subnet should parse"),
+                    text: (*subnet).into(),
+                })
+                .collect(),
+        };
+
+        let client = plugin.dns_client(&config).await?;
+
+        let questions: Vec<_> = subnets
+            .iter()
+            .flat_map(|subnet| {
+                config
+                    .qtypes
+                    .item
+                    .iter()
+                    .map(move |qtype| (subnet, qtype.item))
+            })
+            .collect();
+
+        let results: Vec<_> = stream::iter(questions)
+            .map(|(subnet, qtype)| {
+                let config = &config;
+                let client = &client;
+                let name = &name;
+
+                async move {
+                    let answer = ask(config, client, name, qtype, subnet, call.head).await;
+                    (subnet, qtype, answer)
+                }
+            })
+            .buffered(config.tasks.item)
+            .collect()
+            .await;
+
+        // number the distinct answer sets for each type in the order they
+        // were first seen, so that subnets that got the same answers share a
+        // variant
+        let mut variants: Vec<(hickory_proto::rr::RecordType, Vec<String>)> = Vec::new();
+
+        let rows = results
+            .into_iter()
+            .map(|(subnet, qtype, answer)| {
+                let answer = match answer {
+                    Ok(answer) => answer,
+                    Err(err) => return Value::error(err.into(), call.head),
+                };
+
+                let variant = match variants
+                    .iter()
+                    .position(|(t, answers)| *t == qtype && *answers == answer.answers)
+                {
+                    Some(idx) => idx,
+                    None => {
+                        variants.push((qtype, answer.answers.clone()));
+                        variants.len() - 1
+                    }
+                };
+
+                Value::record(
+                    record![
+                        "region"  => subnet
+                            .region
+                            .as_ref()
+                            .map_or(Value::nothing(Span::unknown()), |region| {
+                                Value::string(region, Span::unknown())
+                            }),
+                        "subnet"  => Value::string(&subnet.text, Span::unknown()),
+                        "type"    => Value::string(qtype.to_string(), Span::unknown()),
+                        "rcode"   => Value::string(RCode(answer.rcode).mnemonic(), Span::unknown()),
+                        "answers" => Value::list(
+                            answer
+                                .answers
+                                .into_iter()
+                                .map(|answer| Value::string(answer, Span::unknown()))
+                                .collect(),
+                            Span::unknown(),
+                        ),
+                        "ttl"     => answer
+                            .ttl
+                            .map(serde::util::sec_to_duration)
+                            .unwrap_or(Value::nothing(Span::unknown())),
+                        "scope"   => answer.scope,
+                        "variant" => Value::int(variant as i64, Span::unknown()),
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// What the server answered for one subnet.
+struct Answer {
+    rcode: hickory_proto::op::ResponseCode,
+    answers: Vec<String>,
+    ttl: Option<u32>,
+    scope: Value,
+}
+
+async fn ask(
+    config: &Config,
+    client: &DnsClient,
+    name: &Name,
+    qtype: hickory_proto::rr::RecordType,
+    subnet: &Subnet,
+    span: Span,
+) -> Result<Answer, LabeledError> {
+    let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+    query.set_query_class(config.class.item);
+
+    let mut request = serde::Query(query).into_message(config);
+    request
+        .extensions_mut()
+        .get_or_insert_with(hickory_proto::op::Edns::new)
+        .options_mut()
+        .insert(EdnsOption::Subnet(subnet.subnet));
+
+    config.throttle().await;
+    let resp = client
+        .send_message(config, request)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+    let mut answers: Vec<String> = resp
+        .answers()
+        .iter()
+        .filter(|record| record.record_type() == qtype)
+        .filter_map(|record| record.data().map(|rdata| rdata.to_string()))
+        .collect();
+    answers.sort();
+    answers.dedup();
+
+    // the scope prefix says how much of the subnet the answer applies to; a
+    // server that ignores client subnets leaves the option out
+    let scope = resp
+        .extensions()
+        .as_ref()
+        .and_then(|edns| edns.options().get(EdnsCode::Subnet))
+        .and_then(|opt| match opt {
+            EdnsOption::Subnet(subnet) => Vec::<u8>::try_from(subnet).ok(),
+            _ => None,
+        })
+        .map(|bytes| serde::util::client_subnet(&bytes))
+        .and_then(|subnet| subnet.get_data_by_key("scope_prefix"))
+        .unwrap_or(Value::nothing(Span::unknown()));
+
+    Ok(Answer {
+        rcode: resp.response_code(),
+        ttl: resp.answers().iter().map(|record| record.ttl()).min(),
+        answers,
+        scope,
+    })
+}
+
+/// Parses the subnets to ask on behalf of: a subnet in CIDR notation, a
+/// record with `subnet` and optional `region` columns, or a list of either.
+fn parse_subnets(value: &Value) -> Result<Vec<Subnet>, LabeledError> {
+    match value {
+        Value::List { vals, .. } => vals.iter().map(parse_subnet).collect::<Result<Vec<_>, _>>(),
+        _ => Ok(vec![parse_subnet(value)?]),
+    }
+}
+
+fn parse_subnet(value: &Value) -> Result<Subnet, LabeledError> {
+    let (region, subnet) = match value {
+        Value::Record { .. } => {
+            let subnet = serde::util::column(value, "subnet").ok_or_else(|| {
+                LabeledError::new("invalid subnet")
+                    .with_label("record should have a subnet column", value.span())
+            })?;
+            let region = serde::util::column(value, "region")
+                .map(|region| region.coerce_into_string())
+                .transpose()?;
+
+            (region, subnet)
+        }
+        _ => (None, value.clone()),
+    };
+
+    let span = subnet.span();
+    let text = subnet.coerce_into_string()?;
+    let parsed = ClientSubnet::from_str(&text).map_err(|err| {
+        LabeledError::new("invalid subnet")
+            .with_label(format!("should be a subnet like 192.0.2.0/24: {err}"), span)
+    })?;
+
+    Ok(Subnet {
+        region,
+        subnet: parsed,
+        text,
+    })
+}
+
+impl PluginCommand for DnsGeo {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::GEO
+    }
+
+    fn description(&self) -> &str {
+        "Survey how a name's answers differ by client location, using EDNS client subnets"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks the same question once per client subnet (RFC 7871) and returns a row per subnet and type with the answers, the scope the server said they apply to, and a variant number: subnets with the same variant got the same answers. Without --subnet, a sample subnet from each region's registry is used. The server must pass client subnets on, which most authoritative servers and some public resolvers do."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .required(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "DNS record name",
+            )
+            .named(
+                constants::flags::SUBNET,
+                SyntaxShape::Any,
+                "Client subnet(s) to ask on behalf of, e.g. 192.0.2.0/24, or records with subnet and region columns",
+                None,
+            )
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Query type(s)",
+                Some('t'),
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Query class",
+                None,
+            )
+            .named(
+                constants::flags::TASKS,
+                SyntaxShape::Int,
+                format!("Number of queries to have in flight at once. Default: {}", constants::config::default::TASKS),
+                Some('j'),
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns geo --type A www.example.com",
+                description: "see which regions get which addresses",
+                result: None,
+            },
+            Example {
+                example: "dns geo --type A www.example.com | group-by variant",
+                description: "group the regions by the answers they got",
+                result: None,
+            },
+            Example {
+                example: "dns geo --subnet [198.51.100.0/24 203.0.113.0/24] www.example.com",
+                description: "compare answers for subnets of your own",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "network", "geodns", "ecs", "client subnet", "cdn"]
+    }
+}
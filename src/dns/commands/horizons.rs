@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use futures_util::future;
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{Record, RecordType},
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        error::DnsError,
+        serde::{self, util, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsCompareHorizons;
+
+/// A record as it appears in one view or both, with its TTL in each.
+#[derive(Default)]
+struct Sides {
+    internal: Option<u32>,
+    external: Option<u32>,
+}
+
+impl DnsCompareHorizons {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let name: Spanned<String> = call.req(0)?;
+        let span = name.span;
+        let name = serde::util::parse_name(&name.item)
+            .map(|name| config.input_name(name))
+            .map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), span)
+            })?;
+
+        let server = |flag: &str| -> Result<Config, LabeledError> {
+            match call.get_flag::<Value>(flag)? {
+                Some(server) => config.with_server(&server),
+                None => Err(LabeledError::new("missing server").with_label(
+                    format!("Give the servers of both views with --internal and --external; --{flag} is missing"),
+                    call.head,
+                )),
+            }
+        };
+        let (internal, external) = (
+            server(constants::flags::INTERNAL)?,
+            server(constants::flags::EXTERNAL)?,
+        );
+
+        let mut rows = Vec::new();
+
+        for qtype in config.qtypes.item.iter().map(|qtype| qtype.item) {
+            let (internal_resp, external_resp) = future::join(
+                ask(plugin, &internal, &name, qtype, span),
+                ask(plugin, &external, &name, qtype, span),
+            )
+            .await;
+            let ((internal_rcode, internal_records), (external_rcode, external_records)) =
+                (internal_resp?, external_resp?);
+
+            let mut records: BTreeMap<(String, String, String), Sides> = BTreeMap::new();
+
+            for record in &internal_records {
+                records.entry(key(&config, record)).or_default().internal = Some(record.ttl());
+            }
+            for record in &external_records {
+                records.entry(key(&config, record)).or_default().external = Some(record.ttl());
+            }
+
+            // TTLs count down in caches, so only which records there are
+            // tells the views apart
+            let same = internal_rcode == external_rcode
+                && records
+                    .values()
+                    .all(|sides| sides.internal.is_some() && sides.external.is_some());
+
+            let ttl = |ttl: Option<u32>| {
+                ttl.map_or(Value::nothing(Span::unknown()), |ttl| {
+                    util::sec_to_duration(ttl)
+                })
+            };
+
+            rows.push(Value::record(
+                record![
+                    "name"            => Value::string(config.display_name(&name), Span::unknown()),
+                    "type"            => Value::string(util::rtype_mnemonic(qtype), Span::unknown()),
+                    "same"            => Value::bool(same, Span::unknown()),
+                    "internal_rcode"  => RCode(internal_rcode).into_value(),
+                    "external_rcode"  => RCode(external_rcode).into_value(),
+                    "records"         => Value::list(
+                        records
+                            .into_iter()
+                            .map(|((owner, rtype, rdata), sides)| {
+                                let seen_in = match (sides.internal, sides.external) {
+                                    (Some(_), Some(_)) => "both",
+                                    (Some(_), None) => "internal",
+                                    _ => "external",
+                                };
+
+                                Value::record(
+                                    record![
+                                        "name"         => Value::string(owner, Span::unknown()),
+                                        "type"         => Value::string(rtype, Span::unknown()),
+                                        "rdata"        => Value::string(rdata, Span::unknown()),
+                                        "in"           => Value::string(seen_in, Span::unknown()),
+                                        "internal_ttl" => ttl(sides.internal),
+                                        "external_ttl" => ttl(sides.external),
+                                    ],
+                                    Span::unknown(),
+                                )
+                            })
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                ],
+                call.head,
+            ));
+        }
+
+        Ok(PipelineData::Value(Value::list(rows, call.head), None))
+    }
+}
+
+async fn ask(
+    plugin: &Dns,
+    config: &Config,
+    name: &Name,
+    qtype: RecordType,
+    span: Span,
+) -> Result<(ResponseCode, Vec<Record>), LabeledError> {
+    let client = plugin.dns_client(config).await?;
+
+    let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+    query.set_query_class(config.class.item);
+    let request = serde::Query(query).into_message(config);
+
+    config.throttle().await;
+    let resp = client
+        .send_message(config, request)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+    Ok((resp.response_code(), resp.answers().to_vec()))
+}
+
+/// What identifies a record across the views: everything but the TTL.
+fn key(config: &Config, record: &Record) -> (String, String, String) {
+    (
+        config.display_name(record.name()),
+        util::rtype_mnemonic(record.record_type()),
+        record
+            .data()
+            .map(|rdata| rdata.to_string())
+            .unwrap_or_default(),
+    )
+}
+
+impl PluginCommand for DnsCompareHorizons {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::COMPARE_HORIZONS
+    }
+
+    fn description(&self) -> &str {
+        "Compare the internal and external views of a name in split DNS"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks the --internal and --external servers the same question and returns a row per type with both response codes and the answer records side by side: each record says whether it is in both views or only one, with its TTL in each. same is true when the response codes match and every record is in both views; TTLs aren't compared, since they count down in caches. The other connection flags, like --protocol, apply to both servers."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .required(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Name to compare",
+            )
+            .named(
+                constants::flags::INTERNAL,
+                SyntaxShape::String,
+                "Server with the internal view",
+                None,
+            )
+            .named(
+                constants::flags::EXTERNAL,
+                SyntaxShape::String,
+                "Server with the external view",
+                None,
+            )
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Record type(s) to compare",
+                Some('t'),
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat the name as fully qualified even without a trailing dot",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns compare-horizons intranet.example.com --internal 10.0.0.53 --external 8.8.8.8",
+                description: "see how a name looks from inside and outside",
+                result: None,
+            },
+            Example {
+                example: "dns compare-horizons example.com --internal 10.0.0.53 --external 1.1.1.1 --type [A AAAA MX] | where not same | get records | flatten",
+                description: "list the records that differ between the views",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns", "split", "horizon", "view", "internal", "external", "diff",
+        ]
+    }
+}
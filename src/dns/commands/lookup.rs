@@ -0,0 +1,317 @@
+use std::net::IpAddr;
+
+use futures_util::{future, stream, StreamExt};
+use hickory_proto::rr::{RData, Record, RecordType};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, error::DnsError, selection, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsLookup;
+
+/// An address a name resolved to.
+struct Address {
+    name: Name,
+    ip: IpAddr,
+    ttl: u32,
+}
+
+impl DnsLookup {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let sorted = call.has_flag(constants::flags::SORTED)?;
+
+        let mut names: Vec<Spanned<String>> = call.rest(0)?;
+
+        match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => {}
+            input if names.is_empty() => {
+                for val in input.into_iter() {
+                    let span = val.span();
+                    names.push(Spanned {
+                        item: val.into_string()?,
+                        span,
+                    });
+                }
+            }
+            input => {
+                return Err(LabeledError::new("ambiguous input").with_label(
+                    "Input should either be positional args or piped, but not both",
+                    input.span().unwrap_or(call.head),
+                ))
+            }
+        }
+
+        let names = names
+            .into_iter()
+            .map(|name| {
+                Name::from_utf8(&name.item)
+                    .map(|parsed| config.input_name(parsed))
+                    .map_err(|err| {
+                        LabeledError::new("invalid name")
+                            .with_label(format!("Error parsing name: {}", err), name.span)
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let client = plugin.dns_client(&config).await?;
+
+        let addresses: Vec<Vec<Address>> = stream::iter(names)
+            .map(|name| {
+                let client = client.clone();
+                let config = &config;
+
+                async move {
+                    let mut addresses: Vec<Address> = future::join_all(
+                        config
+                            .address_types()
+                            .iter()
+                            .map(|qtype| resolve(config, &client, &name, *qtype)),
+                    )
+                    .await
+                    .into_iter()
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .flatten()
+                    .collect();
+
+                    if sorted {
+                        let order = selection::sort(
+                            &addresses.iter().map(|addr| addr.ip).collect::<Vec<_>>(),
+                        );
+
+                        addresses.sort_by_key(|addr| order.iter().position(|ip| *ip == addr.ip));
+                    }
+
+                    Ok::<_, LabeledError>(addresses)
+                }
+            })
+            .buffered(config.tasks.item)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+
+        let rows = addresses
+            .into_iter()
+            .flatten()
+            .map(|addr| {
+                Value::record(
+                    record![
+                        "name"    => Value::string(config.display_name(&addr.name), Span::unknown()),
+                        "address" => Value::string(addr.ip.to_string(), Span::unknown()),
+                        "family"  => Value::string(family(addr.ip), Span::unknown()),
+                        "ttl"     => serde::util::sec_to_duration(addr.ttl),
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+fn family(ip: IpAddr) -> &'static str {
+    match ip {
+        IpAddr::V4(_) => "IPv4",
+        IpAddr::V6(_) => "IPv6",
+    }
+}
+
+/// Looks up the addresses of one family for a name, following any CNAMEs.
+/// Recursive resolvers usually include the whole chain in one response, but
+/// if the chain stops short, the query is repeated for where it ended.
+async fn resolve(
+    config: &Config,
+    client: &DnsClient,
+    name: &Name,
+    qtype: RecordType,
+) -> Result<Vec<Address>, LabeledError> {
+    let mut target = name.clone();
+
+    for _ in 0..constants::config::default::MAX_CNAME_CHAIN {
+        let mut query = hickory_proto::op::Query::query(target.clone(), qtype);
+        query.set_query_class(config.class.item);
+
+        let request = serde::Query(query).into_message(config);
+        config.throttle().await;
+        let resp = client
+            .send_message(config, request)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, Span::unknown()))?;
+
+        let addresses: Vec<Address> = resp
+            .answers()
+            .iter()
+            .filter_map(|record| {
+                let ip = match record.data()? {
+                    RData::A(a) => IpAddr::V4(a.0),
+                    RData::AAAA(aaaa) => IpAddr::V6(aaaa.0),
+                    _ => return None,
+                };
+
+                Some(Address {
+                    name: name.clone(),
+                    ip,
+                    ttl: record.ttl(),
+                })
+            })
+            .collect();
+
+        if !addresses.is_empty() {
+            return Ok(addresses);
+        }
+
+        let end = chain_end(resp.answers(), &target);
+
+        if end == target {
+            // no addresses and no alias to follow, e.g. NXDOMAIN or NODATA
+            return Ok(vec![]);
+        }
+
+        target = end;
+    }
+
+    Err(LabeledError::new("CNAME chain too long").with_label(
+        format!(
+            "gave up following CNAMEs for {} after {} hops",
+            name,
+            constants::config::default::MAX_CNAME_CHAIN
+        ),
+        Span::unknown(),
+    ))
+}
+
+/// Follows the CNAME records in an answer section from the given name to
+/// the end of the chain.
+fn chain_end(answers: &[Record], name: &Name) -> Name {
+    let mut end = name.clone();
+
+    // bounded by the number of records so that a loop can't hang us
+    for _ in 0..answers.len() {
+        let next = answers.iter().find_map(|record| match record.data() {
+            Some(RData::CNAME(cname)) if record.name() == &end => Some(cname.0.clone()),
+            _ => None,
+        });
+
+        match next {
+            Some(next) => end = next,
+            None => break,
+        }
+    }
+
+    end
+}
+
+impl PluginCommand for DnsLookup {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::LOOKUP
+    }
+
+    fn description(&self) -> &str {
+        "Look up the IP addresses of names"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Like `host`: queries both A and AAAA records, following CNAMEs, and returns a flat table of addresses with their family. With -4 or -6, only addresses of that family are looked up. Uses the system's nameserver unless --server is given."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .rest(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "DNS record names",
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat names as fully qualified even without a trailing dot",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot",
+                None,
+            )
+            .switch(
+                constants::flags::SORTED,
+                "Order each name's addresses in the order they should be tried when connecting, per RFC 6724 and RFC 8305: unreachable addresses last, otherwise by preference, alternating between IPv6 and IPv4",
+                None,
+            )
+            .named(
+                constants::flags::TASKS,
+                SyntaxShape::Int,
+                format!(
+                    "Number of names to look up at once. Default: {}",
+                    constants::config::default::TASKS
+                ),
+                Some('j'),
+            )
+            .named(
+                constants::flags::RATE,
+                SyntaxShape::Number,
+                "Send at most this many queries per second, to go easy on nameservers you don't run",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns lookup example.com",
+                description: "look up the addresses of a name",
+                result: None,
+            },
+            Example {
+                example: "dns lookup example.com | where family == IPv4 | get address",
+                description: "get just the IPv4 addresses",
+                result: None,
+            },
+            Example {
+                example: "dns lookup --sorted example.com | first | get address",
+                description: "get the address a client should try first",
+                result: None,
+            },
+            Example {
+                example: "open hosts.txt | lines | dns lookup",
+                description: "look up many names at once",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "network", "host", "address", "ip", "resolve"]
+    }
+}
@@ -1 +1,170 @@
+use nu_plugin::{Plugin, PluginCommand};
+use nu_protocol::{Signature, SyntaxShape};
+
+use super::constants;
+use crate::Dns;
+
+pub mod axfr;
+pub mod bench;
+pub mod browse;
+pub mod cache;
+pub mod check;
+pub mod config;
+pub mod ddr;
+pub mod decode;
+pub mod dnssec;
+pub mod doctor;
+pub mod enumerate;
+pub mod fmt;
+pub mod geo;
+pub mod horizons;
+pub mod lookup;
+pub mod myip;
+pub mod name;
+pub mod nat64;
+pub mod ping;
+pub mod probe;
 pub mod query;
+pub mod resolve;
+pub mod scan;
+pub mod serial;
+pub mod serve;
+pub mod soa;
+pub mod spf;
+pub mod sshfp;
+pub mod stats;
+pub mod tlsa;
+pub mod update;
+pub mod watch;
+
+impl Plugin for Dns {
+    fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
+        vec![
+            Box::new(query::DnsQuery),
+            Box::new(bench::DnsBench),
+            Box::new(watch::DnsWatch),
+            Box::new(lookup::DnsLookup),
+            Box::new(cache::DnsCacheList),
+            Box::new(cache::DnsCacheClear),
+            Box::new(config::DnsConfigShow),
+            Box::new(doctor::DnsDoctor),
+            Box::new(serve::DnsServe),
+            Box::new(serve::DnsServeStop),
+            Box::new(stats::DnsStats),
+            Box::new(axfr::DnsAxfr),
+            Box::new(check::dns64::DnsCheckDns64),
+            Box::new(nat64::DnsNat64Prefix),
+            Box::new(geo::DnsGeo),
+            Box::new(check::anycast::DnsCheckAnycast),
+            Box::new(probe::DnsProbe),
+            Box::new(dnssec::DnsDnssecKeytag),
+            Box::new(dnssec::DnsDnssecUpdateAnchors),
+            Box::new(check::cds::DnsCheckCds),
+            Box::new(check::zonemd::DnsCheckZonemd),
+            Box::new(check::rrsig::DnsCheckRrsig),
+            Box::new(serial::DnsSerialCompare),
+            Box::new(serial::DnsSerialNext),
+            Box::new(soa::DnsSoa),
+            Box::new(decode::DnsDecode),
+            Box::new(fmt::DnsFmt),
+            Box::new(resolve::DnsResolve),
+            Box::new(name::DnsNameParse),
+            Box::new(name::DnsNameValidate),
+            Box::new(scan::DnsScanServices),
+            Box::new(enumerate::DnsEnumerate),
+            Box::new(check::apex::DnsCheckApex),
+            Box::new(check::hijack::DnsCheckHijack),
+            Box::new(check::captive::DnsCheckCaptive),
+            Box::new(check::fragmentation::DnsCheckFragmentation),
+            Box::new(check::transport::DnsCheckTransport),
+            Box::new(check::minimal::DnsCheckMinimal),
+            Box::new(check::soa::DnsCheckSoa),
+            Box::new(ping::DnsPing),
+            Box::new(update::DnsUpdate),
+            Box::new(update::DnsUpdateSelf),
+            Box::new(myip::DnsMyip),
+            Box::new(ddr::DnsDdr),
+            Box::new(horizons::DnsCompareHorizons),
+            Box::new(tlsa::DnsTlsaGenerate),
+            Box::new(sshfp::DnsSshfpGenerate),
+            Box::new(spf::DnsSpfFlatten),
+            Box::new(browse::DnsBrowse),
+        ]
+    }
+
+    fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").into()
+    }
+}
+
+/// Adds the flags that control how to reach the nameserver, which every
+/// command that sends queries shares.
+pub(crate) fn connection_flags(signature: Signature) -> Signature {
+    signature
+        .named(
+            constants::flags::SERVER,
+            SyntaxShape::String,
+            "Nameserver to query (defaults to system config or 8.8.8.8). A host name is looked up with the system resolver and its addresses tried in order. Link-local IPv6 addresses take a zone index, e.g. fe80::1%eth0",
+            Some('s'),
+        )
+        .switch(
+            constants::flags::IPV4,
+            "Only use IPv4: reach the nameserver over it, and, for dns lookup, only look up IPv4 addresses",
+            Some('4'),
+        )
+        .switch(
+            constants::flags::IPV6,
+            "Only use IPv6: reach the nameserver over it, and, for dns lookup, only look up IPv6 addresses",
+            Some('6'),
+        )
+        .named(
+            constants::flags::PROTOCOL,
+            SyntaxShape::String,
+            "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
+            Some('p'),
+        )
+        .named(
+            constants::flags::DNSSEC,
+            SyntaxShape::String,
+            "Perform DNSSEC validation on records. Choices are: \"none\", \"strict\" (error if record has no RRSIG or does not validate), \"opportunistic\" (validate if RRSIGs present, otherwise no validation; default)",
+            Some('d'),
+        )
+        .switch(
+            constants::flags::DNSSEC_OK,
+            "Set the DO bit so DNSSEC records (RRSIG, NSEC, etc.) are returned, without validating them",
+            None,
+        )
+        .switch(
+            constants::flags::CD,
+            "Set the CD (checking disabled) bit so a validating resolver returns data even if it does not validate. The plugin's own validation still applies unless turned off with --dnssec none or --dnssec-ok",
+            None,
+        )
+        .named(
+            constants::flags::DNS_NAME,
+            SyntaxShape::String,
+            "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
+            Some('n'),
+        )
+        .named(
+            constants::flags::TIMEOUT,
+            SyntaxShape::Duration,
+            format!("How long a request can take before timing out. Be aware the concurrency level can affect this. Default: resolv.conf's timeout without --server, otherwise {}sec", constants::config::default::TIMEOUT.as_secs()),
+            None,
+        )
+        .switch(
+            constants::flags::VERBOSE,
+            "Log every message sent and received to stderr",
+            Some('v'),
+        )
+        .switch(
+            constants::flags::DEBUG,
+            "Like --verbose, but also log a hex dump of each message",
+            None,
+        )
+        .named(
+            constants::flags::LOG_FILE,
+            SyntaxShape::Filepath,
+            "Append every query and its response to this file as a line of JSON with the time, server, question, rcode, round trip time, and answers",
+            None,
+        )
+}
@@ -0,0 +1,135 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, myip, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsMyip;
+
+impl DnsMyip {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let sightings = myip::discover(plugin, &config).await;
+
+        let rows = sightings
+            .iter()
+            .map(|sighting| {
+                let (ip, error) = match &sighting.addr {
+                    Ok(addr) => (
+                        Value::string(addr.to_string(), Span::unknown()),
+                        Value::nothing(Span::unknown()),
+                    ),
+                    Err(err) => (
+                        Value::nothing(Span::unknown()),
+                        Value::string(err, Span::unknown()),
+                    ),
+                };
+                let agrees = match &sighting.addr {
+                    Ok(addr) => Value::bool(
+                        myip::consensus(&sightings, sighting.family) == Some(*addr),
+                        Span::unknown(),
+                    ),
+                    Err(_) => Value::nothing(Span::unknown()),
+                };
+
+                Value::record(
+                    record![
+                        "service" => Value::string(sighting.service, Span::unknown()),
+                        "family"  => Value::string(serde::util::rtype_mnemonic(sighting.family), Span::unknown()),
+                        "server"  => Value::string(sighting.server.to_string(), Span::unknown()),
+                        "ip"      => ip,
+                        "agrees"  => agrees,
+                        "rtt"     => serde::util::std_to_duration(sighting.rtt),
+                        "error"   => error,
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl PluginCommand for DnsMyip {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::MYIP
+    }
+
+    fn description(&self) -> &str {
+        "Find this host's public addresses, as DNS servers see them"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks servers that answer with the address a question came from, over IPv4 and over IPv6, what that address is: Cloudflare's whoami.cloudflare TXT record in the CHAOS class, Google's o-o.myaddr.l.google.com TXT record, and OpenDNS's myip.opendns.com address records. Each is asked directly, rather than through --server, since a resolver in between would answer with its own address. Returns a row per service and address family with the address it saw, whether it agrees with what most of the services saw for that family, and the round trip time, or the error if it didn't answer; a family the host has no route over shows up as errors. --ipv4 or --ipv6 limits it to one family. No HTTP is involved."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .switch(
+                constants::flags::IPV4,
+                "Only find the IPv4 address",
+                Some('4'),
+            )
+            .switch(
+                constants::flags::IPV6,
+                "Only find the IPv6 address",
+                Some('6'),
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                "How long to wait for each service to answer",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns myip",
+                description: "see this host's public addresses",
+                result: None,
+            },
+            Example {
+                example: "dns myip --ipv4 | where agrees | first | get ip",
+                description: "get just the public IPv4 address",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns", "myip", "whoami", "public", "ip", "address", "external", "nat",
+        ]
+    }
+}
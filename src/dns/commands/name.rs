@@ -0,0 +1,259 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        constants,
+        names::{self, Usage},
+        serde::util,
+        value::DnsName,
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsNameParse;
+
+impl DnsNameParse {
+    fn parse(value: &Value) -> Result<Value, LabeledError> {
+        let span = value.span();
+        let name = util::parse_name(value.as_str()?).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), span)
+        })?;
+
+        Ok(DnsName::new(&name).into_value(span))
+    }
+}
+
+impl PluginCommand for DnsNameParse {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let name: Option<Value> = call.opt(0)?;
+
+        match (name, input) {
+            (Some(name), PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _)) => {
+                Ok(PipelineData::Value(Self::parse(&name)?, None))
+            }
+            (None, PipelineData::Value(list @ Value::List { .. }, _)) => {
+                let span = list.span();
+                let names = list
+                    .into_list()?
+                    .iter()
+                    .map(Self::parse)
+                    .collect::<Result<_, _>>()?;
+
+                Ok(PipelineData::Value(Value::list(names, span), None))
+            }
+            (None, PipelineData::Value(name, _)) => {
+                Ok(PipelineData::Value(Self::parse(&name)?, None))
+            }
+            (None, _) => Err(LabeledError::new("no name")
+                .with_label("Give a name to parse or pipe it in", call.head)),
+            (Some(_), input) => Err(LabeledError::new("ambiguous input").with_label(
+                "Input should either be a positional arg or piped, but not both",
+                input.span().unwrap_or(call.head),
+            )),
+        }
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::NAME_PARSE
+    }
+
+    fn description(&self) -> &str {
+        "Parse a string into a DNS name that keeps its meaning through a pipeline"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Returns a DnsName value, which shows as the name but compares the way DNS does: == and != ignore case, ends-with is true for a name at or under the given zone, and sorting follows the canonical order of RFC 4034, by labels from the right. Its parts can be had with a cell path: name, ascii (punycode), unicode, parent (the name with its first label removed, or nothing for the root), labels, fqdn, depth (the number of labels), and special_use, which for a name at or under a special-use zone like localhost, local, or home.arpa is the zone, the RFC that reserved it, how questions about it are meant to be answered, and why, or else nothing."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name()).optional(
+            constants::flags::NAME,
+            SyntaxShape::String,
+            "Name to parse",
+        )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "(dns name parse WWW.Example.com.) == www.example.com",
+                description: "compare names regardless of case",
+                result: None,
+            },
+            Example {
+                example: "(dns name parse bücher.example).ascii",
+                description: "get the punycode form of an internationalized name",
+                result: None,
+            },
+            Example {
+                example: "open hosts.txt | lines | dns name parse | where $it ends-with example.com | sort",
+                description: "list the names under a zone in canonical order",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "name",
+            "parse",
+            "punycode",
+            "idna",
+            "parent",
+            "canonical",
+        ]
+    }
+}
+
+#[derive(Debug)]
+pub struct DnsNameValidate;
+
+impl DnsNameValidate {
+    fn validate(usage: Usage, value: &Value) -> Result<Value, LabeledError> {
+        let name = value.as_str()?;
+        let violations = names::validate(name, usage);
+
+        Ok(Value::record(
+            record![
+                "name"       => Value::string(name, Span::unknown()),
+                "as"         => Value::string(usage.to_string(), Span::unknown()),
+                "valid"      => Value::bool(violations.is_empty(), Span::unknown()),
+                "violations" => Value::list(
+                    violations
+                        .into_iter()
+                        .map(|violation| {
+                            Value::record(
+                                record![
+                                    "index" => violation.index.map_or(
+                                        Value::nothing(Span::unknown()),
+                                        |index| Value::int(index as i64, Span::unknown()),
+                                    ),
+                                    "label" => violation.label.map_or(
+                                        Value::nothing(Span::unknown()),
+                                        |label| Value::string(label, Span::unknown()),
+                                    ),
+                                    "rule"  => Value::string(violation.rule, Span::unknown()),
+                                ],
+                                Span::unknown(),
+                            )
+                        })
+                        .collect(),
+                    Span::unknown(),
+                ),
+            ],
+            value.span(),
+        ))
+    }
+}
+
+impl PluginCommand for DnsNameValidate {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let usage = match call.get_flag::<Spanned<String>>(constants::flags::AS)? {
+            Some(usage) => usage.item.parse().map_err(|err: String| {
+                LabeledError::new("invalid usage").with_label(err, usage.span)
+            })?,
+            None => Usage::Dns,
+        };
+
+        let name: Option<Value> = call.opt(0)?;
+
+        match (name, input) {
+            (Some(name), PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _)) => {
+                Ok(PipelineData::Value(Self::validate(usage, &name)?, None))
+            }
+            (None, PipelineData::Value(list @ Value::List { .. }, _)) => {
+                let span = list.span();
+                let verdicts = list
+                    .into_list()?
+                    .iter()
+                    .map(|name| Self::validate(usage, name))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(PipelineData::Value(Value::list(verdicts, span), None))
+            }
+            (None, PipelineData::Value(name, _)) => {
+                Ok(PipelineData::Value(Self::validate(usage, &name)?, None))
+            }
+            (None, _) => Err(LabeledError::new("no name")
+                .with_label("Give a name to validate or pipe it in", call.head)),
+            (Some(_), input) => Err(LabeledError::new("ambiguous input").with_label(
+                "Input should either be a positional arg or piped, but not both",
+                input.span().unwrap_or(call.head),
+            )),
+        }
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::NAME_VALIDATE
+    }
+
+    fn description(&self) -> &str {
+        "Check whether a name is valid for what it's used as"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Any name is a valid DNS name as long as its labels are at most 63 octets and the whole name at most 255 in wire format. With --as hostname, labels also have to be letters, digits, and hyphens, not starting or ending with a hyphen, and the top-level label can't be all digits (RFC 952, RFC 1123). --as mail checks a mail address's local part and its domain as a host name, or just a domain if there is no @. --as srv checks that the name starts with a service label like _sip and a protocol label like _tcp (RFC 2782, RFC 6335), followed by a host name. Returns whether the name is valid and each rule it breaks, with the label that breaks it."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Name to validate",
+            )
+            .named(
+                constants::flags::AS,
+                SyntaxShape::String,
+                "What the name is used as: dns, hostname, mail, or srv. Default: dns",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns name validate my_host.example.com --as hostname",
+                description: "find out why a name can't be a host name",
+                result: None,
+            },
+            Example {
+                example: "dns name validate _sip._tcp.example.com --as srv",
+                description: "check the owner name of an SRV record",
+                result: None,
+            },
+            Example {
+                example:
+                    "open hosts.txt | lines | dns name validate --as hostname | where not valid",
+                description: "list the invalid host names in a file",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "name", "hostname", "validate", "label", "rfc1123"]
+    }
+}
@@ -0,0 +1,101 @@
+use std::str::FromStr;
+
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{record, Example, LabeledError, PipelineData, Signature, Span, Value};
+
+use crate::{
+    dns::{config::Config, constants, nat64},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsNat64Prefix;
+
+impl DnsNat64Prefix {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let client = plugin.dns_client(&config).await?;
+        let name = Name::from_str(nat64::WELL_KNOWN_NAME).unwrap();
+        let discovery = nat64::discover(&config, &client, &name, call.head).await?;
+
+        let prefixes = discovery
+            .prefixes
+            .into_iter()
+            .map(|prefix| {
+                Value::record(
+                    record![
+                        "pref64" => Value::string(prefix.to_string(), Span::unknown()),
+                        "prefix" => Value::string(prefix.prefix.to_string(), Span::unknown()),
+                        "length" => Value::int(prefix.len.into(), Span::unknown()),
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        Ok(PipelineData::Value(
+            Value::list(prefixes, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl PluginCommand for DnsNat64Prefix {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::NAT64_PREFIX
+    }
+
+    fn description(&self) -> &str {
+        "Discover the NAT64 prefixes (Pref64) in use on this network"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Implements the discovery in RFC 7050: the AAAA records the resolver synthesizes for ipv4only.arpa reveal the prefixes its NAT64 gateways use. Returns one row per distinct prefix, or an empty list if the resolver does not do DNS64."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns nat64-prefix",
+                description: "discover the NAT64 prefixes through the system resolver",
+                result: None,
+            },
+            Example {
+                example: "let pref64 = dns nat64-prefix | first | get prefix",
+                description: "keep the first prefix for building IPv4-embedded addresses",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns", "network", "nat64", "dns64", "pref64", "ipv6", "rfc7050",
+        ]
+    }
+}
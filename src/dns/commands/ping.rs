@@ -0,0 +1,259 @@
+use std::time::{Duration, Instant};
+
+use hickory_proto::rr::RecordType;
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsPing;
+
+impl DnsPing {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine, call)?;
+
+        if let Some(server) = call.opt::<Value>(0)? {
+            config = config.with_server(&server)?;
+        }
+
+        let name = match call.get_flag::<Spanned<String>>(constants::flags::NAME)? {
+            Some(name) => Name::from_utf8(&name.item).map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), name.span)
+            })?,
+            None => Name::root(),
+        };
+
+        let count = match call.get_flag::<Spanned<i64>>(constants::flags::COUNT)? {
+            Some(count) => usize::try_from(count.item).map_err(|err| {
+                LabeledError::new("invalid input")
+                    .with_label(format!("should be positive int: {err}"), count.span)
+            })?,
+            None => constants::config::default::PING_COUNT,
+        };
+
+        let interval = match call.get_flag_value(constants::flags::INTERVAL) {
+            Some(val @ Value::Duration { .. }) => {
+                Duration::from_nanos(val.as_duration()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), val.span())
+                })?)
+            }
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("interval should be a duration", val.span()))
+            }
+            None => constants::config::default::PING_INTERVAL,
+        };
+
+        if interval.is_zero() {
+            return Err(LabeledError::new("invalid duration").with_label(
+                "interval should be more than zero",
+                call.get_flag_value(constants::flags::INTERVAL)
+                    .map_or(call.head, |val| val.span()),
+            ));
+        }
+
+        let client = plugin.dns_client(&config).await?;
+        let signals = engine.signals().clone();
+        let mut ticker = tokio::time::interval(interval);
+
+        let mut probes = Vec::with_capacity(count);
+        let mut rtts = Vec::with_capacity(count);
+
+        for seq in 0..count {
+            ticker.tick().await;
+            if signals.interrupted() {
+                break;
+            }
+
+            // a question every resolver can answer from its cache, so that
+            // the time measured is the round trip rather than a recursion
+            let mut query = hickory_proto::op::Query::query(name.clone(), RecordType::SOA);
+            query.set_query_class(config.class.item);
+            let request = serde::Query(query).into_message(&config);
+
+            let time = chrono::Local::now().fixed_offset();
+            let sent = Instant::now();
+            let resp = client.send_message(&config, request).await;
+            let rtt = sent.elapsed();
+
+            let (rcode, rtt, error) = match resp {
+                Ok(resp) => {
+                    rtts.push(rtt);
+                    (
+                        Value::string(RCode(resp.response_code()).mnemonic(), Span::unknown()),
+                        serde::util::std_to_duration(rtt),
+                        Value::nothing(Span::unknown()),
+                    )
+                }
+                Err(err) => (
+                    Value::nothing(Span::unknown()),
+                    Value::nothing(Span::unknown()),
+                    Value::string(err.to_string(), Span::unknown()),
+                ),
+            };
+
+            probes.push(Value::record(
+                record![
+                    "seq"   => Value::int(seq as i64, Span::unknown()),
+                    "time"  => Value::date(time, Span::unknown()),
+                    "rcode" => rcode,
+                    "rtt"   => rtt,
+                    "error" => error,
+                ],
+                Span::unknown(),
+            ));
+        }
+
+        if call.has_flag(constants::flags::HISTOGRAM)? {
+            return Ok(PipelineData::Value(
+                serde::util::latency_histogram(&rtts).with_span(call.head),
+                config.metadata(self.name()),
+            ));
+        }
+
+        let sent = probes.len();
+        let received = rtts.len();
+        let loss = match sent {
+            0 => 0.0,
+            sent => (sent - received) as f64 / sent as f64 * 100.0,
+        };
+
+        let duration_or_nothing = |rtt: Option<Duration>| {
+            rtt.map(serde::util::std_to_duration)
+                .unwrap_or(Value::nothing(Span::unknown()))
+        };
+
+        let avg = (!rtts.is_empty()).then(|| rtts.iter().sum::<Duration>() / rtts.len() as u32);
+        let stddev = avg.map(|avg| {
+            let variance = rtts
+                .iter()
+                .map(|rtt| (rtt.as_secs_f64() - avg.as_secs_f64()).powi(2))
+                .sum::<f64>()
+                / rtts.len() as f64;
+            Duration::from_secs_f64(variance.sqrt())
+        });
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "server"   => Value::string(config.server.item.to_string(), Span::unknown()),
+                    "protocol" => Value::string(config.protocol.item.to_string(), Span::unknown()),
+                    "sent"     => Value::int(sent as i64, Span::unknown()),
+                    "received" => Value::int(received as i64, Span::unknown()),
+                    "loss"     => Value::float(loss, Span::unknown()),
+                    "min"      => duration_or_nothing(rtts.iter().min().copied()),
+                    "avg"      => duration_or_nothing(avg),
+                    "max"      => duration_or_nothing(rtts.iter().max().copied()),
+                    "stddev"   => duration_or_nothing(stddev),
+                    "probes"   => Value::list(probes, Span::unknown()),
+                ],
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl PluginCommand for DnsPing {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::PING
+    }
+
+    fn description(&self) -> &str {
+        "Measure a server's round trip time with a series of small queries, like ping"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Sends --count queries for the SOA record of the root, or of --name, one every --interval, and waits for each answer before the next. Returns a row per probe with its response code and round trip time, or its error, along with how many were sent and answered, the percentage lost, and the minimum, average, maximum, and standard deviation of the round trip times. Any response counts as an answer, whatever its response code. With --histogram, returns instead a row per round trip time bucket, with the bucket's bounds and how many answers arrived within them, ready for charting. Stops early on ctrl-c."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .optional(
+                constants::flags::NAMESERVER,
+                SyntaxShape::String,
+                "Server to ping. Default: --server",
+            )
+            .named(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Name whose SOA record to ask for. Default: the root",
+                None,
+            )
+            .named(
+                constants::flags::COUNT,
+                SyntaxShape::Int,
+                format!(
+                    "Number of probes to send. Default: {}",
+                    constants::config::default::PING_COUNT
+                ),
+                Some('c'),
+            )
+            .named(
+                constants::flags::INTERVAL,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to wait between probes. Default: {}sec",
+                    constants::config::default::PING_INTERVAL.as_secs()
+                ),
+                Some('i'),
+            )
+            .switch(
+                constants::flags::HISTOGRAM,
+                "Return a table of round trip time buckets and their counts instead of the summary",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns ping 1.1.1.1",
+                description: "measure the round trip time to a public resolver",
+                result: None,
+            },
+            Example {
+                example: "dns ping --count 20 --interval 200ms | get probes | where error != null",
+                description: "list the probes the system resolver didn't answer",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "ping", "latency", "rtt", "loss", "health", "network"]
+    }
+}
@@ -0,0 +1,387 @@
+use std::{future::Future, pin::Pin};
+
+use hickory_proto::{
+    op::{Message, ResponseCode},
+    rr::{
+        rdata::opt::{EdnsCode, EdnsOption},
+        Name, RData, RecordType,
+    },
+    xfer::DnsResponse,
+};
+use hickory_resolver::config::Protocol;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        client::ClientPool,
+        config::Config,
+        constants,
+        error::DnsError,
+        serde::{self, DnssecMode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsProbe;
+
+/// The outcome of probing for one capability: whether the server has it, if
+/// that could be told, and what was seen.
+struct Finding {
+    supported: Option<bool>,
+    detail: String,
+}
+
+type Probe<'a> =
+    Pin<Box<dyn Future<Output = (&'static str, Result<Finding, LabeledError>)> + Send + 'a>>;
+
+impl DnsProbe {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut config = Config::from_nu(engine, call)?;
+
+        if let Some(server) = call.opt::<Value>(0)? {
+            config = config.with_server(&server)?;
+        }
+
+        // the point is to see what the server does, not what we make of it
+        config.dnssec_mode.item = DnssecMode::None;
+
+        let pool = &plugin.pool;
+        let config = &config;
+        let span = call.head;
+
+        let probes: Vec<Probe> = vec![
+            Box::pin(async move { ("edns", edns(config, pool, span).await) }),
+            Box::pin(async move { ("udp_payload", udp_payload(config, pool, span).await) }),
+            Box::pin(async move { ("tcp", tcp(config, pool, span).await) }),
+            Box::pin(async move { ("dnssec_validation", dnssec(config, pool, span).await) }),
+            Box::pin(async move { ("qname_minimization", qname_min(config, pool, span).await) }),
+            Box::pin(async move { ("cookies", cookies(config, pool, span).await) }),
+            Box::pin(async move { ("case_preserved", case(config, pool, span).await) }),
+        ];
+
+        let rows = futures_util::future::join_all(probes)
+            .await
+            .into_iter()
+            .map(|(capability, finding)| {
+                let finding = finding.unwrap_or_else(|err| Finding {
+                    supported: None,
+                    detail: err.msg,
+                });
+
+                Value::record(
+                    record![
+                        "capability" => Value::string(capability, Span::unknown()),
+                        "supported"  => finding
+                            .supported
+                            .map_or(Value::nothing(Span::unknown()), |supported| {
+                                Value::bool(supported, Span::unknown())
+                            }),
+                        "detail"     => Value::string(finding.detail, Span::unknown()),
+                    ],
+                    call.head,
+                )
+            })
+            .collect();
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+fn request(config: &Config, name: &str, qtype: RecordType) -> Message {
+    let query = hickory_proto::op::Query::query(
+        Name::from_ascii(name).expect("probe name should parse"),
+        qtype,
+    );
+
+    serde::Query(query).into_message(config)
+}
+
+async fn send(
+    config: &Config,
+    pool: &ClientPool,
+    request: Message,
+    span: Span,
+) -> Result<DnsResponse, LabeledError> {
+    let client = pool.get(config).await?;
+    config.throttle().await;
+    client
+        .send_message(config, request)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span).into())
+}
+
+async fn edns(config: &Config, pool: &ClientPool, span: Span) -> Result<Finding, LabeledError> {
+    let request = request(config, constants::probe::NAME, RecordType::A);
+    let resp = send(config, pool, request, span).await?;
+
+    Ok(match (resp.extensions(), resp.response_code()) {
+        (Some(edns), _) => Finding {
+            supported: Some(true),
+            detail: format!("version {}", edns.version()),
+        },
+        (None, ResponseCode::FormErr) => Finding {
+            supported: Some(false),
+            detail: "FORMERR in response to an EDNS query".into(),
+        },
+        (None, rcode) => Finding {
+            supported: Some(false),
+            detail: format!(
+                "no OPT record in the {} response",
+                serde::RCode(rcode).mnemonic()
+            ),
+        },
+    })
+}
+
+async fn udp_payload(
+    config: &Config,
+    pool: &ClientPool,
+    span: Span,
+) -> Result<Finding, LabeledError> {
+    let request = request(config, constants::probe::NAME, RecordType::A);
+    let resp = send(config, pool, request, span).await?;
+
+    Ok(match resp.extensions() {
+        Some(edns) => Finding {
+            supported: Some(true),
+            detail: format!("{} bytes", edns.max_payload()),
+        },
+        None => Finding {
+            supported: Some(false),
+            detail: "512 bytes, without EDNS".into(),
+        },
+    })
+}
+
+async fn tcp(config: &Config, pool: &ClientPool, span: Span) -> Result<Finding, LabeledError> {
+    let mut tcp_config = config.clone();
+    tcp_config.protocol.item = Protocol::Tcp;
+
+    let request = request(&tcp_config, constants::probe::NAME, RecordType::A);
+
+    Ok(match send(&tcp_config, pool, request, span).await {
+        Ok(resp) => Finding {
+            supported: Some(true),
+            detail: format!(
+                "answered {} over TCP",
+                serde::RCode(resp.response_code()).mnemonic()
+            ),
+        },
+        Err(err) => Finding {
+            supported: Some(false),
+            detail: err
+                .labels
+                .first()
+                .map_or(err.msg, |label| label.text.clone()),
+        },
+    })
+}
+
+/// A validating resolver sets the AD bit on answers from a signed zone, and
+/// refuses to answer for a zone whose signatures are broken.
+async fn dnssec(config: &Config, pool: &ClientPool, span: Span) -> Result<Finding, LabeledError> {
+    let mut signed = request(config, constants::probe::NAME, RecordType::A);
+    signed.set_authentic_data(true);
+    if let Some(edns) = signed.extensions_mut() {
+        edns.set_dnssec_ok(true);
+    }
+
+    let mut bogus = request(config, constants::probe::BOGUS_NAME, RecordType::A);
+    bogus.set_authentic_data(true);
+
+    let (signed, bogus) = futures_util::future::join(
+        send(config, pool, signed, span),
+        send(config, pool, bogus, span),
+    )
+    .await;
+    let (authentic, bogus_rcode) = (signed?.authentic_data(), bogus?.response_code());
+
+    Ok(Finding {
+        supported: Some(authentic && bogus_rcode == ResponseCode::ServFail),
+        detail: format!(
+            "AD bit {} for {}, {} for {}",
+            if authentic { "set" } else { "not set" },
+            constants::probe::NAME,
+            serde::RCode(bogus_rcode).mnemonic(),
+            constants::probe::BOGUS_NAME,
+        ),
+    })
+}
+
+/// The zone for this name answers differently depending on whether the
+/// resolver asked for the full name at every level, or only for one more
+/// label at a time.
+async fn qname_min(
+    config: &Config,
+    pool: &ClientPool,
+    span: Span,
+) -> Result<Finding, LabeledError> {
+    let request = request(config, constants::probe::QNAME_MIN_NAME, RecordType::TXT);
+    let resp = send(config, pool, request, span).await?;
+
+    let txt: String = resp
+        .answers()
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::TXT(txt)) => Some(
+                txt.iter()
+                    .map(|part| String::from_utf8_lossy(part))
+                    .collect::<String>(),
+            ),
+            _ => None,
+        })
+        .collect();
+
+    Ok(match txt.as_str() {
+        "" => Finding {
+            supported: None,
+            detail: format!(
+                "no TXT record for {} ({})",
+                constants::probe::QNAME_MIN_NAME,
+                serde::RCode(resp.response_code()).mnemonic()
+            ),
+        },
+        txt => Finding {
+            supported: Some(txt.starts_with("HOORAY")),
+            detail: txt.into(),
+        },
+    })
+}
+
+/// Sends a client cookie (RFC 7873); a server that supports cookies returns
+/// it along with a server cookie of its own.
+async fn cookies(config: &Config, pool: &ClientPool, span: Span) -> Result<Finding, LabeledError> {
+    let client_cookie: [u8; 8] = rand::random();
+
+    let mut request = request(config, constants::probe::NAME, RecordType::A);
+    if let Some(edns) = request.extensions_mut() {
+        edns.options_mut().insert(EdnsOption::Unknown(
+            u16::from(EdnsCode::Cookie),
+            client_cookie.to_vec(),
+        ));
+    }
+
+    let resp = send(config, pool, request, span).await?;
+    let cookie = resp
+        .extensions()
+        .as_ref()
+        .and_then(|edns| edns.options().get(EdnsCode::Cookie))
+        .and_then(|opt| match opt {
+            EdnsOption::Unknown(_, data) => Some(data.clone()),
+            _ => None,
+        });
+
+    Ok(match cookie {
+        Some(cookie) if cookie.len() >= 16 && cookie[..8] == client_cookie => Finding {
+            supported: Some(true),
+            detail: format!("{}-byte server cookie", cookie.len() - 8),
+        },
+        Some(_) => Finding {
+            supported: Some(false),
+            detail: "malformed cookie in response".into(),
+        },
+        None => Finding {
+            supported: Some(false),
+            detail: "no cookie in response".into(),
+        },
+    })
+}
+
+/// Whether the server echoes the question with its case exactly as asked,
+/// which lets clients use the case as extra entropy against spoofing (DNS
+/// 0x20).
+async fn case(config: &Config, pool: &ClientPool, span: Span) -> Result<Finding, LabeledError> {
+    let mut request = request(config, constants::probe::NAME, RecordType::A);
+    let name = serde::util::randomize_case(request.queries()[0].name());
+    request.queries_mut()[0].set_name(name.clone());
+
+    let resp = send(config, pool, request, span).await?;
+    let echoed = resp.queries().first().map(|query| query.name().clone());
+
+    Ok(match echoed {
+        Some(echoed) => Finding {
+            supported: Some(echoed.eq_case(&name)),
+            detail: format!("asked for {name}, got {echoed}"),
+        },
+        None => Finding {
+            supported: None,
+            detail: "no question in response".into(),
+        },
+    })
+}
+
+impl PluginCommand for DnsProbe {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::PROBE
+    }
+
+    fn description(&self) -> &str {
+        "Test what a resolver supports"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Probes for EDNS, the largest UDP payload it accepts, TCP, DNSSEC validation, QNAME minimization, DNS cookies, and whether it preserves the case of the question (DNS 0x20). Returns a row per capability saying whether it is supported, or nothing if that couldn't be told, with what was seen. The DNSSEC and QNAME minimization probes rely on test zones on the internet, so only make sense for recursive resolvers."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name())).optional(
+            constants::flags::NAMESERVER,
+            SyntaxShape::String,
+            "Nameserver to probe. Default: --server",
+        )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns probe 9.9.9.9",
+                description: "see what a public resolver supports",
+                result: None,
+            },
+            Example {
+                example: "dns probe | where supported == false",
+                description: "list what the system resolver lacks",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "network",
+            "capabilities",
+            "edns",
+            "cookies",
+            "qname minimization",
+            "0x20",
+        ]
+    }
+}
@@ -1,41 +1,45 @@
 use std::{
-    sync::{atomic::AtomicBool, Arc},
-    time::Duration,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use futures_util::{
-    select,
+    future,
     stream::{FuturesOrdered, FuturesUnordered},
-    FutureExt, StreamExt,
+    StreamExt,
+};
+use hickory_proto::{
+    op::{Message, ResponseCode},
+    xfer::DnsResponse,
 };
-use hickory_client::client::ClientHandle;
-use nu_plugin::{EngineInterface, EvaluatedCall, Plugin, PluginCommand};
+use hickory_resolver::config::Protocol;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
 use nu_protocol::{
-    Example, LabeledError, ListStream, PipelineData, Signals, Signature, Span, SyntaxShape, Value,
+    record, Example, LabeledError, ListStream, PipelineData, Signature, Span, SyntaxShape, Value,
 };
-use tokio::{sync::mpsc, task::JoinSet};
+use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
-use tracing_subscriber::prelude::*;
 
 use crate::{
     dns::{
-        client::DnsClient,
+        cache::{Cache, CacheKey},
+        client::ClientPool,
         config::Config,
         constants,
+        error::DnsError,
         serde::{self, Query},
+        special, wire,
     },
     Dns,
 };
 
+/// Where the next query starts among the server's addresses with `--rotate`.
+static ROTATION: AtomicUsize = AtomicUsize::new(0);
+
 pub type DnsQueryResult = FuturesUnordered<Result<Value, LabeledError>>;
-pub type DnsQueryPluginClient = Arc<
-    tokio::sync::RwLock<
-        Option<(
-            DnsClient,
-            JoinSet<Result<(), hickory_proto::error::ProtoError>>,
-        )>,
-    >,
->;
 
 #[derive(Debug)]
 pub struct DnsQuery;
@@ -48,13 +52,33 @@ impl DnsQuery {
         call: &EvaluatedCall,
         input: PipelineData,
     ) -> Result<PipelineData, LabeledError> {
-        let _ = tracing_subscriber::registry()
-            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
-            .with(tracing_subscriber::EnvFilter::from_default_env())
-            .try_init();
+        let config = Config::from_nu(engine, call)?;
+        plugin.load_cache(&config)?;
+
+        if let Some(id) = &config.id {
+            tracing::warn!(
+                query.id = id.item,
+                "using a fixed message ID makes responses easier to spoof"
+            );
+        }
 
-        let config = Config::from_nu(engine.get_plugin_config()?, call)?;
-        let arg_inputs: Value = call.nth(0).unwrap_or(Value::nothing(call.head));
+        // several names are queried as if they had been piped in as a list.
+        // each one is wrapped in a list of its own so that a list of labels
+        // is never mistaken for several names, or the other way around.
+        let mut args: Vec<Value> = call.rest(0)?;
+        let arg_inputs: Value = match args.len() {
+            0 => Value::nothing(call.head),
+            1 => args.remove(0),
+            _ => Value::list(
+                args.into_iter()
+                    .map(|arg| match arg {
+                        Value::List { .. } => arg,
+                        _ => Value::list(vec![arg.clone()], arg.span()),
+                    })
+                    .collect(),
+                call.head,
+            ),
+        };
 
         let input: PipelineData = match input {
             PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => {
@@ -72,16 +96,12 @@ impl DnsQuery {
             }
         };
 
-        let client = tokio::time::timeout(config.timeout.item, plugin.dns_client(&config))
-            .await
-            .map_err(|_| {
-                LabeledError::new("timed out").with_label(
-                    format!("connecting to {} timed out", config.server.item),
-                    config.server.span,
-                )
-            })??;
+        // connect up front so that connection errors are reported once
+        // rather than for every query
+        plugin.pool.get(&config).await?;
 
         let config = Arc::new(config);
+        let metadata = config.metadata(self.name());
 
         match input {
             PipelineData::Value(val, _) => {
@@ -91,14 +111,27 @@ impl DnsQuery {
                     tracing::debug!(phase = "input", data.kind = "value");
                 }
 
-                let values = Self::query(config, val, client.clone()).await;
+                let cancel = plugin.interrupt_token(engine.signals().clone());
+                let _done = cancel.clone().drop_guard();
+                let values = Self::query(
+                    config.clone(),
+                    val,
+                    plugin.pool.clone(),
+                    plugin.cache.clone(),
+                    cancel.clone(),
+                )
+                .await;
+
+                if cancel.is_cancelled() && !config.partial.item {
+                    return Err(interrupted(call.head));
+                }
 
                 let val = PipelineData::Value(
                     Value::list(
                         values.into_iter().collect::<Result<Vec<_>, _>>()?,
                         Span::unknown(),
                     ),
-                    None,
+                    metadata,
                 );
 
                 tracing::trace!(phase = "return", ?val);
@@ -109,25 +142,21 @@ impl DnsQuery {
                 tracing::debug!(phase = "input", data.kind = "stream");
 
                 let span = stream.span();
-                let ctrlc = Signals::new(Arc::new(AtomicBool::new(false)));
+                let cancel = plugin.interrupt_token(engine.signals().clone());
                 let (request_tx, request_rx) = mpsc::channel(config.tasks.item);
                 let (resp_tx, mut resp_rx) = mpsc::channel(config.tasks.item);
 
-                plugin.spawn(watch_sigterm(ctrlc.clone(), plugin.cancel.clone()));
-
                 plugin.spawn(coordinate_queries(
                     config,
-                    client,
+                    plugin.pool.clone(),
+                    plugin.cache.clone(),
                     request_rx,
                     resp_tx,
-                    plugin.cancel.clone(),
+                    cancel.clone(),
                 ));
 
                 plugin
-                    .spawn_blocking({
-                        let cancel = plugin.cancel.clone();
-                        move || stream_requests(stream, cancel, request_tx)
-                    })
+                    .spawn_blocking(move || stream_requests(stream, cancel, request_tx))
                     .await;
 
                 Ok(PipelineData::ListStream(
@@ -143,9 +172,9 @@ impl DnsQuery {
                         })
                         .inspect(|val| log_response_val(val, "return")),
                         span,
-                        ctrlc,
+                        engine.signals().clone(),
                     ),
-                    None,
+                    metadata,
                 ))
             }
             data => Err(LabeledError::new("invalid input").with_label(
@@ -158,10 +187,13 @@ impl DnsQuery {
     pub(crate) async fn query(
         config: Arc<Config>,
         input: Value,
-        client: DnsClient,
+        pool: Arc<ClientPool>,
+        cache: Arc<Cache>,
+        cancel: CancellationToken,
     ) -> DnsQueryResult {
         let in_span = input.span();
-        let queries = match Query::try_from_value(&input, &config) {
+
+        let queries = match Self::plan(&input, &config) {
             Ok(queries) => queries,
             Err(err) => {
                 return vec![Ok(Value::error(err.into(), in_span))]
@@ -172,61 +204,420 @@ impl DnsQuery {
 
         tracing::debug!(request.queries = ?queries);
 
+        // the questions are sent concurrently rather than one after the other.
+        // over TCP and TLS, the pooled client's multiplexer pipelines them on
+        // the one connection, telling responses apart by message ID.
         futures_util::stream::iter(queries)
-            .then(|query| {
-                let mut client = client.clone();
-                let config = config.clone();
+            .map(|(config, query)| {
+                let pool = pool.clone();
+                let cache = cache.clone();
 
                 async move {
-                    let parts = query.0.into_parts();
+                    let question = Query(query.0.clone()).into_value(&config);
+                    let deadline = config
+                        .deadline
+                        .as_ref()
+                        .map(|deadline| tokio::time::Instant::from_std(deadline.item));
 
-                    if tracing::enabled!(tracing::Level::TRACE) {
-                        tracing::trace!(query.phase = "start", query.parts = ?parts);
-                    } else {
-                        tracing::debug!(query.phase = "start");
+                    // questions still waiting for a task when the deadline
+                    // passes aren't sent at all
+                    if deadline.is_some_and(|deadline| deadline <= tokio::time::Instant::now()) {
+                        return Ok(Some(timeout_row(&config, question, 0)));
                     }
 
-                    let request = tokio::time::timeout(
-                        config.timeout.item,
-                        client.query(parts.name, parts.query_class, parts.query_type),
-                    );
-
-                    request
-                        .await
-                        .map_err(|_| {
-                            LabeledError::new("timed out").with_label(
-                                format!("request to {} timed out", config.server.item),
-                                config.server.span,
-                            )
-                        })?
-                        .map_err(|err| {
-                            LabeledError::new("DNS error")
-                                .with_label(format!("Error in DNS response: {:?}", err), in_span)
-                        })
-                        .and_then(|resp: hickory_proto::xfer::DnsResponse| {
-                            let msg = serde::Message::new(resp.into_message());
-                            msg.into_value(&config)
-                        })
-                        .inspect_err(
-                            |err| tracing::debug!(query.phase = "finish", query.error = ?err),
-                        )
-                        .inspect(|resp| {
-                            log_response_val(resp, "finish");
-                        })
+                    // how many times the question has been sent, across the
+                    // server's addresses
+                    let attempts = AtomicUsize::new(0);
+
+                    let exchange = Self::answer(&config, query, &pool, &cache, in_span, &attempts);
+
+                    let result = match deadline {
+                        Some(deadline) => tokio::time::timeout_at(deadline, exchange)
+                            .await
+                            .unwrap_or_else(|_| {
+                                Ok(Some(timeout_row(
+                                    &config,
+                                    question.clone(),
+                                    attempts.load(Ordering::Relaxed),
+                                )))
+                            }),
+                        None => exchange.await,
+                    };
+
+                    match result {
+                        Err(err) if config.errors_as_values.item => Ok(Some(error_row(
+                            &config,
+                            question,
+                            &err,
+                            attempts.load(Ordering::Relaxed),
+                        ))),
+                        result => result,
+                    }
                 }
             })
+            .buffered(config.tasks.item)
+            // stop waiting on outstanding questions once the user interrupts,
+            // keeping the responses received so far
+            .take_until(cancel.cancelled_owned())
+            .filter_map(|resp| future::ready(resp.transpose()))
             .collect::<FuturesUnordered<_>>()
             .await
     }
-}
 
-async fn watch_sigterm(ctrlc: Signals, cancel: CancellationToken) -> Result<(), LabeledError> {
-    while !ctrlc.interrupted() {
-        tokio::time::sleep(Duration::from_millis(500)).await;
+    /// Asks one question, or answers it from the cache or locally, and turns
+    /// the response into its row, or `None` if `--rcode` filters it out.
+    async fn answer(
+        config: &Config,
+        query: Query,
+        pool: &ClientPool,
+        cache: &Cache,
+        in_span: Span,
+        attempts: &AtomicUsize,
+    ) -> Result<Option<Value>, LabeledError> {
+        let request = query.into_message(config);
+
+        if tracing::enabled!(tracing::Level::TRACE) {
+            tracing::trace!(query.phase = "start", query.request = ?request);
+        } else {
+            tracing::debug!(query.phase = "start");
+        }
+
+        let cache_key = config.cache.item.then(|| {
+            CacheKey::new(
+                &request.queries()[0],
+                config.server.item,
+                config.dnssec_ok.item,
+                config.checking_disabled.item,
+                config.dnssec_mode.item.clone(),
+            )
+        });
+
+        let sent_name = request.queries()[0].name().clone();
+
+        // names under localhost. and invalid. are answered here,
+        // with no server to credit for the answer
+        let local = special::local_answer(&request);
+
+        if config.strict_special_use.item && local.is_none() {
+            if let Some(special) =
+                special::special_use(&sent_name).filter(|special| special.warns())
+            {
+                return Err(LabeledError::new("special-use name")
+                    .with_code("dns::special_use")
+                    .with_label(
+                        format!(
+                            "{} is under {} (RFC {}): {}; not sent because of --{}",
+                            sent_name,
+                            special.zone,
+                            special.rfc,
+                            special.reason,
+                            constants::flags::STRICT_SPECIAL_USE
+                        ),
+                        in_span,
+                    ));
+            }
+        }
+
+        let (resp, tcp_retry, cached, server) =
+            match (local, cache_key.as_ref().and_then(|key| cache.get(key))) {
+                (Some(message), _) => {
+                    let resp = DnsResponse::from_message(message).map_err(|err| {
+                        LabeledError::new("internal error")
+                            .with_label(format!("failed to build local answer: {}", err), in_span)
+                    })?;
+
+                    (resp, false, false, None)
+                }
+                (None, Some(message)) => {
+                    let resp = DnsResponse::from_message(message).map_err(|err| {
+                        LabeledError::new("internal error")
+                            .with_label(format!("failed to load cached response: {}", err), in_span)
+                    })?;
+
+                    (resp, false, true, Some(config.server.item))
+                }
+                (None, None) => {
+                    let (resp, tcp_retry, server) =
+                        Self::exchange(config, pool, request, in_span, attempts).await?;
+
+                    if let Some(key) = cache_key {
+                        cache.insert(
+                            key,
+                            &resp,
+                            config.min_ttl.as_ref().map(|ttl| ttl.item),
+                            config.max_ttl.as_ref().map(|ttl| ttl.item),
+                        );
+                    }
+
+                    (resp, tcp_retry, false, Some(server))
+                }
+            };
+
+        if config.fail_on_nxdomain.item {
+            if let Some(err @ DnsError::NxDomain { .. }) = DnsError::from_response(&resp, in_span) {
+                return Err(err.into());
+            }
+        }
+
+        if !config.rcode_matches(resp.response_code()) {
+            tracing::debug!(
+                query.phase = "finish",
+                query.filtered = true,
+                query.rcode = ?resp.response_code()
+            );
+            return Ok(None);
+        }
+
+        // a response that doesn't echo the exact case of the
+        // question may well be spoofed
+        let case_match = (config.randomize_case.item && !cached).then(|| {
+            resp.queries()
+                .first()
+                .is_some_and(|query| query.name().eq_case(&sent_name))
+        });
+
+        if case_match == Some(false) {
+            tracing::warn!(query.phase = "verify", query.case_match = false, query.name = %sent_name);
+        }
+
+        let problems = match config.lenient.item {
+            true => wire::problems(resp.as_buffer()),
+            false => Vec::new(),
+        };
+
+        let rcode = resp.response_code();
+        let message = serde::Message::new(resp.into_message())
+            .with_tcp_retry(tcp_retry)
+            .with_cached(cached)
+            .with_case_match(case_match);
+        let message = match server {
+            Some(server) => message.with_server(server),
+            None => message,
+        };
+
+        message
+            .into_value(config)
+            .and_then(|message| wire::insert_problems(message, problems, config))
+            .and_then(|message| match config.errors_as_values.item {
+                true => rcode_error(message, rcode, attempts.load(Ordering::Relaxed)),
+                false => Ok(message),
+            })
+            .inspect_err(|err| tracing::debug!(query.phase = "finish", query.error = ?err))
+            .inspect(|resp| {
+                log_response_val(resp, "finish");
+            })
+            .map(Some)
     }
 
-    cancel.cancel();
-    Ok(())
+    /// Works out the questions to ask for the input, each paired with the
+    /// config to ask it with. Records in the input can name their own server
+    /// to ask.
+    fn plan(
+        input: &Value,
+        config: &Arc<Config>,
+    ) -> Result<Vec<(Arc<Config>, Query)>, LabeledError> {
+        let config = match input {
+            Value::List { vals, .. }
+                if vals.iter().any(|val| matches!(val, Value::Record { .. })) =>
+            {
+                return Ok(vals
+                    .iter()
+                    .map(|val| Self::plan(val, config))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .flatten()
+                    .collect());
+            }
+            Value::Record { .. } => match serde::util::column(input, constants::columns::SERVER) {
+                Some(server) => Arc::new(config.with_server(&server)?),
+                None => config.clone(),
+            },
+            _ => config.clone(),
+        };
+
+        Ok(Query::try_from_value(input, &config)?
+            .into_iter()
+            .map(|query| (config.clone(), query))
+            .collect())
+    }
+
+    /// Sends the request to each address of the server in turn until one of
+    /// them answers with anything but SERVFAIL, which only makes a difference
+    /// for a server given as a host name with more than one address, or the
+    /// system nameservers. The addresses are gone through `--attempts` times,
+    /// starting from the next one each query with `--rotate`, like the
+    /// system's stub resolver does. Also returns whether the answer came from
+    /// retrying over TCP, and which address it came from.
+    async fn exchange(
+        config: &Config,
+        pool: &ClientPool,
+        request: Message,
+        in_span: Span,
+        attempts: &AtomicUsize,
+    ) -> Result<(DnsResponse, bool, SocketAddr), LabeledError> {
+        let addrs = &config.server_addrs;
+        let start = match config.rotate.item {
+            true => ROTATION.fetch_add(1, Ordering::Relaxed) % addrs.len(),
+            false => 0,
+        };
+        let tries: Vec<SocketAddr> = addrs
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(addrs.len() * config.attempts.item)
+            .copied()
+            .collect();
+        let (last, rest) = tries
+            .split_last()
+            .expect("a server has at least one address");
+
+        for addr in rest {
+            let config = config.with_addr(*addr);
+            attempts.fetch_add(1, Ordering::Relaxed);
+
+            match Self::exchange_with(&config, pool, request.clone(), in_span).await {
+                Ok((resp, _)) if resp.response_code() == ResponseCode::ServFail => {
+                    tracing::debug!(query.phase = "retry", query.retry.reason = "servfail", query.server = %addr);
+                }
+                Ok((resp, tcp_retry)) => return Ok((resp, tcp_retry, *addr)),
+                Err(err) => {
+                    tracing::debug!(query.phase = "retry", query.retry.reason = "server", query.server = %addr, query.error = ?err);
+                }
+            }
+        }
+
+        let config = config.with_addr(*last);
+        attempts.fetch_add(1, Ordering::Relaxed);
+        let (resp, tcp_retry) = Self::exchange_with(&config, pool, request, in_span).await?;
+
+        Ok((resp, tcp_retry, *last))
+    }
+
+    /// Sends the request, retrying over TCP if the response comes back
+    /// truncated. Also returns whether that retry happened.
+    async fn exchange_with(
+        config: &Config,
+        pool: &ClientPool,
+        request: Message,
+        in_span: Span,
+    ) -> Result<(DnsResponse, bool), LabeledError> {
+        let client = pool.get(config).await?;
+        config.throttle().await;
+        let resp = client
+            .send_message(config, request.clone())
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, in_span))?;
+
+        // a truncated UDP response is incomplete, so ask again over TCP unless
+        // the user explicitly wants the truncated answer
+        if !resp.truncated() || config.protocol.item != Protocol::Udp || config.ignore_tc.item {
+            return Ok((resp, false));
+        }
+
+        tracing::debug!(query.phase = "retry", query.retry.reason = "truncated");
+
+        let mut tcp_config = config.clone();
+        tcp_config.protocol.item = Protocol::Tcp;
+
+        let tcp_client = pool.get(&tcp_config).await?;
+        pool.stats.record_retry();
+        config.throttle().await;
+        let resp = tcp_client
+            .send_message(&tcp_config, request)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, in_span))?;
+
+        Ok((resp, true))
+    }
+}
+
+/// Stands in for the response to a question that didn't get one before the
+/// `--deadline`, so that it is still accounted for in the output. It has the
+/// columns of a message, empty, so it can be handled like the rest.
+fn timeout_row(config: &Config, question: Value, attempts: usize) -> Value {
+    let mut row = serde::Message::empty_value(question, config.server.item);
+    row.push("timeout", Value::bool(true, Span::unknown()));
+    row.push(
+        "error",
+        error_value(
+            "timeout",
+            None,
+            "no response before the deadline".into(),
+            attempts,
+        ),
+    );
+
+    Value::record(row, Span::unknown())
+}
+
+/// Stands in for the response to a question that failed, with
+/// `--errors-as-values`, shaped like [`timeout_row`].
+fn error_row(config: &Config, question: Value, err: &LabeledError, attempts: usize) -> Value {
+    let kind = DnsError::kind(err.code.as_deref());
+    let message = err
+        .labels
+        .first()
+        .map_or_else(|| err.msg.clone(), |label| label.text.clone());
+
+    let mut row = serde::Message::empty_value(question, config.server.item);
+    row.push("timeout", Value::bool(kind == "timeout", Span::unknown()));
+    row.push(
+        "error",
+        error_value(kind, err.code.as_deref(), message, attempts),
+    );
+
+    Value::record(row, Span::unknown())
+}
+
+/// Adds the error column to a response, with `--errors-as-values`: nothing,
+/// unless its response code says the server failed to answer or refused to.
+fn rcode_error(
+    message: Value,
+    rcode: ResponseCode,
+    attempts: usize,
+) -> Result<Value, LabeledError> {
+    let span = message.span();
+    let mut message = message.into_record()?;
+
+    let error = match rcode {
+        ResponseCode::ServFail => error_value(
+            "servfail",
+            Some("dns::servfail"),
+            "the server failed to answer".into(),
+            attempts,
+        ),
+        ResponseCode::Refused => error_value(
+            "refused",
+            Some("dns::refused"),
+            "the server refused to answer".into(),
+            attempts,
+        ),
+        _ => Value::nothing(Span::unknown()),
+    };
+    message.push("error", error);
+
+    Ok(Value::record(message, span))
+}
+
+fn error_value(kind: &str, code: Option<&str>, message: String, attempts: usize) -> Value {
+    Value::record(
+        record![
+            "kind"     => Value::string(kind, Span::unknown()),
+            "code"     => code.map_or(Value::nothing(Span::unknown()), |code| {
+                Value::string(code, Span::unknown())
+            }),
+            "message"  => Value::string(message, Span::unknown()),
+            "attempts" => Value::int(attempts as i64, Span::unknown()),
+        ],
+        Span::unknown(),
+    )
+}
+
+fn interrupted(span: Span) -> LabeledError {
+    LabeledError::new("interrupted").with_label(
+        "interrupted before all responses were received; pass --partial to keep the ones that were",
+        span,
+    )
 }
 
 fn stream_requests(
@@ -258,11 +649,15 @@ fn stream_requests(
 
 async fn coordinate_queries(
     config: Arc<Config>,
-    client: DnsClient,
+    pool: Arc<ClientPool>,
+    cache: Arc<Cache>,
     mut request_rx: mpsc::Receiver<Value>,
     resp_tx: mpsc::Sender<Result<Value, LabeledError>>,
     cancel: CancellationToken,
 ) -> Result<(), LabeledError> {
+    // once there is nothing left to query, there is nothing left to interrupt
+    let _done = cancel.clone().drop_guard();
+
     tracing::trace!(task.query_coordinator.phase = "start");
     let mut buf = Vec::with_capacity(config.tasks.item);
     let mut result_queue = FuturesOrdered::new();
@@ -274,19 +669,14 @@ async fn coordinate_queries(
             tracing::trace!(query = ?val, query.phase = "received");
 
             let config = config.clone();
-            let client = client.clone();
+            let pool = pool.clone();
+            let cache = cache.clone();
             let cancel = cancel.clone();
 
             let handle = tokio::spawn(async move {
                 tracing::trace!(task.query_exec.phase = "start");
 
-                let mut query = Box::pin(DnsQuery::query(config, val, client).fuse());
-                let mut cancelled = Box::pin(cancel.cancelled().fuse());
-
-                let result = select! {
-                    resp = query => resp,
-                    _ = cancelled => vec![Err(LabeledError::new("canceled"))].into_iter().collect(),
-                };
+                let result = DnsQuery::query(config, val, pool, cache, cancel).await;
 
                 tracing::trace!(
                     task.query_exec.phase = "end",
@@ -314,6 +704,14 @@ async fn coordinate_queries(
                 })?;
             }
         }
+
+        if cancel.is_cancelled() {
+            if !config.partial.item {
+                let _ = resp_tx.send(Err(interrupted(Span::unknown()))).await;
+            }
+
+            break;
+        }
     }
 
     tracing::trace!(task.query_coordinator.phase = "exit");
@@ -335,16 +733,6 @@ pub(crate) fn log_response_val(resp: &Value, phase: &str) {
     }
 }
 
-impl Plugin for Dns {
-    fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
-        vec![Box::new(DnsQuery)]
-    }
-
-    fn version(&self) -> String {
-        env!("CARGO_PKG_VERSION").into()
-    }
-}
-
 impl PluginCommand for DnsQuery {
     type Plugin = Dns;
 
@@ -369,7 +757,7 @@ impl PluginCommand for DnsQuery {
     }
 
     fn signature(&self) -> nu_protocol::Signature {
-        Signature::build(self.name())
+        super::connection_flags(Signature::build(self.name()))
             .rest(
                 constants::flags::NAME,
 
@@ -388,18 +776,14 @@ impl PluginCommand for DnsQuery {
                 "DNS record name",
             )
             .named(
-                constants::flags::SERVER,
-                SyntaxShape::String,
-                "Nameserver to query (defaults to system config or 8.8.8.8)",
-                Some('s'),
-            )
-            .named(
-                constants::flags::PROTOCOL,
-                SyntaxShape::String,
-                "Protocol to use to connect to the nameserver: UDP, TCP. (default: UDP)",
-                Some('p'),
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                format!(
+                    "Query type(s). Default: {:?}, or `type` in the plugin config",
+                    constants::config::default::QTYPES
+                ),
+                Some('t'),
             )
-            .named(constants::flags::TYPE, SyntaxShape::Any, "Query type", Some('t'))
             .named(constants::flags::CLASS, SyntaxShape::Any, "Query class", None)
             .switch(
                 constants::flags::CODE,
@@ -407,16 +791,161 @@ impl PluginCommand for DnsQuery {
                 Some('c'),
             )
             .named(
-                constants::flags::DNSSEC,
+                constants::flags::RCODE_FILTER,
+                SyntaxShape::Any,
+                "Only return responses with the given response code(s), e.g. NOERROR, NXDOMAIN, or numeric codes",
+                None,
+            )
+            .named(
+                constants::flags::ID,
+                SyntaxShape::Int,
+                "Use a fixed message ID instead of a random one, e.g. to reproduce a packet trace. WARNING: predictable IDs make responses easier to spoof. Only supported over UDP and TCP, and bypasses DNSSEC validation",
+                None,
+            )
+            .switch(
+                constants::flags::CACHE,
+                "Answer from the plugin's response cache when possible, and cache new responses for as long as their TTLs allow",
+                None,
+            )
+            .switch(
+                constants::flags::NO_CACHE,
+                "Neither answer from nor add to the cache, even if it is turned on in the plugin config",
+                None,
+            )
+            .named(
+                constants::flags::MIN_TTL,
+                SyntaxShape::Duration,
+                "Keep cached responses for at least this long, even if their TTLs are shorter",
+                None,
+            )
+            .named(
+                constants::flags::MAX_TTL,
+                SyntaxShape::Duration,
+                "Keep cached responses for at most this long, even if their TTLs are longer",
+                None,
+            )
+            .switch(
+                constants::flags::IGNORE_TC,
+                "Return truncated UDP responses as is instead of retrying over TCP",
+                None,
+            )
+            .switch(
+                constants::flags::RANDOMIZE_CASE,
+                "Randomize the case of the query name and check that the response echoes it exactly, in the case_match column. A mismatch suggests a spoofed response",
+                None,
+            )
+            .switch(
+                constants::flags::SORT,
+                "Order the records in each section canonically, by name, type, then data, so output is stable between runs",
+                None,
+            )
+            .switch(
+                constants::flags::DEDUP,
+                "Drop records repeated within a section",
+                None,
+            )
+            .switch(
+                constants::flags::GROUP_RRSETS,
+                "Group the records in each section by name, type, and class into rows with a records list and the set's lowest TTL",
+                None,
+            )
+            .switch(
+                constants::flags::COMPACT,
+                "Leave out empty sections and columns with nothing in them",
+                None,
+            )
+            .named(
+                constants::flags::OUTPUT_SCHEMA,
                 SyntaxShape::String,
-                "Perform DNSSEC validation on records. Choices are: \"none\", \"strict\" (error if record has no RRSIG or does not validate), \"opportunistic\" (validate if RRSIGs present, otherwise no validation; default)",
-                Some('d'),
+                "Layout of the returned records, for scripts written against an older one: v1 has the message and record columns of 3.0.7, with A and AAAA rdata as address strings; v2, the default, has today's columns, with A and AAAA rdata as records with ip, family, and reverse_name",
+                None,
+            )
+            .switch(
+                constants::flags::SCALAR,
+                "Make every column a scalar, for `to csv` and the like: sections become their records in presentation format, nested records become columns named by their path, and lists are joined with --separator",
+                None,
             )
             .named(
-                constants::flags::DNS_NAME,
+                constants::flags::SEPARATOR,
                 SyntaxShape::String,
-                "DNS name of the TLS certificate in use by the nameserver (for TLS and HTTPS only)",
-                Some('n'),
+                format!(
+                    "What to join lists with, with --scalar. Default: \"{}\"",
+                    constants::config::default::SEPARATOR
+                ),
+                None,
+            )
+            .switch(
+                constants::flags::WITH_WIRE,
+                "Add each record's canonical wire format (RFC 4034 section 6.2) as binary and its SHA-256 as hex, for spotting changes and duplicates by exact content",
+                None,
+            )
+            .switch(
+                constants::flags::STRICT_SPECIAL_USE,
+                "Refuse to send questions about special-use names, like those under local. or onion., that a server can't meaningfully answer",
+                None,
+            )
+            .switch(
+                constants::flags::FAIL_ON_NXDOMAIN,
+                "Return an error instead of the response when a name does not exist",
+                None,
+            )
+            .switch(
+                constants::flags::NO_AUTO_PTR,
+                "Query IP addresses as names instead of doing a reverse (PTR) lookup for them",
+                None,
+            )
+            .switch(
+                constants::flags::WITH_QUESTION,
+                "Add the question to every record in the answer, authority, and additional sections, so each still says what was asked after they're flattened into one table",
+                None,
+            )
+            .switch(
+                constants::flags::LENIENT,
+                "Salvage what can be parsed from malformed responses instead of failing, with an error column on each record that couldn't be. Bypasses DNSSEC validation",
+                None,
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat names as fully qualified even without a trailing dot",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot. By default, every name is shown with one",
+                None,
+            )
+            .named(
+                constants::flags::EDNS_OPT,
+                SyntaxShape::Any,
+                "EDNS options to attach to the query, as <code>:<hex> or a list of them, e.g. [65001:beef 65002:]",
+                None,
+            )
+            .switch(
+                constants::flags::PARTIAL,
+                "When interrupted, return the responses received so far instead of an error",
+                None,
+            )
+            .switch(
+                constants::flags::ERRORS_AS_VALUES,
+                "Return a row with an error column for each question that failed, instead of failing the command",
+                None,
+            )
+            .named(
+                constants::flags::DEADLINE,
+                SyntaxShape::Duration,
+                "Give up on questions still unanswered this long after the command started, returning a row with timeout set for each instead",
+                None,
+            )
+            .named(
+                constants::flags::ATTEMPTS,
+                SyntaxShape::Int,
+                "How many times to go through the server's addresses before giving up. Default: resolv.conf's attempts without --server, otherwise 1",
+                None,
+            )
+            .switch(
+                constants::flags::ROTATE,
+                "Start each query at the next of the server's addresses, spreading the load. Default: whether resolv.conf has rotate, without --server",
+                None,
             )
             .named(
                 constants::flags::TASKS,
@@ -425,14 +954,14 @@ impl PluginCommand for DnsQuery {
                 Some('j'),
             )
             .named(
-                constants::flags::TIMEOUT,
-                SyntaxShape::Duration,
-                format!("How long a request can take before timing out. Be aware the concurrency level can affect this. Default: {}sec", constants::config::default::TIMEOUT.as_secs()),
+                constants::flags::RATE,
+                SyntaxShape::Number,
+                "Send at most this many queries per second, to go easy on nameservers you don't run",
                 None,
             )
     }
 
-    fn examples(&self) -> Vec<nu_protocol::Example> {
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
         vec![
             Example {
                 example: "dns query google.com",
@@ -454,6 +983,26 @@ impl PluginCommand for DnsQuery {
                 description: "specify query types by numeric ID, and get numeric IDs in output",
                 result: None,
             },
+            Example {
+                example: "['google.com', 'nonexistent.invalid'] | dns query --rcode-filter NXDOMAIN",
+                description: "only keep responses for names that do not exist",
+                result: None,
+            },
+            Example {
+                example: "dns query google.com amazon.com",
+                description: "query several names at once",
+                result: None,
+            },
+            Example {
+                example: "dns query --with-question google.com amazon.com | get answer | flatten",
+                description: "collect the answers to several questions into one table that still says which question each answers",
+                result: None,
+            },
+            Example {
+                example: "dns query 8.8.8.8",
+                description: "reverse lookup of an IP address, like `dig -x`",
+                result: None,
+            },
             Example {
                 example: "'google.com' | dns query",
                 description: "pipe name to command",
@@ -469,9 +1018,29 @@ impl PluginCommand for DnsQuery {
                 description: "query record name that has labels with non-renderable bytes",
                 result: None,
             },
+            Example {
+                example: "[{name: [0x[00ff] example com]}] | dns query",
+                description: "labels can be a mix of binary and strings",
+                result: None,
+            },
             Example {
                 example: "[{{name: 'google.com', type: 'A'}}, {{name: 'amazon.com', type: 'A'}}] | dns query",
-                description: "pipe table of queries to command (ignores --type flag)",
+                description: "pipe table of queries to command (type and class columns override the flags)",
+                result: None,
+            },
+            Example {
+                example: "dns query --type ANY cloudflare.com | select answer minimal_any",
+                description: "ask for all records of a name; minimal_any is true if the server declined per RFC 8482",
+                result: None,
+            },
+            Example {
+                example: "open queries.csv | dns query",
+                description: "run a query per row, with name, type, class, and server columns; empty cells fall back to the flags",
+                result: None,
+            },
+            Example {
+                example: "open names.txt | lines | dns query --server 192.0.2.53 --rate 10",
+                description: "query a server you don't run for many names, at most 10 per second",
                 result: None,
             },
         ]
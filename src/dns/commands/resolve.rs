@@ -0,0 +1,319 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures_util::{stream, StreamExt};
+use hickory_proto::{op::ResponseCode, rr::RecordType};
+use hickory_resolver::{
+    config::{
+        LookupIpStrategy, NameServerConfig, NameServerConfigGroup, ResolverConfig, ResolverOpts,
+        TlsClientConfig,
+    },
+    error::ResolveErrorKind,
+    system_conf, Name,
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        client,
+        config::Config,
+        constants,
+        serde::{self, DnssecMode, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsResolve;
+
+impl DnsResolve {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let no_hosts = call.has_flag(constants::flags::NO_HOSTS)?;
+
+        let mut names: Vec<Spanned<String>> = call.rest(0)?;
+
+        match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => {}
+            input if names.is_empty() => {
+                for val in input.into_iter() {
+                    let span = val.span();
+                    names.push(Spanned {
+                        item: val.into_string()?,
+                        span,
+                    });
+                }
+            }
+            input => {
+                return Err(LabeledError::new("ambiguous input").with_label(
+                    "Input should either be positional args or piped, but not both",
+                    input.span().unwrap_or(call.head),
+                ))
+            }
+        }
+
+        let names = names
+            .into_iter()
+            .map(|name| {
+                Name::from_utf8(&name.item)
+                    .map(|parsed| (name.clone(), config.input_name(parsed)))
+                    .map_err(|err| {
+                        LabeledError::new("invalid name")
+                            .with_label(format!("Error parsing name: {}", err), name.span)
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (resolver_config, opts) = resolver_config(&config, no_hosts);
+        let resolver = plugin.resolver(resolver_config, opts);
+
+        let questions: Vec<_> = names
+            .into_iter()
+            .flat_map(|(given, name)| {
+                config
+                    .qtypes
+                    .item
+                    .iter()
+                    .map(move |qtype| (given.clone(), name.clone(), qtype.item))
+            })
+            .collect();
+
+        let rows = stream::iter(questions)
+            .map(|(given, name, qtype)| {
+                let resolver = resolver.clone();
+                let config = &config;
+
+                async move {
+                    let lookup = resolver.lookup(name.clone(), qtype).await;
+                    resolution(config, given, &name, qtype, lookup)
+                }
+            })
+            .buffered(config.tasks.item)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// Sets up the stub resolver the way the system would, with the system's
+/// search list and, unless `--server` is given, its nameservers.
+fn resolver_config(config: &Config, no_hosts: bool) -> (ResolverConfig, ResolverOpts) {
+    let (system, mut opts) = system_conf::read_system_conf()
+        .unwrap_or_else(|_| (ResolverConfig::default(), ResolverOpts::default()));
+
+    // the system nameservers are only picked when no server was given
+    let resolver_config = match config.server.span == Span::unknown() {
+        true => system,
+        false => {
+            let tls_config = matches!(
+                config.protocol.item,
+                hickory_resolver::config::Protocol::Tls
+                    | hickory_resolver::config::Protocol::Https
+                    | hickory_resolver::config::Protocol::Quic
+            )
+            .then(|| TlsClientConfig(Arc::new(client::tls_client_config())));
+
+            let name_servers: Vec<_> = config
+                .server_addrs
+                .iter()
+                .map(|addr| NameServerConfig {
+                    tls_dns_name: config.dns_name.as_ref().map(|name| name.item.clone()),
+                    tls_config: tls_config.clone(),
+                    ..NameServerConfig::new(*addr, config.protocol.item)
+                })
+                .collect();
+
+            ResolverConfig::from_parts(
+                system.domain().cloned(),
+                system.search().to_vec(),
+                NameServerConfigGroup::from(name_servers),
+            )
+        }
+    };
+
+    opts.timeout = config.timeout.item;
+    opts.use_hosts_file = !no_hosts;
+    opts.validate = config.dnssec_mode.item == DnssecMode::Strict;
+    opts.ip_strategy = match (config.ipv4.item, config.ipv6.item) {
+        (true, _) => LookupIpStrategy::Ipv4Only,
+        (_, true) => LookupIpStrategy::Ipv6Only,
+        _ => opts.ip_strategy,
+    };
+
+    (resolver_config, opts)
+}
+
+/// Turns the outcome of a lookup into a row. A name that doesn't exist or has
+/// no records of the type isn't an error, just a row with no records.
+fn resolution(
+    config: &Config,
+    given: Spanned<String>,
+    name: &Name,
+    qtype: RecordType,
+    lookup: Result<hickory_resolver::lookup::Lookup, hickory_resolver::error::ResolveError>,
+) -> Result<Value, LabeledError> {
+    let (resolved, rcode, records, valid_until) = match lookup {
+        Ok(lookup) => (
+            lookup.query().name().clone(),
+            ResponseCode::NoError,
+            lookup
+                .records()
+                .iter()
+                .map(|record| serde::Record(record.clone()).into_value(config))
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(lookup.valid_until()),
+        ),
+        Err(err) => match err.kind() {
+            ResolveErrorKind::NoRecordsFound {
+                query,
+                response_code,
+                negative_ttl,
+                ..
+            } => (
+                query.name().clone(),
+                *response_code,
+                Vec::new(),
+                negative_ttl.map(|ttl| Instant::now() + Duration::from_secs(ttl.into())),
+            ),
+            _ => {
+                return Err(LabeledError::new("resolution failed").with_label(
+                    format!("could not resolve {name} {qtype}: {err}"),
+                    given.span,
+                ))
+            }
+        },
+    };
+
+    let valid_until = valid_until.map_or(Value::nothing(Span::unknown()), |valid_until| {
+        let remaining = valid_until.saturating_duration_since(Instant::now());
+        let date = chrono::Local::now().fixed_offset()
+            + chrono::Duration::from_std(remaining).unwrap_or_default();
+
+        Value::date(date, Span::unknown())
+    });
+
+    Ok(Value::record(
+        record![
+            "name"        => Value::string(given.item, Span::unknown()),
+            "type"        => Value::string(serde::util::rtype_mnemonic(qtype), Span::unknown()),
+            "resolved"    => Value::string(config.display_name(&resolved), Span::unknown()),
+            "rcode"       => RCode(rcode).into_value(),
+            "records"     => Value::list(records, Span::unknown()),
+            "valid_until" => valid_until,
+        ],
+        Span::unknown(),
+    ))
+}
+
+impl PluginCommand for DnsResolve {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::RESOLVE
+    }
+
+    fn description(&self) -> &str {
+        "Resolve names the way the system's stub resolver would"
+    }
+
+    fn extra_description(&self) -> &str {
+        "A counterpart to `dns query`, which sends exactly the question given. This goes through hickory's stub resolver instead: names are tried with the system's search list, the hosts file is consulted, failed queries are retried, and answers are cached for as long as the plugin runs. Each name and type gets a row with the name that was resolved in the end, the records, and when they expire from the cache. With --fqdn, names aren't searched. Uses the system's nameservers unless --server is given."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .rest(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "DNS record names",
+            )
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Record type(s) to resolve",
+                Some('t'),
+            )
+            .switch(
+                constants::flags::NO_HOSTS,
+                "Don't answer from the hosts file",
+                None,
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat names as fully qualified even without a trailing dot, so the search list isn't tried",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot",
+                None,
+            )
+            .switch(
+                constants::flags::CODE,
+                "Return code fields with both string and numeric representations",
+                Some('c'),
+            )
+            .named(
+                constants::flags::TASKS,
+                SyntaxShape::Int,
+                format!(
+                    "Number of names to resolve at once. Default: {}",
+                    constants::config::default::TASKS
+                ),
+                Some('j'),
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns resolve www",
+                description: "resolve a short name with the system's search list",
+                result: None,
+            },
+            Example {
+                example: "dns resolve localhost --type A",
+                description: "see what the hosts file says",
+                result: None,
+            },
+            Example {
+                example: "dns resolve example.com --type [MX TXT] | get records | flatten",
+                description: "resolve several types and flatten the records into one table",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "resolve", "resolver", "stub", "search", "hosts"]
+    }
+}
@@ -0,0 +1,206 @@
+use futures_util::{stream, StreamExt};
+use hickory_proto::rr::RecordType;
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{client::DnsClient, config::Config, constants, error::DnsError, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsScanServices;
+
+impl DnsScanServices {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let all = call.has_flag(constants::flags::ALL)?;
+        let domain: Spanned<String> = call.req(0)?;
+        let span = domain.span;
+        let domain = serde::util::parse_name(&domain.item)
+            .map(|domain| config.input_name(domain))
+            .map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), span)
+            })?;
+
+        let client = plugin.dns_client(&config).await?;
+
+        let rows = stream::iter(constants::scan::SERVICES)
+            .map(|(prefix, qtype, service)| {
+                let client = client.clone();
+                let config = &config;
+                let domain = &domain;
+
+                async move {
+                    let name = Name::from_ascii(prefix)
+                        .and_then(|prefix| prefix.append_domain(domain))
+                        .map_err(|err| {
+                            LabeledError::new("invalid name").with_label(
+                                format!("{prefix} can't go under {domain}: {err}"),
+                                span,
+                            )
+                        })?;
+
+                    let records = scan(config, &client, &name, *qtype, span).await?;
+
+                    Ok::<_, LabeledError>((name, *qtype, *service, records))
+                }
+            })
+            .buffered(config.tasks.item)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|(_, _, _, records)| all || !records.is_empty())
+            .map(|(name, qtype, service, records)| {
+                Ok(Value::record(
+                    record![
+                        "name"    => Value::string(config.display_name(&name), Span::unknown()),
+                        "type"    => Value::string(qtype.to_string(), Span::unknown()),
+                        "service" => Value::string(service, Span::unknown()),
+                        "found"   => Value::bool(!records.is_empty(), Span::unknown()),
+                        "records" => Value::list(
+                            records
+                                .into_iter()
+                                .map(|record| serde::Record(record).into_value(&config))
+                                .collect::<Result<_, _>>()?,
+                            Span::unknown(),
+                        ),
+                    ],
+                    call.head,
+                ))
+            })
+            .collect::<Result<Vec<_>, LabeledError>>()?;
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+/// Asks for the records of one well known name. Only records of the type
+/// asked for count, so that a CNAME pointing nowhere isn't taken for the
+/// service.
+async fn scan(
+    config: &Config,
+    client: &DnsClient,
+    name: &Name,
+    qtype: RecordType,
+    span: Span,
+) -> Result<Vec<hickory_proto::rr::Record>, LabeledError> {
+    let answers = client
+        .answers(config, name, qtype)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+    Ok(answers
+        .into_iter()
+        .filter(|record| record.record_type() == qtype)
+        .collect())
+}
+
+impl PluginCommand for DnsScanServices {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SCAN_SERVICES
+    }
+
+    fn description(&self) -> &str {
+        "Find which well known services a domain publishes records for"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Asks for a curated list of underscore names under the domain: SRV records for services like SIP, XMPP, Matrix, LDAP, Kerberos, autodiscover, CalDAV, CardDAV, and mail access, and TXT records for DMARC, MTA-STS, TLS reporting, BIMI, and ACME challenges. Returns a row for each name that has records of its type, or for every name with --all."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .required(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Domain to scan",
+            )
+            .switch(
+                constants::flags::ALL,
+                "Include the names that have no records, with found set to false",
+                Some('a'),
+            )
+            .switch(
+                constants::flags::FQDN,
+                "Treat the domain as fully qualified even without a trailing dot",
+                None,
+            )
+            .switch(
+                constants::flags::NO_ROOT_DOT,
+                "Show names without the trailing root dot",
+                None,
+            )
+            .named(
+                constants::flags::TASKS,
+                SyntaxShape::Int,
+                format!(
+                    "Number of names to ask about at once. Default: {}",
+                    constants::config::default::TASKS
+                ),
+                Some('j'),
+            )
+            .named(
+                constants::flags::RATE,
+                SyntaxShape::Number,
+                "Send at most this many queries per second, to go easy on nameservers you don't run",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns scan services example.com",
+                description: "list the services a domain advertises",
+                result: None,
+            },
+            Example {
+                example: "dns scan services example.com | where type == SRV | get records | flatten | get rdata",
+                description: "see where the SRV records point",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "scan",
+            "srv",
+            "txt",
+            "services",
+            "recon",
+            "inventory",
+        ]
+    }
+}
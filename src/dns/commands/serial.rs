@@ -0,0 +1,213 @@
+use std::{
+    cmp::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        constants,
+        serde::util,
+        serial::{self, Format},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsSerialCompare;
+
+#[derive(Debug)]
+pub struct DnsSerialNext;
+
+/// Reads a serial from an int, or from a record with a `serial` column, like
+/// the rdata of SOA records from `dns query`.
+fn serial_from_value(value: &Value) -> Result<u32, LabeledError> {
+    let value = match value {
+        Value::Record { .. } => util::column(value, "serial").ok_or_else(|| {
+            LabeledError::new("invalid serial")
+                .with_label("record should have a serial column", value.span())
+        })?,
+        value => value.clone(),
+    };
+
+    u32::try_from(value.as_int()?).map_err(|err| {
+        LabeledError::new("invalid serial").with_label(
+            format!("should be between 0 and 4294967295: {err}"),
+            value.span(),
+        )
+    })
+}
+
+impl PluginCommand for DnsSerialCompare {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let a = serial_from_value(&call.req(0)?)?;
+        let b = serial_from_value(&call.req(1)?)?;
+
+        let order = match serial::compare(a, b) {
+            Some(Ordering::Less) => "less",
+            Some(Ordering::Equal) => "equal",
+            Some(Ordering::Greater) => "greater",
+            None => "undefined",
+        };
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "a"        => Value::int(a.into(), Span::unknown()),
+                    "b"        => Value::int(b.into(), Span::unknown()),
+                    "order"    => Value::string(order, Span::unknown()),
+                    "distance" => Value::int(serial::distance(a, b), Span::unknown()),
+                ],
+                call.head,
+            ),
+            None,
+        ))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SERIAL_COMPARE
+    }
+
+    fn description(&self) -> &str {
+        "Compare two SOA serials with serial number arithmetic"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Serials wrap around at 2^32, so a serial just past the wrap is newer than one just before it even though it is smaller (RFC 1982). Returns whether a is less than, equal to, or greater than b, or undefined if they are exactly 2^31 apart, along with how far b is ahead of a."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        Signature::build(self.name())
+            .required("a", SyntaxShape::Any, "Serial to compare")
+            .required("b", SyntaxShape::Any, "Serial to compare it with")
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns serial compare 4294967295 1",
+                description: "a serial that wrapped around is still newer",
+                result: None,
+            },
+            Example {
+                example: "dns serial compare (dns query -t SOA example.com | get 0.answer.0.rdata) 2024013100",
+                description: "see whether a zone has picked up a change",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "soa", "serial", "rfc1982", "zone"]
+    }
+}
+
+impl PluginCommand for DnsSerialNext {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current = match (call.opt::<Value>(0)?, input) {
+            (Some(serial), PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _)) => {
+                serial
+            }
+            (None, PipelineData::Value(serial, _)) => serial,
+            (None, PipelineData::Empty) => {
+                return Err(LabeledError::new("no serial")
+                    .with_label("Give the current serial or pipe it in", call.head))
+            }
+            (_, input) => {
+                return Err(LabeledError::new("ambiguous input").with_label(
+                    "Input should either be a positional arg or piped, but not both",
+                    input.span().unwrap_or(call.head),
+                ))
+            }
+        };
+        let current = serial_from_value(&current)?;
+
+        let format = match call.get_flag::<Spanned<String>>(constants::flags::FORMAT)? {
+            Some(format) => format.item.parse().map_err(|err: String| {
+                LabeledError::new("invalid format").with_label(err, format.span)
+            })?,
+            None => Format::Increment,
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        Ok(PipelineData::Value(
+            Value::int(serial::next(current, format, now).into(), call.head),
+            None,
+        ))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SERIAL_NEXT
+    }
+
+    fn description(&self) -> &str {
+        "Work out the next SOA serial for a zone"
+    }
+
+    fn extra_description(&self) -> &str {
+        "With --format increment, the default, the serial goes up by one, wrapping around at 2^32. With date, it becomes today's date in UTC followed by a two digit counter (YYYYMMDDnn), and with unixtime, the current time in seconds. When that wouldn't be newer than the current serial, e.g. because today's counter is used up, the serial goes up by one instead, so the result is always newer."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        Signature::build(self.name())
+            .optional(
+                "serial",
+                SyntaxShape::Any,
+                "Current serial, or a SOA rdata record with a serial column",
+            )
+            .named(
+                constants::flags::FORMAT,
+                SyntaxShape::String,
+                "How to pick the next serial: increment, date, or unixtime. Default: increment",
+                Some('f'),
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns serial next 4294967295",
+                description: "the serial after the largest one wraps around to 0",
+                result: None,
+            },
+            Example {
+                example: "dns serial next --format date 2024013105",
+                description: "bump a date-based serial",
+                result: None,
+            },
+            Example {
+                example: "dns query -t SOA example.com | get 0.answer.0.rdata | dns serial next --format date",
+                description: "work out the next serial from what a zone is serving",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "soa", "serial", "rfc1982", "zone", "increment"]
+    }
+}
@@ -0,0 +1,291 @@
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
+use hickory_proto::serialize::txt::Parser;
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        constants,
+        serde::Record,
+        server::{self, Zone},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsServe;
+
+impl DnsServe {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let mut records = Vec::new();
+
+        match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => {}
+            input => {
+                for val in input.into_iter() {
+                    records.push(Record::try_from(&val)?.0);
+                }
+            }
+        }
+
+        if let Some(path) = call.get_flag::<Spanned<PathBuf>>(constants::flags::ZONE)? {
+            records.extend(Self::read_zone(engine, call, path)?);
+        }
+
+        if records.is_empty() {
+            return Err(LabeledError::new("no records").with_label(
+                "Pipe in a table of records or give a zone file with --zone",
+                call.head,
+            ));
+        }
+
+        let listen = match call.get_flag_value(constants::flags::LISTEN) {
+            Some(Value::Int { val, internal_span }) => SocketAddr::from((
+                [127, 0, 0, 1],
+                u16::try_from(val).map_err(|err| {
+                    LabeledError::new("invalid port").with_label(
+                        format!("should be between 0 and 65535: {err}"),
+                        internal_span,
+                    )
+                })?,
+            )),
+            Some(val @ Value::String { .. }) => {
+                let span = val.span();
+                val.into_string()?.parse().map_err(|err| {
+                    LabeledError::new("invalid address")
+                        .with_label(format!("Error parsing socket address: {err}"), span)
+                })?
+            }
+            Some(val) => {
+                return Err(LabeledError::new("invalid address")
+                    .with_label("should be a socket address or a port number", val.span()))
+            }
+            None => constants::config::default::SERVE_LISTEN,
+        };
+
+        let count = records.len();
+        let (udp, tcp) = server::bind(listen).await.map_err(|err| {
+            LabeledError::new("could not listen").with_label(
+                format!("could not bind to {listen}: {err}"),
+                call.get_flag_value(constants::flags::LISTEN)
+                    .map_or(call.head, |val| val.span()),
+            )
+        })?;
+        let addr = udp.local_addr().map_err(|err| {
+            LabeledError::new("could not listen").with_label(err.to_string(), call.head)
+        })?;
+
+        let cancel = plugin.add_server(addr);
+
+        // the plugin has to stay alive for as long as anything is being
+        // served, even when no command is running
+        engine.set_gc_disabled(true)?;
+
+        plugin.spawn(async move {
+            server::serve(Arc::new(Zone::new(records)), udp, tcp, cancel).await;
+            Ok(())
+        });
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "address" => Value::string(addr.to_string(), Span::unknown()),
+                    "records" => Value::int(count as i64, Span::unknown()),
+                ],
+                call.head,
+            ),
+            None,
+        ))
+    }
+
+    fn read_zone(
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        path: Spanned<PathBuf>,
+    ) -> Result<Vec<hickory_proto::rr::Record>, LabeledError> {
+        let zone_err =
+            |msg: String| LabeledError::new("invalid zone file").with_label(msg, path.span);
+
+        let full_path = PathBuf::from(engine.get_current_dir()?).join(&path.item);
+        let contents = std::fs::read_to_string(&full_path)
+            .map_err(|err| zone_err(format!("could not read {}: {err}", path.item.display())))?;
+
+        let origin = match call.get_flag::<Spanned<String>>(constants::flags::ORIGIN)? {
+            Some(origin) => Some(Name::from_utf8(&origin.item).map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing origin: {}", err), origin.span)
+            })?),
+            None => None,
+        };
+
+        let (_, rrsets) = Parser::new(contents, Some(full_path), origin)
+            .parse()
+            .map_err(|err| zone_err(err.to_string()))?;
+
+        Ok(rrsets
+            .into_values()
+            .flat_map(|rrset| rrset.records_without_rrsigs().cloned().collect::<Vec<_>>())
+            .collect())
+    }
+}
+
+impl PluginCommand for DnsServe {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SERVE
+    }
+
+    fn description(&self) -> &str {
+        "Serve a fixed set of records on a local port"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Starts an authoritative server in the background that answers UDP and TCP queries from the piped table of records and/or a zone file, and returns the address it listens on. Records are rows with name, type, class, ttl, and rdata columns, where rdata is in zone file format, or for A and AAAA records, the address record `dns query` returns; type, class, and ttl default to A, IN, and 1hr. Handy for running scripts and tests against deterministic data without the network. Stop it with `dns serve stop`."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .named(
+                constants::flags::ZONE,
+                SyntaxShape::Filepath,
+                "Zone file to serve records from",
+                Some('z'),
+            )
+            .named(
+                constants::flags::ORIGIN,
+                SyntaxShape::String,
+                "Origin for relative names in the zone file, if it has no $ORIGIN",
+                None,
+            )
+            .named(
+                constants::flags::LISTEN,
+                SyntaxShape::Any,
+                format!(
+                    "Address or port to listen on. Default: {} (a free port)",
+                    constants::config::default::SERVE_LISTEN
+                ),
+                Some('l'),
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "let srv = [{name: example.com, type: A, rdata: 192.0.2.1}] | dns serve; dns query --server $srv.address example.com",
+                description: "serve a record and query it",
+                result: None,
+            },
+            Example {
+                example: "dns serve --zone example.com.zone --listen 127.0.0.1:5353",
+                description: "serve a zone file on a fixed port",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "network", "server", "mock", "test", "zone"]
+    }
+}
+
+#[derive(Debug)]
+pub struct DnsServeStop;
+
+impl PluginCommand for DnsServeStop {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let addr = match call.opt::<Spanned<String>>(0)? {
+            Some(addr) => Some(addr.item.parse::<SocketAddr>().map_err(|err| {
+                LabeledError::new("invalid address")
+                    .with_label(format!("Error parsing socket address: {err}"), addr.span)
+            })?),
+            None => None,
+        };
+
+        let (stopped, remaining) = plugin.stop_servers(addr);
+
+        if remaining == 0 {
+            engine.set_gc_disabled(false)?;
+        }
+
+        Ok(PipelineData::Value(
+            Value::list(
+                stopped
+                    .into_iter()
+                    .map(|addr| Value::string(addr.to_string(), Span::unknown()))
+                    .collect(),
+                call.head,
+            ),
+            None,
+        ))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SERVE_STOP
+    }
+
+    fn description(&self) -> &str {
+        "Stop servers started with `dns serve`"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Stops the server listening on the given address, or all of them if none is given, and returns the addresses that were stopped."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name()).optional(
+            "address",
+            SyntaxShape::String,
+            "Address of the server to stop",
+        )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns serve stop $srv.address",
+                description: "stop one server",
+                result: None,
+            },
+            Example {
+                example: "dns serve stop",
+                description: "stop all servers",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "network", "server"]
+    }
+}
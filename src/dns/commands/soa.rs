@@ -0,0 +1,156 @@
+use hickory_proto::rr::{RData, RecordType};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants,
+        error::DnsError,
+        serde::{self, util, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsSoa;
+
+impl DnsSoa {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let name: Spanned<String> = call.req(0)?;
+        let span = name.span;
+        let name = Name::from_utf8(&name.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), name.span)
+        })?;
+
+        let client = plugin.dns_client(&config).await?;
+
+        let mut query = hickory_proto::op::Query::query(name.clone(), RecordType::SOA);
+        query.set_query_class(config.class.item);
+        let request = serde::Query(query).into_message(&config);
+
+        config.throttle().await;
+        let resp = client
+            .send_message(&config, request)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+        // a name below the apex has no SOA of its own, but the negative
+        // answer carries the SOA of the zone it is in
+        let record = resp
+            .answers()
+            .iter()
+            .chain(resp.name_servers())
+            .find(|record| record.record_type() == RecordType::SOA)
+            .ok_or_else(|| {
+                LabeledError::new("no SOA record").with_label(
+                    format!(
+                        "{} answered {} with no SOA record for {name}",
+                        config.server.item,
+                        RCode(resp.response_code()).mnemonic()
+                    ),
+                    span,
+                )
+            })?;
+
+        let Some(RData::SOA(soa)) = record.data() else {
+            return Err(LabeledError::new("no SOA record")
+                .with_label(format!("the SOA record for {name} has no rdata"), span));
+        };
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "zone"    => Value::string(config.display_name(record.name()), Span::unknown()),
+                    "mname"   => Value::string(config.display_name(soa.mname()), Span::unknown()),
+                    "rname"   => Value::string(config.display_name(soa.rname()), Span::unknown()),
+                    "email"   => util::rname_to_email(soa.rname())
+                        .map_or(Value::nothing(Span::unknown()), |email| {
+                            Value::string(email, Span::unknown())
+                        }),
+                    "serial"  => Value::int(soa.serial().into(), Span::unknown()),
+                    "refresh" => util::sec_to_duration(soa.refresh() as u64),
+                    "retry"   => util::sec_to_duration(soa.retry() as u64),
+                    "expire"  => util::sec_to_duration(soa.expire() as u64),
+                    "minimum" => util::sec_to_duration(soa.minimum()),
+                    "ttl"     => util::sec_to_duration(record.ttl()),
+                ],
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl PluginCommand for DnsSoa {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SOA
+    }
+
+    fn description(&self) -> &str {
+        "Look up the SOA record of the zone a name is in"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Returns the SOA as a single record with its timers as durations and the responsible person's mailbox (RNAME) as an email address. For a name below the apex of its zone, the zone's SOA from the authority section is used, and the zone column says which zone that is."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .required(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Zone or name in it",
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Query class",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns soa example.com",
+                description: "show a zone's SOA",
+                result: None,
+            },
+            Example {
+                example: "dns soa www.example.com | get zone email",
+                description: "find which zone a name is in and who to contact about it",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "soa", "zone", "serial", "refresh", "expire"]
+    }
+}
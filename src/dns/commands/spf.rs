@@ -0,0 +1,510 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    pin::Pin,
+};
+
+use hickory_proto::rr::{Name, RData, RecordType};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        config::Config,
+        constants,
+        error::DnsError,
+        spf::{self, Term},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsSpfFlatten;
+
+/// What a record comes to once its includes are expanded and its names
+/// looked up.
+#[derive(Default)]
+struct Flat {
+    ip4: Vec<(Ipv4Addr, u8)>,
+    ip6: Vec<(Ipv6Addr, u8)>,
+    /// Terms that don't pass, which only the top record keeps, in order.
+    denied: Vec<Term>,
+    /// Terms that pass but can't be turned into addresses, kept as they are.
+    kept: Vec<Term>,
+    /// The qualifier of the record's `all`, or of its redirect's.
+    all: Option<char>,
+    exp: Option<String>,
+    /// Whether a term stands for the record's own domain, so that the record
+    /// can only be included, not flattened.
+    opaque: bool,
+}
+
+struct Flattener<'a> {
+    config: &'a Config,
+    client: &'a DnsClient,
+    span: Span,
+    lookups: usize,
+    visited: HashSet<Name>,
+    /// The top record, as it was published.
+    original: Option<String>,
+    warnings: Vec<String>,
+}
+
+impl Flattener<'_> {
+    /// The SPF record at a domain, if it has exactly one.
+    async fn record(&mut self, domain: &Name) -> Result<Option<String>, LabeledError> {
+        let records: Vec<String> = self
+            .client
+            .answers(self.config, domain, RecordType::TXT)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, self.config.server.item, self.span))?
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::TXT(txt)) => Some(
+                    txt.iter()
+                        .map(|data| String::from_utf8_lossy(data).into_owned())
+                        .collect::<String>(),
+                ),
+                _ => None,
+            })
+            .filter(|text| spf::is_spf(text))
+            .collect();
+
+        match &records[..] {
+            [record] => Ok(Some(record.clone())),
+            [] => {
+                self.warnings.push(format!(
+                    "{} has no SPF record",
+                    self.config.display_name(domain)
+                ));
+                Ok(None)
+            }
+            _ => {
+                self.warnings.push(format!(
+                    "{} has {} SPF records, where there should be one",
+                    self.config.display_name(domain),
+                    records.len()
+                ));
+                Ok(None)
+            }
+        }
+    }
+
+    /// The addresses of a name, in both families, whatever `--ipv4` and
+    /// `--ipv6` say, since SPF covers both.
+    async fn addresses(&mut self, name: &Name) -> Result<Vec<IpAddr>, LabeledError> {
+        let mut addrs = Vec::new();
+
+        for qtype in [RecordType::A, RecordType::AAAA] {
+            let records = self
+                .client
+                .answers(self.config, name, qtype)
+                .await
+                .map_err(|err| DnsError::from_proto(&err, self.config.server.item, self.span))?;
+            addrs.extend(records.iter().filter_map(|record| match record.data() {
+                Some(RData::A(addr)) => Some(IpAddr::V4(addr.0)),
+                Some(RData::AAAA(addr)) => Some(IpAddr::V6(addr.0)),
+                _ => None,
+            }));
+        }
+
+        Ok(addrs)
+    }
+
+    async fn mx_addresses(&mut self, name: &Name) -> Result<Vec<IpAddr>, LabeledError> {
+        let exchanges: Vec<Name> = self
+            .client
+            .answers(self.config, name, RecordType::MX)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, self.config.server.item, self.span))?
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::MX(mx)) => Some(mx.exchange().clone()),
+                _ => None,
+            })
+            .collect();
+
+        let mut addrs = Vec::new();
+        for exchange in exchanges {
+            addrs.extend(self.addresses(&exchange).await?);
+        }
+
+        Ok(addrs)
+    }
+
+    fn name(&self, domain: &str) -> Result<Name, LabeledError> {
+        Name::from_utf8(domain).map_err(|err| {
+            LabeledError::new("invalid name").with_label(
+                format!("Error parsing name {domain} in SPF record: {}", err),
+                self.span,
+            )
+        })
+    }
+
+    /// Flattens the record at a domain. Only the top record keeps terms that
+    /// don't pass: within an include, they only keep the include from
+    /// matching, which a flattened record has no way to say.
+    fn flatten<'s>(
+        &'s mut self,
+        domain: Name,
+        top: bool,
+    ) -> Pin<Box<dyn Future<Output = Result<Flat, LabeledError>> + 's>> {
+        Box::pin(async move {
+            let mut flat = Flat::default();
+
+            if !self.visited.insert(domain.clone()) {
+                self.warnings.push(format!(
+                    "{} is included more than once, or in a loop; only its first appearance counts",
+                    self.config.display_name(&domain)
+                ));
+                return Ok(flat);
+            }
+            let Some(record) = self.record(&domain).await? else {
+                return Ok(flat);
+            };
+            if top && self.original.is_none() {
+                self.original = Some(record.clone());
+            }
+
+            let mut redirect = None;
+            for term in spf::parse(&record) {
+                if term.looks_up() {
+                    self.lookups += 1;
+                }
+                if term.names_own_domain() {
+                    flat.opaque = true;
+                }
+
+                let (qualifier, name, arg) = match &term {
+                    Term::Modifier { name, value } => {
+                        match name.as_str() {
+                            "redirect" => redirect = Some(value.clone()),
+                            "exp" if top => flat.exp = Some(value.clone()),
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    Term::Mechanism {
+                        qualifier,
+                        name,
+                        arg,
+                    } => (*qualifier, name.as_str(), arg.as_str()),
+                };
+
+                if qualifier != '+' && name != "all" {
+                    match top {
+                        true => {
+                            if !flat.ip4.is_empty() || !flat.ip6.is_empty() || !flat.kept.is_empty()
+                            {
+                                self.warnings.push(format!(
+                                    "{term} comes after terms that pass, but the flattened record puts it first, where it takes precedence"
+                                ));
+                            }
+                            flat.denied.push(term);
+                        }
+                        false => self.warnings.push(format!(
+                            "{term} in the record of {} is left out: a term that doesn't pass only keeps an include from matching",
+                            self.config.display_name(&domain)
+                        )),
+                    }
+                    continue;
+                }
+
+                match name {
+                    "all" => match top {
+                        true => flat.all = Some(qualifier),
+                        false if qualifier == '+' => self.warnings.push(format!(
+                            "the record of {} ends in +all, so including it lets any host send mail",
+                            self.config.display_name(&domain)
+                        )),
+                        false => {}
+                    },
+                    "ip4" => match arg.strip_prefix(':').and_then(|net| parse_net(net, 32)) {
+                        Some(net) => flat.ip4.push(net),
+                        None => self.warnings.push(format!("{term} is not a valid ip4 mechanism")),
+                    },
+                    "ip6" => match arg.strip_prefix(':').and_then(|net| parse_net(net, 128)) {
+                        Some(net) => flat.ip6.push(net),
+                        None => self.warnings.push(format!("{term} is not a valid ip6 mechanism")),
+                    },
+                    "a" | "mx" => {
+                        let (v4, v6) = term.dual_cidr().map_err(|err| {
+                            LabeledError::new("invalid SPF record").with_label(err, self.span)
+                        })?;
+                        let target = match term.domain() {
+                            Some(target) => self.name(target)?,
+                            None => domain.clone(),
+                        };
+                        let addrs = match name {
+                            "a" => self.addresses(&target).await?,
+                            _ => self.mx_addresses(&target).await?,
+                        };
+
+                        for addr in addrs {
+                            match addr {
+                                IpAddr::V4(addr) => flat.ip4.push((addr, v4)),
+                                IpAddr::V6(addr) => flat.ip6.push((addr, v6)),
+                            }
+                        }
+                    }
+                    "include" => {
+                        let Some(target) = term.domain() else {
+                            self.warnings.push(format!("{term} names no domain"));
+                            continue;
+                        };
+                        let included = self.flatten(self.name(target)?, false).await?;
+
+                        match included.opaque {
+                            true => {
+                                self.warnings.push(format!(
+                                    "{term} is kept as it is, since its record uses macros for its own domain"
+                                ));
+                                flat.kept.push(term);
+                            }
+                            false => {
+                                flat.ip4.extend(included.ip4);
+                                flat.ip6.extend(included.ip6);
+                                flat.kept.extend(included.kept);
+                            }
+                        }
+                    }
+                    // ptr without a domain means the record's own, which
+                    // has to be spelled out for the term to mean the same
+                    // anywhere else
+                    "ptr" if term.domain().is_none() => flat.kept.push(Term::Mechanism {
+                        qualifier,
+                        name: name.into(),
+                        arg: format!(":{}", domain.to_utf8().trim_end_matches('.')),
+                    }),
+                    "ptr" | "exists" => {
+                        self.warnings
+                            .push(format!("{term} can't be turned into addresses; it is kept as it is"));
+                        flat.kept.push(term);
+                    }
+                    _ => self
+                        .warnings
+                        .push(format!("{term} is not a known mechanism and is left out")),
+                }
+            }
+
+            // a redirect only counts when there is no all (RFC 7208 section
+            // 6.1), and its record takes the place of this one's end
+            if let Some(target) = redirect.filter(|_| flat.all.is_none()) {
+                let redirected = self.flatten(self.name(&target)?, top).await?;
+                flat.opaque |= redirected.opaque;
+                flat.ip4.extend(redirected.ip4);
+                flat.ip6.extend(redirected.ip6);
+                flat.denied.extend(redirected.denied);
+                flat.kept.extend(redirected.kept);
+                flat.all = redirected.all;
+            }
+
+            Ok(flat)
+        })
+    }
+}
+
+/// Parses an address with an optional prefix length, which defaults to the
+/// whole address, `full` bits long.
+fn parse_net<A: std::str::FromStr>(net: &str, full: u8) -> Option<(A, u8)> {
+    let (addr, len) = net.split_once('/').unwrap_or((net, ""));
+    let len = match len {
+        "" => full,
+        len => len.parse().ok().filter(|len| *len <= full)?,
+    };
+
+    Some((addr.parse().ok()?, len))
+}
+
+fn net_string(addr: impl std::fmt::Display, len: u8, full: u8) -> String {
+    match len == full {
+        true => addr.to_string(),
+        false => format!("{addr}/{len}"),
+    }
+}
+
+impl DnsSpfFlatten {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let domain: Spanned<String> = call.req(0)?;
+        let span = domain.span;
+        let domain = Name::from_utf8(&domain.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), domain.span)
+        })?;
+
+        let client = plugin.dns_client(&config).await?;
+        let mut flattener = Flattener {
+            config: &config,
+            client: &client,
+            span,
+            lookups: 0,
+            visited: HashSet::new(),
+            original: None,
+            warnings: Vec::new(),
+        };
+
+        let flat = flattener.flatten(domain.clone(), true).await?;
+        let Some(original) = flattener.original else {
+            return Err(LabeledError::new("no SPF record")
+                .with_label(flattener.warnings.pop().unwrap_or_default(), span));
+        };
+        let lookups_before = flattener.lookups;
+        let mut warnings = flattener.warnings;
+
+        let ip4 = spf::aggregate_v4(&flat.ip4);
+        let ip6 = spf::aggregate_v6(&flat.ip6);
+
+        let mut terms = vec!["v=spf1".to_string()];
+        terms.extend(flat.denied.iter().map(Term::to_string));
+        terms.extend(
+            ip4.iter()
+                .map(|(addr, len)| format!("ip4:{}", net_string(addr, *len, 32))),
+        );
+        terms.extend(
+            ip6.iter()
+                .map(|(addr, len)| format!("ip6:{}", net_string(addr, *len, 128))),
+        );
+        terms.extend(flat.kept.iter().map(Term::to_string));
+        if let Some(qualifier) = flat.all {
+            terms.push(match qualifier {
+                '+' => "all".into(),
+                qualifier => format!("{qualifier}all"),
+            });
+        }
+        if let Some(exp) = &flat.exp {
+            terms.push(format!("exp={exp}"));
+        }
+        let flattened = terms.join(" ");
+
+        let lookups_after = flat
+            .denied
+            .iter()
+            .chain(&flat.kept)
+            .filter(|term| term.looks_up())
+            .count();
+        if lookups_after > spf::LOOKUP_LIMIT {
+            warnings.push(format!(
+                "the flattened record still takes {lookups_after} lookups, more than the {} allowed",
+                spf::LOOKUP_LIMIT
+            ));
+        }
+
+        let strings = spf::split_strings(&flattened);
+        if strings.len() > 1 {
+            warnings.push(format!(
+                "at {} bytes, the record is longer than the {} a TXT string can hold, so it has to be published as {} strings",
+                flattened.len(),
+                spf::STRING_LIMIT,
+                strings.len()
+            ));
+        }
+        if flattened.len() > spf::UDP_LIMIT {
+            warnings.push(format!(
+                "at {} bytes, the record may not fit in a 512-byte UDP response (RFC 7208 section 3.4); consider splitting it across includes",
+                flattened.len()
+            ));
+        }
+
+        let strings_value = |strings: Vec<String>| {
+            Value::list(
+                strings
+                    .into_iter()
+                    .map(|val| Value::string(val, Span::unknown()))
+                    .collect(),
+                Span::unknown(),
+            )
+        };
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "domain"    => Value::string(config.display_name(&domain), Span::unknown()),
+                    "original"  => Value::string(original, Span::unknown()),
+                    "flattened" => Value::string(&flattened, Span::unknown()),
+                    "ip4"       => strings_value(ip4.iter().map(|(addr, len)| net_string(addr, *len, 32)).collect()),
+                    "ip6"       => strings_value(ip6.iter().map(|(addr, len)| net_string(addr, *len, 128)).collect()),
+                    "lookups"   => Value::record(
+                        record![
+                            "before" => Value::int(lookups_before as i64, Span::unknown()),
+                            "after"  => Value::int(lookups_after as i64, Span::unknown()),
+                        ],
+                        Span::unknown(),
+                    ),
+                    "length"    => Value::int(flattened.len() as i64, Span::unknown()),
+                    "strings"   => strings_value(strings),
+                    "warnings"  => strings_value(warnings),
+                ],
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl PluginCommand for DnsSpfFlatten {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SPF_FLATTEN
+    }
+
+    fn description(&self) -> &str {
+        "Flatten an SPF record into ip4 and ip6 mechanisms"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Expands the includes, redirects, a, and mx mechanisms of a domain's SPF record (RFC 7208) into the addresses they stand for, folds those into the fewest prefixes that cover them, and returns the record they make, which takes far fewer than the 10 DNS lookups SPF allows. Terms that can't be turned into addresses, like ptr and exists, are kept as they are, as are includes whose records use macros for their own domain. Terms that don't pass are kept from the top record only, ahead of the addresses. Warns about anything that changes what the record means, and when the result needs splitting into several TXT strings or may not fit a UDP response. The flattened record has to be regenerated whenever an included record changes."
+    }
+
+    fn signature(&self) -> Signature {
+        super::connection_flags(Signature::build(self.name())).required(
+            constants::flags::NAME,
+            SyntaxShape::String,
+            "Domain whose SPF record to flatten",
+        )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns spf flatten example.com | get flattened",
+                description: "flatten a domain's SPF record",
+                result: None,
+            },
+            Example {
+                example: "dns spf flatten example.com | get strings | each { $'\"($in)\"' } | str join ' '",
+                description: "quote the flattened record for a zone file, split into TXT strings",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns", "spf", "flatten", "txt", "mail", "email", "include", "ip4", "ip6",
+        ]
+    }
+}
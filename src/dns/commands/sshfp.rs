@@ -0,0 +1,337 @@
+use std::path::PathBuf;
+
+use hickory_proto::rr::{
+    dnssec::DigestType,
+    rdata::{
+        sshfp::{Algorithm, FingerprintType},
+        SSHFP,
+    },
+    RData,
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsSshfpGenerate;
+
+/// The SSHFP algorithm number of an SSH public key type (RFC 4255, 6594,
+/// 7479, and 8709).
+fn algorithm(key_type: &str) -> Option<Algorithm> {
+    match key_type {
+        "ssh-rsa" => Some(Algorithm::RSA),
+        "ssh-dss" => Some(Algorithm::DSA),
+        "ecdsa-sha2-nistp256" | "ecdsa-sha2-nistp384" | "ecdsa-sha2-nistp521" => {
+            Some(Algorithm::ECDSA)
+        }
+        "ssh-ed25519" => Some(Algorithm::Ed25519),
+        "ssh-ed448" => Some(Algorithm::Ed448),
+        _ => None,
+    }
+}
+
+/// A public key found in a line of an OpenSSH public key file, of
+/// `ssh-keyscan` output, or of a known_hosts file.
+struct PublicKey {
+    host: Option<String>,
+    key_type: String,
+    algorithm: Algorithm,
+    blob: Vec<u8>,
+}
+
+/// Reads the public key of a line: its type, then its base64 blob, and, if
+/// anything comes before them, the host the key belongs to. Comments and
+/// blank lines have none.
+fn parse_line(line: &str) -> Option<Result<PublicKey, String>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let Some(idx) = tokens.iter().position(|token| algorithm(token).is_some()) else {
+        return Some(Err(format!("no SSH public key in: {line}")));
+    };
+    let key_type = tokens[idx];
+
+    let parse = || {
+        let blob = tokens
+            .get(idx + 1)
+            .ok_or_else(|| format!("{key_type} key is missing its base64 data"))?;
+        let blob = data_encoding::BASE64
+            .decode(blob.as_bytes())
+            .map_err(|err| format!("invalid base64 in {key_type} key: {err}"))?;
+
+        // the blob starts with the key type again, as an SSH string
+        let inner = blob
+            .get(..4)
+            .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+            .and_then(|len| blob.get(4..4 + len));
+        if inner != Some(key_type.as_bytes()) {
+            return Err(format!("{key_type} key data is not a {key_type} key"));
+        }
+
+        Ok(PublicKey {
+            host: idx.checked_sub(1).and_then(|host| owner_host(tokens[host])),
+            key_type: key_type.into(),
+            algorithm: algorithm(key_type).expect("key type should be known"),
+            blob,
+        })
+    };
+
+    Some(parse())
+}
+
+/// The host name of the hosts field of `ssh-keyscan` or known_hosts: its
+/// first entry, without any port. Hashed entries and addresses have no name
+/// that SSHFP records could be at.
+fn owner_host(hosts: &str) -> Option<String> {
+    let host = hosts.split(',').next()?;
+    let host = match host.strip_prefix('[') {
+        Some(host) => host.split(']').next()?,
+        None => host,
+    };
+
+    match host.starts_with('|') || host.parse::<std::net::IpAddr>().is_ok() {
+        true => None,
+        false => Some(host.into()),
+    }
+}
+
+impl DnsSshfpGenerate {
+    fn rows(
+        config: &Config,
+        key: &PublicKey,
+        owner: Option<&Name>,
+        span: Span,
+    ) -> Result<Vec<Value>, LabeledError> {
+        let err = |msg: String| LabeledError::new("invalid public key").with_label(msg, span);
+
+        let owner = match (owner, &key.host) {
+            (Some(owner), _) => Some(owner.clone()),
+            (None, Some(host)) => Some(
+                Name::from_utf8(format!("{}.", host.trim_end_matches('.')))
+                    .map_err(|e| err(format!("invalid host name {host}: {e}")))?,
+            ),
+            (None, None) => None,
+        };
+        let owner = owner.map(|owner| serde::util::escape_name(&owner, true));
+
+        // SHA-1 alongside SHA-256, for clients that predate RFC 6594
+        [
+            (FingerprintType::SHA1, DigestType::SHA1),
+            (FingerprintType::SHA256, DigestType::SHA256),
+        ]
+        .into_iter()
+        .map(|(fingerprint_type, digest_type)| {
+            let fingerprint = digest_type
+                .hash(&key.blob)
+                .map_err(|e| err(e.to_string()))?
+                .as_ref()
+                .to_vec();
+            let sshfp = SSHFP::new(key.algorithm, fingerprint_type, fingerprint);
+            let rdata = sshfp.to_string();
+
+            let line = match &owner {
+                Some(owner) => Value::string(format!("{owner} IN SSHFP {rdata}"), Span::unknown()),
+                None => Value::nothing(Span::unknown()),
+            };
+
+            let mut row = nu_protocol::Record::new();
+            row.push(
+                "owner",
+                owner
+                    .as_ref()
+                    .map_or(Value::nothing(Span::unknown()), |owner| {
+                        Value::string(owner, Span::unknown())
+                    }),
+            );
+            row.push("key_type", Value::string(&key.key_type, Span::unknown()));
+            if let Value::Record { val, .. } =
+                serde::RData(RData::SSHFP(sshfp)).into_value(config)?
+            {
+                row.extend(val.into_owned());
+            }
+            row.push("rdata", Value::string(rdata, Span::unknown()));
+            row.push("line", line);
+
+            Ok(Value::record(row, Span::unknown()))
+        })
+        .collect()
+    }
+}
+
+impl PluginCommand for DnsSshfpGenerate {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+
+        let (text, span) = match call.opt::<Spanned<PathBuf>>(0)? {
+            Some(path) => {
+                let full_path = PathBuf::from(engine.get_current_dir()?).join(&path.item);
+                let text = std::fs::read_to_string(&full_path).map_err(|err| {
+                    LabeledError::new("invalid public key").with_label(
+                        format!("could not read {}: {err}", path.item.display()),
+                        path.span,
+                    )
+                })?;
+
+                (text, path.span)
+            }
+            None => {
+                let input = input.into_value(call.head)?;
+                let span = input.span();
+                let text = match input {
+                    Value::String { val, .. } => val,
+                    Value::List { vals, .. } => vals
+                        .into_iter()
+                        .map(|val| val.into_string())
+                        .collect::<Result<Vec<_>, _>>()?
+                        .join("\n"),
+                    Value::Nothing { .. } => {
+                        return Err(LabeledError::new("missing input").with_label(
+                            "Give a public key file, or pipe in keys or ssh-keyscan output",
+                            call.head,
+                        ))
+                    }
+                    val => {
+                        return Err(LabeledError::new("invalid input").with_label(
+                            format!(
+                                "expected a string or a list of strings, not {}",
+                                val.get_type()
+                            ),
+                            span,
+                        ))
+                    }
+                };
+
+                (text, span)
+            }
+        };
+
+        let owner = match call.get_flag::<Spanned<String>>(constants::flags::NAME)? {
+            Some(name) => Some(Name::from_utf8(&name.item).map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), name.span)
+            })?),
+            None => None,
+        };
+
+        let mut rows = Vec::new();
+        for key in text.lines().filter_map(parse_line) {
+            let key =
+                key.map_err(|err| LabeledError::new("invalid public key").with_label(err, span))?;
+            rows.extend(Self::rows(&config, &key, owner.as_ref(), span)?);
+        }
+
+        Ok(PipelineData::Value(
+            Value::list(rows, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::SSHFP_GENERATE
+    }
+
+    fn description(&self) -> &str {
+        "Make SSHFP records (RFC 4255) for SSH public keys"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Reads OpenSSH public keys, one per line, from a file such as /etc/ssh/ssh_host_ed25519_key.pub, or piped in, as from `ssh-keyscan`. Returns a row per key and fingerprint type, SHA-1 and SHA-256, with the record's fields, its RDATA in presentation format, and, once there is an owner name, a line to paste into a zone file. The owner is the host that ssh-keyscan or known_hosts lines start with, unless --name is given. Hashed host names and addresses can't be owners."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .optional(
+                constants::flags::KEY_FILE,
+                SyntaxShape::Filepath,
+                "Public key file. Default: the input",
+            )
+            .named(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Owner name for the zone file lines, e.g. host.example.com",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example:
+                    "dns sshfp generate /etc/ssh/ssh_host_ed25519_key.pub --name host.example.com",
+                description: "make an SSHFP record for this host's Ed25519 key",
+                result: None,
+            },
+            Example {
+                example: "^ssh-keyscan host.example.com | dns sshfp generate | get line | to text",
+                description: "make zone file lines for every key a server offers",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "sshfp", "ssh", "fingerprint", "host key", "keyscan"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the expected fingerprints are those of ssh-keygen -r
+    #[test]
+    fn fingerprints() {
+        let key = parse_line(
+            "host.example.com ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIPXX4fdF3toq1NhLTTWpTUxN3mhd36o7WK82eu9lg8CO",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(key.host.as_deref(), Some("host.example.com"));
+        assert_eq!(key.key_type, "ssh-ed25519");
+        assert_eq!(key.algorithm, Algorithm::Ed25519);
+
+        let config = Config::from_values(|_| None).unwrap();
+        let rows = DnsSshfpGenerate::rows(&config, &key, None, Span::test_data()).unwrap();
+        let col = |row: &Value, col| row.as_record().unwrap().get(col).unwrap().clone();
+        let fingerprints: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                data_encoding::HEXLOWER.encode(&col(row, "fingerprint").into_binary().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            fingerprints,
+            [
+                "429ad37a27bcbd2439f749606cd6c54e367e700f",
+                "4fdff683048ffdcfaa05e3d16e7f04177b242436bfc5d475ac874f3ba5182124",
+            ]
+        );
+        assert_eq!(
+            col(&rows[0], "owner").into_string().unwrap(),
+            "host.example.com."
+        );
+
+        assert!(parse_line("# comment").is_none());
+        assert!(parse_line("ssh-ed25519 AAAAB3NzaC1yc2EAAAADAQABAAAAgQ")
+            .unwrap()
+            .is_err());
+    }
+}
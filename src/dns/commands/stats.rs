@@ -0,0 +1,71 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Example, LabeledError, PipelineData, Signature};
+
+use crate::{dns::constants, Dns};
+
+#[derive(Debug)]
+pub struct DnsStats;
+
+impl PluginCommand for DnsStats {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let stats = plugin.pool.stats.to_value(call.head);
+
+        if call.has_flag(constants::flags::RESET)? {
+            plugin.pool.stats.reset();
+        }
+
+        Ok(PipelineData::Value(stats, None))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::STATS
+    }
+
+    fn description(&self) -> &str {
+        "Show counters for the queries sent since the plugin started"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Counts the queries sent by every command, the responses by response code, timeouts, errors, TCP retries, and bytes sent and received, along with per-server counts and average round trip times. Responses served from the cache are not counted, since nothing was sent. The counters last as long as the plugin process."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name()).switch(
+            constants::flags::RESET,
+            "Reset the counters after showing them",
+            None,
+        )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns stats",
+                description: "show the counters for this session",
+                result: None,
+            },
+            Example {
+                example: "dns stats | get servers | sort-by avg_rtt",
+                description: "compare the servers that were queried by speed",
+                result: None,
+            },
+            Example {
+                example: "dns stats --reset; open names.txt | lines | dns query; dns stats",
+                description: "count the queries sent by one run",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "network", "statistics", "metrics", "counters"]
+    }
+}
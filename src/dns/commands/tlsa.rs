@@ -0,0 +1,231 @@
+use std::path::PathBuf;
+
+use hickory_proto::rr::{
+    rdata::{
+        tlsa::{CertUsage, Matching, Selector},
+        TLSA,
+    },
+    RData,
+};
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{config::Config, constants, dane, serde},
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsTlsaGenerate;
+
+impl DnsTlsaGenerate {
+    pub(crate) async fn run_impl(
+        &self,
+        _plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+
+        let field = |flag: &str, default: u8, max: u8| -> Result<u8, LabeledError> {
+            match call.get_flag::<Spanned<i64>>(flag)? {
+                Some(val) => u8::try_from(val.item)
+                    .ok()
+                    .filter(|val| *val <= max)
+                    .ok_or_else(|| {
+                        LabeledError::new("invalid input")
+                            .with_label(format!("should be between 0 and {max}"), val.span)
+                    }),
+                None => Ok(default),
+            }
+        };
+        let usage = CertUsage::from(field(constants::flags::USAGE, 3, 3)?);
+        let selector = Selector::from(field(constants::flags::SELECTOR, 1, 1)?);
+        let matching = Matching::from(field(constants::flags::MATCHING, 1, 2)?);
+
+        let cert = call.get_flag::<Spanned<PathBuf>>(constants::flags::CERT)?;
+        let connect = call.get_flag::<Spanned<String>>(constants::flags::CONNECT)?;
+
+        let (chain, owner, span) = match (cert, connect) {
+            (Some(path), None) => {
+                let full_path = PathBuf::from(engine.get_current_dir()?).join(&path.item);
+                let contents = std::fs::read(&full_path).map_err(|err| {
+                    LabeledError::new("invalid certificate").with_label(
+                        format!("could not read {}: {err}", path.item.display()),
+                        path.span,
+                    )
+                })?;
+                let chain = dane::read_certificates(&contents).map_err(|err| {
+                    LabeledError::new("invalid certificate").with_label(err, path.span)
+                })?;
+
+                (chain, None, path.span)
+            }
+            (None, Some(endpoint)) => {
+                let err = |msg: String| {
+                    LabeledError::new("TLS connection failed").with_label(msg, endpoint.span)
+                };
+                let (host, port) = dane::host_port(&endpoint.item).map_err(err)?;
+                let chain = dane::fetch_chain(&host, port, config.timeout.item)
+                    .await
+                    .map_err(err)?;
+
+                // TLSA records of a service live under its port and protocol
+                // (RFC 6698 section 3); an address has no name to put them at
+                let owner = match host.parse::<std::net::IpAddr>() {
+                    Ok(_) => None,
+                    Err(_) => {
+                        Name::from_utf8(format!("_{port}._tcp.{}.", host.trim_end_matches('.')))
+                            .ok()
+                    }
+                };
+
+                (chain, owner, endpoint.span)
+            }
+            _ => {
+                return Err(LabeledError::new("invalid input")
+                    .with_label("Give either --cert or --connect", call.head))
+            }
+        };
+
+        let owner = match call.get_flag::<Spanned<String>>(constants::flags::NAME)? {
+            Some(name) => Some(Name::from_utf8(&name.item).map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), name.span)
+            })?),
+            None => owner,
+        };
+
+        let cert_data = dane::pick_certificate(&chain, usage)
+            .and_then(|cert| dane::association_data(cert, selector, matching))
+            .map_err(|err| LabeledError::new("invalid certificate").with_label(err, span))?;
+        let tlsa = TLSA::new(usage, selector, matching, cert_data);
+        let rdata = tlsa.to_string();
+
+        let line = match &owner {
+            Some(owner) => Value::string(
+                format!("{} IN TLSA {rdata}", serde::util::escape_name(owner, true)),
+                Span::unknown(),
+            ),
+            None => Value::nothing(Span::unknown()),
+        };
+
+        let mut row = nu_protocol::Record::new();
+        row.push(
+            "owner",
+            owner.map_or(Value::nothing(Span::unknown()), |owner| {
+                Value::string(serde::util::escape_name(&owner, true), Span::unknown())
+            }),
+        );
+        if let Value::Record { val, .. } = serde::RData(RData::TLSA(tlsa)).into_value(&config)? {
+            row.extend(val.into_owned());
+        }
+        row.push("rdata", Value::string(rdata, Span::unknown()));
+        row.push("line", line);
+
+        Ok(PipelineData::Value(
+            Value::record(row, call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl PluginCommand for DnsTlsaGenerate {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::TLSA_GENERATE
+    }
+
+    fn description(&self) -> &str {
+        "Make a TLSA record (DANE, RFC 6698) for a certificate"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Takes the certificate from a PEM or DER file with --cert, or from the chain a TLS server presents with --connect, which accepts whatever certificate it is given, since it is the TLSA record that is to vouch for it. Usages 1 and 3 are about the end entity's certificate, the first in the file or chain; usages 0 and 2 are about its issuer's, the second. Returns the record's fields, its RDATA in presentation format, and, once there is an owner name, a line to paste into a zone file. With --connect, the owner is _port._tcp.host."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .named(
+                constants::flags::CERT,
+                SyntaxShape::Filepath,
+                "PEM or DER file with the certificate, or the chain starting with it",
+                None,
+            )
+            .named(
+                constants::flags::CONNECT,
+                SyntaxShape::String,
+                "host:port of a TLS server to take the certificate chain from. Default port: 443",
+                None,
+            )
+            .named(
+                constants::flags::USAGE,
+                SyntaxShape::Int,
+                "Certificate usage: 0 (PKIX-TA), 1 (PKIX-EE), 2 (DANE-TA), or 3 (DANE-EE). Default: 3",
+                None,
+            )
+            .named(
+                constants::flags::SELECTOR,
+                SyntaxShape::Int,
+                "What of the certificate to match: 0 (the whole certificate) or 1 (its public key). Default: 1",
+                None,
+            )
+            .named(
+                constants::flags::MATCHING,
+                SyntaxShape::Int,
+                "How to match it: 0 (exactly), 1 (SHA-256), or 2 (SHA-512). Default: 1",
+                None,
+            )
+            .named(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "Owner name for the zone file line, e.g. _25._tcp.mail.example.com",
+                None,
+            )
+            .named(
+                constants::flags::TIMEOUT,
+                SyntaxShape::Duration,
+                format!(
+                    "How long connecting with --connect can take. Default: {}sec",
+                    constants::config::default::TIMEOUT.as_secs()
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns tlsa generate --connect mail.example.com:25",
+                description: "make a DANE-EE record for the public key a mail server uses",
+                result: None,
+            },
+            Example {
+                example: "dns tlsa generate --cert fullchain.pem --usage 2 --name _443._tcp.example.com | get line",
+                description: "make a zone file line pinning the issuer of a certificate",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["dns", "tlsa", "dane", "certificate", "x509", "tls", "pin"]
+    }
+}
@@ -0,0 +1,641 @@
+use std::{net::IpAddr, time::Duration};
+
+use hickory_proto::{
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{DNSClass, Name, RData, RecordType},
+};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape, Value,
+};
+
+use crate::{
+    dns::{
+        config::Config,
+        constants::{self, flags},
+        error::DnsError,
+        myip,
+        serde::{self, util, RCode, RType},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsUpdate;
+
+/// The operations an update can be made of: the prerequisites that must hold
+/// for it to be applied, and the changes it makes. Each is named after the
+/// flag that gives it, which is also what goes in the `op` column of piped in
+/// rows.
+const OPS: &[&str] = &[
+    flags::NAME_IN_USE,
+    flags::NAME_NOT_IN_USE,
+    flags::RRSET_EXISTS,
+    flags::RRSET_NOT_EXISTS,
+    flags::ADD,
+    flags::DELETE,
+];
+
+/// The records of an update message, sorted into the sections they go in.
+#[derive(Default)]
+struct Update {
+    prerequisites: Vec<hickory_proto::rr::Record>,
+    updates: Vec<hickory_proto::rr::Record>,
+}
+
+impl DnsUpdate {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let zone: Spanned<String> = call.req(0)?;
+        let zone_span = zone.span;
+        let zone = util::parse_name(&zone.item).map(fqdn).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing zone: {}", err), zone_span)
+        })?;
+
+        let mut update = Update::default();
+
+        match input {
+            PipelineData::Empty | PipelineData::Value(Value::Nothing { .. }, _) => {}
+            input => {
+                for row in input.into_iter() {
+                    let op = util::column(&row, "op").ok_or_else(|| {
+                        LabeledError::new("missing column").with_label(
+                            format!("row should have an op column, one of {}", OPS.join(", ")),
+                            row.span(),
+                        )
+                    })?;
+                    let op_span = op.span();
+                    let op = op.into_string()?;
+                    let op = OPS.iter().find(|known| **known == op).ok_or_else(|| {
+                        LabeledError::new("invalid op")
+                            .with_label(format!("op should be one of {}", OPS.join(", ")), op_span)
+                    })?;
+
+                    update.push(op, &row, &zone, config.class.item)?;
+                }
+            }
+        }
+
+        for op in OPS {
+            if let Some(val) = call.get_flag_value(op) {
+                for entry in entries(val) {
+                    update.push(op, &entry, &zone, config.class.item)?;
+                }
+            }
+        }
+
+        if update.updates.is_empty() {
+            return Err(LabeledError::new("nothing to update").with_label(
+                format!(
+                    "give records to change with --{} or --{}, or pipe in rows with an op column",
+                    flags::ADD,
+                    flags::DELETE
+                ),
+                call.head,
+            ));
+        }
+
+        let resp = update.send(plugin, &config, &zone, call.head).await?;
+
+        Ok(PipelineData::Value(
+            serde::Message::new(resp)
+                .with_server(config.server.item)
+                .into_value(&config)?
+                .with_span(call.head),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct DnsUpdateSelf;
+
+impl DnsUpdateSelf {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+
+        let parse = |name: Spanned<String>| {
+            util::parse_name(&name.item).map(fqdn).map_err(|err| {
+                LabeledError::new("invalid name")
+                    .with_label(format!("Error parsing name: {}", err), name.span)
+            })
+        };
+        let name: Spanned<String> = call.req(0)?;
+        let name_span = name.span;
+        let name = parse(name)?;
+        let zone = match call.get_flag::<Spanned<String>>(flags::ZONE)? {
+            Some(zone) => parse(zone)?,
+            None => name.base_name(),
+        };
+
+        let ttl = match call.get_flag_value(flags::TTL) {
+            Some(val @ Value::Duration { .. }) => u32::try_from(val.as_duration()? / 1_000_000_000)
+                .map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), val.span())
+                })?,
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("ttl should be a duration", val.span()))
+            }
+            None => constants::config::default::UPDATE_TTL.as_secs() as u32,
+        };
+
+        let sightings = myip::discover(plugin, &config).await;
+
+        let mut update = Update::default();
+        let mut addresses = Vec::new();
+
+        for family in config.address_types() {
+            let Some(addr) = myip::consensus(&sightings, *family) else {
+                continue;
+            };
+
+            let rdata = match addr {
+                IpAddr::V4(addr) => RData::A(addr.into()),
+                IpAddr::V6(addr) => RData::AAAA(addr.into()),
+            };
+            let mut record = hickory_proto::rr::Record::from_rdata(name.clone(), ttl, rdata);
+            record.set_dns_class(config.class.item);
+
+            // the whole RRset is replaced, so that an old address doesn't
+            // linger next to the new one
+            let mut rrset = bare(record.clone());
+            rrset.set_dns_class(DNSClass::ANY);
+            update.updates.push(rrset);
+            update.updates.push(record);
+
+            addresses.push(Value::string(addr.to_string(), Span::unknown()));
+        }
+
+        if update.updates.is_empty() {
+            let errors: Vec<String> =
+                sightings
+                    .iter()
+                    .filter_map(|sighting| {
+                        sighting.addr.as_ref().err().map(|err| {
+                            format!("{} over {}: {err}", sighting.service, sighting.family)
+                        })
+                    })
+                    .collect();
+
+            return Err(LabeledError::new("no public address found").with_label(
+                format!("no service said what the address is: {}", errors.join("; ")),
+                name_span,
+            ));
+        }
+
+        let resp = update.send(plugin, &config, &zone, call.head).await?;
+
+        Ok(PipelineData::Value(
+            Value::record(
+                record![
+                    "name"      => Value::string(config.display_name(&name), Span::unknown()),
+                    "zone"      => Value::string(config.display_name(&zone), Span::unknown()),
+                    "addresses" => Value::list(addresses, Span::unknown()),
+                    "ttl"       => util::std_to_duration(Duration::from_secs(ttl as u64)),
+                    "server"    => Value::string(config.server.item.to_string(), Span::unknown()),
+                    "rcode"     => RCode(resp.response_code()).into_value(),
+                ],
+                call.head,
+            ),
+            config.metadata(self.name()),
+        ))
+    }
+}
+
+impl Update {
+    /// Sends the update to the zone's primary, signed when a TSIG key is
+    /// configured, and returns the response if the server applied it.
+    async fn send(
+        self,
+        plugin: &Dns,
+        config: &Config,
+        zone: &Name,
+        span: Span,
+    ) -> Result<Message, LabeledError> {
+        let mut message = Message::new();
+        let mut zone_query = Query::query(zone.clone(), RecordType::SOA);
+        zone_query.set_query_class(config.class.item);
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Update)
+            .add_query(zone_query)
+            .add_answers(self.prerequisites)
+            .add_name_servers(self.updates);
+        message
+            .extensions_mut()
+            .get_or_insert_with(hickory_proto::op::Edns::new)
+            .set_max_payload(constants::config::default::EDNS_PAYLOAD)
+            .set_version(0);
+
+        // the TSIG record has to be the last thing added to the message
+        let verifier = match &config.tsig {
+            Some(tsig) => {
+                let now = chrono::Utc::now().timestamp() as u32;
+                message.finalize(&tsig.signer()?, now).map_err(|err| {
+                    LabeledError::new("signing failed").with_label(err.to_string(), tsig.key.span)
+                })?
+            }
+            None => None,
+        };
+
+        let client = plugin.dns_client(config).await?;
+        config.throttle().await;
+        let resp = client
+            .send_message(config, message)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?;
+
+        // a server that can't check the signature answers unsigned, with
+        // the reason in its response code, which is reported as is
+        let resp = match verifier {
+            Some(mut verify) if !resp.signature().is_empty() => {
+                verify(resp.as_buffer()).map_err(|err| {
+                    LabeledError::new("invalid TSIG signature")
+                        .with_code("dns::update::tsig")
+                        .with_label(
+                            format!("the response's signature didn't verify: {err}"),
+                            span,
+                        )
+                })?
+            }
+            _ => resp,
+        };
+
+        if resp.response_code() != ResponseCode::NoError {
+            return Err(refused(resp.response_code(), config, zone, span));
+        }
+
+        Ok(resp.into_message())
+    }
+
+    /// Encodes one operation as a record, the way RFC 2136 section 2.4 and
+    /// 2.5 lay out: which of them it is goes by the record's class, and
+    /// whether it has a TTL and rdata.
+    fn push(
+        &mut self,
+        op: &str,
+        entry: &Value,
+        zone: &Name,
+        class: DNSClass,
+    ) -> Result<(), LabeledError> {
+        let span = entry.span();
+        let has_rdata = util::column(entry, "rdata").is_some();
+
+        let mut record = match (entry, has_rdata) {
+            (Value::String { val, .. }, _) => {
+                let name = util::parse_name(val).map_err(|err| {
+                    LabeledError::new("invalid name")
+                        .with_label(format!("Error parsing name: {}", err), span)
+                })?;
+                hickory_proto::rr::Record::with(name, RecordType::ANY, 0)
+            }
+            (Value::Record { .. }, true) => serde::Record::try_from(entry)?.0,
+            (Value::Record { .. }, false) => {
+                let name = util::column(entry, constants::columns::NAME).ok_or_else(|| {
+                    LabeledError::new("missing column")
+                        .with_label("record should have a name", span)
+                })?;
+                let name_span = name.span();
+                let name = util::parse_name(&name.into_string()?).map_err(|err| {
+                    LabeledError::new("invalid name")
+                        .with_label(format!("Error parsing name: {}", err), name_span)
+                })?;
+                let rtype = match util::column(entry, constants::columns::TYPE) {
+                    Some(val) => RType::try_from(&val)?.0,
+                    None => RecordType::ANY,
+                };
+
+                hickory_proto::rr::Record::with(name, rtype, 0)
+            }
+            (val, _) => {
+                return Err(LabeledError::new("invalid input").with_label(
+                    format!("--{op} takes names or records, not {}", val.get_type()),
+                    span,
+                ))
+            }
+        };
+
+        let name = fqdn(record.name().clone());
+        if !zone.zone_of(&name) {
+            return Err(LabeledError::new("not in zone")
+                .with_label(format!("{name} is not in the zone {zone}"), span));
+        }
+        record.set_name(name);
+
+        let rtype = record.record_type();
+        let needs_type = |record: &hickory_proto::rr::Record| match rtype {
+            RecordType::ANY => Err(LabeledError::new("missing column")
+                .with_label(format!("--{op} needs the type of the records"), span)),
+            _ => Ok(record.clone()),
+        };
+
+        match op {
+            flags::NAME_IN_USE => {
+                record
+                    .set_rr_type(RecordType::ANY)
+                    .set_dns_class(DNSClass::ANY);
+                self.prerequisites.push(bare(record));
+            }
+            flags::NAME_NOT_IN_USE => {
+                record
+                    .set_rr_type(RecordType::ANY)
+                    .set_dns_class(DNSClass::NONE);
+                self.prerequisites.push(bare(record));
+            }
+            // with rdata, the RRset must be exactly the records given;
+            // without, it only has to exist
+            flags::RRSET_EXISTS if has_rdata => {
+                record.set_dns_class(class).set_ttl(0);
+                self.prerequisites.push(record);
+            }
+            flags::RRSET_EXISTS => {
+                record.set_dns_class(DNSClass::ANY);
+                self.prerequisites.push(bare(needs_type(&record)?));
+            }
+            flags::RRSET_NOT_EXISTS => {
+                record.set_dns_class(DNSClass::NONE);
+                self.prerequisites.push(bare(needs_type(&record)?));
+            }
+            flags::ADD if has_rdata => {
+                record.set_dns_class(class);
+                self.updates.push(record);
+            }
+            flags::ADD => {
+                return Err(LabeledError::new("missing column")
+                    .with_label("records to add should have rdata", span))
+            }
+            // with rdata, just that record goes; without, the whole RRset,
+            // or everything at the name if there's no type either
+            flags::DELETE if has_rdata => {
+                record.set_dns_class(DNSClass::NONE).set_ttl(0);
+                self.updates.push(record);
+            }
+            flags::DELETE => {
+                record.set_dns_class(DNSClass::ANY);
+                self.updates.push(bare(record));
+            }
+            _ => unreachable!("ops are checked against OPS"),
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds the flags that give the TSIG key to sign updates with.
+fn tsig_flags(signature: Signature) -> Signature {
+    signature
+        .named(
+            flags::TSIG_KEY,
+            SyntaxShape::String,
+            "Name of the TSIG key to sign the update with",
+            Some('k'),
+        )
+        .named(
+            flags::TSIG_SECRET,
+            SyntaxShape::String,
+            "The TSIG key's secret, base64 encoded",
+            None,
+        )
+        .named(
+            flags::TSIG_ALGORITHM,
+            SyntaxShape::String,
+            format!(
+                "The TSIG key's algorithm: hmac-sha256, hmac-sha384, or hmac-sha512. Default: {}",
+                constants::config::default::TSIG_ALGORITHM
+            ),
+            None,
+        )
+}
+
+/// The record without a TTL or rdata, as prerequisites and deletions of
+/// whole RRsets are sent.
+fn bare(record: hickory_proto::rr::Record) -> hickory_proto::rr::Record {
+    let mut bare = hickory_proto::rr::Record::with(record.name().clone(), record.record_type(), 0);
+    bare.set_dns_class(record.dns_class());
+    bare
+}
+
+/// Names in an update are all taken to be fully qualified, trailing dot or
+/// not.
+fn fqdn(mut name: Name) -> Name {
+    name.set_fqdn(true);
+    name
+}
+
+/// A flag's value as the entries it holds, whether it was given one or a
+/// list.
+fn entries(val: Value) -> Vec<Value> {
+    match val {
+        Value::List { vals, .. } => vals,
+        val => vec![val],
+    }
+}
+
+/// Explains why the server didn't apply the update. The response codes that
+/// RFC 2136 section 3.2 gives to failed prerequisites say which kind failed.
+fn refused(rcode: ResponseCode, config: &Config, zone: &Name, span: Span) -> LabeledError {
+    let reason = match rcode {
+        ResponseCode::NXDomain => format!("a --{} prerequisite failed", flags::NAME_IN_USE),
+        ResponseCode::YXDomain => format!("a --{} prerequisite failed", flags::NAME_NOT_IN_USE),
+        ResponseCode::NXRRSet => format!("a --{} prerequisite failed", flags::RRSET_EXISTS),
+        ResponseCode::YXRRSet => format!("a --{} prerequisite failed", flags::RRSET_NOT_EXISTS),
+        ResponseCode::NotAuth => format!(
+            "{} is not authoritative for {zone}, or didn't accept the update's TSIG key",
+            config.server.item
+        ),
+        ResponseCode::NotZone => format!("a name in the update is not in {zone}"),
+        ResponseCode::Refused => format!("{} refused the update", config.server.item),
+        rcode => format!("the server answered {}", RCode(rcode).mnemonic()),
+    };
+
+    LabeledError::new("update not applied")
+        .with_code(format!(
+            "dns::update::{}",
+            RCode(rcode).mnemonic().to_lowercase()
+        ))
+        .with_label(format!("{reason} ({})", RCode(rcode).mnemonic()), span)
+}
+
+impl PluginCommand for DnsUpdate {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::UPDATE
+    }
+
+    fn description(&self) -> &str {
+        "Change the records of a zone with a dynamic update (RFC 2136)"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Sends --server, which should be the zone's primary, an update that adds the records given with --add and deletes those given with --delete, but only if all of the prerequisites hold: that a name has records (--name-in-use), or none (--name-not-in-use), or that an RRset exists (--rrset-exists) or doesn't (--rrset-not-exists). A record given to --rrset-exists with rdata requires the RRset to be exactly those records, which makes for a compare-and-swap. A record to --delete with rdata deletes just it; without, its whole RRset, or everything at the name if it has no type either. Records are given as rows like those `dns query` returns, and names as strings; the same operations can be piped in as rows with an op column naming the flag. Returns the server's response, or an error saying which kind of prerequisite failed."
+    }
+
+    fn signature(&self) -> Signature {
+        let records = |signature: Signature, flag: &str, desc: &str| {
+            signature.named(flag, SyntaxShape::Any, desc, None)
+        };
+
+        let signature = tsig_flags(super::connection_flags(Signature::build(self.name())))
+            .required(flags::ZONE, SyntaxShape::String, "Zone to update");
+        let signature = records(signature, flags::ADD, "Records to add");
+        let signature = records(
+            signature,
+            flags::DELETE,
+            "Records, RRsets, or names to delete",
+        );
+        let signature = records(
+            signature,
+            flags::NAME_IN_USE,
+            "Names that must have records",
+        );
+        let signature = records(
+            signature,
+            flags::NAME_NOT_IN_USE,
+            "Names that must have no records",
+        );
+        let signature = records(
+            signature,
+            flags::RRSET_EXISTS,
+            "RRsets that must exist, and with rdata, be exactly these records",
+        );
+        records(
+            signature,
+            flags::RRSET_NOT_EXISTS,
+            "RRsets, by name and type, that must not exist",
+        )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns update example.com --server ns1.example.com --add {name: www.example.com, type: A, ttl: 5min, rdata: 192.0.2.1}",
+                description: "add an address record",
+                result: None,
+            },
+            Example {
+                example: "dns update example.com --server ns1.example.com --rrset-exists {name: www.example.com, type: A, rdata: 192.0.2.1} --delete {name: www.example.com, type: A, rdata: 192.0.2.1} --add {name: www.example.com, type: A, rdata: 192.0.2.2}",
+                description: "swap an address, but only if it is still the old one",
+                result: None,
+            },
+            Example {
+                example: "[[op name type rdata]; [name-not-in-use new.example.com null null] [add new.example.com TXT hello]] | dns update example.com --server ns1.example.com",
+                description: "create a name only if it doesn't exist yet",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "update",
+            "ddns",
+            "dynamic",
+            "nsupdate",
+            "rfc2136",
+            "prerequisite",
+        ]
+    }
+}
+
+impl PluginCommand for DnsUpdateSelf {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::UPDATE_SELF
+    }
+
+    fn description(&self) -> &str {
+        "Point a name at this host's public addresses with a dynamic update, for dynamic DNS"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Finds the host's public IPv4 and IPv6 addresses by asking servers that answer with the address a question came from (Cloudflare's whoami.cloudflare, Google's o-o.myaddr.l.google.com, and OpenDNS's myip.opendns.com), taking the address most of them agree on, then sends --server, which should be the zone's primary, an update that replaces the name's A and AAAA records with them. An address family that no service answered over is left alone, as are both with --ipv4 or --ipv6 limiting it to the other. The zone defaults to the name's parent. Most servers only accept updates signed with a TSIG key, given with --tsig-key and --tsig-secret. Returns the name, the addresses it now points at, and the server's response code."
+    }
+
+    fn signature(&self) -> Signature {
+        tsig_flags(super::connection_flags(Signature::build(self.name())))
+            .required(
+                flags::NAME,
+                SyntaxShape::String,
+                "Name to point at this host",
+            )
+            .named(
+                flags::ZONE,
+                SyntaxShape::String,
+                "Zone the name is in. Default: the name's parent",
+                Some('z'),
+            )
+            .named(
+                flags::TTL,
+                SyntaxShape::Duration,
+                format!(
+                    "TTL of the address records. Default: {}min",
+                    constants::config::default::UPDATE_TTL.as_secs() / 60
+                ),
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                example: "dns update self home.example.com --server ns1.example.com --tsig-key ddns-key --tsig-secret $env.DDNS_SECRET",
+                description: "point a name at this host's public addresses",
+                result: None,
+            },
+            Example {
+                example: "dns update self home.example.com --ipv4 --ttl 1min --server ns1.example.com --tsig-key ddns-key --tsig-secret $env.DDNS_SECRET",
+                description: "update only the IPv4 address, with a short TTL",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns", "update", "ddns", "dynamic", "self", "myip", "public", "address", "tsig",
+        ]
+    }
+}
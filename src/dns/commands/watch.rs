@@ -0,0 +1,320 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use hickory_proto::rr::RecordType;
+use hickory_resolver::Name;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{
+    record, Example, LabeledError, ListStream, PipelineData, Signature, Span, Spanned, SyntaxShape,
+    Value,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    dns::{
+        client::DnsClient,
+        config::Config,
+        constants,
+        error::DnsError,
+        serde::{self, RCode},
+    },
+    Dns,
+};
+
+#[derive(Debug)]
+pub struct DnsWatch;
+
+impl DnsWatch {
+    pub(crate) async fn run_impl(
+        &self,
+        plugin: &Dns,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = Config::from_nu(engine, call)?;
+        let metadata = config.metadata(self.name());
+        let name: Spanned<String> = call.req(0)?;
+        let name = Name::from_utf8(&name.item).map_err(|err| {
+            LabeledError::new("invalid name")
+                .with_label(format!("Error parsing name: {}", err), name.span)
+        })?;
+
+        let rounds = match call.get_flag::<Spanned<i64>>(constants::flags::COUNT)? {
+            Some(count) => Some(usize::try_from(count.item).map_err(|err| {
+                LabeledError::new("invalid input")
+                    .with_label(format!("should be positive int: {err}"), count.span)
+            })?),
+            None => None,
+        };
+
+        let interval = match call.get_flag_value(constants::flags::INTERVAL) {
+            Some(val @ Value::Duration { .. }) => {
+                Duration::from_nanos(val.as_duration()?.try_into().map_err(|err| {
+                    LabeledError::new("invalid duration")
+                        .with_label(format!("should be positive duration: {err}"), val.span())
+                })?)
+            }
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("interval should be a duration", val.span()))
+            }
+            None => constants::config::default::WATCH_INTERVAL,
+        };
+
+        if interval.is_zero() {
+            return Err(LabeledError::new("invalid duration").with_label(
+                "interval should be more than zero",
+                call.get_flag_value(constants::flags::INTERVAL)
+                    .map_or(call.head, |val| val.span()),
+            ));
+        }
+
+        let alert = call.has_flag(constants::flags::ALERT_ON_CHANGE)?;
+
+        let client = plugin.dns_client(&config).await?;
+
+        let (row_tx, mut row_rx) = mpsc::channel(config.qtypes.item.len());
+
+        plugin.spawn(watch(config, name, client, interval, rounds, alert, row_tx));
+
+        Ok(PipelineData::ListStream(
+            ListStream::new(
+                std::iter::from_fn(move || {
+                    tokio::task::block_in_place(|| {
+                        row_rx.blocking_recv().map(|row| {
+                            row.unwrap_or_else(|err| Value::error(err.into(), Span::unknown()))
+                        })
+                    })
+                }),
+                call.head,
+                engine.signals().clone(),
+            ),
+            metadata,
+        ))
+    }
+}
+
+/// What was observed the last time a given record type was queried.
+struct Observation {
+    answers: Vec<String>,
+    /// When the answers would expire from a cache that took them the first
+    /// time they were seen.
+    expires: Option<Instant>,
+}
+
+async fn watch(
+    config: Config,
+    name: Name,
+    client: DnsClient,
+    interval: Duration,
+    rounds: Option<usize>,
+    alert: bool,
+    row_tx: mpsc::Sender<Result<Value, LabeledError>>,
+) -> Result<(), LabeledError> {
+    let mut previous = HashMap::<RecordType, Observation>::new();
+    let mut ticker = tokio::time::interval(interval);
+
+    for round in (0..).take_while(|round| rounds.is_none_or(|rounds| *round < rounds)) {
+        ticker.tick().await;
+
+        for qtype in config.qtypes.item.iter().map(|qtype| qtype.item) {
+            let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+            query.set_query_class(config.class.item);
+
+            let request = serde::Query(query).into_message(&config);
+            let sent = Instant::now();
+            let resp = client.send_message(&config, request).await;
+            let rtt = sent.elapsed();
+
+            let row = match resp {
+                Err(err) => {
+                    Err(DnsError::from_proto(&err, config.server.item, Span::unknown()).into())
+                }
+                Ok(resp) => {
+                    let mut answers: Vec<String> = resp
+                        .answers()
+                        .iter()
+                        .filter_map(|record| record.data().map(|rdata| rdata.to_string()))
+                        .collect();
+                    answers.sort();
+                    answers.dedup();
+
+                    let ttl = resp.answers().iter().map(|record| record.ttl()).min();
+
+                    // the first round has nothing to compare against, so it
+                    // is never considered a change
+                    let prev = previous.get(&qtype);
+                    let changed = prev.is_some_and(|prev| prev.answers != answers);
+
+                    // the countdown carries on from round to round while the
+                    // answers stay the same, and starts over from the TTL once
+                    // they change or run out, as a cache would refetch them
+                    let now = Instant::now();
+                    let expires = match prev.and_then(|prev| prev.expires) {
+                        Some(expires) if !changed && expires > now => Some(expires),
+                        _ => ttl.map(|ttl| now + Duration::from_secs(ttl.into())),
+                    };
+                    let countdown = expires.map(|expires| expires.saturating_duration_since(now));
+
+                    let row = Value::record(
+                        record![
+                            "round"   => Value::int(round as i64, Span::unknown()),
+                            "time"    => Value::date(chrono::Local::now().fixed_offset(), Span::unknown()),
+                            "name"    => Value::string(config.display_name(&name), Span::unknown()),
+                            "type"    => Value::string(qtype.to_string(), Span::unknown()),
+                            "rcode"   => Value::string(RCode(resp.response_code()).mnemonic(), Span::unknown()),
+                            "answers" => Value::list(
+                                answers
+                                    .iter()
+                                    .map(|answer| Value::string(answer, Span::unknown()))
+                                    .collect(),
+                                Span::unknown(),
+                            ),
+                            "ttl"     => ttl
+                                .map(serde::util::sec_to_duration)
+                                .unwrap_or(Value::nothing(Span::unknown())),
+                            "countdown" => countdown
+                                .map(serde::util::std_to_duration)
+                                .unwrap_or(Value::nothing(Span::unknown())),
+                            "rtt"     => serde::util::std_to_duration(rtt),
+                            "changed" => Value::bool(changed, Span::unknown()),
+                        ],
+                        Span::unknown(),
+                    );
+
+                    let row = match (alert, prev) {
+                        (true, Some(prev)) if changed => Err(LabeledError::new("answers changed")
+                            .with_label(
+                                format!(
+                                    "{} {} answers changed in round {round} from [{}] to [{}]",
+                                    config.display_name(&name),
+                                    qtype,
+                                    prev.answers.join(", "),
+                                    answers.join(", "),
+                                ),
+                                Span::unknown(),
+                            )),
+                        _ => Ok(row),
+                    };
+
+                    previous.insert(qtype, Observation { answers, expires });
+
+                    row
+                }
+            };
+
+            // the receiving end goes away when the user stops consuming the
+            // stream, which is our cue to stop
+            if row_tx.send(row).await.is_err() {
+                tracing::debug!(watch.phase = "exit", watch.round = round);
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl PluginCommand for DnsWatch {
+    type Plugin = Dns;
+
+    fn run(
+        &self,
+        plugin: &Self::Plugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        plugin
+            .runtime
+            .block_on(self.run_impl(plugin, engine, call, input))
+    }
+
+    fn name(&self) -> &str {
+        constants::commands::WATCH
+    }
+
+    fn description(&self) -> &str {
+        "Repeatedly query a name and stream how its answers change"
+    }
+
+    fn extra_description(&self) -> &str {
+        "Each round queries every requested type and emits one row per type with the sorted answer set, the lowest TTL, the round trip time, and whether the answers changed since the previous round. The countdown column is how long until the answers would expire from a cache that took them when they were first seen; it starts over from the TTL when the answers change or the countdown runs out. With --alert-on-change, a changed row is emitted as an error instead, saying what the answers changed from and to. Runs until interrupted, or for --count rounds."
+    }
+
+    fn signature(&self) -> nu_protocol::Signature {
+        super::connection_flags(Signature::build(self.name()))
+            .required(
+                constants::flags::NAME,
+                SyntaxShape::String,
+                "DNS record name",
+            )
+            .named(
+                constants::flags::TYPE,
+                SyntaxShape::Any,
+                "Query type(s)",
+                Some('t'),
+            )
+            .named(
+                constants::flags::CLASS,
+                SyntaxShape::Any,
+                "Query class",
+                None,
+            )
+            .named(
+                constants::flags::INTERVAL,
+                SyntaxShape::Duration,
+                format!(
+                    "How long to wait between rounds. Default: {}sec",
+                    constants::config::default::WATCH_INTERVAL.as_secs()
+                ),
+                Some('i'),
+            )
+            .named(
+                constants::flags::COUNT,
+                SyntaxShape::Int,
+                "Stop after this many rounds",
+                None,
+            )
+            .switch(
+                constants::flags::ALERT_ON_CHANGE,
+                "Emit an error instead of a row when the answers change",
+                None,
+            )
+    }
+
+    fn examples(&self) -> Vec<nu_protocol::Example<'_>> {
+        vec![
+            Example {
+                example: "dns watch --type A --interval 30sec example.com",
+                description: "watch the A records of a name every 30 seconds",
+                result: None,
+            },
+            Example {
+                example: "dns watch --type A example.com | where changed | first",
+                description: "wait until the answers change",
+                result: None,
+            },
+            Example {
+                example: "dns watch --type A --alert-on-change example.com",
+                description:
+                    "watch a name and raise an error saying how its answers changed when they do",
+                result: None,
+            },
+        ]
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec![
+            "dns",
+            "network",
+            "monitor",
+            "poll",
+            "cutover",
+            "propagation",
+        ]
+    }
+}
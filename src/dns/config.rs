@@ -1,35 +1,91 @@
 use std::{
-    net::{IpAddr, SocketAddr},
+    collections::{BTreeSet, HashMap},
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    path::PathBuf,
     str::FromStr,
-    time::Duration,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use hickory_proto::rr::{DNSClass, RecordType};
 use hickory_resolver::config::{Protocol, ResolverConfig};
-use nu_plugin::EvaluatedCall;
-use nu_protocol::{record, LabeledError, Span, Spanned, Value};
+use nu_plugin::{EngineInterface, EvaluatedCall};
+use nu_protocol::{record, DataSource, LabeledError, PipelineMetadata, Span, Spanned, Value};
 
 use crate::spanned;
 
 use super::{
     constants::{self, flags},
-    serde::{self, DnssecMode, RType},
+    rate::RateLimiter,
+    serde::{self, DnssecMode, RCode, RType},
 };
 
-#[derive(Debug)]
+/// The unknown plugin config keys that have already been warned about.
+static WARNED_KEYS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+#[derive(Clone, Debug)]
 pub struct Config {
     pub protocol: Spanned<Protocol>,
     pub server: Spanned<SocketAddr>,
+    /// The addresses to try the server at, in order: those of a server given
+    /// as a host name, or without `--server`, every system nameserver.
+    /// `server` is the first.
+    pub server_addrs: Vec<SocketAddr>,
+    /// The host name the server was given as, if it was.
+    pub server_host: Option<String>,
+    pub ipv4: Spanned<bool>,
+    pub ipv6: Spanned<bool>,
 
     pub qtypes: Spanned<Vec<Spanned<RecordType>>>,
     pub class: Spanned<DNSClass>,
 
     pub code: Spanned<bool>,
     pub dnssec_mode: Spanned<DnssecMode>,
+    pub dnssec_ok: Spanned<bool>,
+    pub checking_disabled: Spanned<bool>,
     pub dns_name: Option<Spanned<String>>,
+    pub ignore_tc: Spanned<bool>,
+    pub partial: Spanned<bool>,
+    pub errors_as_values: Spanned<bool>,
+    /// When the whole command has to be done by, from `--deadline`.
+    pub deadline: Option<Spanned<Instant>>,
+    pub no_auto_ptr: Spanned<bool>,
+    pub fqdn: Spanned<bool>,
+    pub no_root_dot: Spanned<bool>,
+    pub lenient: Spanned<bool>,
+    pub with_question: Spanned<bool>,
+    pub fail_on_nxdomain: Spanned<bool>,
+    pub sort: Spanned<bool>,
+    pub dedup: Spanned<bool>,
+    pub group_rrsets: Spanned<bool>,
+    pub compact: Spanned<bool>,
+    pub output_schema: Spanned<serde::OutputSchema>,
+    pub scalar: Spanned<bool>,
+    pub separator: Spanned<String>,
+    pub with_wire: Spanned<bool>,
+    pub strict_special_use: Spanned<bool>,
+    pub tsig: Option<Tsig>,
+    pub randomize_case: Spanned<bool>,
+    pub cache: Spanned<bool>,
+    pub cache_file: Option<Spanned<PathBuf>>,
+    pub min_ttl: Option<Spanned<Duration>>,
+    pub max_ttl: Option<Spanned<Duration>>,
+    pub log_file: Option<Spanned<PathBuf>>,
+    pub trust_anchors: Option<Spanned<PathBuf>>,
+    pub rcode_filter: Option<Spanned<Vec<RCode>>>,
+    pub id: Option<Spanned<u16>>,
+    pub rate: Option<Arc<RateLimiter>>,
+    pub edns_opts: Vec<(u16, Vec<u8>)>,
+    pub verbose: Spanned<bool>,
+    pub debug: Spanned<bool>,
+    pub log_level: Option<Spanned<String>>,
 
     pub tasks: Spanned<usize>,
     pub timeout: Spanned<Duration>,
+    /// How many times to go through the server's addresses before giving up.
+    pub attempts: Spanned<usize>,
+    /// Whether to start at a different address for each query.
+    pub rotate: Spanned<bool>,
 }
 
 impl TryFrom<Value> for Config {
@@ -49,34 +105,134 @@ impl TryFrom<&EvaluatedCall> for Config {
     }
 }
 
+/// The settings that were given, as they were given, and where.
+pub type Given = Vec<(String, Value, Source)>;
+
+/// Where a setting was given, in order of precedence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    Flag,
+    /// The `NU_PLUGIN_DNS_*` variable it was read from.
+    Env(String),
+    PluginConfig,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Flag => write!(f, "flag"),
+            Source::Env(var) => write!(f, "env {var}"),
+            Source::PluginConfig => write!(f, "plugin config"),
+        }
+    }
+}
+
 impl Config {
-    pub fn from_nu(
-        plugin_config: Option<Value>,
+    /// Builds the config from, in order of precedence, the command's flags,
+    /// `NU_PLUGIN_DNS_*` environment variables, and the plugin config.
+    pub fn from_nu(engine: &EngineInterface, call: &EvaluatedCall) -> Result<Self, LabeledError> {
+        Self::from_nu_with_sources(engine, call).map(|(config, _)| config)
+    }
+
+    /// Like [`Config::from_nu`], along with every setting that was given, as
+    /// it was given, and where.
+    pub fn from_nu_with_sources(
+        engine: &EngineInterface,
         call: &EvaluatedCall,
-    ) -> Result<Self, LabeledError> {
-        tracing::debug!(?plugin_config, ?call);
+    ) -> Result<(Self, Given), LabeledError> {
+        let plugin_config = engine.get_plugin_config()?;
+        let env = Self::env_values(engine.get_env_vars()?)?;
+
+        tracing::debug!(?plugin_config, ?env, ?call);
 
         let plugin_config = match plugin_config {
             None => Value::record(record!(), Span::unknown()),
             Some(cfg) => cfg,
         };
 
-        Config::from_values(|name| {
-            let cfg_val = plugin_config.get_data_by_key(name);
+        let mut given = Vec::new();
+
+        let config = Config::from_values(|name| {
+            let cfg_val = match env.get(name) {
+                Some(val) => Some((val.clone(), Source::Env(constants::config::env_var(name)))),
+                None => plugin_config
+                    .get_data_by_key(name)
+                    .map(|val| (val, Source::PluginConfig)),
+            };
             let call_val = match (call.has_flag(name), call.get_flag_value(name)) {
                 (Ok(true), None) => Some(Value::bool(true, Span::unknown())),
                 (_, val) => val,
             };
 
-            match (cfg_val, call_val) {
-                (None, None) => None,
-                (None, val @ Some(_)) => val,
-                (val @ Some(_), None) => val,
+            // CLI flags take precedence over env and config
+            let (val, source) = match (cfg_val, call_val) {
+                (_, Some(val)) => (val, Source::Flag),
+                (Some((val, source)), None) => (val, source),
+                (None, None) => return None,
+            };
 
-                // CLI flags take precedence over config
-                (Some(_), callv @ Some(_)) => callv,
+            if !given.iter().any(|(key, _, _)| key == name) {
+                given.push((name.to_string(), val.clone(), source));
             }
-        })
+
+            Some(val)
+        })?;
+
+        super::set_log_level(config.log_level.as_ref().map(|level| level.item.as_str()));
+
+        // a misspelled key would otherwise be silently ignored. The config is
+        // read on every invocation, so each key is only warned about once.
+        if let Value::Record { val, .. } = &plugin_config {
+            let mut warned = WARNED_KEYS.lock().unwrap();
+            for (key, _) in val
+                .iter()
+                .filter(|(key, _)| !constants::config::KEYS.contains(&key.as_str()))
+            {
+                if warned.insert(key.clone()) {
+                    tracing::warn!(config.key = %key, "unknown key in dns plugin config");
+                }
+            }
+        }
+
+        Ok((config, given))
+    }
+
+    /// Picks out the `NU_PLUGIN_DNS_*` environment variables, keyed by the
+    /// flag they stand in for.
+    fn env_values(
+        env: HashMap<String, Value>,
+    ) -> Result<HashMap<&'static str, Value>, LabeledError> {
+        constants::config::ENV_FLAGS
+            .iter()
+            .filter_map(|flag| {
+                let var = constants::config::env_var(flag);
+                let val = env.get(&var)?;
+
+                // everything in the environment is a string, so durations
+                // need to be parsed by hand
+                let val = match *flag {
+                    flags::TIMEOUT => {
+                        let span = val.span();
+                        let parsed = val
+                            .as_str()
+                            .ok()
+                            .and_then(parse_duration)
+                            .and_then(|duration| i64::try_from(duration.as_nanos()).ok());
+
+                        match parsed {
+                            Some(nanos) => Ok(Value::duration(nanos, span)),
+                            None => Err(LabeledError::new("invalid duration").with_label(
+                                format!("{var} should be a duration, e.g. 5sec or 500ms"),
+                                span,
+                            )),
+                        }
+                    }
+                    _ => Ok(val.clone()),
+                };
+
+                Some(val.map(|val| (*flag, val)))
+            })
+            .collect()
     }
 
     pub fn from_values<F>(mut get_value: F) -> Result<Self, LabeledError>
@@ -118,37 +274,78 @@ impl Config {
             }
         };
 
-        let (addr, protocol) = match get_value(flags::SERVER) {
-            Some(ref value @ Value::String { .. }) => {
-                let protocol = protocol.unwrap_or(spanned!(Protocol::Udp, Span::unknown()));
+        let ipv4 = match get_value(flags::IPV4) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
 
-                let addr = SocketAddr::from_str(value.as_str().unwrap())
-                    .or_else(|_| {
-                        IpAddr::from_str(value.as_str().unwrap()).map(|ip| {
-                            SocketAddr::new(ip, constants::config::default_port(protocol.item))
-                        })
-                    })
-                    .map_err(|err| {
-                        LabeledError::new("invalid server")
-                            .with_label(err.to_string(), value.clone().span())
-                    })?;
+        let ipv6 = match get_value(flags::IPV6) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        if ipv4.item && ipv6.item {
+            return Err(LabeledError::new("invalid config combination")
+                .with_label("only one address family can be chosen", ipv4.span)
+                .with_label("other family chosen here", ipv6.span));
+        }
+
+        let allows = |ip: IpAddr| allows_family(ipv4.item, ipv6.item, ip);
 
-                let addr = spanned!(addr, value.span());
+        let server = get_value(flags::SERVER);
+        let server_given = server.is_some();
+
+        let (addr, server_addrs, protocol, server_host) = match server {
+            Some(ref value @ Value::String { .. }) => {
+                let protocol = protocol.unwrap_or(spanned!(Protocol::Udp, Span::unknown()));
+                let (addrs, host) = parse_server(value, protocol.item, ipv4.item, ipv6.item)?;
 
-                (addr, protocol)
+                (spanned!(addrs[0], value.span()), addrs, protocol, host)
             }
             None => {
                 let (config, _) =
                     hickory_resolver::system_conf::read_system_conf().unwrap_or_default();
                 tracing::debug!(?config);
-                match config.name_servers() {
-                    [ns, ..] => (
-                        spanned!(ns.socket_addr, Span::unknown()),
-                        spanned!(ns.protocol, Span::unknown()),
-                    ),
+
+                // only the system nameservers of the chosen family, if any
+                let name_servers: Vec<_> = config
+                    .name_servers()
+                    .iter()
+                    .filter(|ns| allows(ns.socket_addr.ip()))
+                    .cloned()
+                    .collect();
+
+                let (addr, protocol) = match name_servers.as_slice() {
+                    // a configured protocol still applies to the system
+                    // nameserver, on that protocol's port
+                    [ns, ..] => match protocol {
+                        Some(protocol) => (
+                            spanned!(
+                                SocketAddr::new(
+                                    ns.socket_addr.ip(),
+                                    constants::config::default_port(protocol.item)
+                                ),
+                                Span::unknown()
+                            ),
+                            protocol,
+                        ),
+                        None => (
+                            spanned!(ns.socket_addr, Span::unknown()),
+                            spanned!(ns.protocol, Span::unknown()),
+                        ),
+                    },
                     [] => {
                         let config = ResolverConfig::default();
-                        let ns = config.name_servers().first().unwrap();
+                        // the defaults have servers of both families
+                        let ns = config
+                            .name_servers()
+                            .iter()
+                            .find(|ns| allows(ns.socket_addr.ip()))
+                            .unwrap();
 
                         // if protocol is explicitly configured, it should take
                         // precedence over the system config
@@ -157,7 +354,19 @@ impl Config {
                             protocol.unwrap_or(spanned!(ns.protocol, Span::unknown())),
                         )
                     }
+                };
+
+                // the others are fallen back on in turn, on the same port as
+                // the first. each is listed once per protocol, hence the dedup.
+                let mut server_addrs = vec![addr.item];
+                for ns in &name_servers {
+                    let ns_addr = SocketAddr::new(ns.socket_addr.ip(), addr.item.port());
+                    if !server_addrs.contains(&ns_addr) {
+                        server_addrs.push(ns_addr);
+                    }
                 }
+
+                (addr, server_addrs, protocol, None)
             }
             Some(val) => {
                 return Err(LabeledError::new("invalid server address")
@@ -165,6 +374,12 @@ impl Config {
             }
         };
 
+        // a server given by name is also the name on its certificate
+        let dns_name = match (dns_name, &server_host) {
+            (None, Some(host)) if needs_dns_name => Some(spanned!(host.clone(), addr.span)),
+            (dns_name, _) => dns_name,
+        };
+
         if needs_dns_name && dns_name.is_none() {
             return Err(LabeledError::new("need DNS name").with_label(
                 "protocol needs to be accompanied by --dns-name",
@@ -194,14 +409,19 @@ impl Config {
                 val.span()
             ),
             None => spanned!(
-                vec![
-                    spanned!(RecordType::AAAA, Span::unknown()),
-                    spanned!(RecordType::A, Span::unknown()),
-                ],
+                constants::config::default::QTYPES
+                    .iter()
+                    .map(|qtype| spanned!(*qtype, Span::unknown()))
+                    .collect(),
                 Span::unknown()
             ),
         };
 
+        if qtypes.item.is_empty() {
+            return Err(LabeledError::new("invalid input")
+                .with_label("at least one query type is needed", qtypes.span));
+        }
+
         let class = match get_value(constants::flags::CLASS) {
             Some(val) => {
                 let span = val.span();
@@ -217,6 +437,335 @@ impl Config {
             _ => spanned!(false, Span::unknown()),
         };
 
+        let ignore_tc = match get_value(constants::flags::IGNORE_TC) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let partial = match get_value(constants::flags::PARTIAL) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let errors_as_values = match get_value(constants::flags::ERRORS_AS_VALUES) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let deadline = match get_value(constants::flags::DEADLINE) {
+            Some(val @ Value::Duration { .. }) => {
+                let span = val.span();
+                let timeout =
+                    Duration::from_nanos(val.as_duration()?.try_into().map_err(|err| {
+                        LabeledError::new("invalid duration")
+                            .with_label(format!("should be positive duration: {err}"), val.span())
+                    })?);
+                Some(spanned!(Instant::now() + timeout, span))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("deadline should be a positive duration", val.span()))
+            }
+        };
+
+        let no_auto_ptr = match get_value(constants::flags::NO_AUTO_PTR) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let fqdn = match get_value(constants::flags::FQDN) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let no_root_dot = match get_value(constants::flags::NO_ROOT_DOT) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let lenient = match get_value(constants::flags::LENIENT) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let with_question = match get_value(constants::flags::WITH_QUESTION) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let fail_on_nxdomain = match get_value(constants::flags::FAIL_ON_NXDOMAIN) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let sort = match get_value(constants::flags::SORT) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let dedup = match get_value(constants::flags::DEDUP) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let group_rrsets = match get_value(constants::flags::GROUP_RRSETS) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let compact = match get_value(constants::flags::COMPACT) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let output_schema = match get_value(constants::flags::OUTPUT_SCHEMA) {
+            Some(val) => {
+                let span = val.span();
+                spanned!(serde::OutputSchema::try_from(val)?, span)
+            }
+            None => spanned!(serde::OutputSchema::default(), Span::unknown()),
+        };
+
+        let scalar = match get_value(constants::flags::SCALAR) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let with_wire = match get_value(constants::flags::WITH_WIRE) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let separator = match get_value(constants::flags::SEPARATOR) {
+            Some(val) => {
+                let span = val.span();
+                spanned!(val.into_string()?, span)
+            }
+            None => spanned!(
+                constants::config::default::SEPARATOR.into(),
+                Span::unknown()
+            ),
+        };
+
+        let strict_special_use = match get_value(constants::flags::STRICT_SPECIAL_USE) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let tsig = match (
+            get_value(constants::flags::TSIG_KEY),
+            get_value(constants::flags::TSIG_SECRET),
+        ) {
+            (None, None) => None,
+            (Some(key), Some(secret)) => {
+                let key_span = key.span();
+                let key = serde::util::parse_name(&key.into_string()?).map_err(|err| {
+                    LabeledError::new("invalid name")
+                        .with_label(format!("Error parsing TSIG key name: {err}"), key_span)
+                })?;
+
+                let secret_span = secret.span();
+                let secret = data_encoding::BASE64
+                    .decode(secret.into_string()?.trim().as_bytes())
+                    .map_err(|err| {
+                        LabeledError::new("invalid TSIG secret")
+                            .with_label(format!("should be base64: {err}"), secret_span)
+                    })?;
+
+                let algorithm = match get_value(constants::flags::TSIG_ALGORITHM) {
+                    Some(val) => {
+                        let span = val.span();
+                        spanned!(val.into_string()?, span)
+                    }
+                    None => spanned!(
+                        constants::config::default::TSIG_ALGORITHM.into(),
+                        Span::unknown()
+                    ),
+                };
+                let algorithm = spanned!(
+                    hickory_proto::rr::dnssec::rdata::tsig::TsigAlgorithm::from_name(
+                        hickory_proto::rr::Name::from_ascii(&algorithm.item).unwrap_or_default()
+                    ),
+                    algorithm.span
+                );
+                if !algorithm.item.supported() {
+                    return Err(LabeledError::new("unsupported TSIG algorithm").with_label(
+                        "should be one of hmac-sha256, hmac-sha384, or hmac-sha512",
+                        algorithm.span,
+                    ));
+                }
+
+                Some(Tsig {
+                    key: spanned!(key, key_span),
+                    secret,
+                    algorithm,
+                })
+            }
+            (Some(given), None) | (None, Some(given)) => {
+                return Err(LabeledError::new("invalid config combination").with_label(
+                    format!(
+                        "--{} and --{} go together",
+                        constants::flags::TSIG_KEY,
+                        constants::flags::TSIG_SECRET
+                    ),
+                    given.span(),
+                ))
+            }
+        };
+
+        let randomize_case = match get_value(constants::flags::RANDOMIZE_CASE) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let cache = match get_value(constants::flags::CACHE) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        // lets one call skip a cache that is turned on in the plugin config
+        let cache = match get_value(constants::flags::NO_CACHE) {
+            Some(val @ Value::Bool { .. }) if val.as_bool().unwrap() => {
+                spanned!(false, val.span())
+            }
+            _ => cache,
+        };
+
+        let min_ttl = match get_value(constants::flags::MIN_TTL) {
+            Some(val @ Value::Duration { .. }) => {
+                let span = val.span();
+                Some(spanned!(
+                    Duration::from_nanos(val.as_duration()?.try_into().map_err(|err| {
+                        LabeledError::new("invalid duration")
+                            .with_label(format!("should be positive duration: {err}"), val.span())
+                    })?),
+                    span
+                ))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("minimum TTL should be a positive duration", val.span()))
+            }
+        };
+
+        let max_ttl = match get_value(constants::flags::MAX_TTL) {
+            Some(val @ Value::Duration { .. }) => {
+                let span = val.span();
+                Some(spanned!(
+                    Duration::from_nanos(val.as_duration()?.try_into().map_err(|err| {
+                        LabeledError::new("invalid duration")
+                            .with_label(format!("should be positive duration: {err}"), val.span())
+                    })?),
+                    span
+                ))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("should be duration")
+                    .with_label("maximum TTL should be a positive duration", val.span()))
+            }
+        };
+
+        if let (Some(min_ttl), Some(max_ttl)) = (&min_ttl, &max_ttl) {
+            if min_ttl.item > max_ttl.item {
+                return Err(LabeledError::new("invalid input")
+                    .with_label(
+                        "minimum TTL should not be more than the maximum",
+                        min_ttl.span,
+                    )
+                    .with_label("maximum TTL", max_ttl.span));
+            }
+        }
+
+        let cache_file = match get_value(constants::flags::CACHE_FILE) {
+            Some(val @ Value::String { .. }) => {
+                let span = val.span();
+                Some(spanned!(PathBuf::from(val.into_string()?), span))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("should be string")
+                    .with_label("cache file should be a path", val.span()))
+            }
+        };
+
+        let log_file = match get_value(constants::flags::LOG_FILE) {
+            Some(val @ Value::String { .. }) => {
+                let span = val.span();
+                Some(spanned!(PathBuf::from(val.into_string()?), span))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("should be string")
+                    .with_label("log file should be a path", val.span()))
+            }
+        };
+
+        let trust_anchors = match get_value(constants::flags::TRUST_ANCHORS) {
+            Some(val @ Value::String { .. }) => {
+                let span = val.span();
+                Some(spanned!(PathBuf::from(val.into_string()?), span))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("should be string")
+                    .with_label("trust anchor file should be a path", val.span()))
+            }
+        };
+
+        let rcode_filter = match get_value(constants::flags::RCODE_FILTER) {
+            Some(list @ Value::List { .. }) => {
+                let span = list.span();
+                Some(spanned!(
+                    list.as_list()?
+                        .iter()
+                        .map(RCode::try_from)
+                        .collect::<Result<Vec<_>, _>>()?,
+                    span
+                ))
+            }
+            Some(ref val) => Some(spanned!(vec![RCode::try_from(val)?], val.span())),
+            None => None,
+        };
+
         let dnssec_mode = match get_value(constants::flags::DNSSEC) {
             Some(val) => {
                 let span = val.span();
@@ -225,6 +774,157 @@ impl Config {
             None => spanned!(serde::DnssecMode::Opportunistic, Span::unknown()),
         };
 
+        let dnssec_ok = match get_value(constants::flags::DNSSEC_OK) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        // the DO bit only asks for the DNSSEC records; looking at them as
+        // they came means not letting the validator strip or reject them
+        let dnssec_mode = match (dnssec_ok.item, dnssec_mode) {
+            (true, mode) if mode.item != DnssecMode::None && mode.span != Span::unknown() => {
+                return Err(LabeledError::new("invalid config combination")
+                    .with_label(
+                        "--dnssec-ok returns DNSSEC records without validating them, so it cannot be used with DNSSEC validation",
+                        mode.span,
+                    )
+                    .with_label("DO bit requested here", dnssec_ok.span))
+            }
+            (true, _) => spanned!(DnssecMode::None, dnssec_ok.span),
+            (false, mode) => mode,
+        };
+
+        let checking_disabled = match get_value(constants::flags::CD) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        let id = match get_value(constants::flags::ID) {
+            Some(val @ Value::Int { .. }) => {
+                let span = val.span();
+                let id = u16::try_from(val.as_int()?).map_err(|err| {
+                    LabeledError::new("invalid message ID")
+                        .with_label(format!("should be between 0 and 65535: {err}"), span)
+                })?;
+
+                if !matches!(protocol.item, Protocol::Udp | Protocol::Tcp) {
+                    return Err(LabeledError::new("invalid config combination").with_label(
+                        "a fixed message ID is only supported over UDP and TCP",
+                        span,
+                    ));
+                }
+
+                if dnssec_mode.item == DnssecMode::Strict {
+                    return Err(LabeledError::new("invalid config combination").with_label(
+                        "a fixed message ID bypasses DNSSEC validation, so it cannot be used with strict DNSSEC",
+                        span,
+                    ));
+                }
+
+                Some(spanned!(id, span))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("should be int")
+                    .with_label("message ID should be an int", val.span()))
+            }
+        };
+
+        // lenient responses are read off the socket by the plugin itself,
+        // like those to a fixed ID, so the same limits apply
+        if lenient.item {
+            if !matches!(protocol.item, Protocol::Udp | Protocol::Tcp) {
+                return Err(LabeledError::new("invalid config combination")
+                    .with_label("--lenient is only supported over UDP and TCP", lenient.span)
+                    .with_label("protocol chosen here", protocol.span));
+            }
+
+            if dnssec_mode.item == DnssecMode::Strict {
+                return Err(LabeledError::new("invalid config combination")
+                    .with_label(
+                        "--lenient bypasses DNSSEC validation, so it cannot be used with strict DNSSEC",
+                        lenient.span,
+                    )
+                    .with_label("strict DNSSEC chosen here", dnssec_mode.span));
+            }
+        }
+
+        let debug = match get_value(constants::flags::DEBUG) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(false, Span::unknown()),
+        };
+
+        // the hex dumps of --debug come on top of the rendered messages
+        let verbose = match get_value(constants::flags::VERBOSE) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap() || debug.item, val.span())
+            }
+            _ => spanned!(debug.item, debug.span),
+        };
+
+        let log_level = match get_value(constants::flags::LOG_LEVEL) {
+            Some(val @ Value::String { .. }) => {
+                let span = val.span();
+                let level = val.into_string()?;
+
+                tracing_subscriber::EnvFilter::try_new(&level).map_err(|err| {
+                    LabeledError::new("invalid log level").with_label(
+                        format!(
+                            "should be a level like \"debug\", or a RUST_LOG style filter: {err}"
+                        ),
+                        span,
+                    )
+                })?;
+
+                Some(spanned!(level, span))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("should be string")
+                    .with_label("log level should be a string", val.span()))
+            }
+        };
+
+        let edns_opts = match get_value(constants::flags::EDNS_OPT) {
+            Some(val @ Value::String { .. }) => vec![parse_edns_opt(&val)?],
+            Some(Value::List { vals, .. }) => {
+                vals.iter().map(parse_edns_opt).collect::<Result<_, _>>()?
+            }
+            None => Vec::new(),
+            Some(val) => {
+                return Err(LabeledError::new("invalid EDNS option").with_label(
+                    "EDNS options should be a string or list of strings like 65001:beef",
+                    val.span(),
+                ))
+            }
+        };
+
+        let rate = match get_value(constants::flags::RATE) {
+            Some(val @ (Value::Int { .. } | Value::Float { .. })) => {
+                let qps = val.coerce_float()?;
+
+                if !qps.is_finite() || qps <= 0.0 {
+                    return Err(LabeledError::new("invalid input").with_label(
+                        "rate should be a positive number of queries per second",
+                        val.span(),
+                    ));
+                }
+
+                Some(Arc::new(RateLimiter::new(qps)))
+            }
+            None => None,
+            Some(val) => {
+                return Err(LabeledError::new("should be number")
+                    .with_label("rate should be a number of queries per second", val.span()))
+            }
+        };
+
         let tasks = match get_value(constants::flags::TASKS) {
             Some(val @ Value::Int { .. }) => {
                 let span = val.span();
@@ -244,6 +944,19 @@ impl Config {
             }
         };
 
+        // zero tasks would never make progress
+        if tasks.item == 0 {
+            return Err(LabeledError::new("invalid input")
+                .with_label("number of tasks should be at least 1", tasks.span));
+        }
+
+        // without --server, the system nameservers are asked the way the
+        // system's own stub resolver would ask them
+        let system = match server_given {
+            true => None,
+            false => SystemOptions::read(),
+        };
+
         let timeout = match get_value(constants::flags::TIMEOUT) {
             Some(val @ Value::Duration { .. }) => {
                 let span = val.span();
@@ -255,7 +968,10 @@ impl Config {
                     span
                 )
             }
-            None => spanned!(constants::config::default::TIMEOUT, Span::unknown()),
+            None => spanned!(
+                system.map_or(constants::config::default::TIMEOUT, |system| system.timeout),
+                Span::unknown()
+            ),
 
             Some(val) => {
                 return Err(LabeledError::new("should be duration")
@@ -263,16 +979,504 @@ impl Config {
             }
         };
 
+        let attempts = match get_value(constants::flags::ATTEMPTS) {
+            Some(val @ Value::Int { .. }) => {
+                let span = val.span();
+                match usize::try_from(val.as_int()?) {
+                    Ok(attempts) if attempts > 0 => spanned!(attempts, span),
+                    _ => {
+                        return Err(LabeledError::new("invalid input")
+                            .with_label("attempts should be at least 1", span))
+                    }
+                }
+            }
+            None => spanned!(system.map_or(1, |system| system.attempts), Span::unknown()),
+            Some(val) => {
+                return Err(LabeledError::new("should be int")
+                    .with_label("attempts should be an int", val.span()))
+            }
+        };
+
+        let rotate = match get_value(constants::flags::ROTATE) {
+            Some(val @ Value::Bool { .. }) => {
+                spanned!(val.as_bool().unwrap(), val.span())
+            }
+            _ => spanned!(system.is_some_and(|system| system.rotate), Span::unknown()),
+        };
+
         Ok(Self {
             protocol,
             server: addr,
+            server_addrs,
+            server_host,
+            ipv4,
+            ipv6,
             qtypes,
             code,
             class,
             dnssec_mode,
+            dnssec_ok,
+            checking_disabled,
             dns_name,
+            ignore_tc,
+            partial,
+            errors_as_values,
+            deadline,
+            no_auto_ptr,
+            fqdn,
+            no_root_dot,
+            lenient,
+            with_question,
+            fail_on_nxdomain,
+            sort,
+            dedup,
+            group_rrsets,
+            compact,
+            output_schema,
+            scalar,
+            separator,
+            with_wire,
+            strict_special_use,
+            tsig,
+            randomize_case,
+            cache,
+            cache_file,
+            min_ttl,
+            max_ttl,
+            log_file,
+            trust_anchors,
+            rcode_filter,
+            id,
+            rate,
+            edns_opts,
+            verbose,
+            debug,
+            log_level,
             tasks,
             timeout,
+            attempts,
+            rotate,
         })
     }
 }
+
+/// Parses an EDNS option given as `<code>:<hex data>`, e.g. `65001:beef`.
+/// The data may be empty.
+fn parse_edns_opt(value: &Value) -> Result<(u16, Vec<u8>), LabeledError> {
+    let opt_err =
+        |msg: String| LabeledError::new("invalid EDNS option").with_label(msg, value.span());
+
+    let opt = value.as_str()?;
+    let (code, data) = opt
+        .split_once(':')
+        .ok_or_else(|| opt_err("should be <code>:<hex>, e.g. 65001:beef".into()))?;
+
+    let code = code
+        .trim()
+        .parse::<u16>()
+        .map_err(|err| opt_err(format!("option code should be between 0 and 65535: {err}")))?;
+    let data = data_encoding::HEXLOWER_PERMISSIVE
+        .decode(data.trim().as_bytes())
+        .map_err(|err| opt_err(format!("option data should be hex: {err}")))?;
+
+    Ok((code, data))
+}
+
+impl Config {
+    /// Copy of the config that sends queries to a different server, e.g. one
+    /// given in a column of the input, over the same protocol.
+    pub fn with_server(&self, value: &Value) -> Result<Self, LabeledError> {
+        let mut config = self.clone();
+        let (addrs, host) =
+            parse_server(value, self.protocol.item, self.ipv4.item, self.ipv6.item)?;
+
+        // a DNS name that came from the old server's host name is not the
+        // name on the new server's certificate; one given with --dns-name is
+        // kept, as in from_values
+        if config.dns_name.as_ref().map(|name| &name.item) == self.server_host.as_ref() {
+            config.dns_name = None;
+        }
+        let needs_dns_name = matches!(
+            self.protocol.item,
+            Protocol::Tls | Protocol::Https | Protocol::Quic
+        );
+        config.dns_name = match (config.dns_name, &host) {
+            (None, Some(host)) if needs_dns_name => Some(spanned!(host.clone(), value.span())),
+            (dns_name, _) => dns_name,
+        };
+
+        if needs_dns_name && config.dns_name.is_none() {
+            return Err(LabeledError::new("need DNS name").with_label(
+                "a server given by address needs to be accompanied by --dns-name",
+                value.span(),
+            ));
+        }
+
+        config.server = spanned!(addrs[0], value.span());
+        config.server_addrs = addrs;
+        config.server_host = host;
+        Ok(config)
+    }
+
+    /// The config for asking the server at one of its other addresses.
+    pub fn with_addr(&self, addr: SocketAddr) -> Self {
+        let mut config = self.clone();
+        config.server.item = addr;
+        config
+    }
+
+    /// Pipeline metadata for a command's output, whose content type names
+    /// the command and the server it asked, so that what comes next in the
+    /// pipeline can tell with `metadata` where the data came from.
+    pub fn metadata(&self, command: &str) -> Option<PipelineMetadata> {
+        Some(PipelineMetadata {
+            data_source: DataSource::None,
+            content_type: Some(format!(
+                "{}; command=\"{command}\"; server=\"{}\"; protocol={}",
+                constants::CONTENT_TYPE,
+                self.server.item,
+                self.protocol.item,
+            )),
+        })
+    }
+
+    /// Waits for the rate limit, if any, to allow another query. This is kept
+    /// apart from sending so that the wait doesn't count against the timeout.
+    pub async fn throttle(&self) {
+        if let Some(rate) = &self.rate {
+            rate.acquire().await;
+        }
+    }
+
+    /// The address record types to look up for a name, narrowed down to one
+    /// family by `--ipv4` or `--ipv6`.
+    pub fn address_types(&self) -> &'static [RecordType] {
+        match (self.ipv4.item, self.ipv6.item) {
+            (true, _) => &[RecordType::A],
+            (_, true) => &[RecordType::AAAA],
+            _ => &[RecordType::A, RecordType::AAAA],
+        }
+    }
+
+    /// Shows a name in presentation format. The plugin has no search list, so
+    /// every name it sends is absolute, and names are shown with the trailing
+    /// root dot whether or not they were given with one, unless
+    /// `--no-root-dot` is set, in which case none are.
+    pub fn display_name(&self, name: &hickory_resolver::Name) -> String {
+        serde::util::escape_name(name, !self.no_root_dot.item)
+    }
+
+    /// Marks a name given as input fully qualified if `--fqdn` is set.
+    pub fn input_name(&self, mut name: hickory_resolver::Name) -> hickory_resolver::Name {
+        if self.fqdn.item {
+            name.set_fqdn(true);
+        }
+
+        name
+    }
+
+    /// Whether a response with the given code should be returned, given the
+    /// configured response code filter, if any.
+    pub fn rcode_matches(&self, rcode: hickory_proto::op::ResponseCode) -> bool {
+        match &self.rcode_filter {
+            None => true,
+            Some(filter) => filter.item.contains(&RCode(rcode)),
+        }
+    }
+}
+
+/// A TSIG key (RFC 8945) to sign messages with, as dynamic updates need.
+#[derive(Clone)]
+pub struct Tsig {
+    pub key: Spanned<hickory_proto::rr::Name>,
+    pub secret: Vec<u8>,
+    pub algorithm: Spanned<hickory_proto::rr::dnssec::rdata::tsig::TsigAlgorithm>,
+}
+
+// the secret stays out of debug logs
+impl std::fmt::Debug for Tsig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tsig")
+            .field("key", &self.key.item)
+            .field("algorithm", &self.algorithm.item)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Tsig {
+    pub fn signer(&self) -> Result<hickory_proto::rr::dnssec::tsig::TSigner, LabeledError> {
+        hickory_proto::rr::dnssec::tsig::TSigner::new(
+            self.secret.clone(),
+            self.algorithm.item.clone(),
+            self.key.item.clone(),
+            constants::config::default::TSIG_FUDGE,
+        )
+        .map_err(|err| {
+            LabeledError::new("invalid TSIG key").with_label(err.to_string(), self.key.span)
+        })
+    }
+}
+
+/// The `options` of resolv.conf that say how the system's stub resolver
+/// retries: `timeout:n`, `attempts:n`, and `rotate`.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemOptions {
+    timeout: Duration,
+    attempts: usize,
+    rotate: bool,
+}
+
+impl SystemOptions {
+    #[cfg(unix)]
+    pub fn read() -> Option<Self> {
+        let conf = std::fs::read(constants::config::RESOLV_CONF).ok()?;
+        let conf = resolv_conf::Config::parse(conf)
+            .inspect_err(|err| tracing::debug!(resolv_conf.error = %err))
+            .ok()?;
+
+        Some(Self {
+            timeout: Duration::from_secs(conf.timeout.into()),
+            attempts: (conf.attempts as usize).max(1),
+            rotate: conf.rotate,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn read() -> Option<Self> {
+        None
+    }
+}
+
+/// Parses a duration like nushell writes them, e.g. `5sec`, `500ms`, or
+/// `1.5min`. A bare number is taken to be seconds.
+fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    let (num, unit) = input.split_at(
+        input
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(input.len()),
+    );
+
+    let scale = match unit.trim() {
+        "ns" => 1e-9,
+        "us" | "µs" => 1e-6,
+        "ms" => 1e-3,
+        "" | "s" | "sec" => 1.0,
+        "min" => 60.0,
+        "hr" => 3600.0,
+        _ => return None,
+    };
+
+    Duration::try_from_secs_f64(num.parse::<f64>().ok()? * scale).ok()
+}
+
+/// Parses a server given as an address, or, failing that, as a host name,
+/// which is looked up with the system resolver. Returns the addresses to try,
+/// in the order the system resolver gave them, and the host name, if there
+/// was one. Only addresses of the family chosen with `--ipv4` or `--ipv6` are
+/// kept.
+fn parse_server(
+    value: &Value,
+    protocol: Protocol,
+    ipv4: bool,
+    ipv6: bool,
+) -> Result<(Vec<SocketAddr>, Option<String>), LabeledError> {
+    let server = value.as_str()?;
+    let invalid = |msg: String| LabeledError::new("invalid server").with_label(msg, value.span());
+    let port = constants::config::default_port(protocol);
+
+    match parse_addr(server, port) {
+        Ok(Some(addr)) if allows_family(ipv4, ipv6, addr.ip()) => return Ok((vec![addr], None)),
+        Ok(Some(addr)) => {
+            return Err(LabeledError::new("invalid config combination").with_label(
+                format!("{} is not an {} address", addr.ip(), family_name(ipv4)),
+                value.span(),
+            ))
+        }
+        Ok(None) => {}
+        Err(err) => return Err(invalid(err)),
+    }
+
+    let (host, port) = match server.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse::<u16>()
+                .map_err(|err| invalid(format!("invalid port {port}: {err}")))?,
+        ),
+        None => (server, port),
+    };
+
+    hickory_resolver::Name::from_utf8(host)
+        .map_err(|err| invalid(format!("not an address or host name: {err}")))?;
+
+    let mut addrs: Vec<SocketAddr> = lookup_host(host, port)
+        .map_err(|err| {
+            LabeledError::new("could not resolve server")
+                .with_label(format!("could not look up {host}: {err}"), value.span())
+        })?
+        .into_iter()
+        .filter(|addr| allows_family(ipv4, ipv6, addr.ip()))
+        .collect();
+
+    // getaddrinfo gives an address once per socket type
+    let mut seen = Vec::new();
+    addrs.retain(|addr| {
+        let new = !seen.contains(addr);
+        seen.push(*addr);
+        new
+    });
+
+    if addrs.is_empty() {
+        return Err(LabeledError::new("could not resolve server").with_label(
+            match ipv4 || ipv6 {
+                true => format!("{host} has no {} address", family_name(ipv4)),
+                false => format!("{host} has no address"),
+            },
+            value.span(),
+        ));
+    }
+
+    tracing::debug!(server.host = host, server.addrs = ?addrs);
+
+    Ok((addrs, Some(host.to_string())))
+}
+
+/// Looks up a host name with the system resolver. Inside the plugin's runtime,
+/// which is where commands build their config and swap servers for each row,
+/// this goes through tokio's `lookup_host`, and the worker thread is handed
+/// off while it waits, so that queries already in flight keep going.
+fn lookup_host(host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+    use tokio::runtime::{Handle, RuntimeFlavor};
+
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| {
+                handle
+                    .block_on(async { Ok(tokio::net::lookup_host((host, port)).await?.collect()) })
+            })
+        }
+        _ => Ok((host, port).to_socket_addrs()?.collect()),
+    }
+}
+
+/// Parses a server address, with or without a port. Returns `None` if it's
+/// not an address at all, and may be a host name.
+fn parse_addr(server: &str, port: u16) -> Result<Option<SocketAddr>, String> {
+    // a link-local IPv6 address needs a zone index to say which link it is on,
+    // as in fe80::1%eth0 or [fe80::1%2]:53
+    let (server, scope_id) = match server.split_once('%') {
+        None => (server.to_string(), None),
+        Some((addr, rest)) => {
+            let (zone, rest) = rest.split_at(rest.find(']').unwrap_or(rest.len()));
+            (format!("{addr}{rest}"), Some(zone_index(zone)?))
+        }
+    };
+
+    let Ok(mut addr) = SocketAddr::from_str(&server)
+        .or_else(|_| IpAddr::from_str(&server).map(|ip| SocketAddr::new(ip, port)))
+    else {
+        return match scope_id {
+            Some(_) => Err(format!("not an IPv6 address: {server}")),
+            None => Ok(None),
+        };
+    };
+
+    match (&mut addr, scope_id) {
+        (_, None) => {}
+        (SocketAddr::V6(addr), Some(scope_id)) => addr.set_scope_id(scope_id),
+        (SocketAddr::V4(_), Some(_)) => {
+            return Err("only IPv6 addresses can have a zone index".into())
+        }
+    }
+
+    Ok(Some(addr))
+}
+
+fn allows_family(ipv4: bool, ipv6: bool, ip: IpAddr) -> bool {
+    !(ipv4 && ip.is_ipv6() || ipv6 && ip.is_ipv4())
+}
+
+fn family_name(ipv4: bool) -> &'static str {
+    match ipv4 {
+        true => "IPv4",
+        false => "IPv6",
+    }
+}
+
+/// Resolves the zone index of a scoped IPv6 address, which is either an
+/// interface index or, on Unix, an interface name.
+pub(crate) fn zone_index(zone: &str) -> Result<u32, String> {
+    if let Ok(index) = zone.parse::<u32>() {
+        return Ok(index);
+    }
+
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(zone)
+            .map_err(|_| format!("invalid interface name: {zone:?}"))?;
+
+        // SAFETY: the name is a valid NUL terminated string for the duration
+        // of the call
+        match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+            0 => Err(format!("no such interface: {zone}")),
+            index => Ok(index),
+        }
+    }
+
+    #[cfg(not(unix))]
+    Err(format!(
+        "zone index should be an interface number, not {zone:?}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_keys_are_the_settings_read() {
+        let mut read = Vec::new();
+        Config::from_values(|name| {
+            read.push(name.to_string());
+            None
+        })
+        .unwrap();
+
+        let read: BTreeSet<&str> = read.iter().map(String::as_str).collect();
+        let keys: BTreeSet<&str> = constants::config::KEYS.iter().copied().collect();
+        assert!(read.is_subset(&keys), "{:?}", read.difference(&keys));
+
+        // the algorithm is only looked at along with a key
+        assert_eq!(
+            keys.difference(&read).copied().collect::<Vec<_>>(),
+            [flags::TSIG_ALGORITHM]
+        );
+    }
+
+    #[test]
+    fn with_server_dns_name() {
+        let config = |server: &str, dns_name: Option<&str>| {
+            Config::from_values(|name| match name {
+                flags::SERVER => Some(Value::test_string(server)),
+                flags::PROTOCOL => Some(Value::test_string("tls")),
+                flags::DNS_NAME => dns_name.map(Value::test_string),
+                _ => None,
+            })
+            .unwrap()
+        };
+        let dns_name = |config: &Config| config.dns_name.as_ref().unwrap().item.clone();
+
+        // the name came from the old server, so it can't be the new one's
+        let by_name = config("localhost", None);
+        assert_eq!(dns_name(&by_name), "localhost");
+        assert!(by_name
+            .with_server(&Value::test_string("127.0.0.2"))
+            .is_err());
+
+        // one given with --dns-name stays
+        let given = config("127.0.0.1", Some("dns.example"));
+        let swapped = given.with_server(&Value::test_string("localhost")).unwrap();
+        assert_eq!(dns_name(&swapped), "dns.example");
+        assert_eq!(swapped.server_host.as_deref(), Some("localhost"));
+    }
+}
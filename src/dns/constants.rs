@@ -1,7 +1,62 @@
 pub mod commands {
     pub const QUERY: &str = "dns query";
+    pub const BENCH: &str = "dns bench";
+    pub const WATCH: &str = "dns watch";
+    pub const LOOKUP: &str = "dns lookup";
+    pub const CACHE_LIST: &str = "dns cache list";
+    pub const CACHE_CLEAR: &str = "dns cache clear";
+    pub const CONFIG_SHOW: &str = "dns config show";
+    pub const DOCTOR: &str = "dns doctor";
+    pub const SERVE: &str = "dns serve";
+    pub const SERVE_STOP: &str = "dns serve stop";
+    pub const STATS: &str = "dns stats";
+    pub const AXFR: &str = "dns axfr";
+    pub const CHECK_DNS64: &str = "dns check dns64";
+    pub const NAT64_PREFIX: &str = "dns nat64-prefix";
+    pub const GEO: &str = "dns geo";
+    pub const CHECK_ANYCAST: &str = "dns check anycast";
+    pub const PROBE: &str = "dns probe";
+    pub const DNSSEC_KEYTAG: &str = "dns dnssec keytag";
+    pub const DNSSEC_UPDATE_ANCHORS: &str = "dns dnssec update-anchors";
+    pub const CHECK_CDS: &str = "dns check cds";
+    pub const CHECK_ZONEMD: &str = "dns check zonemd";
+    pub const CHECK_RRSIG: &str = "dns check rrsig";
+    pub const CHECK_APEX: &str = "dns check apex";
+    pub const CHECK_HIJACK: &str = "dns check hijack";
+    pub const CHECK_CAPTIVE: &str = "dns check captive";
+    pub const CHECK_FRAGMENTATION: &str = "dns check fragmentation";
+    pub const CHECK_TRANSPORT: &str = "dns check transport";
+    pub const CHECK_MINIMAL: &str = "dns check minimal";
+    pub const CHECK_SOA: &str = "dns check soa";
+    pub const PING: &str = "dns ping";
+    pub const UPDATE: &str = "dns update";
+    pub const UPDATE_SELF: &str = "dns update self";
+    pub const MYIP: &str = "dns myip";
+    pub const DDR: &str = "dns ddr";
+    pub const SERIAL_COMPARE: &str = "dns serial compare";
+    pub const SERIAL_NEXT: &str = "dns serial next";
+    pub const SOA: &str = "dns soa";
+    pub const DECODE: &str = "dns decode";
+    pub const FMT: &str = "dns fmt";
+    pub const RESOLVE: &str = "dns resolve";
+    pub const NAME_PARSE: &str = "dns name parse";
+    pub const NAME_VALIDATE: &str = "dns name validate";
+    pub const SCAN_SERVICES: &str = "dns scan services";
+    pub const ENUMERATE: &str = "dns enumerate";
+    pub const COMPARE_HORIZONS: &str = "dns compare-horizons";
+    pub const TLSA_GENERATE: &str = "dns tlsa generate";
+    pub const SSHFP_GENERATE: &str = "dns sshfp generate";
+    pub const SPF_FLATTEN: &str = "dns spf flatten";
+    pub const BROWSE: &str = "dns browse";
 }
 
+/// The tracing target that wire-level messages are logged under.
+pub const WIRE_LOG_TARGET: &str = "nu_plugin_dns::wire";
+
+/// The media type in the pipeline metadata of the plugin's output, with
+/// parameters saying which command made it and which server it came from.
+pub const CONTENT_TYPE: &str = "application/x-nu-dns";
+
 pub mod flags {
     pub const DNS_NAME: &str = "dns-name";
     pub const NAME: &str = "name";
@@ -10,19 +65,259 @@ pub mod flags {
     pub const TYPE: &str = "type";
     pub const CLASS: &str = "class";
     pub const DNSSEC: &str = "dnssec";
+    pub const DNSSEC_OK: &str = "dnssec-ok";
+    pub const CD: &str = "cd";
+    pub const NO_HOSTS: &str = "no-hosts";
+    pub const AS: &str = "as";
+    pub const ALL: &str = "all";
+    pub const WORDLIST: &str = "wordlist";
+    pub const INTERNAL: &str = "internal";
+    pub const EXTERNAL: &str = "external";
+    pub const IPV4: &str = "ipv4";
+    pub const IPV6: &str = "ipv6";
     pub const CODE: &str = "code";
     pub const TASKS: &str = "tasks";
+    pub const RATE: &str = "rate";
+    pub const EDNS_OPT: &str = "edns-opt";
+    pub const ZONE: &str = "zone";
+    pub const ORIGIN: &str = "origin";
+    pub const LISTEN: &str = "listen";
+    pub const RESET: &str = "reset";
+    pub const VERBOSE: &str = "verbose";
+    pub const DEBUG: &str = "debug";
+    pub const LOG_LEVEL: &str = "log-level";
+    pub const SUBNET: &str = "subnet";
+    pub const NAMESERVER: &str = "nameserver";
+    pub const KEY: &str = "key";
+    pub const FLAGS: &str = "flags";
+    pub const ALGORITHM: &str = "algorithm";
+    pub const PUBLIC_KEY: &str = "public-key";
+    pub const WARN_WITHIN: &str = "warn-within";
+    pub const FORMAT: &str = "format";
+    pub const URL: &str = "url";
     pub const TIMEOUT: &str = "timeout";
+    pub const IGNORE_TC: &str = "ignore-tc";
+    pub const PARTIAL: &str = "partial";
+    pub const ERRORS_AS_VALUES: &str = "errors-as-values";
+    pub const SLOW: &str = "slow";
+    pub const OUTPUT_SCHEMA: &str = "output-schema";
+    pub const SCALAR: &str = "scalar";
+    pub const SEPARATOR: &str = "separator";
+    pub const WITH_WIRE: &str = "with-wire";
+    pub const DEADLINE: &str = "deadline";
+    pub const ATTEMPTS: &str = "attempts";
+    pub const ROTATE: &str = "rotate";
+    pub const NO_AUTO_PTR: &str = "no-auto-ptr";
+    pub const FQDN: &str = "fqdn";
+    pub const NO_ROOT_DOT: &str = "no-root-dot";
+    pub const OFFSETS: &str = "offsets";
+    pub const LENIENT: &str = "lenient";
+    pub const WITH_QUESTION: &str = "with-question";
+    pub const FAIL_ON_NXDOMAIN: &str = "fail-on-nxdomain";
+    pub const SORT: &str = "sort";
+    pub const DEDUP: &str = "dedup";
+    pub const GROUP_RRSETS: &str = "group-rrsets";
+    pub const COMPACT: &str = "compact";
+    pub const STRICT_SPECIAL_USE: &str = "strict-special-use";
+    pub const ADD: &str = "add";
+    pub const DELETE: &str = "delete";
+    pub const NAME_IN_USE: &str = "name-in-use";
+    pub const NAME_NOT_IN_USE: &str = "name-not-in-use";
+    pub const RRSET_EXISTS: &str = "rrset-exists";
+    pub const RRSET_NOT_EXISTS: &str = "rrset-not-exists";
+    pub const TSIG_KEY: &str = "tsig-key";
+    pub const TSIG_SECRET: &str = "tsig-secret";
+    pub const TSIG_ALGORITHM: &str = "tsig-algorithm";
+    pub const TTL: &str = "ttl";
+    pub const SORTED: &str = "sorted";
+    pub const RANDOMIZE_CASE: &str = "0x20";
+    pub const RCODE_FILTER: &str = "rcode-filter";
+    pub const ID: &str = "id";
+    pub const COUNT: &str = "count";
+    pub const DURATION: &str = "duration";
+    pub const INTERVAL: &str = "interval";
+    pub const ALERT_ON_CHANGE: &str = "alert-on-change";
+    pub const HISTOGRAM: &str = "histogram";
+    pub const CACHE: &str = "cache";
+    pub const CACHE_FILE: &str = "cache-file";
+    pub const NO_CACHE: &str = "no-cache";
+    pub const MIN_TTL: &str = "min-ttl";
+    pub const MAX_TTL: &str = "max-ttl";
+    pub const LOG_FILE: &str = "log-file";
+    pub const TRUST_ANCHORS: &str = "trust-anchors";
+    pub const CERT: &str = "cert";
+    pub const CONNECT: &str = "connect";
+    pub const USAGE: &str = "usage";
+    pub const SELECTOR: &str = "selector";
+    pub const MATCHING: &str = "matching";
+    pub const KEY_FILE: &str = "key-file";
+    pub const SERVICE: &str = "service";
+    pub const WAIT: &str = "wait";
+    pub const INTERFACE: &str = "interface";
+    pub const CONTINUOUS: &str = "continuous";
 }
 
 pub mod config {
     use hickory_resolver::config::Protocol;
 
+    /// Where the system's stub resolver is configured.
+    #[cfg(unix)]
+    pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
     pub mod default {
-        use std::time::Duration;
+        use std::{
+            net::{IpAddr, Ipv4Addr, SocketAddr},
+            time::Duration,
+        };
+
+        use hickory_proto::rr::RecordType;
 
         pub const TASKS: usize = 8;
         pub const TIMEOUT: Duration = Duration::from_secs(5);
+        pub const EDNS_PAYLOAD: u16 = 1232;
+        pub const TSIG_ALGORITHM: &str = "hmac-sha256";
+        /// How far apart the clocks of the plugin and the server may be for
+        /// a TSIG signature to be accepted, in seconds (RFC 8945 section
+        /// 10).
+        pub const TSIG_FUDGE: u16 = 300;
+        pub const UPDATE_TTL: Duration = Duration::from_secs(300);
+        pub const SLOW_RTT: Duration = Duration::from_millis(500);
+        pub const SEPARATOR: &str = "; ";
+        pub const BENCH_COUNT: usize = 100;
+        pub const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+        pub const PING_COUNT: usize = 4;
+        pub const PING_INTERVAL: Duration = Duration::from_secs(1);
+        pub const BROWSE_WAIT: Duration = Duration::from_secs(3);
+        /// The longest `dns browse --continuous` waits between queries, as
+        /// the interval doubles from a second (RFC 6762 section 5.2).
+        pub const BROWSE_MAX_INTERVAL: Duration = Duration::from_secs(3600);
+        pub const MAX_CNAME_CHAIN: usize = 8;
+        pub const QTYPES: &[RecordType] = &[RecordType::AAAA, RecordType::A];
+        pub const SERVE_LISTEN: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        pub const ROOT_ANCHORS_URL: &str = "https://data.iana.org/root-anchors/root-anchors.xml";
+
+        /// Client subnets `dns geo` asks on behalf of by default: a sample
+        /// from the address space of each regional internet registry.
+        pub const GEO_SUBNETS: &[(&str, &str)] = &[
+            ("north-america", "24.0.0.0/24"),
+            ("south-america", "200.0.0.0/24"),
+            ("europe", "2.0.0.0/24"),
+            ("africa", "41.0.0.0/24"),
+            ("asia", "133.0.0.0/24"),
+            ("oceania", "1.120.0.0/24"),
+        ];
+
+        /// Independent public resolvers `dns check hijack` asks over DNS over
+        /// HTTPS, which a middlebox can't tamper with, with the name their
+        /// certificates are issued for.
+        pub const DOH_RESOLVERS: &[(&str, &str, SocketAddr)] = &[
+            (
+                "cloudflare",
+                "cloudflare-dns.com",
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 443),
+            ),
+            (
+                "google",
+                "dns.google",
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 443),
+            ),
+            (
+                "quad9",
+                "dns.quad9.net",
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)), 443),
+            ),
+        ];
+
+        /// RRsets `dns check rrsig` looks at by default: those of a zone apex,
+        /// and the ones most names have.
+        pub const RRSIG_TYPES: &[RecordType] = &[
+            RecordType::SOA,
+            RecordType::NS,
+            RecordType::DNSKEY,
+            RecordType::A,
+            RecordType::AAAA,
+            RecordType::MX,
+        ];
+    }
+
+    use super::flags;
+
+    /// Flags that can also be set with a `NU_PLUGIN_DNS_*` environment
+    /// variable.
+    pub const ENV_FLAGS: &[&str] = &[
+        flags::SERVER,
+        flags::PROTOCOL,
+        flags::DNS_NAME,
+        flags::DNSSEC,
+        flags::TIMEOUT,
+    ];
+
+    /// The keys of the plugin config (`$env.config.plugins.dns`): the flags
+    /// that have a default there. Any other key is reported as unknown.
+    pub const KEYS: &[&str] = &[
+        // where and how to ask
+        flags::SERVER,
+        flags::PROTOCOL,
+        flags::DNS_NAME,
+        flags::IPV4,
+        flags::IPV6,
+        flags::TIMEOUT,
+        flags::ATTEMPTS,
+        flags::ROTATE,
+        flags::TASKS,
+        flags::RATE,
+        flags::DEADLINE,
+        // what to ask
+        flags::TYPE,
+        flags::CLASS,
+        flags::DNSSEC,
+        flags::DNSSEC_OK,
+        flags::CD,
+        flags::ID,
+        flags::EDNS_OPT,
+        flags::RANDOMIZE_CASE,
+        flags::TSIG_KEY,
+        flags::TSIG_SECRET,
+        flags::TSIG_ALGORITHM,
+        flags::TRUST_ANCHORS,
+        flags::NO_AUTO_PTR,
+        flags::FQDN,
+        flags::STRICT_SPECIAL_USE,
+        // how to take the response
+        flags::IGNORE_TC,
+        flags::PARTIAL,
+        flags::LENIENT,
+        flags::FAIL_ON_NXDOMAIN,
+        flags::ERRORS_AS_VALUES,
+        flags::RCODE_FILTER,
+        // output format
+        flags::OUTPUT_SCHEMA,
+        flags::COMPACT,
+        flags::CODE,
+        flags::NO_ROOT_DOT,
+        flags::WITH_QUESTION,
+        flags::WITH_WIRE,
+        flags::SORT,
+        flags::DEDUP,
+        flags::GROUP_RRSETS,
+        flags::SCALAR,
+        flags::SEPARATOR,
+        // caching and logging
+        flags::CACHE,
+        flags::NO_CACHE,
+        flags::MIN_TTL,
+        flags::MAX_TTL,
+        flags::CACHE_FILE,
+        flags::LOG_FILE,
+        flags::LOG_LEVEL,
+        flags::VERBOSE,
+        flags::DEBUG,
+    ];
+
+    /// Name of the environment variable that stands in for a flag, e.g.
+    /// `NU_PLUGIN_DNS_DNS_NAME` for `--dns-name`.
+    pub fn env_var(flag: &str) -> String {
+        format!("NU_PLUGIN_DNS_{}", flag.to_uppercase().replace('-', "_"))
     }
 
     pub fn default_port(protocol: Protocol) -> u16 {
@@ -35,19 +330,192 @@ pub mod config {
     }
 }
 
+/// Names `dns probe` asks about.
+pub mod probe {
+    /// A name in a signed zone, for the probes that just need an answer.
+    pub const NAME: &str = "example.com.";
+
+    /// A name whose DNSSEC signatures are deliberately broken.
+    pub const BOGUS_NAME: &str = "dnssec-failed.org.";
+
+    /// A name that answers differently to resolvers that minimize QNAMEs.
+    pub const QNAME_MIN_NAME: &str = "qnamemintest.internet.nl.";
+}
+
+/// Well known underscore names `dns scan services` looks for under a domain,
+/// with the type of record they are published as and what they are for.
+pub mod scan {
+    use hickory_proto::rr::RecordType;
+
+    pub const SERVICES: &[(&str, RecordType, &str)] = &[
+        ("_sip._tcp", RecordType::SRV, "SIP"),
+        ("_sip._udp", RecordType::SRV, "SIP"),
+        ("_sips._tcp", RecordType::SRV, "SIP over TLS"),
+        (
+            "_sipfederationtls._tcp",
+            RecordType::SRV,
+            "Skype for Business federation",
+        ),
+        ("_xmpp-client._tcp", RecordType::SRV, "XMPP clients"),
+        ("_xmpp-server._tcp", RecordType::SRV, "XMPP federation"),
+        ("_matrix._tcp", RecordType::SRV, "Matrix federation"),
+        (
+            "_autodiscover._tcp",
+            RecordType::SRV,
+            "Exchange autodiscover",
+        ),
+        ("_ldap._tcp", RecordType::SRV, "LDAP"),
+        ("_ldaps._tcp", RecordType::SRV, "LDAP over TLS"),
+        (
+            "_gc._tcp",
+            RecordType::SRV,
+            "Active Directory global catalog",
+        ),
+        ("_kerberos._tcp", RecordType::SRV, "Kerberos"),
+        ("_kerberos._udp", RecordType::SRV, "Kerberos"),
+        (
+            "_kpasswd._tcp",
+            RecordType::SRV,
+            "Kerberos password changes",
+        ),
+        (
+            "_vlmcs._tcp",
+            RecordType::SRV,
+            "Windows key management service",
+        ),
+        ("_caldav._tcp", RecordType::SRV, "CalDAV"),
+        ("_caldavs._tcp", RecordType::SRV, "CalDAV over TLS"),
+        ("_carddav._tcp", RecordType::SRV, "CardDAV"),
+        ("_carddavs._tcp", RecordType::SRV, "CardDAV over TLS"),
+        ("_imap._tcp", RecordType::SRV, "IMAP"),
+        ("_imaps._tcp", RecordType::SRV, "IMAP over TLS"),
+        ("_pop3._tcp", RecordType::SRV, "POP3"),
+        ("_pop3s._tcp", RecordType::SRV, "POP3 over TLS"),
+        ("_submission._tcp", RecordType::SRV, "mail submission"),
+        (
+            "_submissions._tcp",
+            RecordType::SRV,
+            "mail submission over TLS",
+        ),
+        ("_stun._udp", RecordType::SRV, "STUN"),
+        ("_turn._udp", RecordType::SRV, "TURN"),
+        ("_ntp._udp", RecordType::SRV, "NTP"),
+        ("_minecraft._tcp", RecordType::SRV, "Minecraft"),
+        ("_dmarc", RecordType::TXT, "DMARC policy"),
+        ("_mta-sts", RecordType::TXT, "MTA-STS policy"),
+        ("_smtp._tls", RecordType::TXT, "SMTP TLS reporting"),
+        ("default._bimi", RecordType::TXT, "BIMI logo"),
+        ("_acme-challenge", RecordType::TXT, "ACME DNS-01 challenge"),
+    ];
+}
+
+/// Names operating systems and browsers resolve to find out whether they are
+/// behind a captive portal, which `dns check captive` asks about too, with
+/// the addresses they are known to have. Only Microsoft's has fixed
+/// addresses; the others only need to be public.
+pub mod captive {
+    pub const PROBES: &[(&str, &[&str])] = &[
+        ("detectportal.firefox.com.", &[]),
+        ("connectivitycheck.gstatic.com.", &[]),
+        ("captive.apple.com.", &[]),
+        ("www.msftconnecttest.com.", &[]),
+        (
+            "dns.msftncsi.com.",
+            &["131.107.255.255", "fd3e:4f5a:5b81::1"],
+        ),
+    ];
+}
+
+/// Names `dns doctor` asks about: one that should always resolve, one that is
+/// signed with DNSSEC, and one whose signatures are broken on purpose, which
+/// validating resolvers refuse to answer for.
+pub mod doctor {
+    pub const KNOWN_GOOD: &str = "example.com.";
+    pub const SIGNED: &str = "isc.org.";
+    pub const BOGUS: &str = "dnssec-failed.org.";
+}
+
+/// The test `dns check fragmentation` makes: the name whose signed DNSKEY
+/// records it asks for, and the EDNS buffer sizes it advertises in turn.
+pub mod fragmentation {
+    pub const NAME: &str = ".";
+    pub const BUFSIZES: &[u16] = &[512, 1232, 1432, 2048, 4096];
+}
+
+/// Services that answer a question with the address it came from, for
+/// finding the host's public addresses: the service, the name to ask, its
+/// type and class, and the server to ask over IPv4 and over IPv6. Services
+/// that answer with an A record answer with an AAAA record when asked over
+/// IPv6.
+pub mod myip {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use hickory_proto::rr::{DNSClass, RecordType};
+
+    pub const SERVICES: &[(&str, &str, RecordType, DNSClass, IpAddr, IpAddr)] = &[
+        (
+            "cloudflare",
+            "whoami.cloudflare.",
+            RecordType::TXT,
+            DNSClass::CH,
+            IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+            IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111)),
+        ),
+        (
+            "google",
+            "o-o.myaddr.l.google.com.",
+            RecordType::TXT,
+            DNSClass::IN,
+            // ns1.google.com
+            IpAddr::V4(Ipv4Addr::new(216, 239, 32, 10)),
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0x4860, 0x4802, 0x32, 0, 0, 0, 0xa)),
+        ),
+        (
+            "opendns",
+            "myip.opendns.com.",
+            RecordType::A,
+            DNSClass::IN,
+            IpAddr::V4(Ipv4Addr::new(208, 67, 222, 222)),
+            IpAddr::V6(Ipv6Addr::new(0x2620, 0x119, 0x35, 0, 0, 0, 0, 0x35)),
+        ),
+    ];
+}
+
+/// Codes of the EDNS options that hickory doesn't decode itself.
+pub mod edns {
+    pub const NSID: u16 = 3;
+    pub const EXPIRE: u16 = 9;
+    pub const COOKIE: u16 = 10;
+    pub const KEEPALIVE: u16 = 11;
+    pub const PADDING: u16 = 12;
+    pub const EDE: u16 = 15;
+}
+
 pub mod columns {
     pub const NAME: &str = "name";
     pub const TYPE: &str = "type";
     pub const CLASS: &str = "class";
+    pub const SERVER: &str = "server";
 
     pub const MESSAGE_COLS: &[&str] = &[
         "header",
+        "id",
+        "rcode",
+        "opcode",
         "question",
         "answer",
         "authority",
         "additional",
         "edns",
         "size",
+        "server",
+        "negative",
+        "negative_ttl",
+        "tcp_retry",
+        "cached",
+        "case_match",
+        "minimal_any",
+        "warning",
     ];
 
     pub const HEADER_COLS: &[&str] = &[
@@ -66,6 +534,19 @@ pub mod columns {
         "additional_count",
     ];
     pub const QUERY_COLS: &[&str] = &["name", "type", "class"];
-    pub const RECORD_COLS: &[&str] = &["name", "type", "class", "ttl", "rdata"];
+    pub const RECORD_COLS: &[&str] = &["name", "type", "class", "ttl", "rdata", "raw"];
+
+    /// The columns of messages and records as of 3.0.7, which
+    /// `--output-schema v1` keeps to.
+    pub const MESSAGE_COLS_V1: &[&str] = &[
+        "header",
+        "question",
+        "answer",
+        "authority",
+        "additional",
+        "edns",
+        "size",
+    ];
+    pub const RECORD_COLS_V1: &[&str] = &["name", "type", "class", "ttl", "rdata"];
     pub const CODE_COLS: &[&str] = &["name", "code"];
 }
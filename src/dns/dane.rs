@@ -0,0 +1,283 @@
+//! Just enough X.509 to make TLSA records (RFC 6698): reading certificates
+//! from PEM or DER, finding a certificate's public key, and fetching the
+//! chain a TLS server presents.
+
+use std::{net::IpAddr, sync::Arc, time::Duration, time::SystemTime};
+
+use hickory_proto::rr::{
+    dnssec::DigestType,
+    rdata::tlsa::{CertUsage, Matching, Selector},
+};
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use tokio::net::TcpStream;
+
+/// Reads the certificates of a PEM file, in the order they appear, or the
+/// single certificate of a DER file.
+pub fn read_certificates(contents: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let Ok(text) = std::str::from_utf8(contents) else {
+        return Ok(vec![contents.to_vec()]);
+    };
+    if !text.contains("-----BEGIN") {
+        return Ok(vec![contents.to_vec()]);
+    }
+
+    let mut certs = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(BEGIN) {
+        let body = &rest[start + BEGIN.len()..];
+        let end = body
+            .find(END)
+            .ok_or("certificate is missing its END line")?;
+        let base64: String = body[..end].split_whitespace().collect();
+        certs.push(
+            data_encoding::BASE64
+                .decode(base64.as_bytes())
+                .map_err(|err| format!("invalid base64 in certificate: {err}"))?,
+        );
+        rest = &body[end + END.len()..];
+    }
+
+    match certs.is_empty() {
+        true => Err("no certificates found; expected PEM CERTIFICATE blocks or DER".into()),
+        false => Ok(certs),
+    }
+}
+
+/// A DER element, with what follows it.
+struct Der<'a> {
+    tag: u8,
+    contents: &'a [u8],
+    whole: &'a [u8],
+    rest: &'a [u8],
+}
+
+/// Splits the first DER element off of `input`.
+fn der_element(input: &[u8]) -> Option<Der<'_>> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (len, rest) = match first {
+        len @ 0..=0x7f => (len as usize, rest),
+        0x81..=0x84 => {
+            let octets = (first & 0x7f) as usize;
+            if rest.len() < octets {
+                return None;
+            }
+            let len = rest[..octets]
+                .iter()
+                .fold(0usize, |len, octet| (len << 8) | *octet as usize);
+            (len, &rest[octets..])
+        }
+        _ => return None,
+    };
+
+    if rest.len() < len {
+        return None;
+    }
+    let header = input.len() - rest.len();
+
+    Some(Der {
+        tag,
+        contents: &rest[..len],
+        whole: &input[..header + len],
+        rest: &rest[len..],
+    })
+}
+
+/// The DER SubjectPublicKeyInfo of a certificate, which TLSA selector 1
+/// matches against (RFC 5280 section 4.1).
+pub fn subject_public_key_info(cert: &[u8]) -> Result<&[u8], String> {
+    const SEQUENCE: u8 = 0x30;
+    const VERSION: u8 = 0xa0;
+
+    let invalid = || "not a DER X.509 certificate".to_string();
+    let sequence = |input| {
+        der_element(input)
+            .filter(|der| der.tag == SEQUENCE)
+            .ok_or_else(invalid)
+    };
+
+    let cert = sequence(cert)?;
+    let mut tbs = sequence(cert.contents)?.contents;
+
+    // the version is optional; then come the serial number, signature
+    // algorithm, issuer, validity, and subject before the key
+    if let Some(der) = der_element(tbs).filter(|der| der.tag == VERSION) {
+        tbs = der.rest;
+    }
+    for _ in 0..5 {
+        tbs = der_element(tbs).ok_or_else(invalid)?.rest;
+    }
+
+    Ok(sequence(tbs)?.whole)
+}
+
+/// Which certificate of a chain, end entity first, a TLSA record of the
+/// given usage is about: the end entity's own for usages 1 and 3, and its
+/// issuer's for the CA and trust anchor usages 0 and 2.
+pub fn pick_certificate(chain: &[Vec<u8>], usage: CertUsage) -> Result<&[u8], String> {
+    match usage {
+        CertUsage::CA | CertUsage::TrustAnchor => {
+            chain.get(1).map(Vec::as_slice).ok_or_else(|| {
+                "usages 0 and 2 need the issuer's certificate, which the chain lacks".into()
+            })
+        }
+        _ => chain
+            .first()
+            .map(Vec::as_slice)
+            .ok_or_else(|| "no certificate".into()),
+    }
+}
+
+/// The certificate association data of a TLSA record for a certificate.
+pub fn association_data(
+    cert: &[u8],
+    selector: Selector,
+    matching: Matching,
+) -> Result<Vec<u8>, String> {
+    let selected = match selector {
+        Selector::Full => cert,
+        Selector::Spki => subject_public_key_info(cert)?,
+        selector => return Err(format!("unsupported selector {}", u8::from(selector))),
+    };
+
+    let digest_type = match matching {
+        Matching::Raw => return Ok(selected.to_vec()),
+        Matching::Sha256 => DigestType::SHA256,
+        Matching::Sha512 => DigestType::SHA512,
+        matching => return Err(format!("unsupported matching type {}", u8::from(matching))),
+    };
+
+    digest_type
+        .hash(selected)
+        .map(|digest| digest.as_ref().to_vec())
+        .map_err(|err| err.to_string())
+}
+
+/// Takes any certificate: a TLSA record is what vouches for it, not the web
+/// PKI, and nothing is sent over the connection.
+struct AcceptAny;
+
+impl ServerCertVerifier for AcceptAny {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Splits `host:port`, or `[v6 address]:port`, with the port defaulting to
+/// 443.
+pub fn host_port(endpoint: &str) -> Result<(String, u16), String> {
+    let (host, port) = match endpoint.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => (
+            host,
+            port.parse::<u16>()
+                .map_err(|err| format!("invalid port in {endpoint}: {err}"))?,
+        ),
+        _ => (endpoint, 443),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    match host.is_empty() {
+        true => Err(format!("no host in {endpoint}")),
+        false => Ok((host.into(), port)),
+    }
+}
+
+/// The certificate chain a TLS server presents, end entity first.
+pub async fn fetch_chain(host: &str, port: u16, timeout: Duration) -> Result<Vec<Vec<u8>>, String> {
+    tokio::time::timeout(timeout, handshake(host, port))
+        .await
+        .map_err(|_| format!("timed out connecting to {host}:{port}"))?
+}
+
+async fn handshake(host: &str, port: u16) -> Result<Vec<Vec<u8>>, String> {
+    let server_name = match host.parse::<IpAddr>() {
+        Ok(ip) => rustls::ServerName::IpAddress(ip),
+        Err(_) => rustls::ServerName::try_from(host)
+            .map_err(|err| format!("invalid host name {host}: {err}"))?,
+    };
+
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(AcceptAny))
+        .with_no_client_auth();
+
+    let tcp = TcpStream::connect((host, port))
+        .await
+        .map_err(|err| format!("could not connect to {host}:{port}: {err}"))?;
+    let tls = tokio_rustls::TlsConnector::from(Arc::new(config))
+        .connect(server_name, tcp)
+        .await
+        .map_err(|err| format!("TLS handshake with {host}:{port} failed: {err}"))?;
+
+    let (_, connection) = tls.get_ref();
+    match connection.peer_certificates() {
+        Some(certs) if !certs.is_empty() => Ok(certs.iter().map(|cert| cert.0.clone()).collect()),
+        _ => Err(format!("{host}:{port} presented no certificate")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A self-signed P-256 certificate for example.com.
+    const CERT: &str = "-----BEGIN CERTIFICATE-----
+MIIBgjCCASmgAwIBAgIUORC7IcyvjHxbj2KVFbXaC/sBfF8wCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLZXhhbXBsZS5jb20wIBcNMjYxMDE2MDMzODM3WhgPMjEyNjA5
+MjIwMzM4MzdaMBYxFDASBgNVBAMMC2V4YW1wbGUuY29tMFkwEwYHKoZIzj0CAQYI
+KoZIzj0DAQcDQgAEr28HzCfUG3dW40tU64qu9LlxuqUnhd6OWhwsAo1RM52f/phO
+w+woUd1aojAn2u8+kjog/GsJVqK2lviiUsVgCqNTMFEwHQYDVR0OBBYEFPPQ5/3Q
+K+xMaqEdwS8UtBni85EgMB8GA1UdIwQYMBaAFPPQ5/3QK+xMaqEdwS8UtBni85Eg
+MA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgXiaAEUyilPNEoIN1
+4cQyYKNOQ4s40velaUIzX++W8VMCIAdmi1hKmaISQsngQXm3G0P1XCCRmFEybPbX
+mUyjBdxB
+-----END CERTIFICATE-----
+";
+
+    // the expected digests are those of openssl, e.g. for the public key:
+    // openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | sha256sum
+    #[test]
+    fn association_data_digests() {
+        let chain = read_certificates(CERT.as_bytes()).unwrap();
+        assert_eq!(chain.len(), 1);
+        let cert = pick_certificate(&chain, CertUsage::DomainIssued).unwrap();
+
+        let data = |selector, matching| {
+            data_encoding::HEXLOWER.encode(&association_data(cert, selector, matching).unwrap())
+        };
+        assert_eq!(
+            data(Selector::Spki, Matching::Sha256),
+            "11df3764b12a197c5545310fa4798c556798ed648e8d25a2de6811d78a2e1c28"
+        );
+        assert_eq!(
+            data(Selector::Full, Matching::Sha256),
+            "689ae7fa50906312e62005b7ee0a2c97abbf99e3988dad36e5310bd44e8b08d9"
+        );
+        assert_eq!(
+            data(Selector::Full, Matching::Sha512),
+            "a4b714f7850fe81aa6aa79072866475ea10003a675015b34225ae30811693d19a2a55770d0f6bd0b8fcda900ee7d6a04f57fdf96dd385640d2fa500611605b8f"
+        );
+        assert_eq!(
+            data(Selector::Full, Matching::Raw),
+            data_encoding::HEXLOWER.encode(cert)
+        );
+
+        // DER reads the same as PEM
+        assert_eq!(read_certificates(cert).unwrap(), chain);
+
+        // the issuer's usages need a second certificate
+        assert!(pick_certificate(&chain, CertUsage::TrustAnchor).is_err());
+    }
+}
@@ -0,0 +1,628 @@
+//! DNSSEC key handling that works on keys exactly as published, rather than
+//! going through hickory's DNSKEY type, which drops the flags it doesn't know
+//! about and with them the right key tag.
+
+use std::cmp::Ordering;
+
+use hickory_proto::{
+    rr::{
+        dnssec::{
+            rdata::{DNSSECRData, DNSKEY},
+            tbs, Algorithm, DigestType, PublicKey, PublicKeyEnum,
+        },
+        Name, RData, Record, RecordType,
+    },
+    serialize::binary::BinEncodable,
+};
+use nu_protocol::{LabeledError, Span, Value};
+
+use super::{serde::util, serial};
+
+/// The protocol field of every DNSKEY record (RFC 4034 section 2.1.2).
+const PROTOCOL: u8 = 3;
+
+/// The RDATA of a DNSKEY or CDNSKEY record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsKey {
+    pub name: Option<String>,
+    pub flags: u16,
+    pub protocol: u8,
+    pub algorithm: u8,
+    pub public_key: Vec<u8>,
+}
+
+impl DnsKey {
+    /// The key tag, per [RFC 4034 appendix
+    /// B](https://www.rfc-editor.org/rfc/rfc4034#appendix-B), which DS and
+    /// RRSIG records refer to keys by.
+    pub fn key_tag(&self) -> u16 {
+        // RSA/MD5 keys use the low bits of the modulus instead
+        if self.algorithm == 1 {
+            let len = self.public_key.len();
+            return match len {
+                3.. => u16::from_be_bytes([self.public_key[len - 3], self.public_key[len - 2]]),
+                _ => 0,
+            };
+        }
+
+        let mut acc: u32 = 0;
+
+        for (idx, byte) in self.rdata().iter().enumerate() {
+            acc += if idx & 1 == 0 {
+                u32::from(*byte) << 8
+            } else {
+                u32::from(*byte)
+            };
+        }
+
+        acc += (acc >> 16) & 0xffff;
+        (acc & 0xffff) as u16
+    }
+
+    /// The RDATA in wire format.
+    pub fn rdata(&self) -> Vec<u8> {
+        let mut rdata = Vec::with_capacity(4 + self.public_key.len());
+        rdata.extend_from_slice(&self.flags.to_be_bytes());
+        rdata.push(self.protocol);
+        rdata.push(self.algorithm);
+        rdata.extend_from_slice(&self.public_key);
+        rdata
+    }
+
+    /// The digest of the key a DS record for it would hold, per [RFC 4034
+    /// section 5.1.4](https://www.rfc-editor.org/rfc/rfc4034#section-5.1.4),
+    /// or `None` if the digest type is not supported.
+    pub fn ds_digest(&self, owner: &Name, digest_type: DigestType) -> Option<Vec<u8>> {
+        let owner = owner.to_lowercase().to_bytes().ok()?;
+        let digest = digest_type.digest_all(&[&owner, &self.rdata()]).ok()?;
+
+        Some(digest.as_ref().to_vec())
+    }
+
+    pub fn secure_entry_point(&self) -> bool {
+        self.flags & 0x0001 != 0
+    }
+
+    /// Parses a key in presentation format, either just the RDATA, as in
+    /// `257 3 13 mdsswUyr...`, or a whole zone file line with the owner name
+    /// up front.
+    pub fn from_presentation(input: &str, span: Span) -> Result<Self, LabeledError> {
+        let invalid = |msg: String| LabeledError::new("invalid DNSKEY").with_label(msg, span);
+
+        let tokens: Vec<&str> = input.split_whitespace().collect();
+        let (name, rdata) = match tokens.iter().position(|token| {
+            token.eq_ignore_ascii_case("DNSKEY") || token.eq_ignore_ascii_case("CDNSKEY")
+        }) {
+            Some(idx) => (
+                tokens.first().map(|name| name.to_string()),
+                &tokens[idx + 1..],
+            ),
+            None => (None, &tokens[..]),
+        };
+
+        let [flags, protocol, algorithm, key @ ..] = rdata else {
+            return Err(invalid(
+                "should be flags, protocol, algorithm, and public key".into(),
+            ));
+        };
+
+        Ok(Self {
+            name,
+            flags: flags
+                .parse()
+                .map_err(|err| invalid(format!("invalid flags {flags}: {err}")))?,
+            protocol: protocol
+                .parse()
+                .map_err(|err| invalid(format!("invalid protocol {protocol}: {err}")))?,
+            algorithm: parse_algorithm(algorithm)
+                .ok_or_else(|| invalid(format!("unknown algorithm {algorithm}")))?,
+            public_key: data_encoding::BASE64
+                .decode(key.concat().as_bytes())
+                .map_err(|err| invalid(format!("public key is not valid base64: {err}")))?,
+        })
+    }
+
+    /// Reads a key from a value: a presentation format string, a record like
+    /// `dns query` returns for DNSKEY records, or a record with `flags`,
+    /// `algorithm`, and `public_key` columns.
+    pub fn try_from_value(value: &Value) -> Result<Self, LabeledError> {
+        let span = value.span();
+
+        match value {
+            Value::String { val, .. } => Self::from_presentation(val, span),
+            Value::Record { .. } => {
+                let name = util::column(value, "name")
+                    .map(|name| name.coerce_into_string())
+                    .transpose()?;
+
+                let key = match util::column(value, "rdata") {
+                    Some(Value::String { val, .. }) => Self::from_presentation(&val, span)?,
+                    Some(rdata @ Value::Record { .. }) => Self::from_columns(&rdata, span)?,
+                    Some(rdata) => {
+                        return Err(LabeledError::new("invalid DNSKEY")
+                            .with_label("rdata should be a string or a record", rdata.span()))
+                    }
+                    None => Self::from_columns(value, span)?,
+                };
+
+                Ok(Self {
+                    name: name.or(key.name),
+                    ..key
+                })
+            }
+            _ => Err(LabeledError::new("invalid DNSKEY")
+                .with_label("should be a string or a record", span)),
+        }
+    }
+
+    /// Reads a key from columns, taking flags either as a number or as the
+    /// `zone_key`, `secure_entry_point`, and `revoke` booleans that `dns
+    /// query` returns.
+    fn from_columns(value: &Value, span: Span) -> Result<Self, LabeledError> {
+        let missing = |col: &str| {
+            LabeledError::new("invalid DNSKEY").with_label(format!("missing {col} column"), span)
+        };
+        let flag = |col: &str| -> Result<bool, LabeledError> {
+            util::column(value, col).map_or(Ok(false), |val| Ok(val.as_bool()?))
+        };
+
+        let flags = match util::column(value, "flags") {
+            Some(flags) => u16::try_from(flags.as_int()?).map_err(|err| {
+                LabeledError::new("invalid DNSKEY")
+                    .with_label(format!("invalid flags: {err}"), flags.span())
+            })?,
+            None => {
+                (u16::from(flag("zone_key")?) << 8)
+                    | (u16::from(flag("revoke")?) << 7)
+                    | u16::from(flag("secure_entry_point")?)
+            }
+        };
+
+        let protocol = match util::column(value, "protocol") {
+            Some(protocol) => u8::try_from(protocol.as_int()?).map_err(|err| {
+                LabeledError::new("invalid DNSKEY")
+                    .with_label(format!("invalid protocol: {err}"), protocol.span())
+            })?,
+            None => PROTOCOL,
+        };
+
+        let algorithm = util::column(value, "algorithm").ok_or_else(|| missing("algorithm"))?;
+        let algorithm = parse_algorithm(&algorithm.coerce_str()?).ok_or_else(|| {
+            LabeledError::new("invalid DNSKEY").with_label("unknown algorithm", algorithm.span())
+        })?;
+
+        let public_key = util::column(value, "public_key").ok_or_else(|| missing("public_key"))?;
+        let public_key = match &public_key {
+            Value::Binary { val, .. } => val.clone(),
+            val => data_encoding::BASE64
+                .decode(val.coerce_str()?.as_bytes())
+                .map_err(|err| {
+                    LabeledError::new("invalid DNSKEY")
+                        .with_label(format!("public key is not valid base64: {err}"), val.span())
+                })?,
+        };
+
+        Ok(Self {
+            name: None,
+            flags,
+            protocol,
+            algorithm,
+            public_key,
+        })
+    }
+}
+
+impl From<&DNSKEY> for DnsKey {
+    fn from(key: &DNSKEY) -> Self {
+        Self {
+            name: None,
+            flags: key.flags(),
+            protocol: PROTOCOL,
+            algorithm: key.algorithm().into(),
+            public_key: key.public_key().to_vec(),
+        }
+    }
+}
+
+/// Parses an algorithm given by number or by mnemonic, e.g. `13` or
+/// `ECDSAP256SHA256`.
+pub fn parse_algorithm(input: &str) -> Option<u8> {
+    if let Ok(num) = input.parse() {
+        return Some(num);
+    }
+
+    (0..=u8::MAX).find(|num| match Algorithm::from_u8(*num) {
+        Algorithm::Unknown(_) => false,
+        known => known.as_str().eq_ignore_ascii_case(input),
+    })
+}
+
+/// A root key as listed in IANA's trust anchor file (RFC 7958): the DS digest
+/// of the key, and when it is meant to be trusted.
+#[derive(Clone, Debug)]
+pub struct AnchorDigest {
+    pub id: String,
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    pub digest: Vec<u8>,
+    pub valid_from: Option<chrono::DateTime<chrono::FixedOffset>>,
+    pub valid_until: Option<chrono::DateTime<chrono::FixedOffset>>,
+}
+
+impl AnchorDigest {
+    pub fn valid_at(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.valid_from.is_none_or(|from| from <= now)
+            && self.valid_until.is_none_or(|until| now < until)
+    }
+
+    /// Whether this is the digest of the given root key.
+    pub fn matches(&self, key: &DnsKey) -> bool {
+        key.key_tag() == self.key_tag
+            && key.algorithm == self.algorithm
+            && DigestType::from_u8(self.digest_type)
+                .ok()
+                .and_then(|digest_type| key.ds_digest(&Name::root(), digest_type))
+                .is_some_and(|digest| digest == self.digest)
+    }
+}
+
+/// Checks that a zone's DNSKEY set, as found among `records` along with its
+/// RRSIGs, is signed by one of the `trusted` keys with a signature that is
+/// current at `now`, in seconds since the epoch. Returns the tag of the key
+/// that signed it.
+pub fn verify_dnskey_set(
+    zone: &Name,
+    records: &[Record],
+    trusted: &[DnsKey],
+    now: u32,
+) -> Result<u16, String> {
+    let keys: Vec<Record> = records
+        .iter()
+        .filter(|record| record.record_type() == RecordType::DNSKEY && record.name() == zone)
+        .cloned()
+        .collect();
+    if keys.is_empty() {
+        return Err(format!("no DNSKEY records for {zone}"));
+    }
+
+    let sigs = records.iter().filter_map(|record| match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::RRSIG(sig)))
+            if record.name() == zone && sig.type_covered() == RecordType::DNSKEY =>
+        {
+            Some(sig)
+        }
+        _ => None,
+    });
+
+    let mut errors = Vec::new();
+    for sig in sigs {
+        let Some(key) = trusted.iter().find(|key| {
+            key.key_tag() == sig.key_tag() && key.algorithm == u8::from(sig.algorithm())
+        }) else {
+            continue;
+        };
+
+        // signature times are compared in serial number arithmetic (RFC 4034
+        // section 3.1.5), so they keep working past 2106
+        if serial::compare(now, sig.sig_inception()) == Some(Ordering::Less) {
+            errors.push(format!(
+                "signature by key {} is not valid yet",
+                sig.key_tag()
+            ));
+            continue;
+        }
+        if serial::compare(now, sig.sig_expiration()) == Some(Ordering::Greater) {
+            errors.push(format!("signature by key {} has expired", sig.key_tag()));
+            continue;
+        }
+
+        let verified =
+            tbs::rrset_tbs_with_sig(zone, keys[0].dns_class(), sig, &keys).and_then(|tbs| {
+                PublicKeyEnum::from_public_bytes(&key.public_key, sig.algorithm())?.verify(
+                    sig.algorithm(),
+                    tbs.as_ref(),
+                    sig.sig(),
+                )
+            });
+        match verified {
+            Ok(()) => return Ok(sig.key_tag()),
+            Err(err) => errors.push(format!(
+                "signature by key {} is invalid: {err}",
+                sig.key_tag()
+            )),
+        }
+    }
+
+    match errors.is_empty() {
+        true => Err(format!(
+            "the DNSKEY set of {zone} is not signed by a trusted key"
+        )),
+        false => Err(errors.join("; ")),
+    }
+}
+
+/// Parses the XML trust anchor file IANA publishes for the root zone, in the
+/// format of [RFC 7958](https://www.rfc-editor.org/rfc/rfc7958). The format
+/// is simple and fixed enough that picking out the elements by name does.
+pub fn parse_root_anchors(xml: &str) -> Result<Vec<AnchorDigest>, String> {
+    let element = |body: &str, name: &str| -> Option<String> {
+        let start = body.find(&format!("<{name}>"))? + name.len() + 2;
+        let end = start + body[start..].find(&format!("</{name}>"))?;
+        Some(body[start..end].trim().to_string())
+    };
+    let attribute = |tag: &str, name: &str| -> Option<String> {
+        let start = tag.find(&format!("{name}=\""))? + name.len() + 2;
+        let end = start + tag[start..].find('"')?;
+        Some(tag[start..end].to_string())
+    };
+    let date = |tag: &str, name: &str| {
+        attribute(tag, name)
+            .map(|date| {
+                chrono::DateTime::parse_from_rfc3339(&date)
+                    .map_err(|err| format!("invalid {name} date {date}: {err}"))
+            })
+            .transpose()
+    };
+
+    match element(xml, "Zone").as_deref() {
+        Some(".") => {}
+        Some(zone) => return Err(format!("the trust anchors are for {zone}, not the root")),
+        None => return Err("not a trust anchor file: there is no Zone element".into()),
+    }
+
+    xml.split("<KeyDigest")
+        .skip(1)
+        .map(|digest| {
+            let (tag, body) = digest
+                .split_once('>')
+                .ok_or("unterminated KeyDigest element")?;
+            let field = |name: &str| {
+                element(body, name).ok_or_else(|| format!("KeyDigest is missing its {name}"))
+            };
+            let number = |name: &str| -> Result<u16, String> {
+                field(name)?
+                    .parse()
+                    .map_err(|err| format!("invalid {name}: {err}"))
+            };
+
+            Ok(AnchorDigest {
+                id: attribute(tag, "id").unwrap_or_default(),
+                key_tag: number("KeyTag")?,
+                algorithm: u8::try_from(number("Algorithm")?)
+                    .map_err(|err| format!("invalid Algorithm: {err}"))?,
+                digest_type: u8::try_from(number("DigestType")?)
+                    .map_err(|err| format!("invalid DigestType: {err}"))?,
+                digest: data_encoding::HEXLOWER_PERMISSIVE
+                    .decode(field("Digest")?.as_bytes())
+                    .map_err(|err| format!("invalid Digest: {err}"))?,
+                valid_from: date(tag, "validFrom")?,
+                valid_until: date(tag, "validUntil")?,
+            })
+        })
+        .collect()
+}
+
+/// Reads trust anchors saved by `dns dnssec update-anchors`: root keys in
+/// zone file format, one per line, with `;` comments.
+pub fn parse_anchor_file(contents: &str, span: Span) -> Result<Vec<DnsKey>, LabeledError> {
+    contents
+        .lines()
+        .map(|line| line.split(';').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| DnsKey::from_presentation(line, span))
+        .collect()
+}
+
+/// Writes trust anchors in the format [`parse_anchor_file`] reads.
+pub fn format_anchor_file(keys: &[DnsKey], source: &str) -> String {
+    let mut contents = format!(
+        "; root keys matching the trust anchors from {source}\n; written by nu_plugin_dns {}\n",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    for key in keys {
+        contents.push_str(&format!(
+            ". IN DNSKEY {} {} {} {}\n",
+            key.flags,
+            key.protocol,
+            key.algorithm,
+            data_encoding::BASE64.encode(&key.public_key)
+        ));
+    }
+
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    /// The root zone's 2017 KSK.
+    const ROOT_KSK_2017: &str = ". IN DNSKEY 257 3 8 AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=";
+
+    /// IANA's trust anchor file as published after the 2018 root KSK
+    /// rollover.
+    const ROOT_ANCHORS_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrustAnchor id="380DC50D-484E-40D0-A3AE-68F2B18F61C7" source="http://data.iana.org/root-anchors/root-anchors.xml">
+<Zone>.</Zone>
+<KeyDigest id="Kjqmt7v" validFrom="2010-07-15T00:00:00+00:00" validUntil="2019-01-11T00:00:00+00:00">
+<KeyTag>19036</KeyTag>
+<Algorithm>8</Algorithm>
+<DigestType>2</DigestType>
+<Digest>49AAC11D7B6F6446702E54A1607371607A1A41855200FD2CE1CDDE32F24E8FB5</Digest>
+</KeyDigest>
+<KeyDigest id="Klajeyz" validFrom="2017-02-02T00:00:00+00:00">
+<KeyTag>20326</KeyTag>
+<Algorithm>8</Algorithm>
+<DigestType>2</DigestType>
+<Digest>E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D</Digest>
+</KeyDigest>
+</TrustAnchor>
+"#;
+
+    fn key(presentation: &str) -> DnsKey {
+        DnsKey::from_presentation(presentation, Span::test_data()).unwrap()
+    }
+
+    #[test]
+    fn key_tag() {
+        // the key of the DS example in RFC 4034 section 5.4
+        let dskey = key("dskey.example.com. 86400 IN DNSKEY 256 3 5 \
+            AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMz \
+            NXxeYCmZDRD99WYwYqUSdjMmmAphXdvxegXd/M5+X7OrzKBaMbCVdFLUUh6DhweJ \
+            BjEVv5f2wwjM9XzcnOf+EPbtG9DMBmADjFDc2w/rljwvFw==");
+        assert_eq!(dskey.key_tag(), 60485);
+        assert_eq!(
+            dskey.ds_digest(
+                &Name::from_str("dskey.example.com.").unwrap(),
+                DigestType::SHA1
+            ),
+            Some(
+                data_encoding::HEXUPPER
+                    .decode(b"2BB183AF5F22588179A53B0A98631FAD1A292118")
+                    .unwrap()
+            ),
+        );
+
+        assert_eq!(key(ROOT_KSK_2017).key_tag(), 20326);
+
+        // RSA/MD5 keys take the tag from the modulus instead (RFC 4034
+        // appendix B.1): the two octets before the last
+        let rsamd5 = DnsKey {
+            name: None,
+            flags: 256,
+            protocol: 3,
+            algorithm: 1,
+            public_key: vec![0x01, 0x03, 0xab, 0xcd, 0x12, 0x34, 0x56],
+        };
+        assert_eq!(rsamd5.key_tag(), 0x1234);
+    }
+
+    #[test]
+    fn root_anchors() {
+        let anchors = parse_root_anchors(ROOT_ANCHORS_XML).unwrap();
+        assert_eq!(anchors.len(), 2);
+
+        let [ksk_2010, ksk_2017] = &anchors[..] else {
+            unreachable!()
+        };
+        assert_eq!(ksk_2010.id, "Kjqmt7v");
+        assert_eq!(ksk_2010.key_tag, 19036);
+        assert_eq!(ksk_2017.key_tag, 20326);
+        assert_eq!((ksk_2017.algorithm, ksk_2017.digest_type), (8, 2));
+        assert!(ksk_2017.valid_until.is_none());
+
+        let now = chrono::DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .to_utc();
+        assert!(!ksk_2010.valid_at(now));
+        assert!(ksk_2017.valid_at(now));
+
+        let root_ksk = key(ROOT_KSK_2017);
+        assert!(ksk_2017.matches(&root_ksk));
+        assert!(!ksk_2010.matches(&root_ksk));
+    }
+
+    #[test]
+    fn root_anchors_for_another_zone() {
+        let xml = ROOT_ANCHORS_XML.replace("<Zone>.</Zone>", "<Zone>example.</Zone>");
+        assert!(parse_root_anchors(&xml).unwrap_err().contains("example."));
+
+        let xml = ROOT_ANCHORS_XML.replace("<Zone>.</Zone>", "");
+        assert!(parse_root_anchors(&xml).is_err());
+    }
+
+    #[test]
+    fn dnskey_set_signature() {
+        use hickory_proto::rr::{
+            dnssec::{rdata::RRSIG, KeyFormat, KeyPair},
+            DNSClass,
+        };
+
+        let keypair = |algorithm| {
+            let pkcs8 = KeyPair::generate_pkcs8(algorithm).unwrap();
+            KeyFormat::Pkcs8
+                .decode_key(&pkcs8, None, algorithm)
+                .unwrap()
+        };
+        let dnskey = |keypair: &KeyPair<_>| {
+            let dnskey = keypair.to_dnskey(Algorithm::ED25519).unwrap();
+            let record = Record::from_rdata(
+                Name::root(),
+                3600,
+                RData::DNSSEC(DNSSECRData::DNSKEY(dnskey.clone())),
+            );
+            (DnsKey::from(&dnskey), record)
+        };
+
+        let ksk = keypair(Algorithm::ED25519);
+        let (key, record) = dnskey(&ksk);
+        let (other_key, other_record) = dnskey(&keypair(Algorithm::ED25519));
+        let set = vec![record, other_record];
+
+        // valid from 1000 to 2000
+        let unsigned = RRSIG::new(
+            RecordType::DNSKEY,
+            Algorithm::ED25519,
+            0,
+            3600,
+            2000,
+            1000,
+            key.key_tag(),
+            Name::root(),
+            Vec::new(),
+        );
+        let tbs = tbs::rrset_tbs_with_sig(&Name::root(), DNSClass::IN, &unsigned, &set).unwrap();
+        let sig = RRSIG::new(
+            RecordType::DNSKEY,
+            Algorithm::ED25519,
+            0,
+            3600,
+            2000,
+            1000,
+            key.key_tag(),
+            Name::root(),
+            ksk.sign(Algorithm::ED25519, &tbs).unwrap(),
+        );
+        let mut records = set.clone();
+        records.push(Record::from_rdata(
+            Name::root(),
+            3600,
+            RData::DNSSEC(DNSSECRData::RRSIG(sig)),
+        ));
+
+        let root = Name::root();
+        let tag = key.key_tag();
+        let trusted = [key];
+        assert_eq!(verify_dnskey_set(&root, &records, &trusted, 1500), Ok(tag));
+
+        // a key that didn't sign the set, or a signature out of its time
+        assert!(verify_dnskey_set(&root, &records, &[other_key], 1500).is_err());
+        assert!(verify_dnskey_set(&root, &records, &trusted, 500)
+            .unwrap_err()
+            .contains("not valid yet"));
+        assert!(verify_dnskey_set(&root, &records, &trusted, 2500)
+            .unwrap_err()
+            .contains("expired"));
+
+        // a set with a key slipped in after signing
+        let (_, extra) = dnskey(&keypair(Algorithm::ED25519));
+        records.push(extra);
+        assert!(verify_dnskey_set(&root, &records, &trusted, 1500)
+            .unwrap_err()
+            .contains("invalid"));
+    }
+
+    #[test]
+    fn anchor_file_round_trip() {
+        let keys = vec![key(ROOT_KSK_2017)];
+        let contents = format_anchor_file(&keys, "test");
+        assert_eq!(
+            parse_anchor_file(&contents, Span::test_data()).unwrap(),
+            keys
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::{io, net::SocketAddr};
+
+use hickory_proto::{
+    error::{ProtoError, ProtoErrorKind},
+    op::{Message, ResponseCode},
+};
+use nu_protocol::{LabeledError, Span};
+
+use super::serde::RCode;
+
+/// The ways talking to a nameserver can go wrong. Each kind of error is
+/// reported with a stable code, e.g. `dns::timeout`, so that scripts can tell
+/// them apart without matching on the message.
+#[derive(Debug)]
+pub enum DnsError {
+    /// No response arrived in time.
+    Timeout { server: SocketAddr, span: Span },
+
+    /// The server actively refused the connection.
+    ConnectionRefused { server: SocketAddr, span: Span },
+
+    /// The TLS handshake failed, e.g. because the certificate did not match
+    /// the DNS name.
+    Tls { message: String, span: Span },
+
+    /// Any other failure to connect or exchange messages.
+    Connection { message: String, span: Span },
+
+    /// The name does not exist.
+    NxDomain { name: String, span: Span },
+
+    /// The server failed to answer the question.
+    ServFail { name: String, span: Span },
+
+    /// A message could not be encoded or decoded.
+    Parse { message: String, span: Span },
+}
+
+impl DnsError {
+    /// The stable code for this kind of error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DnsError::Timeout { .. } => "dns::timeout",
+            DnsError::ConnectionRefused { .. } => "dns::connection_refused",
+            DnsError::Tls { .. } => "dns::tls",
+            DnsError::Connection { .. } => "dns::connection",
+            DnsError::NxDomain { .. } => "dns::nxdomain",
+            DnsError::ServFail { .. } => "dns::servfail",
+            DnsError::Parse { .. } => "dns::parse",
+        }
+    }
+
+    /// The kind of failure an error code stands for, broad enough for
+    /// scripts to treat each alike: the server never answered, turned the
+    /// question away, failed to answer it, couldn't be reached, or sent
+    /// something unreadable.
+    pub fn kind(code: Option<&str>) -> &'static str {
+        match code {
+            Some("dns::timeout") => "timeout",
+            Some("dns::connection_refused") => "refused",
+            Some("dns::tls" | "dns::connection") => "network",
+            Some("dns::servfail") => "servfail",
+            Some("dns::nxdomain") => "nxdomain",
+            Some("dns::parse") => "parse",
+            _ => "other",
+        }
+    }
+
+    /// Works out what kind of error the DNS library ran into.
+    pub fn from_proto(err: &ProtoError, server: SocketAddr, span: Span) -> Self {
+        match err.kind() {
+            ProtoErrorKind::Timeout => DnsError::Timeout { server, span },
+            ProtoErrorKind::Io(io_err) if io_err.kind() == io::ErrorKind::ConnectionRefused => {
+                DnsError::ConnectionRefused { server, span }
+            }
+            ProtoErrorKind::RustlsError(tls_err) => DnsError::Tls {
+                message: tls_err.to_string(),
+                span,
+            },
+            ProtoErrorKind::BadQueryCount(_)
+            | ProtoErrorKind::CharacterDataTooLong { .. }
+            | ProtoErrorKind::LabelOverlapsWithOther { .. }
+            | ProtoErrorKind::DomainNameTooLong(_)
+            | ProtoErrorKind::EdnsNameNotRoot(_)
+            | ProtoErrorKind::FormError { .. }
+            | ProtoErrorKind::IncorrectRDataLengthRead { .. }
+            | ProtoErrorKind::LabelBytesTooLong(_)
+            | ProtoErrorKind::PointerNotPriorToLabel { .. }
+            | ProtoErrorKind::MaxBufferSizeExceeded(_)
+            | ProtoErrorKind::UnknownAlgorithmTypeValue(_)
+            | ProtoErrorKind::UnknownDnsClassValue(_)
+            | ProtoErrorKind::UnknownRecordTypeValue(_)
+            | ProtoErrorKind::UnrecognizedLabelCode(_) => DnsError::Parse {
+                message: err.to_string(),
+                span,
+            },
+            _ => DnsError::Connection {
+                message: err.to_string(),
+                span,
+            },
+        }
+    }
+
+    /// The error a response stands for, if its response code is one that
+    /// means the question could not be answered.
+    pub fn from_response(message: &Message, span: Span) -> Option<Self> {
+        let name = message
+            .queries()
+            .first()
+            .map(|query| query.name().to_utf8())
+            .unwrap_or_default();
+
+        match message.response_code() {
+            ResponseCode::NXDomain => Some(DnsError::NxDomain { name, span }),
+            ResponseCode::ServFail => Some(DnsError::ServFail { name, span }),
+            _ => None,
+        }
+    }
+}
+
+impl From<DnsError> for LabeledError {
+    fn from(err: DnsError) -> Self {
+        let code = err.code();
+
+        let (msg, label, span) = match err {
+            DnsError::Timeout { server, span } => {
+                ("timed out", format!("request to {server} timed out"), span)
+            }
+            DnsError::ConnectionRefused { server, span } => (
+                "connection refused",
+                format!("{server} refused the connection"),
+                span,
+            ),
+            DnsError::Tls { message, span } => ("TLS error", message, span),
+            DnsError::Connection { message, span } => ("connection error", message, span),
+            DnsError::NxDomain { name, span } => (
+                "name does not exist",
+                format!("{name}: {}", RCode(ResponseCode::NXDomain).mnemonic()),
+                span,
+            ),
+            DnsError::ServFail { name, span } => (
+                "server failure",
+                format!("{name}: {}", RCode(ResponseCode::ServFail).mnemonic()),
+                span,
+            ),
+            DnsError::Parse { message, span } => ("parse error", message, span),
+        };
+
+        LabeledError::new(msg)
+            .with_label(label, span)
+            .with_code(code)
+    }
+}
@@ -0,0 +1,95 @@
+//! How each server fared when a command asks many of them, summed up so that
+//! the ones that didn't answer, or answered slowly, stand out rather than
+//! being scattered through the rows as errors.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use nu_protocol::{record, Span, Value};
+
+use super::serde::util;
+
+#[derive(Default)]
+struct ServerHealth {
+    sent: usize,
+    rtts: Vec<Duration>,
+    /// How many times each kind of failure happened, by the kinds of
+    /// [`DnsError::kind`](super::error::DnsError::kind).
+    failures: BTreeMap<&'static str, usize>,
+}
+
+/// The outcomes of the queries sent to each server, in the order the servers
+/// were first asked.
+#[derive(Default)]
+pub struct Health {
+    servers: Vec<(String, ServerHealth)>,
+}
+
+impl Health {
+    fn server(&mut self, server: &str) -> &mut ServerHealth {
+        let index = match self.servers.iter().position(|(name, _)| name == server) {
+            Some(index) => index,
+            None => {
+                self.servers.push((server.into(), ServerHealth::default()));
+                self.servers.len() - 1
+            }
+        };
+
+        &mut self.servers[index].1
+    }
+
+    pub fn answered(&mut self, server: &str, rtt: Duration) {
+        let health = self.server(server);
+        health.sent += 1;
+        health.rtts.push(rtt);
+    }
+
+    pub fn failed(&mut self, server: &str, kind: &'static str) {
+        let health = self.server(server);
+        health.sent += 1;
+        *health.failures.entry(kind).or_default() += 1;
+    }
+
+    /// A row for each server that never answered, failed some of the time,
+    /// or took longer than `slow` on average to answer. Servers that did
+    /// fine are left out, so an empty list means all of them did.
+    pub fn summary(&self, slow: Duration) -> Value {
+        let rows = self
+            .servers
+            .iter()
+            .filter_map(|(server, health)| {
+                let avg = (!health.rtts.is_empty())
+                    .then(|| health.rtts.iter().sum::<Duration>() / health.rtts.len() as u32);
+
+                let status = match avg {
+                    None => "unreachable",
+                    Some(_) if !health.failures.is_empty() => "degraded",
+                    Some(avg) if avg > slow => "slow",
+                    Some(_) => return None,
+                };
+
+                Some(Value::record(
+                    record![
+                        "server"   => Value::string(server, Span::unknown()),
+                        "status"   => Value::string(status, Span::unknown()),
+                        "sent"     => Value::int(health.sent as i64, Span::unknown()),
+                        "answered" => Value::int(health.rtts.len() as i64, Span::unknown()),
+                        "failures" => Value::record(
+                            health
+                                .failures
+                                .iter()
+                                .map(|(kind, count)| {
+                                    ((*kind).into(), Value::int(*count as i64, Span::unknown()))
+                                })
+                                .collect(),
+                            Span::unknown(),
+                        ),
+                        "avg_rtt"  => avg.map_or(Value::nothing(Span::unknown()), util::std_to_duration),
+                    ],
+                    Span::unknown(),
+                ))
+            })
+            .collect();
+
+        Value::list(rows, Span::unknown())
+    }
+}
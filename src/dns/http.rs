@@ -0,0 +1,111 @@
+//! Just enough of an HTTPS client to download a file, for the few things, like
+//! the root trust anchors, that are only published over the web. It speaks
+//! HTTP/2 through h2, the same stack DNS over HTTPS uses, so the end of the
+//! body is framed rather than guessed from the connection closing.
+
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use http::{header, Request, StatusCode, Uri};
+use tokio::net::TcpStream;
+
+use super::client;
+
+/// Downloads the body of an `https://` URL, failing on anything but a 200
+/// response, or a body that ends before its Content-Length says it should.
+pub async fn get(url: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+    tokio::time::timeout(timeout, fetch(url))
+        .await
+        .map_err(|_| format!("timed out fetching {url}"))?
+}
+
+async fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|err| format!("invalid URL {url}: {err}"))?;
+    if uri.scheme_str() != Some("https") {
+        return Err(format!("only https URLs are supported: {url}"));
+    }
+
+    // IPv6 literals keep their brackets in the URL's host
+    let host = uri
+        .host()
+        .ok_or_else(|| format!("no host in {url}"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = uri.port_u16().unwrap_or(443);
+
+    let server_name = match host.parse::<IpAddr>() {
+        Ok(ip) => rustls::ServerName::IpAddress(ip),
+        Err(_) => rustls::ServerName::try_from(host)
+            .map_err(|err| format!("invalid host name {host}: {err}"))?,
+    };
+
+    let mut tls_config = client::tls_client_config();
+    tls_config.alpn_protocols = vec![b"h2".to_vec()];
+
+    let tcp = TcpStream::connect((host, port))
+        .await
+        .map_err(|err| format!("could not connect to {host}: {err}"))?;
+    let tls = tokio_rustls::TlsConnector::from(Arc::new(tls_config))
+        .connect(server_name, tcp)
+        .await
+        .map_err(|err| format!("TLS handshake with {host} failed: {err}"))?;
+
+    if tls.get_ref().1.alpn_protocol() != Some(b"h2") {
+        return Err(format!("{host} does not support HTTP/2"));
+    }
+
+    let (mut sender, connection) = h2::client::handshake(tls)
+        .await
+        .map_err(|err| format!("HTTP/2 handshake with {host} failed: {err}"))?;
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            tracing::debug!(http.error = %err, "HTTP/2 connection closed");
+        }
+    });
+
+    let request = Request::get(uri.clone())
+        .header(
+            header::USER_AGENT,
+            concat!("nu_plugin_dns/", env!("CARGO_PKG_VERSION")),
+        )
+        .body(())
+        .map_err(|err| format!("invalid request for {url}: {err}"))?;
+    let (response, _) = sender
+        .send_request(request, true)
+        .map_err(|err| format!("could not send request to {host}: {err}"))?;
+    let response = response
+        .await
+        .map_err(|err| format!("could not read response from {host}: {err}"))?;
+
+    if response.status() != StatusCode::OK {
+        return Err(format!("{url} returned {}", response.status()));
+    }
+
+    let content_length = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .map(|len| {
+            len.to_str()
+                .ok()
+                .and_then(|len| len.parse::<usize>().ok())
+                .ok_or_else(|| format!("invalid Content-Length from {host}"))
+        })
+        .transpose()?;
+
+    let mut body = response.into_body();
+    let mut contents = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|err| format!("could not read response from {host}: {err}"))?;
+        let _ = body.flow_control().release_capacity(chunk.len());
+        contents.extend_from_slice(&chunk);
+    }
+
+    match content_length {
+        Some(len) if len != contents.len() => Err(format!(
+            "response from {host} was {} bytes, but its Content-Length is {len}",
+            contents.len()
+        )),
+        _ => Ok(contents),
+    }
+}
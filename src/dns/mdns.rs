@@ -0,0 +1,299 @@
+//! Just enough multicast DNS (RFC 6762) and DNS-based service discovery
+//! (RFC 6763) to browse the services on the local link: the query to send,
+//! the service instances in what comes back, and the interface to use.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use hickory_proto::{
+    error::ProtoError,
+    op::{Message, MessageType, OpCode, Query},
+    rr::{Name, RData, RecordType},
+};
+
+/// The domain that multicast DNS names are under.
+pub const DOMAIN: &str = "local.";
+
+/// The name whose PTR records list the service types on the link, rather
+/// than the instances of one (RFC 6763 section 9).
+pub const SERVICE_TYPES: &str = "_services._dns-sd._udp.local.";
+
+/// The name to browse for a service type given as e.g. `_http._tcp`, which
+/// is under `local.` whether or not it was given with it.
+pub fn service_name(service: &str) -> Result<Name, String> {
+    let mut name =
+        Name::from_utf8(service).map_err(|err| format!("invalid service type {service}: {err}"))?;
+    let local = Name::from_ascii(DOMAIN).expect("local. should be a valid name");
+
+    match local.zone_of(&name) {
+        true => {
+            name.set_fqdn(true);
+            Ok(name)
+        }
+        false => name
+            .append_domain(&local)
+            .map_err(|err| format!("invalid service type {service}: {err}")),
+    }
+}
+
+/// A query for the instances of a service, or for the service types when
+/// `service` is [`SERVICE_TYPES`]. It is sent from an ordinary port, so
+/// responders answer it with unicast as well (RFC 6762 section 6.7).
+pub fn query(service: &Name) -> Result<Vec<u8>, ProtoError> {
+    let mut message = Message::new();
+    message
+        .add_query(Query::query(service.clone(), RecordType::PTR))
+        .set_id(0)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(false);
+
+    message.to_vec()
+}
+
+/// A service instance that a response points to, with what the response
+/// says about where to reach it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instance {
+    pub name: Name,
+    /// The instance part of the name, e.g. `Living Room` of
+    /// `Living Room._airplay._tcp.local.`, which may have spaces and dots in
+    /// it. For service types, it is the type, e.g. `_http._tcp`.
+    pub label: String,
+    /// 0 for a goodbye, which says the instance is gone (RFC 6762 section
+    /// 10.1).
+    pub ttl: u32,
+    pub host: Option<Name>,
+    pub port: Option<u16>,
+    pub txt: Vec<String>,
+    pub addrs: Vec<IpAddr>,
+}
+
+/// The instances of a service that the PTR records of a response point to.
+/// Responders put the SRV, TXT, and address records of each in the same
+/// response (RFC 6763 section 12), so those are filled in where present.
+pub fn instances(message: &Message, service: &Name) -> Vec<Instance> {
+    let records: Vec<_> = message
+        .answers()
+        .iter()
+        .chain(message.additionals())
+        .collect();
+    let local = Name::from_ascii(DOMAIN).expect("local. should be a valid name");
+
+    records
+        .iter()
+        .filter(|record| record.name() == service)
+        .filter_map(|record| match record.data() {
+            Some(RData::PTR(ptr)) => Some((ptr.0.clone(), record.ttl())),
+            _ => None,
+        })
+        .map(|(name, ttl)| {
+            // an instance is named under its service type, and a service type
+            // under local.
+            let parent = match service.zone_of(&name) {
+                true => service,
+                false => &local,
+            };
+            let label = name
+                .iter()
+                .take(name.num_labels().saturating_sub(parent.num_labels()) as usize)
+                .map(String::from_utf8_lossy)
+                .collect::<Vec<_>>()
+                .join(".");
+
+            let mut instance = Instance {
+                name,
+                label,
+                ttl,
+                host: None,
+                port: None,
+                txt: Vec::new(),
+                addrs: Vec::new(),
+            };
+
+            for record in records
+                .iter()
+                .filter(|record| record.name() == &instance.name)
+            {
+                match record.data() {
+                    Some(RData::SRV(srv)) => {
+                        instance.host = Some(srv.target().clone());
+                        instance.port = Some(srv.port());
+                    }
+                    Some(RData::TXT(txt)) => instance.txt.extend(
+                        txt.iter()
+                            .filter(|string| !string.is_empty())
+                            .map(|string| String::from_utf8_lossy(string).into_owned()),
+                    ),
+                    _ => {}
+                }
+            }
+
+            if let Some(host) = &instance.host {
+                for record in records.iter().filter(|record| record.name() == host) {
+                    let addr = match record.data() {
+                        Some(RData::A(a)) => IpAddr::V4(a.0),
+                        Some(RData::AAAA(aaaa)) => IpAddr::V6(aaaa.0),
+                        _ => continue,
+                    };
+                    if !instance.addrs.contains(&addr) {
+                        instance.addrs.push(addr);
+                    }
+                }
+            }
+
+            instance
+        })
+        .collect()
+}
+
+/// The IPv4 address of an interface, by index, which IPv4 multicast picks
+/// interfaces by.
+#[cfg(unix)]
+pub fn interface_ipv4(index: u32) -> Result<Ipv4Addr, String> {
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+
+    // SAFETY: getifaddrs fills in a list it allocated, which is freed below
+    // once it has been read, and nothing from it is kept
+    unsafe {
+        if libc::getifaddrs(&mut addrs) != 0 {
+            return Err(format!(
+                "could not list interfaces: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        let mut found = None;
+        let mut cursor = addrs;
+        while let Some(ifaddr) = cursor.as_ref() {
+            cursor = ifaddr.ifa_next;
+
+            let addr = ifaddr.ifa_addr;
+            if addr.is_null()
+                || i32::from((*addr).sa_family) != libc::AF_INET
+                || libc::if_nametoindex(ifaddr.ifa_name) != index
+            {
+                continue;
+            }
+
+            let addr = &*(addr as *const libc::sockaddr_in);
+            found = Some(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)));
+            break;
+        }
+
+        libc::freeifaddrs(addrs);
+        found.ok_or_else(|| format!("interface {index} has no IPv4 address"))
+    }
+}
+
+#[cfg(not(unix))]
+pub fn interface_ipv4(index: u32) -> Result<Ipv4Addr, String> {
+    Err(format!(
+        "give the IPv4 address of interface {index} rather than its number"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::rr::{
+        rdata::{A, PTR, SRV, TXT},
+        Record,
+    };
+
+    use super::*;
+
+    fn name(name: &str) -> Name {
+        Name::from_utf8(name).unwrap()
+    }
+
+    /// Instance names are free-form UTF-8, spaces and case and all, as they
+    /// come off the wire, rather than host names to be normalized.
+    fn http_instance(label: &str) -> Name {
+        Name::from_labels([label.as_bytes(), b"_http", b"_tcp", b"local"]).unwrap()
+    }
+
+    #[test]
+    fn service_names() {
+        assert_eq!(
+            service_name("_http._tcp").unwrap(),
+            name("_http._tcp.local.")
+        );
+        assert_eq!(
+            service_name("_http._tcp.local").unwrap(),
+            name("_http._tcp.local.")
+        );
+        assert!(service_name("_http..tcp").is_err());
+    }
+
+    #[test]
+    fn instances_with_their_records() {
+        let service = name("_http._tcp.local.");
+        let instance = http_instance("Living Room");
+        let host = name("printer.local.");
+
+        let mut message = Message::new();
+        message
+            .set_message_type(MessageType::Response)
+            .add_answer(Record::from_rdata(
+                service.clone(),
+                4500,
+                RData::PTR(PTR(instance.clone())),
+            ))
+            // a goodbye for another instance
+            .add_answer(Record::from_rdata(
+                service.clone(),
+                0,
+                RData::PTR(PTR(http_instance("Kitchen"))),
+            ))
+            // records of a service that wasn't asked about
+            .add_answer(Record::from_rdata(
+                name("_ipp._tcp.local."),
+                4500,
+                RData::PTR(PTR(name("Printer._ipp._tcp.local."))),
+            ))
+            .add_additional(Record::from_rdata(
+                instance.clone(),
+                120,
+                RData::SRV(SRV::new(0, 0, 8080, host.clone())),
+            ))
+            .add_additional(Record::from_rdata(
+                instance.clone(),
+                4500,
+                RData::TXT(TXT::new(vec!["path=/".into(), "".into()])),
+            ))
+            .add_additional(Record::from_rdata(
+                host.clone(),
+                120,
+                RData::A(A::new(192, 0, 2, 7)),
+            ));
+
+        let found = instances(&message, &service);
+        assert_eq!(found.len(), 2);
+
+        let living_room = &found[0];
+        assert_eq!(living_room.label, "Living Room");
+        assert_eq!(living_room.ttl, 4500);
+        assert_eq!(living_room.host, Some(host));
+        assert_eq!(living_room.port, Some(8080));
+        assert_eq!(living_room.txt, ["path=/"]);
+        assert_eq!(living_room.addrs, [IpAddr::from([192, 0, 2, 7])]);
+
+        let kitchen = &found[1];
+        assert_eq!((kitchen.label.as_str(), kitchen.ttl), ("Kitchen", 0));
+        assert_eq!(kitchen.host, None);
+    }
+
+    #[test]
+    fn service_types() {
+        let service = name(SERVICE_TYPES);
+
+        let mut message = Message::new();
+        message.add_answer(Record::from_rdata(
+            service.clone(),
+            4500,
+            RData::PTR(PTR(name("_http._tcp.local."))),
+        ));
+
+        let found = instances(&message, &service);
+        assert_eq!(found[0].label, "_http._tcp");
+    }
+}
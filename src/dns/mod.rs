@@ -1,70 +1,240 @@
-use std::sync::Arc;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex, OnceLock},
+};
 
 use futures_util::Future;
-use nu_protocol::LabeledError;
-use tokio::task::JoinSet;
+use hickory_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    TokioAsyncResolver,
+};
+use nu_protocol::{LabeledError, Signals, Span, Value};
 use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing_subscriber::{prelude::*, reload, EnvFilter, Registry};
 
-use self::{client::DnsClient, commands::query::DnsQueryPluginClient, config::Config};
+use self::{
+    cache::Cache,
+    client::{ClientPool, DnsClient},
+    commands::query::DnsQuery,
+    config::Config,
+};
 
+mod cache;
 mod client;
 mod commands;
 mod config;
 mod constants;
+mod dane;
+mod dnssec;
+pub mod error;
+mod health;
+mod http;
+mod mdns;
+mod myip;
+mod names;
+mod nat64;
+mod querylog;
+mod rate;
+mod selection;
 mod serde;
+mod serial;
+mod server;
+mod special;
+mod spf;
+mod stats;
+pub mod transport;
 #[macro_use]
 mod util;
+mod value;
+mod wire;
+mod zonemd;
+
+/// Lets the log level be changed from the plugin config after startup.
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// The log filter for the given level, or for `RUST_LOG` if none is given.
+/// Wire logging is switched on per command with `--verbose`, so its events
+/// always pass the filter.
+fn log_filter(level: Option<&str>) -> EnvFilter {
+    let filter = match level {
+        Some(level) => EnvFilter::new(level),
+        None => EnvFilter::from_default_env(),
+    };
+
+    filter.add_directive(
+        format!("{}=trace", constants::WIRE_LOG_TARGET)
+            .parse()
+            .expect("wire log directive should be valid"),
+    )
+}
+
+/// Changes the log level, or goes back to `RUST_LOG` if none is given.
+pub fn set_log_level(level: Option<&str>) {
+    if let Some(handle) = LOG_FILTER.get() {
+        if let Err(err) = handle.reload(log_filter(level)) {
+            tracing::warn!(?err, "could not change log level");
+        }
+    }
+}
+
+/// How often to check whether the user has interrupted a command.
+const INTERRUPT_POLL: std::time::Duration = std::time::Duration::from_millis(100);
 
 pub struct Dns {
     runtime: tokio::runtime::Runtime,
     tasks: TaskTracker,
     cancel: CancellationToken,
-    client: DnsQueryPluginClient,
+    pool: Arc<ClientPool>,
+    cache: Arc<Cache>,
+    servers: Mutex<Vec<(SocketAddr, CancellationToken)>>,
+    resolvers: Mutex<Vec<(ResolverConfig, ResolverOpts, TokioAsyncResolver)>>,
 }
 
 impl Dns {
     pub fn new() -> Self {
+        Self::with_pool(ClientPool::new())
+    }
+
+    /// A plugin that sends every query over the given transport instead of
+    /// the network, e.g. a [`transport::MockTransport`] in tests.
+    pub fn with_transport(transport: Arc<dyn transport::Transport>) -> Self {
+        Self::with_pool(ClientPool::with_transport(transport))
+    }
+
+    fn with_pool(pool: ClientPool) -> Self {
+        let (filter, handle) = reload::Layer::new(log_filter(None));
+
+        if tracing_subscriber::registry()
+            .with(filter)
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .try_init()
+            .is_ok()
+        {
+            let _ = LOG_FILTER.set(handle);
+        }
+
         Self {
             runtime: tokio::runtime::Runtime::new().unwrap(),
             tasks: TaskTracker::new(),
             cancel: CancellationToken::new(),
-            client: Arc::new(tokio::sync::RwLock::new(None)),
+            pool: Arc::new(pool),
+            cache: Arc::new(Cache::new()),
+            servers: Mutex::new(Vec::new()),
+            resolvers: Mutex::new(Vec::new()),
         }
     }
 
+    /// Runs `dns query` on the input outside of Nushell. The flags are given
+    /// as a record, the same way as in the plugin config. Returns the list of
+    /// responses, or the first error.
+    pub fn query(&self, input: Value, flags: Value) -> Result<Value, LabeledError> {
+        let config = Arc::new(Config::try_from(flags)?);
+
+        let values = self.runtime.block_on(DnsQuery::query(
+            config,
+            input,
+            self.pool.clone(),
+            self.cache.clone(),
+            CancellationToken::new(),
+        ));
+
+        Ok(Value::list(
+            values.into_iter().collect::<Result<Vec<_>, _>>()?,
+            Span::unknown(),
+        ))
+    }
+
     pub async fn dns_client(&self, config: &Config) -> Result<DnsClient, LabeledError> {
-        // we could use OnceLock once get_or_try_init is stable
-        if let Some((client, _)) = &*self.client.read().await {
-            return Ok(client.clone());
+        self.pool.get(config).await
+    }
+
+    /// Returns a stub resolver for the given config and options, reusing the
+    /// one made for an earlier command if they're the same, so that its cache
+    /// lives as long as the plugin process.
+    pub fn resolver(&self, config: ResolverConfig, opts: ResolverOpts) -> TokioAsyncResolver {
+        let mut resolvers = self.resolvers.lock().unwrap();
+
+        if let Some((_, _, resolver)) = resolvers
+            .iter()
+            .find(|(pooled, pooled_opts, _)| *pooled == config && *pooled_opts == opts)
+        {
+            return resolver.clone();
         }
 
-        let mut client_guard = self.client.write().await;
+        // the resolver spawns its connections onto the current runtime
+        let _guard = self.runtime.enter();
+        let resolver = TokioAsyncResolver::tokio(config.clone(), opts.clone());
+        resolvers.push((config, opts, resolver.clone()));
+
+        resolver
+    }
+
+    /// Backs the response cache with the file named in the config, if any.
+    pub fn load_cache(&self, config: &Config) -> Result<(), LabeledError> {
+        let Some(path) = &config.cache_file else {
+            return Ok(());
+        };
+
+        self.cache.persist_to(&path.item).map_err(|err| {
+            LabeledError::new("cache error").with_label(
+                format!("could not load cache file {}: {}", path.item.display(), err),
+                path.span,
+            )
+        })
+    }
+
+    /// Returns a token that is cancelled as soon as the user interrupts the
+    /// running command. The caller should cancel the token itself once the
+    /// command is done, which stops watching for the interrupt.
+    pub fn interrupt_token(&self, signals: Signals) -> CancellationToken {
+        let cancel = self.cancel.child_token();
+
+        self.spawn({
+            let cancel = cancel.clone();
 
-        // it is cheap to clone and hand back an owned client because underneath
-        // it is just a mpsc::Sender
-        match &mut *client_guard {
-            Some((client, _)) => Ok(client.clone()),
-            None => {
-                let (client, client_bg) = self.make_dns_client(config).await?;
-                *client_guard = Some((client.clone(), client_bg));
-                Ok(client)
+            async move {
+                while tokio::time::timeout(INTERRUPT_POLL, cancel.cancelled())
+                    .await
+                    .is_err()
+                {
+                    if signals.interrupted() {
+                        tracing::debug!(phase = "interrupted");
+                        cancel.cancel();
+                    }
+                }
+
+                Ok(())
             }
-        }
+        });
+
+        cancel
+    }
+
+    /// Registers a server started by `dns serve`, returning the token that
+    /// stops it.
+    pub fn add_server(&self, addr: SocketAddr) -> CancellationToken {
+        let cancel = self.cancel.child_token();
+        self.servers.lock().unwrap().push((addr, cancel.clone()));
+        cancel
     }
 
-    async fn make_dns_client(
-        &self,
-        config: &Config,
-    ) -> Result<
-        (
-            DnsClient,
-            JoinSet<Result<(), hickory_proto::error::ProtoError>>,
-        ),
-        LabeledError,
-    > {
-        let (client, bg) = DnsClient::new(config).await?;
-        tracing::info!(client.addr = ?config.server, client.protocol = ?config.protocol);
-        Ok((client, bg))
+    /// Stops the server on the given address, or all of them. Returns the
+    /// addresses that were stopped and how many servers are still running.
+    pub fn stop_servers(&self, addr: Option<SocketAddr>) -> (Vec<SocketAddr>, usize) {
+        let mut servers = self.servers.lock().unwrap();
+        let mut stopped = Vec::new();
+
+        servers.retain(|(server, cancel)| {
+            if addr.is_some_and(|addr| addr != *server) {
+                return true;
+            }
+
+            cancel.cancel();
+            stopped.push(*server);
+            false
+        });
+
+        (stopped, servers.len())
     }
 
     pub fn spawn<F>(&self, future: F)
@@ -0,0 +1,127 @@
+//! Finding out the host's public addresses by asking servers that answer
+//! with the address a question came from, so that no HTTP is needed.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use futures_util::future;
+use hickory_proto::rr::{RData, RecordType};
+use hickory_resolver::{config::Protocol, Name};
+
+use super::{config::Config, constants, serde, Dns};
+
+/// What one service said the address is, over one address family.
+pub struct Sighting {
+    pub service: &'static str,
+    pub family: RecordType,
+    pub server: SocketAddr,
+    pub addr: Result<IpAddr, String>,
+    pub rtt: Duration,
+}
+
+/// Asks every service, over each of the address families the config allows,
+/// what address the question came from.
+pub async fn discover(plugin: &Dns, config: &Config) -> Vec<Sighting> {
+    let asks = constants::myip::SERVICES
+        .iter()
+        .flat_map(|service| {
+            config
+                .address_types()
+                .iter()
+                .map(move |family| (service, *family))
+        })
+        .map(
+            |((service, name, rtype, class, v4, v6), family)| async move {
+                let ip = match family {
+                    RecordType::AAAA => *v6,
+                    _ => *v4,
+                };
+                let server = SocketAddr::new(ip, constants::config::default_port(Protocol::Udp));
+
+                // a service that answers with an address answers with one of the
+                // family the question came over
+                let qtype = match (rtype, family) {
+                    (RecordType::A, RecordType::AAAA) => RecordType::AAAA,
+                    (rtype, _) => *rtype,
+                };
+
+                let mut config = config.with_addr(server);
+                config.protocol.item = Protocol::Udp;
+                config.server_addrs = vec![server];
+                config.server_host = None;
+
+                let sent = Instant::now();
+                let addr = ask(plugin, &config, name, qtype, *class).await;
+
+                Sighting {
+                    service,
+                    family,
+                    server,
+                    addr,
+                    rtt: sent.elapsed(),
+                }
+            },
+        );
+
+    future::join_all(asks).await
+}
+
+async fn ask(
+    plugin: &Dns,
+    config: &Config,
+    name: &str,
+    qtype: RecordType,
+    class: hickory_proto::rr::DNSClass,
+) -> Result<IpAddr, String> {
+    let client = plugin.dns_client(config).await.map_err(|err| err.msg)?;
+
+    let name = Name::from_ascii(name).expect("name should parse");
+    let mut query = hickory_proto::op::Query::query(name, qtype);
+    query.set_query_class(class);
+    let request = serde::Query(query).into_message(config);
+
+    config.throttle().await;
+    let resp = client
+        .send_message(config, request)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    // TXT answers can come with others, like the client subnet a resolver
+    // passed on, so the first one that is an address is taken
+    resp.answers()
+        .iter()
+        .find_map(|record| match record.data()? {
+            RData::A(addr) => Some(IpAddr::V4(addr.0)),
+            RData::AAAA(addr) => Some(IpAddr::V6(addr.0)),
+            RData::TXT(txt) => txt.iter().find_map(|data| {
+                std::str::from_utf8(data)
+                    .ok()
+                    .and_then(|text| text.trim_matches('"').parse().ok())
+            }),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            format!(
+                "the answer had no address ({})",
+                serde::RCode(resp.response_code()).mnemonic()
+            )
+        })
+}
+
+/// The address most of the services agree on for a family, if any saw one.
+pub fn consensus(sightings: &[Sighting], family: RecordType) -> Option<IpAddr> {
+    let addrs: Vec<IpAddr> = sightings
+        .iter()
+        .filter(|sighting| sighting.family == family)
+        .filter_map(|sighting| sighting.addr.as_ref().ok().copied())
+        .collect();
+
+    // on a tie, the first service's address wins
+    addrs
+        .iter()
+        .rev()
+        .max_by_key(|addr| addrs.iter().filter(|other| other == addr).count())
+        .copied()
+}
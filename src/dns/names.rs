@@ -0,0 +1,310 @@
+//! Checks names against the rules for what they are used as. Any name can be
+//! a DNS name as long as it fits the length limits of
+//! [RFC 1035](https://www.rfc-editor.org/rfc/rfc1035#section-2.3.4), but host
+//! names are held to the letters, digits, and hyphens of
+//! [RFC 952](https://www.rfc-editor.org/rfc/rfc952) as relaxed by
+//! [RFC 1123](https://www.rfc-editor.org/rfc/rfc1123#page-13), and the owner
+//! names of SRV records start with a service and a protocol label
+//! ([RFC 2782](https://www.rfc-editor.org/rfc/rfc2782),
+//! [RFC 6335](https://www.rfc-editor.org/rfc/rfc6335#section-5.1)).
+
+use std::{fmt, str::FromStr};
+
+use super::serde::util;
+
+const MAX_LABEL: usize = 63;
+const MAX_NAME: usize = 255;
+const MAX_LOCAL_PART: usize = 64;
+const MAX_ADDRESS: usize = 254;
+const MAX_SERVICE: usize = 15;
+
+/// What a name is meant to be used as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Usage {
+    /// Any name in the DNS.
+    Dns,
+    /// The name of a host.
+    Hostname,
+    /// A mail address or the domain after its `@`.
+    Mail,
+    /// The owner name of an SRV record, as in `_sip._tcp.example.com`.
+    Srv,
+}
+
+impl FromStr for Usage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dns" => Ok(Self::Dns),
+            "hostname" => Ok(Self::Hostname),
+            "mail" => Ok(Self::Mail),
+            "srv" => Ok(Self::Srv),
+            _ => Err(format!(
+                "unknown name usage {s}; should be dns, hostname, mail, or srv"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Dns => "dns",
+            Self::Hostname => "hostname",
+            Self::Mail => "mail",
+            Self::Srv => "srv",
+        })
+    }
+}
+
+/// A rule the name breaks, and the label that breaks it, if it's down to one
+/// label rather than the name as a whole.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// Where the label is in the name, counting from 0 on the left.
+    pub index: Option<usize>,
+    /// The label in presentation format.
+    pub label: Option<String>,
+    pub rule: String,
+}
+
+impl Violation {
+    fn name(rule: impl Into<String>) -> Self {
+        Self {
+            index: None,
+            label: None,
+            rule: rule.into(),
+        }
+    }
+
+    fn label(index: usize, label: &[u8], rule: impl Into<String>) -> Self {
+        Self {
+            index: Some(index),
+            label: Some(util::escape_bytes(label, true)),
+            rule: rule.into(),
+        }
+    }
+}
+
+/// Checks a name in presentation format against the rules for its usage,
+/// returning every rule it breaks.
+pub fn validate(name: &str, usage: Usage) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    let domain = match usage {
+        Usage::Mail => match name.rsplit_once('@') {
+            Some((local, domain)) => {
+                if local.is_empty() {
+                    violations.push(Violation::name("the local part before the @ is empty"));
+                } else if local.len() > MAX_LOCAL_PART {
+                    violations.push(Violation::name(format!(
+                        "the local part is {} octets, over the limit of {MAX_LOCAL_PART}",
+                        local.len()
+                    )));
+                }
+
+                if name.len() > MAX_ADDRESS {
+                    violations.push(Violation::name(format!(
+                        "the address is {} octets, over the limit of {MAX_ADDRESS}",
+                        name.len()
+                    )));
+                }
+
+                domain
+            }
+            None => name,
+        },
+        _ => name,
+    };
+
+    let labels = match labels(domain) {
+        Ok(labels) => labels,
+        Err(err) => {
+            violations.push(Violation::name(err));
+            return violations;
+        }
+    };
+
+    violations.extend(check_lengths(&labels));
+
+    let host_labels = match usage {
+        Usage::Dns => return violations,
+        Usage::Hostname | Usage::Mail => 0,
+        Usage::Srv => {
+            violations.extend(check_srv(&labels));
+            2
+        }
+    };
+
+    for (index, label) in labels.iter().enumerate().skip(host_labels) {
+        violations.extend(check_host_label(index, label));
+    }
+
+    // a top-level domain of only digits would be mistaken for an address
+    if let Some((index, tld)) = labels.iter().enumerate().skip(host_labels).last() {
+        if !tld.is_empty() && tld.iter().all(u8::is_ascii_digit) {
+            violations.push(Violation::label(
+                index,
+                tld,
+                "the top-level label can't be all digits",
+            ));
+        }
+    }
+
+    violations
+}
+
+/// The labels of a name, without the root. Unicode labels are converted to
+/// their ASCII form, which is what the rules apply to.
+fn labels(name: &str) -> Result<Vec<Vec<u8>>, String> {
+    if name.is_ascii() || name.contains('\\') {
+        return util::split_labels(name).map(|(labels, _)| labels);
+    }
+
+    name.strip_suffix('.')
+        .unwrap_or(name)
+        .split('.')
+        .map(|label| match label.is_ascii() {
+            true => Ok(label.as_bytes().to_vec()),
+            false => hickory_resolver::Name::from_utf8(label)
+                .map(|name| name.to_ascii().trim_end_matches('.').as_bytes().to_vec())
+                .map_err(|err| format!("{label} is not a valid internationalized label: {err}")),
+        })
+        .collect()
+}
+
+fn check_lengths(labels: &[Vec<u8>]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    for (index, label) in labels.iter().enumerate() {
+        if label.is_empty() {
+            violations.push(Violation::label(index, label, "labels can't be empty"));
+        } else if label.len() > MAX_LABEL {
+            violations.push(Violation::label(
+                index,
+                label,
+                format!(
+                    "the label is {} octets, over the limit of {MAX_LABEL}",
+                    label.len()
+                ),
+            ));
+        }
+    }
+
+    // each label has a length octet, and the root label is one more
+    let wire_len = labels.iter().map(|label| label.len() + 1).sum::<usize>() + 1;
+    if wire_len > MAX_NAME {
+        violations.push(Violation::name(format!(
+            "the name is {wire_len} octets in wire format, over the limit of {MAX_NAME}"
+        )));
+    }
+
+    violations
+}
+
+fn check_host_label(index: usize, label: &[u8]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if let Some(byte) = label
+        .iter()
+        .find(|byte| !byte.is_ascii_alphanumeric() && **byte != b'-')
+    {
+        violations.push(Violation::label(
+            index,
+            label,
+            format!(
+                "{} is not allowed; host names only have letters, digits, and hyphens",
+                util::escape_bytes(&[*byte], true)
+            ),
+        ));
+    }
+
+    if label.starts_with(b"-") || label.ends_with(b"-") {
+        violations.push(Violation::label(
+            index,
+            label,
+            "labels can't start or end with a hyphen",
+        ));
+    }
+
+    violations
+}
+
+/// Checks the service and protocol labels that start an SRV owner name.
+fn check_srv(labels: &[Vec<u8>]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if labels.len() < 3 {
+        violations.push(Violation::name(
+            "an SRV name needs a service label, a protocol label, and a domain, as in _sip._tcp.example.com",
+        ));
+    }
+
+    if let Some(service) = labels.first() {
+        match service.strip_prefix(b"_") {
+            None => violations.push(Violation::label(
+                0,
+                service,
+                "the service label has to start with an underscore",
+            )),
+            Some(name) => {
+                if let Some(rule) = check_service_name(name) {
+                    violations.push(Violation::label(0, service, rule));
+                }
+            }
+        }
+    }
+
+    if let Some(proto) = labels.get(1) {
+        match proto.strip_prefix(b"_") {
+            None => violations.push(Violation::label(
+                1,
+                proto,
+                "the protocol label has to start with an underscore",
+            )),
+            Some([]) => violations.push(Violation::label(1, proto, "the protocol is empty")),
+            Some(name) => {
+                if !name
+                    .iter()
+                    .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'-')
+                {
+                    violations.push(Violation::label(
+                        1,
+                        proto,
+                        "protocols only have letters, digits, and hyphens",
+                    ));
+                }
+            }
+        }
+    }
+
+    violations
+}
+
+/// Checks a service name, without its underscore, against RFC 6335.
+fn check_service_name(name: &[u8]) -> Option<String> {
+    if name.is_empty() || name.len() > MAX_SERVICE {
+        return Some(format!(
+            "service names are 1 to {MAX_SERVICE} characters, not {}",
+            name.len()
+        ));
+    }
+
+    if !name
+        .iter()
+        .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'-')
+    {
+        return Some("service names only have letters, digits, and hyphens".into());
+    }
+
+    if !name.iter().any(u8::is_ascii_alphabetic) {
+        return Some("service names need at least one letter".into());
+    }
+
+    if name.starts_with(b"-") || name.ends_with(b"-") || name.windows(2).any(|w| w == b"--") {
+        return Some("service names can't start or end with a hyphen, or have two in a row".into());
+    }
+
+    None
+}
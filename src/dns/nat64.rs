@@ -0,0 +1,137 @@
+//! Discovery of the NAT64 prefix a DNS64 resolver synthesizes AAAA records
+//! with, per [RFC 7050](https://www.rfc-editor.org/rfc/rfc7050#section-3),
+//! using the address layouts of [RFC
+//! 6052](https://www.rfc-editor.org/rfc/rfc6052#section-2.2).
+
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use hickory_proto::rr::{RData, RecordType};
+use hickory_resolver::Name;
+use nu_protocol::{LabeledError, Span};
+
+use super::{client::DnsClient, config::Config, error::DnsError};
+
+/// The name that only ever has A records, for resolvers to synthesize AAAA
+/// records from.
+pub const WELL_KNOWN_NAME: &str = "ipv4only.arpa.";
+
+/// The addresses of [`WELL_KNOWN_NAME`].
+const WELL_KNOWN_ADDRS: [Ipv4Addr; 2] =
+    [Ipv4Addr::new(192, 0, 0, 170), Ipv4Addr::new(192, 0, 0, 171)];
+
+/// The prefix lengths RFC 6052 allows, longest first, since that is by far the
+/// most common and a match there is the least likely to be a coincidence.
+const PREFIX_LENGTHS: [u8; 6] = [96, 64, 56, 48, 40, 32];
+
+/// A NAT64 prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pref64 {
+    pub prefix: Ipv6Addr,
+    pub len: u8,
+}
+
+impl fmt::Display for Pref64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.prefix, self.len)
+    }
+}
+
+impl Pref64 {
+    /// Works out the prefix an address was synthesized with, if it embeds one
+    /// of the given IPv4 addresses in any of the RFC 6052 layouts.
+    pub fn find(addr: Ipv6Addr, ipv4: &[Ipv4Addr]) -> Option<Self> {
+        PREFIX_LENGTHS.into_iter().find_map(|len| {
+            ipv4.contains(&embedded(addr, len)).then(|| Pref64 {
+                prefix: masked(addr, len),
+                len,
+            })
+        })
+    }
+}
+
+/// The IPv4 address embedded in an IPv6 address under a prefix of the given
+/// length. Bits 64 to 71 are reserved, so the address is split around them
+/// for prefixes shorter than 64 bits.
+fn embedded(addr: Ipv6Addr, len: u8) -> Ipv4Addr {
+    let octets = addr.octets();
+    let start = (len / 8) as usize;
+
+    let bytes: Vec<u8> = (start..16)
+        .filter(|idx| *idx != 8)
+        .take(4)
+        .map(|idx| octets[idx])
+        .collect();
+
+    Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])
+}
+
+fn masked(addr: Ipv6Addr, len: u8) -> Ipv6Addr {
+    let mask = u128::MAX.checked_shl(128 - len as u32).unwrap_or(0);
+    Ipv6Addr::from(u128::from(addr) & mask)
+}
+
+/// What asking a resolver for a name's AAAA records revealed about DNS64.
+pub struct Discovery {
+    /// The AAAA records the resolver returned, which for a name with only A
+    /// records must have been synthesized.
+    pub synthesized: Vec<Ipv6Addr>,
+
+    /// The distinct prefixes they were synthesized with.
+    pub prefixes: Vec<Pref64>,
+}
+
+/// Asks for the AAAA records of a name that only has A records. For names
+/// other than [`WELL_KNOWN_NAME`], the A records are looked up too, to know
+/// which IPv4 addresses to look for.
+pub async fn discover(
+    config: &Config,
+    client: &DnsClient,
+    name: &Name,
+    span: Span,
+) -> Result<Discovery, LabeledError> {
+    let ipv4: Vec<Ipv4Addr> = if *name == Name::from_str(WELL_KNOWN_NAME).unwrap() {
+        WELL_KNOWN_ADDRS.to_vec()
+    } else {
+        client
+            .answers(config, name, RecordType::A)
+            .await
+            .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?
+            .into_iter()
+            .filter_map(|record| match record.into_data()? {
+                RData::A(a) => Some(a.0),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let synthesized: Vec<Ipv6Addr> = client
+        .answers(config, name, RecordType::AAAA)
+        .await
+        .map_err(|err| DnsError::from_proto(&err, config.server.item, span))?
+        .into_iter()
+        .filter_map(|record| match record.into_data()? {
+            RData::AAAA(aaaa) => Some(aaaa.0),
+            _ => None,
+        })
+        .collect();
+
+    let mut prefixes: Vec<Pref64> = Vec::new();
+
+    for prefix in synthesized
+        .iter()
+        .filter_map(|addr| Pref64::find(*addr, &ipv4))
+    {
+        if !prefixes.contains(&prefix) {
+            prefixes.push(prefix);
+        }
+    }
+
+    Ok(Discovery {
+        synthesized,
+        prefixes,
+    })
+}
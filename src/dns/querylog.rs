@@ -0,0 +1,103 @@
+//! Appends every exchange to a file as a line of JSON, so that queries made
+//! while poking around in the shell leave a trail that can be read back with
+//! `open --raw log.jsonl | from json --objects`.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+use hickory_proto::{error::ProtoError, op::Message, xfer::DnsResponse};
+
+use super::{config::Config, serde};
+
+/// Writes one exchange to the log file named in the config. Failing to write
+/// doesn't fail the query, which already happened; it is only logged.
+pub fn append(
+    config: &Config,
+    request: &Message,
+    resp: &Result<DnsResponse, ProtoError>,
+    rtt: Duration,
+) {
+    let Some(path) = &config.log_file else {
+        return;
+    };
+
+    let line = entry(config, request, resp, rtt);
+
+    if let Err(err) = write_line(&path.item, &line) {
+        tracing::warn!(querylog.path = %path.item.display(), querylog.error = %err, "could not write query log");
+    }
+}
+
+fn write_line(path: &Path, line: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    // one write per line, so that lines from queries sent at the same time
+    // don't interleave
+    file.write_all(format!("{line}\n").as_bytes())
+}
+
+fn entry(
+    config: &Config,
+    request: &Message,
+    resp: &Result<DnsResponse, ProtoError>,
+    rtt: Duration,
+) -> String {
+    let query = request.queries().first();
+    let (rcode, answers, error) = match resp {
+        Ok(resp) => (
+            string(&serde::RCode(resp.response_code()).mnemonic()),
+            format!(
+                "[{}]",
+                resp.answers()
+                    .iter()
+                    .map(|record| string(&record.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            "null".into(),
+        ),
+        Err(err) => ("null".into(), "[]".into(), string(&err.to_string())),
+    };
+
+    format!(
+        r#"{{"timestamp":{},"server":{},"protocol":{},"qname":{},"qtype":{},"rcode":{},"rtt_ms":{},"answers":{},"error":{}}}"#,
+        string(&chrono::Local::now().to_rfc3339()),
+        string(&config.server.item.to_string()),
+        string(&config.protocol.item.to_string()),
+        query.map_or("null".into(), |query| string(
+            &config.display_name(query.name())
+        )),
+        query.map_or("null".into(), |query| string(&serde::util::rtype_mnemonic(
+            query.query_type()
+        ))),
+        rcode,
+        rtt.as_secs_f64() * 1000.0,
+        answers,
+        error,
+    )
+}
+
+/// A JSON string literal.
+fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
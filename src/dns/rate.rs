@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use tokio::{sync::Mutex, time::Instant};
+
+/// Token bucket that holds a single token, refilled at the configured rate.
+/// This spaces queries out evenly rather than allowing bursts, which is the
+/// polite thing to do towards someone else's nameserver.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// A limiter that lets through at most `qps` queries per second.
+    pub fn new(qps: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / qps),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the next query may be sent. Waiters are let through in
+    /// the order they arrived.
+    pub async fn acquire(&self) {
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+
+        if *next > now {
+            tokio::time::sleep_until(*next).await;
+        }
+
+        *next = (*next).max(now) + self.interval;
+    }
+}
@@ -0,0 +1,160 @@
+//! Destination address selection, for ordering the addresses a name resolves
+//! to the way a well behaved client would try them: per [RFC
+//! 6724](https://www.rfc-editor.org/rfc/rfc6724#section-6), then interleaved
+//! by family per [RFC 8305](https://www.rfc-editor.org/rfc/rfc8305#section-4).
+
+use std::{
+    cmp::Reverse,
+    net::{IpAddr, Ipv6Addr, SocketAddr, UdpSocket},
+};
+
+/// Scope values from RFC 6724 section 3.1.
+const SCOPE_LINK_LOCAL: u8 = 0x2;
+const SCOPE_SITE_LOCAL: u8 = 0x5;
+const SCOPE_GLOBAL: u8 = 0xe;
+
+/// The default policy table from RFC 6724 section 2.1, as (prefix, prefix
+/// length, precedence, label).
+const POLICY_TABLE: &[(Ipv6Addr, u8, u8, u8)] = &[
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1), 128, 50, 0),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0), 96, 35, 4),
+    (Ipv6Addr::new(0x2002, 0, 0, 0, 0, 0, 0, 0), 16, 30, 2),
+    (Ipv6Addr::new(0x2001, 0, 0, 0, 0, 0, 0, 0), 32, 5, 5),
+    (Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7, 3, 13),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 96, 1, 3),
+    (Ipv6Addr::new(0xfec0, 0, 0, 0, 0, 0, 0, 0), 10, 1, 11),
+    (Ipv6Addr::new(0x3ffe, 0, 0, 0, 0, 0, 0, 0), 16, 1, 12),
+    (Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0, 40, 1),
+];
+
+/// What is known about a destination when deciding where to put it.
+struct Candidate {
+    dst: IpAddr,
+    src: Option<IpAddr>,
+}
+
+/// Orders destination addresses in the order they should be tried.
+pub fn sort(addrs: &[IpAddr]) -> Vec<IpAddr> {
+    let mut candidates: Vec<Candidate> = addrs
+        .iter()
+        .map(|dst| Candidate {
+            dst: *dst,
+            src: source_for(*dst),
+        })
+        .collect();
+
+    // sorting is stable, which takes care of rule 10: otherwise, leave the
+    // order unchanged
+    candidates.sort_by_key(preference);
+
+    interleave(
+        candidates
+            .into_iter()
+            .map(|candidate| candidate.dst)
+            .collect(),
+    )
+}
+
+/// The source address the OS would use to reach the destination, or `None`
+/// if there is no route to it. Connecting a UDP socket does not send
+/// anything.
+fn source_for(dst: IpAddr) -> Option<IpAddr> {
+    let bind: SocketAddr = match dst {
+        IpAddr::V4(_) => "0.0.0.0:0".parse().ok()?,
+        IpAddr::V6(_) => "[::]:0".parse().ok()?,
+    };
+
+    let socket = UdpSocket::bind(bind).ok()?;
+    socket.connect(SocketAddr::new(dst, 53)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+/// The rules of RFC 6724 section 6 that can be applied without knowing
+/// about the host's interfaces beyond its chosen source addresses, as a key
+/// that sorts more preferred destinations first.
+fn preference(candidate: &Candidate) -> (bool, bool, bool, Reverse<u8>, u8, Reverse<u32>) {
+    let dst = candidate.dst;
+
+    // rule 1: avoid unusable destinations
+    let Some(src) = candidate.src else {
+        return (true, true, true, Reverse(0), u8::MAX, Reverse(0));
+    };
+
+    // rule 9: use longest matching prefix. this only applies to IPv6, so
+    // IPv4 destinations all tie.
+    let prefix_len = match (dst, src) {
+        (IpAddr::V6(dst), IpAddr::V6(src)) => common_prefix_len(dst, src),
+        _ => 0,
+    };
+
+    (
+        false,
+        // rule 2: prefer matching scope
+        scope(dst) != scope(src),
+        // rule 5: prefer matching label
+        policy(dst).1 != policy(src).1,
+        // rule 6: prefer higher precedence
+        Reverse(policy(dst).0),
+        // rule 8: prefer smaller scope
+        scope(dst),
+        Reverse(prefix_len),
+    )
+}
+
+/// Moves addresses around so that families alternate, starting with the
+/// family of the most preferred address.
+fn interleave(addrs: Vec<IpAddr>) -> Vec<IpAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+
+    let first_is_v4 = first.is_ipv4();
+    let (mut preferred, mut other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv4() == first_is_v4);
+
+    preferred.reverse();
+    other.reverse();
+
+    let mut interleaved = Vec::with_capacity(preferred.len() + other.len());
+
+    while !preferred.is_empty() || !other.is_empty() {
+        interleaved.extend(preferred.pop());
+        interleaved.extend(other.pop());
+    }
+
+    interleaved
+}
+
+fn scope(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(v4) if v4.is_loopback() || v4.is_link_local() => SCOPE_LINK_LOCAL,
+        IpAddr::V4(_) => SCOPE_GLOBAL,
+        IpAddr::V6(v6) if v6.is_multicast() => (v6.segments()[0] & 0x000f) as u8,
+        IpAddr::V6(v6) if v6.is_loopback() || (v6.segments()[0] & 0xffc0) == 0xfe80 => {
+            SCOPE_LINK_LOCAL
+        }
+        IpAddr::V6(v6) if (v6.segments()[0] & 0xffc0) == 0xfec0 => SCOPE_SITE_LOCAL,
+        IpAddr::V6(_) => SCOPE_GLOBAL,
+    }
+}
+
+/// Looks up the (precedence, label) of an address in the policy table.
+/// IPv4 addresses are looked up as IPv4-mapped IPv6 addresses.
+fn policy(addr: IpAddr) -> (u8, u8) {
+    let v6 = match addr {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    };
+
+    // the table is ordered so that longer prefixes come before the shorter
+    // ones that contain them
+    POLICY_TABLE
+        .iter()
+        .find(|(prefix, len, _, _)| common_prefix_len(v6, *prefix) >= *len as u32)
+        .map_or((40, 1), |(_, _, precedence, label)| (*precedence, *label))
+}
+
+fn common_prefix_len(a: Ipv6Addr, b: Ipv6Addr) -> u32 {
+    (u128::from(a) ^ u128::from(b)).leading_zeros()
+}
@@ -1,11 +1,13 @@
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::net::IpAddr;
 use std::ops::Deref;
 use std::str::FromStr;
 
 use hickory_client::rr::rdata::key;
 use hickory_client::rr::rdata::DNSSECRData;
 use hickory_proto::error::ProtoError;
+use hickory_proto::op::ResponseCode;
 use hickory_proto::rr::dnssec;
 use hickory_proto::rr::rdata::opt::EdnsCode;
 use hickory_proto::rr::rdata::opt::EdnsOption;
@@ -17,6 +19,7 @@ use hickory_proto::rr::rdata::svcb::Unknown;
 use hickory_proto::rr::rdata::tlsa;
 use hickory_proto::rr::RecordType;
 use hickory_proto::serialize::binary::BinEncodable;
+use hickory_proto::serialize::txt::RDataParser;
 use hickory_resolver::Name;
 use nu_protocol::record;
 use nu_protocol::FromValue;
@@ -27,6 +30,21 @@ use nu_protocol::Value;
 use super::config::Config;
 use super::constants;
 
+/// A record type shown by its mnemonic, or as `TYPEnnn` if it has none.
+struct Mnemonic(RecordType);
+
+impl Display for Mnemonic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&util::rtype_mnemonic(self.0))
+    }
+}
+
+impl From<Mnemonic> for u16 {
+    fn from(rtype: Mnemonic) -> Self {
+        rtype.0.into()
+    }
+}
+
 fn code_to_record_u16<C>(code: C, config: &Config) -> Value
 where
     C: Display + Into<u16>,
@@ -79,43 +97,222 @@ where
     }
 }
 
+fn code_record(name: impl Into<String>, code: i64) -> Value {
+    Value::record(
+        nu_protocol::Record::from_iter(std::iter::zip(
+            Vec::from_iter(
+                constants::columns::CODE_COLS
+                    .iter()
+                    .map(|s| String::from(*s)),
+            ),
+            vec![
+                Value::string(name, Span::unknown()),
+                Value::int(code, Span::unknown()),
+            ],
+        )),
+        Span::unknown(),
+    )
+}
+
 pub struct Message {
     msg: hickory_proto::op::Message,
     bytes: Vec<u8>,
+    tcp_retry: bool,
+    cached: bool,
+    case_match: Option<bool>,
+    server: Option<std::net::SocketAddr>,
 }
 
 impl Message {
     pub fn new(msg: hickory_proto::op::Message) -> Self {
         let bytes = msg.to_bytes().expect("unencodable message");
-        Self { msg, bytes }
+        Self {
+            msg,
+            bytes,
+            tcp_retry: false,
+            cached: false,
+            case_match: None,
+            server: None,
+        }
+    }
+
+    /// Parses a message off the wire, keeping the bytes as they were rather
+    /// than re-encoding them. If `lenient` is set, as much as possible is
+    /// salvaged from a malformed message.
+    pub fn from_bytes(bytes: Vec<u8>, lenient: bool) -> Result<Self, ProtoError> {
+        Ok(Self {
+            msg: super::wire::parse_response(&bytes, lenient)?,
+            bytes,
+            tcp_retry: false,
+            cached: false,
+            case_match: None,
+            server: None,
+        })
+    }
+
+    /// Marks the message as the result of retrying a truncated UDP response
+    /// over TCP.
+    pub fn with_tcp_retry(mut self, tcp_retry: bool) -> Self {
+        self.tcp_retry = tcp_retry;
+        self
+    }
+
+    /// Marks the message as having been served from the plugin's cache.
+    pub fn with_cached(mut self, cached: bool) -> Self {
+        self.cached = cached;
+        self
+    }
+
+    /// Records whether the response echoed the randomized case of the
+    /// question.
+    pub fn with_case_match(mut self, case_match: Option<bool>) -> Self {
+        self.case_match = case_match;
+        self
+    }
+
+    /// Records which address of the server the response came from.
+    pub fn with_server(mut self, server: std::net::SocketAddr) -> Self {
+        self.server = Some(server);
+        self
     }
 
     pub fn into_inner(self) -> hickory_proto::op::Message {
         self.msg
     }
 
+    /// The columns of a message with nothing in them, and its sections as
+    /// empty lists, for a row that stands in for a response that never came,
+    /// so that it has the same shape as the rest.
+    pub fn empty_value(question: Value, server: std::net::SocketAddr) -> nu_protocol::Record {
+        constants::columns::MESSAGE_COLS
+            .iter()
+            .map(|col| {
+                let val = match *col {
+                    "question" => question.clone(),
+                    "warning" => question
+                        .get_data_by_key(constants::columns::NAME)
+                        .and_then(|name| util::parse_name(name.as_str().ok()?).ok())
+                        .map_or(Value::nothing(Span::unknown()), |name| {
+                            super::special::warning(&name)
+                        }),
+                    "server" => Value::string(server.to_string(), Span::unknown()),
+                    "answer" | "authority" | "additional" => Value::list(vec![], Span::unknown()),
+                    _ => Value::nothing(Span::unknown()),
+                };
+                ((*col).into(), val)
+            })
+            .collect()
+    }
+
     pub fn size(&self) -> usize {
         self.bytes.len()
     }
 
     pub fn into_value(self, config: &Config) -> Result<Value, LabeledError> {
         let size = Value::filesize(self.size() as i64, Span::unknown());
+        let tcp_retry = Value::bool(self.tcp_retry, Span::unknown());
+        let cached = Value::bool(self.cached, Span::unknown());
+        let case_match = self
+            .case_match
+            .map_or(Value::nothing(Span::unknown()), |case_match| {
+                Value::bool(case_match, Span::unknown())
+            });
+        let server = self
+            .server
+            .map_or(Value::nothing(Span::unknown()), |server| {
+                Value::string(server.to_string(), Span::unknown())
+            });
         let message = self.into_inner();
+        let warning = message
+            .queries()
+            .first()
+            .map_or(Value::nothing(Span::unknown()), |query| {
+                super::special::warning(query.name())
+            });
+        let minimal_any = util::minimal_any(&message)
+            .map_or(Value::nothing(Span::unknown()), |minimal| {
+                Value::bool(minimal, Span::unknown())
+            });
+        let negative_ttl = super::cache::negative_ttl(&message)
+            .map(util::sec_to_duration)
+            .unwrap_or(Value::nothing(Span::unknown()));
+        let negative = match super::cache::negative_kind(&message) {
+            Some(kind) => Value::record(
+                record![
+                    "kind" => Value::string(kind, Span::unknown()),
+                    "soa"  => match super::cache::negative_soa(&message) {
+                        Some(soa) => Record(soa.clone()).into_value(config)?,
+                        None => Value::nothing(Span::unknown()),
+                    },
+                ],
+                Span::unknown(),
+            ),
+            None => Value::nothing(Span::unknown()),
+        };
         let header = Header(message.header()).into_value(config);
+        let id = Value::int(message.id().into(), Span::unknown());
+        let rcode = RCode(message.response_code()).into_value();
+        let opcode = code_record(
+            message.op_code().to_string(),
+            u8::from(message.op_code()) as i64,
+        );
         let mut parts = message.into_parts();
 
-        let question = parts.queries.pop().map_or_else(
-            || Value::record(record!(), Span::unknown()),
-            |q| Query(q).into_value(config),
-        );
+        let question = match (parts.queries.pop(), config.output_schema.item) {
+            (Some(q), _) => Query(q).into_value(config),
+            (None, OutputSchema::V1) => Value::record(record!(), Span::unknown()),
+            (None, OutputSchema::V2) => Query::empty_value(),
+        };
 
         let parse_records =
-            |records: Vec<hickory_client::rr::Record>| -> Result<Value, LabeledError> {
-                Ok(Value::list(
-                    records
+            |mut records: Vec<hickory_client::rr::Record>| -> Result<Value, LabeledError> {
+                if config.sort.item {
+                    util::sort_records(&mut records);
+                }
+
+                if config.dedup.item {
+                    util::dedup_records(&mut records);
+                }
+
+                // a section can't be one cell as a table of records, so it is
+                // one as the records in presentation format instead
+                if config.scalar.item {
+                    return Ok(Value::string(
+                        records
+                            .iter()
+                            .map(|record| util::presentation_line(record, config))
+                            .collect::<Vec<_>>()
+                            .join(&config.separator.item),
+                        Span::unknown(),
+                    ));
+                }
+
+                let rows = match config.group_rrsets.item {
+                    true => util::group_rrsets(records)
+                        .into_iter()
+                        .map(|rrset| RRset(rrset).into_value(config))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    false => records
                         .into_iter()
                         .map(|record| Record(record).into_value(config))
-                        .collect::<Result<_, _>>()?,
+                        .collect::<Result<Vec<_>, _>>()?,
+                };
+
+                Ok(Value::list(
+                    rows.into_iter()
+                        .map(|record| {
+                            // so that each record still says what was asked
+                            // once the sections are flattened into one table
+                            match config.with_question.item {
+                                true => {
+                                    let mut record = record.into_record()?;
+                                    record.push("question", question.clone());
+                                    Ok(Value::record(record, Span::unknown()))
+                                }
+                                false => Ok(record),
+                            }
+                        })
+                        .collect::<Result<_, LabeledError>>()?,
                     Span::unknown(),
                 ))
             };
@@ -128,13 +325,51 @@ impl Message {
             .map(|edns| Edns(edns).into_value(config))
             .unwrap_or(Value::nothing(Span::unknown()));
 
-        Ok(Value::record(
+        let message = Value::record(
             nu_protocol::Record::from_iter(std::iter::zip(
                 Vec::from_iter(constants::columns::MESSAGE_COLS.iter().map(|s| (*s).into())),
-                vec![header, question, answer, authority, additional, edns, size],
+                vec![
+                    header,
+                    id,
+                    rcode,
+                    opcode,
+                    question,
+                    answer,
+                    authority,
+                    additional,
+                    edns,
+                    size,
+                    server,
+                    negative,
+                    negative_ttl,
+                    tcp_retry,
+                    cached,
+                    case_match,
+                    minimal_any,
+                    warning,
+                ],
             )),
             Span::unknown(),
-        ))
+        );
+
+        let message = match config.output_schema.item {
+            OutputSchema::V1 => {
+                let mut message = message.into_record()?;
+                message.retain(|col, _| constants::columns::MESSAGE_COLS_V1.contains(&col));
+                Value::record(message, Span::unknown())
+            }
+            OutputSchema::V2 => message,
+        };
+
+        let message = match config.compact.item {
+            true => util::compact(message),
+            false => message,
+        };
+
+        Ok(match config.scalar.item {
+            true => util::scalar(message, &config.separator.item),
+            false => message,
+        })
     }
 }
 
@@ -206,11 +441,23 @@ impl<'r> Header<'r> {
 pub struct Query(pub(crate) hickory_proto::op::query::Query);
 
 impl Query {
+    /// The columns of a question with nothing in them, for a message that
+    /// came without one.
+    pub fn empty_value() -> Value {
+        Value::record(
+            constants::columns::QUERY_COLS
+                .iter()
+                .map(|col| ((*col).into(), Value::nothing(Span::unknown())))
+                .collect(),
+            Span::unknown(),
+        )
+    }
+
     pub fn into_value(self, config: &Config) -> Value {
         let Query(query) = self;
 
-        let name = Value::string(query.name().to_utf8(), Span::unknown());
-        let qtype = code_to_record_u16(query.query_type(), config);
+        let name = Value::string(config.display_name(query.name()), Span::unknown());
+        let qtype = code_to_record_u16(Mnemonic(query.query_type()), config);
         let class = code_to_record_u16(query.query_class(), config);
 
         Value::record(
@@ -224,12 +471,47 @@ impl Query {
 }
 
 impl Query {
+    /// Builds the request message to send for this query, the same way
+    /// hickory's client would, with any adjustments the config calls for.
+    pub fn into_message(self, config: &Config) -> hickory_proto::op::Message {
+        let Query(mut query) = self;
+
+        if config.randomize_case.item {
+            query.set_name(util::randomize_case(query.name()));
+        }
+
+        let mut message = hickory_proto::op::Message::new();
+
+        message
+            .add_query(query)
+            .set_id(config.id.as_ref().map_or_else(rand::random, |id| id.item))
+            .set_message_type(hickory_proto::op::MessageType::Query)
+            .set_op_code(hickory_proto::op::OpCode::Query)
+            .set_recursion_desired(true)
+            .set_checking_disabled(config.checking_disabled.item);
+
+        let edns = message
+            .extensions_mut()
+            .get_or_insert_with(hickory_proto::op::Edns::new);
+        edns.set_max_payload(constants::config::default::EDNS_PAYLOAD)
+            .set_version(0)
+            .set_dnssec_ok(config.dnssec_ok.item);
+
+        for (code, data) in &config.edns_opts {
+            edns.options_mut()
+                .insert(EdnsOption::Unknown(*code, data.clone()));
+        }
+
+        message
+    }
+
     pub fn try_from_value(value: &Value, config: &Config) -> Result<Vec<Self>, LabeledError> {
         tracing::debug!(?value);
 
         match value {
-            // If a record is given, it must have at least a name and qtype and
-            // will be used as is, overriding any command line arguments.
+            // If a record is given, it must have at least a name. Its type
+            // and class columns override the command line arguments, which
+            // fill in for any that are missing.
             rec @ Value::Record { .. } => {
                 let span = rec.span();
 
@@ -238,36 +520,50 @@ impl Query {
                         .with_label(format!("Record must have a column named '{}'", col), span)
                 };
 
-                let name = Name::from_utf8(
-                    String::from_value(
-                        rec.get_data_by_key(constants::columns::NAME)
-                            .ok_or_else(|| must_have_col_err(constants::columns::NAME))?,
-                    )
+                let name = config.input_name(match rec
+                    .get_data_by_key(constants::columns::NAME)
+                    .ok_or_else(|| must_have_col_err(constants::columns::NAME))?
+                {
+                    // there is only one name in a record, so a list can only
+                    // be its labels
+                    labels @ Value::List { .. } => {
+                        let span = labels.span();
+                        labels_to_name(labels.as_list()?, span)?
+                    }
+                    val => util::parse_name(&String::from_value(val).map_err(|err| {
+                        LabeledError::new("invalid value").with_label(
+                            format!("Could not convert value to String: {}", err),
+                            span,
+                        )
+                    })?)
                     .map_err(|err| {
-                        LabeledError::new("invalid value")
-                            .with_label(format!("Could not convert value to String: {}", err), span)
+                        LabeledError::new("invalid name")
+                            .with_label(format!("Could not convert string to name: {}", err), span)
                     })?,
-                )
-                .map_err(|err| {
-                    LabeledError::new("invalid name")
-                        .with_label(format!("Could not convert string to name: {}", err), span)
-                })?;
+                });
 
-                let qtype = RType::try_from(
-                    &rec.get_data_by_key(constants::columns::TYPE)
-                        .ok_or_else(|| must_have_col_err(constants::columns::TYPE))?,
-                )?;
+                let qtypes = match util::column(rec, constants::columns::TYPE) {
+                    Some(Value::List { vals, .. }) => vals
+                        .iter()
+                        .map(|val| RType::try_from(val).map(|qtype| qtype.0))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    Some(val) => vec![RType::try_from(&val)?.0],
+                    None => config.qtypes.item.iter().map(|qtype| qtype.item).collect(),
+                };
 
-                let class = rec
-                    .get_data_by_key(constants::columns::CLASS)
+                let class = util::column(rec, constants::columns::CLASS)
                     .map(DNSClass::try_from)
-                    .unwrap_or(Ok(DNSClass(hickory_proto::rr::DNSClass::IN)))?
-                    .0;
+                    .transpose()?
+                    .map_or(config.class.item, |class| class.0);
 
-                let mut query = hickory_proto::op::Query::query(name, qtype.0);
-                query.set_query_class(class);
-
-                Ok(vec![Query(query)])
+                Ok(qtypes
+                    .into_iter()
+                    .map(|qtype| {
+                        let mut query = hickory_proto::op::Query::query(name.clone(), qtype);
+                        query.set_query_class(class);
+                        Query(query)
+                    })
+                    .collect())
             }
 
             // If any other input type is given, the CLI flags fill in the type
@@ -275,10 +571,19 @@ impl Query {
             str_val @ Value::String { val, .. } => {
                 let span = str_val.span();
 
-                let name = Name::from_utf8(val).map_err(|err| {
+                // like `dig -x`, an address means a reverse lookup
+                if !config.no_auto_ptr.item {
+                    if let Ok(ip) = val.parse::<IpAddr>() {
+                        let mut query = hickory_proto::op::Query::query(ip.into(), RecordType::PTR);
+                        query.set_query_class(config.class.item);
+                        return Ok(vec![Query(query)]);
+                    }
+                }
+
+                let name = config.input_name(util::parse_name(val).map_err(|err| {
                     LabeledError::new("invalid name")
                         .with_label(format!("Error parsing name: {}", err), span)
-                })?;
+                })?);
 
                 tracing::debug!(?name);
 
@@ -295,8 +600,11 @@ impl Query {
 
                 Ok(queries)
             }
+            // A list is a list of labels if any of them are binary or other
+            // non-string values, in which case strings are taken as labels
+            // too. A list of only strings is a list of names.
             list @ Value::List { vals, .. } => {
-                if !vals.iter().all(|val| {
+                if !vals.iter().any(|val| {
                     matches!(
                         val,
                         Value::Binary { .. }
@@ -314,33 +622,7 @@ impl Query {
                         .collect());
                 }
 
-                let span = list.span();
-
-                let name = Name::from_labels(
-                    vals.iter()
-                        .map(|val| match val {
-                            Value::Binary { val: bin_val, .. } => Ok(bin_val.clone()),
-                            Value::Int { val, .. } => {
-                                let bytes = val.to_ne_bytes();
-                                let non0 = bytes
-                                    .iter()
-                                    .position(|n| *n != 0)
-                                    .unwrap_or(bytes.len() - 1);
-
-                                Ok(Vec::from(&bytes[non0..]))
-                            }
-                            Value::Bool { val, .. } => Ok(vec![*val as u8]),
-                            Value::Nothing { .. } => Ok(vec![0]),
-
-                            _ => Err(LabeledError::new("invalid name")
-                                .with_label("Invalid input type for name", val.span())),
-                        })
-                        .collect::<Result<Vec<_>, _>>()?,
-                )
-                .map_err(|err| {
-                    LabeledError::new("invalid name")
-                        .with_label(format!("Error parsing into name: {}", err), span)
-                })?;
+                let name = labels_to_name(vals, list.span())?;
 
                 let queries = config
                     .qtypes
@@ -356,36 +638,308 @@ impl Query {
                 Ok(queries)
             }
             val => Err(LabeledError::new("invalid input type").with_label(
-                format!("could not convert input to a DNS record name: {:?}", val),
+                format!(
+                    "could not convert {} to a DNS record name; expected a string, a list of labels, or a record with a name column",
+                    val.get_type()
+                ),
                 val.span(),
             )),
         }
     }
 }
 
+/// Builds a name out of a list of labels given as binary, strings, ints, or
+/// bools.
+fn labels_to_name(labels: &[Value], span: Span) -> Result<Name, LabeledError> {
+    Name::from_labels(
+        labels
+            .iter()
+            .map(|val| match val {
+                Value::Binary { val: bin_val, .. } => Ok(bin_val.clone()),
+                Value::String { val: str_val, .. } => Ok(str_val.as_bytes().to_vec()),
+                Value::Int { val, .. } => {
+                    let bytes = val.to_ne_bytes();
+                    let non0 = bytes
+                        .iter()
+                        .position(|n| *n != 0)
+                        .unwrap_or(bytes.len() - 1);
+
+                    Ok(Vec::from(&bytes[non0..]))
+                }
+                Value::Bool { val, .. } => Ok(vec![*val as u8]),
+                Value::Nothing { .. } => Ok(vec![0]),
+
+                _ => Err(LabeledError::new("invalid name")
+                    .with_label("Invalid input type for name", val.span())),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    )
+    .map_err(|err| {
+        LabeledError::new("invalid name")
+            .with_label(format!("Error parsing into name: {}", err), span)
+    })
+}
+
 pub struct Record(pub(crate) hickory_proto::rr::resource::Record);
 
 impl Record {
     pub fn into_value(self, config: &Config) -> Result<Value, LabeledError> {
         let Record(record) = self;
+        let wire = match config.with_wire.item {
+            true => Some(util::canonical_wire(&record).map_err(|err| {
+                LabeledError::new("invalid record").with_label(
+                    format!("Could not encode record: {err}"),
+                    config.with_wire.span,
+                )
+            })?),
+            false => None,
+        };
         let parts = record.into_parts();
 
-        let name = Value::string(parts.name_labels.to_utf8(), Span::unknown());
-        let rtype = code_to_record_u16(parts.rr_type, config);
+        let name = Value::string(config.display_name(&parts.name_labels), Span::unknown());
+        let rtype = code_to_record_u16(Mnemonic(parts.rr_type), config);
         let class = code_to_record_u16(parts.dns_class, config);
         let ttl = util::sec_to_duration(parts.ttl);
+
+        // escaped presentation format is unambiguous, but not what anyone
+        // wants to match bytes against, so the raw bytes are kept alongside
+        let raw = match util::name_needs_escaping(&parts.name_labels)
+            || parts.rdata.as_ref().is_some_and(util::rdata_needs_escaping)
+        {
+            true => Value::record(
+                record![
+                    "name"  => Value::list(
+                        parts
+                            .name_labels
+                            .iter()
+                            .map(|label| Value::binary(label, Span::unknown()))
+                            .collect(),
+                        Span::unknown(),
+                    ),
+                    "rdata" => parts
+                        .rdata
+                        .as_ref()
+                        .and_then(|rdata| rdata.to_bytes().ok())
+                        .map_or(Value::nothing(Span::unknown()), |bytes| {
+                            Value::binary(bytes, Span::unknown())
+                        }),
+                ],
+                Span::unknown(),
+            ),
+            false => Value::nothing(Span::unknown()),
+        };
+
         let rdata = match parts.rdata {
             Some(data) => RData(data).into_value(config)?,
             None => Value::nothing(Span::unknown()),
         };
 
-        Ok(Value::record(
-            nu_protocol::Record::from_iter(std::iter::zip(
-                Vec::from_iter(constants::columns::RECORD_COLS.iter().map(|s| (*s).into())),
-                vec![name, rtype, class, ttl, rdata],
-            )),
-            Span::unknown(),
-        ))
+        let mut row = nu_protocol::Record::from_iter(std::iter::zip(
+            Vec::from_iter(constants::columns::RECORD_COLS.iter().map(|s| (*s).into())),
+            vec![name, rtype, class, ttl, rdata, raw],
+        ));
+
+        if config.output_schema.item == OutputSchema::V1 {
+            row.retain(|col, _| constants::columns::RECORD_COLS_V1.contains(&col));
+        }
+
+        if let Some(wire) = wire {
+            let sha256 = hickory_proto::rr::dnssec::DigestType::SHA256
+                .hash(&wire)
+                .map(|digest| data_encoding::HEXLOWER.encode(digest.as_ref()))
+                .map_err(|err| {
+                    LabeledError::new("digest failed")
+                        .with_label(err.to_string(), config.with_wire.span)
+                })?;
+            row.push("wire", Value::binary(wire, Span::unknown()));
+            row.push("sha256", Value::string(sha256, Span::unknown()));
+        }
+
+        Ok(Value::record(row, Span::unknown()))
+    }
+}
+
+/// The records of a section that share a name, type, and class, as one row
+/// whose TTL is the lowest of theirs, since that is how long the set as a
+/// whole can be cached. The raw column is left out, since it differs from
+/// record to record. So do the wire format and its digest, so with
+/// `--with-wire` each entry of `records` carries its own, next to its rdata.
+pub struct RRset(pub(crate) Vec<hickory_proto::rr::Record>);
+
+impl RRset {
+    pub fn into_value(self, config: &Config) -> Result<Value, LabeledError> {
+        let ttl = self.0.iter().map(|record| record.ttl()).min().unwrap_or(0);
+        let mut row = None;
+        let mut records = Vec::with_capacity(self.0.len());
+
+        for record in self.0 {
+            let mut cols = Record(record).into_value(config)?.into_record()?;
+            let mut take = |col| cols.remove(col).unwrap_or(Value::nothing(Span::unknown()));
+
+            let entry = match config.with_wire.item {
+                true => Value::record(
+                    record! {
+                        "rdata" => take("rdata"),
+                        "wire" => take("wire"),
+                        "sha256" => take("sha256"),
+                    },
+                    Span::unknown(),
+                ),
+                false => take("rdata"),
+            };
+            records.push(entry);
+
+            row.get_or_insert(cols);
+        }
+
+        let row = match row {
+            Some(mut row) => {
+                row.remove("raw");
+                row.insert("ttl", util::sec_to_duration(ttl));
+                row.push("records", Value::list(records, Span::unknown()));
+                row
+            }
+            None => nu_protocol::Record::new(),
+        };
+
+        Ok(Value::record(row, Span::unknown()))
+    }
+}
+
+impl TryFrom<&Value> for Record {
+    type Error = LabeledError;
+
+    /// Parses a row of a record table, as given to `dns serve`. The type,
+    /// class, and TTL may be left out, and default to A, IN, and an hour
+    /// respectively. The rdata is given in zone file presentation format, or
+    /// in the generic `\# length hex` format of RFC 3597, which also works
+    /// for types given as `TYPEnnn` that have no presentation format of their
+    /// own.
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let span = value.span();
+
+        let name = match util::column(value, constants::columns::NAME) {
+            Some(val) => {
+                let val_span = val.span();
+                util::parse_name(&val.into_string()?).map_err(|err| {
+                    LabeledError::new("invalid name")
+                        .with_label(format!("Error parsing name: {}", err), val_span)
+                })?
+            }
+            None => {
+                return Err(LabeledError::new("missing column")
+                    .with_label("record should have a name", span))
+            }
+        };
+
+        // the code columns of `dns query --code` output are accepted too
+        let code_or_self = |val: Value| match val {
+            val @ Value::Record { .. } => val.get_data_by_key("code").unwrap_or(val),
+            val => val,
+        };
+
+        let rtype = match util::column(value, constants::columns::TYPE).map(code_or_self) {
+            Some(val) => RType::try_from(&val)?.0,
+            None => RecordType::A,
+        };
+
+        let class = match util::column(value, constants::columns::CLASS).map(code_or_self) {
+            Some(val) => DNSClass::try_from(val)?.0,
+            None => hickory_proto::rr::DNSClass::IN,
+        };
+
+        let ttl = match util::column(value, "ttl") {
+            Some(val @ Value::Duration { .. }) => u32::try_from(val.as_duration()? / 1_000_000_000)
+                .map_err(|err| {
+                    LabeledError::new("invalid TTL")
+                        .with_label(format!("should be a positive duration: {err}"), val.span())
+                })?,
+            Some(val @ Value::Int { .. }) => u32::try_from(val.as_int()?).map_err(|err| {
+                LabeledError::new("invalid TTL").with_label(
+                    format!("should be a positive number of seconds: {err}"),
+                    val.span(),
+                )
+            })?,
+            Some(val) => {
+                return Err(LabeledError::new("invalid TTL").with_label(
+                    "TTL should be a duration or a number of seconds",
+                    val.span(),
+                ))
+            }
+            None => 3600,
+        };
+
+        let rdata = match util::column(value, "rdata") {
+            // addresses as they come out of a query
+            Some(val @ Value::Record { .. })
+                if matches!(rtype, RecordType::A | RecordType::AAAA) =>
+            {
+                let ip = util::column(&val, "ip").ok_or_else(|| {
+                    LabeledError::new("invalid rdata")
+                        .with_label("address rdata should have an ip column", val.span())
+                })?;
+                let ip_span = ip.span();
+
+                match (rtype, ip.as_str()?.parse::<IpAddr>()) {
+                    (RecordType::A, Ok(IpAddr::V4(addr))) => {
+                        hickory_proto::rr::RData::A(addr.into())
+                    }
+                    (RecordType::AAAA, Ok(IpAddr::V6(addr))) => {
+                        hickory_proto::rr::RData::AAAA(addr.into())
+                    }
+                    (_, Ok(_)) => {
+                        return Err(LabeledError::new("invalid rdata").with_label(
+                            format!("address is the wrong family for a {rtype} record"),
+                            ip_span,
+                        ))
+                    }
+                    (_, Err(err)) => {
+                        return Err(LabeledError::new("invalid rdata")
+                            .with_label(format!("Error parsing address: {err}"), ip_span))
+                    }
+                }
+            }
+            Some(val @ Value::String { .. }) => {
+                let val_span = val.span();
+                let rdata = val.into_string()?;
+                let rdata_err = |err: String| {
+                    LabeledError::new("invalid rdata").with_label(
+                        format!(
+                            "Error parsing {} rdata: {}",
+                            util::rtype_mnemonic(rtype),
+                            err
+                        ),
+                        val_span,
+                    )
+                };
+
+                match util::parse_generic_rdata(rtype, &rdata) {
+                    Some(parsed) => parsed.map_err(rdata_err)?,
+                    None if matches!(rtype, RecordType::Unknown(_)) => {
+                        return Err(rdata_err(
+                            "rdata of unknown types should be in the generic \\# format, e.g. \"\\# 4 0A000001\"".into(),
+                        ))
+                    }
+                    None => hickory_proto::rr::RData::try_from_str(rtype, &rdata)
+                        .map_err(|err| rdata_err(err.to_string()))?,
+                }
+            }
+            Some(val) => {
+                return Err(LabeledError::new("invalid rdata").with_label(
+                    "rdata should be a string in zone file format, e.g. \"10 mail.example.com.\"",
+                    val.span(),
+                ))
+            }
+            None => {
+                return Err(LabeledError::new("missing column")
+                    .with_label("record should have rdata", span))
+            }
+        };
+
+        let mut record = hickory_proto::rr::Record::from_rdata(name, ttl, rdata);
+        record.set_dns_class(class);
+
+        Ok(Record(record))
     }
 }
 
@@ -394,6 +948,17 @@ pub struct RData(pub(crate) hickory_proto::rr::RData);
 impl RData {
     pub fn into_value(self, config: &Config) -> Result<Value, LabeledError> {
         let val = match self.0 {
+            hickory_proto::rr::RData::A(addr) if config.output_schema.item == OutputSchema::V1 => {
+                Value::string(addr.to_string(), Span::unknown())
+            }
+            hickory_proto::rr::RData::AAAA(addr)
+                if config.output_schema.item == OutputSchema::V1 =>
+            {
+                Value::string(addr.to_string(), Span::unknown())
+            }
+            hickory_proto::rr::RData::A(addr) => util::address_value(IpAddr::V4(addr.0)),
+            hickory_proto::rr::RData::AAAA(addr) => util::address_value(IpAddr::V6(addr.0)),
+
             hickory_proto::rr::RData::CAA(caa) => {
                 let issuer_ctitical = Value::bool(caa.issuer_critical(), Span::unknown());
                 let tag = Value::string(caa.tag().as_str(), Span::unknown());
@@ -445,13 +1010,16 @@ impl RData {
                     Span::unknown(),
                 )
             }
+            hickory_proto::rr::RData::CNAME(name) => {
+                Value::string(config.display_name(&name), Span::unknown())
+            }
             // CSYNC seems to be missing some accessors in the trust-dns lib,
             // which oddly enough actually are serialized in the `Display` impl,
             // so just use that
             // hickory_proto::rr::RData::CSYNC(_) => todo!(),
             hickory_proto::rr::RData::HINFO(hinfo) => {
-                let cpu = util::string_or_binary(hinfo.cpu());
-                let os = util::string_or_binary(hinfo.os());
+                let cpu = util::escape_text(hinfo.cpu());
+                let os = util::escape_text(hinfo.os());
 
                 Value::record(
                     record!(
@@ -465,7 +1033,8 @@ impl RData {
             hickory_proto::rr::RData::HTTPS(hickory_proto::rr::rdata::HTTPS(svcb))
             | hickory_proto::rr::RData::SVCB(svcb) => {
                 let svc_priority = Value::int(svcb.svc_priority() as i64, Span::unknown());
-                let target_name = Value::string(svcb.target_name().to_string(), Span::unknown());
+                let target_name =
+                    Value::string(config.display_name(svcb.target_name()), Span::unknown());
                 let svc_params = svcb.svc_params().iter().map(|(key, value)| {
                     let value = match value {
                         SvcParamValue::Mandatory(param_keys) => Value::list(
@@ -527,7 +1096,7 @@ impl RData {
 
             hickory_proto::rr::RData::MX(mx) => {
                 let preference = Value::int(mx.preference() as i64, Span::unknown());
-                let exchange = Value::string(mx.exchange().to_string(), Span::unknown());
+                let exchange = Value::string(config.display_name(mx.exchange()), Span::unknown());
 
                 Value::record(
                     record![
@@ -541,10 +1110,11 @@ impl RData {
             hickory_proto::rr::RData::NAPTR(naptr) => {
                 let order = Value::int(naptr.order() as i64, Span::unknown());
                 let preference = Value::int(naptr.preference() as i64, Span::unknown());
-                let flags = util::string_or_binary(naptr.flags());
-                let services = util::string_or_binary(naptr.services());
-                let regexp = util::string_or_binary(naptr.regexp());
-                let replacement = Value::string(naptr.replacement().to_string(), Span::unknown());
+                let flags = util::escape_text(naptr.flags());
+                let services = util::escape_text(naptr.services());
+                let regexp = util::escape_text(naptr.regexp());
+                let replacement =
+                    Value::string(config.display_name(naptr.replacement()), Span::unknown());
 
                 Value::record(
                     record![
@@ -560,16 +1130,20 @@ impl RData {
             }
 
             hickory_proto::rr::RData::NULL(null) => util::string_or_binary(null.anything()),
-            hickory_proto::rr::RData::NS(ns) => Value::string(ns.to_string(), Span::unknown()),
+            hickory_proto::rr::RData::NS(ns) => {
+                Value::string(config.display_name(&ns), Span::unknown())
+            }
             hickory_proto::rr::RData::OPENPGPKEY(key) => {
                 Value::binary(key.public_key(), Span::unknown())
             }
             hickory_proto::rr::RData::OPT(opt) => Opt(&opt).into_value(config),
-            hickory_proto::rr::RData::PTR(name) => Value::string(name.to_string(), Span::unknown()),
+            hickory_proto::rr::RData::PTR(name) => {
+                Value::string(config.display_name(&name), Span::unknown())
+            }
 
             hickory_proto::rr::RData::SOA(soa) => {
-                let mname = Value::string(soa.mname().to_string(), Span::unknown());
-                let rname = Value::string(soa.rname().to_string(), Span::unknown());
+                let mname = Value::string(config.display_name(soa.mname()), Span::unknown());
+                let rname = Value::string(config.display_name(soa.rname()), Span::unknown());
                 let serial = Value::int(soa.serial() as i64, Span::unknown());
                 let refresh = util::sec_to_duration(soa.refresh() as u64);
                 let retry = util::sec_to_duration(soa.retry() as u64);
@@ -594,7 +1168,7 @@ impl RData {
                 let priority = Value::int(srv.priority() as i64, Span::unknown());
                 let weight = Value::int(srv.weight() as i64, Span::unknown());
                 let port = Value::int(srv.port() as i64, Span::unknown());
-                let target = Value::string(srv.target().to_string(), Span::unknown());
+                let target = Value::string(config.display_name(srv.target()), Span::unknown());
 
                 Value::record(
                     record![
@@ -679,7 +1253,7 @@ impl RData {
             }
             hickory_proto::rr::RData::TXT(data) => Value::list(
                 data.iter()
-                    .map(|txt_data| util::string_or_binary(Vec::from(txt_data.clone())))
+                    .map(|txt_data| util::escape_text(txt_data))
                     .collect(),
                 Span::unknown(),
             ),
@@ -768,12 +1342,16 @@ impl RData {
                     )
                 }
                 DNSSECRData::NSEC(nsec) => {
-                    let next_domain_name =
-                        Value::string(nsec.next_domain_name().to_string(), Span::unknown());
+                    let next_domain_name = Value::string(
+                        config.display_name(nsec.next_domain_name()),
+                        Span::unknown(),
+                    );
                     let types = Value::list(
                         nsec.type_bit_maps()
                             .iter()
-                            .map(|rtype| Value::string(rtype.to_string(), Span::unknown()))
+                            .map(|rtype| {
+                                Value::string(util::rtype_mnemonic(*rtype), Span::unknown())
+                            })
                             .collect(),
                         Span::unknown(),
                     );
@@ -802,7 +1380,9 @@ impl RData {
                         nsec3
                             .type_bit_maps()
                             .iter()
-                            .map(|rtype| Value::string(rtype.to_string(), Span::unknown()))
+                            .map(|rtype| {
+                                Value::string(util::rtype_mnemonic(*rtype), Span::unknown())
+                            })
                             .collect(),
                         Span::unknown(),
                     );
@@ -844,14 +1424,15 @@ impl RData {
                 }
                 DNSSECRData::SIG(sig) => {
                     let type_covered =
-                        Value::string(sig.type_covered().to_string(), Span::unknown());
+                        Value::string(util::rtype_mnemonic(sig.type_covered()), Span::unknown());
                     let algorithm = Value::string(sig.algorithm().to_string(), Span::unknown());
                     let num_labels = Value::int(sig.num_labels() as i64, Span::unknown());
                     let original_ttl = util::sec_to_duration(sig.original_ttl());
                     let sig_expiration = util::sec_to_date(sig.sig_expiration(), Span::unknown())?;
                     let sig_inception = util::sec_to_date(sig.sig_inception(), Span::unknown())?;
                     let key_tag = Value::int(sig.key_tag() as i64, Span::unknown());
-                    let signer_name = Value::string(sig.signer_name().to_string(), Span::unknown());
+                    let signer_name =
+                        Value::string(config.display_name(sig.signer_name()), Span::unknown());
                     let sig = Value::binary(sig.sig(), Span::unknown());
 
                     Value::record(
@@ -892,22 +1473,14 @@ impl RData {
                         Span::unknown(),
                     )
                 }
-                DNSSECRData::Unknown { code, rdata } => Value::record(
-                    record![
-                        "code"  => Value::int(code as i64, Span::unknown()),
-                        "rdata" => Value::binary(rdata.anything(), Span::unknown()),
-                    ],
-                    Span::unknown(),
-                ),
+                DNSSECRData::Unknown { rdata, .. } => {
+                    Value::string(util::generic_rdata(rdata.anything()), Span::unknown())
+                }
                 rdata => Value::string(rdata.to_string(), Span::unknown()),
             },
-            hickory_proto::rr::RData::Unknown { code: rtype, rdata } => Value::record(
-                record![
-                    "code"  => Value::int(u16::from(rtype) as i64, Span::unknown()),
-                    "rdata" => Value::binary(rdata.anything(), Span::unknown()),
-                ],
-                Span::unknown(),
-            ),
+            hickory_proto::rr::RData::Unknown { rdata, .. } => {
+                Value::string(util::generic_rdata(rdata.anything()), Span::unknown())
+            }
             rdata => Value::string(rdata.to_string(), Span::unknown()),
         };
 
@@ -918,18 +1491,7 @@ impl RData {
 fn parse_ds<D: Deref<Target = dnssec::rdata::DS>>(ds: D) -> Value {
     let key_tag = Value::int(ds.key_tag() as i64, Span::unknown());
     let algorithm = Value::string(ds.algorithm().to_string(), Span::unknown());
-    let digest_type = Value::string(
-        Into::<String>::into(match ds.digest_type() {
-            dnssec::DigestType::SHA1 => "SHA-1",
-            dnssec::DigestType::SHA256 => "SHA-256",
-            dnssec::DigestType::GOSTR34_11_94 => "GOST R 34.11-94",
-            dnssec::DigestType::SHA384 => "SHA-384",
-            dnssec::DigestType::SHA512 => "SHA-512",
-            dnssec::DigestType::ED25519 => "ED25519",
-            _ => "unknown",
-        }),
-        Span::unknown(),
-    );
+    let digest_type = Value::string(util::digest_type_name(ds.digest_type()), Span::unknown());
     let digest = Value::binary(ds.digest(), Span::unknown());
     Value::record(
         record![
@@ -988,10 +1550,13 @@ pub struct Opt<'o>(pub(crate) &'o hickory_proto::rr::rdata::OPT);
 
 impl<'o> Opt<'o> {
     pub fn into_value(self, _config: &Config) -> Value {
-        let opts: HashMap<_, _> = self
-            .0
-            .as_ref()
-            .iter()
+        // options are kept in a hash map, so they are put in order of their
+        // codes to keep the output stable
+        let mut opts: Vec<_> = self.0.as_ref().iter().collect();
+        opts.sort_by_key(|(code, _)| u16::from(**code));
+
+        let opts: Vec<_> = opts
+            .into_iter()
             .map(|(code, option)| {
                 let code = match code {
                     EdnsCode::Zero => "zero".into(),
@@ -1007,6 +1572,7 @@ impl<'o> Opt<'o> {
                     EdnsCode::Keepalive => "keepalive".into(),
                     EdnsCode::Padding => "padding".into(),
                     EdnsCode::Chain => "chain".into(),
+                    EdnsCode::Unknown(constants::edns::EDE) => "EDE".into(),
                     EdnsCode::Unknown(code) => format!("unknown({})", code),
                     ednscode => format!("unknown Edns: {:?}", ednscode),
                 };
@@ -1021,14 +1587,34 @@ impl<'o> Opt<'o> {
                             .collect(),
                         Span::unknown(),
                     ),
-                    EdnsOption::Unknown(code, val) => Value::record(
-                        record![
+                    EdnsOption::Subnet(subnet) => match Vec::<u8>::try_from(subnet) {
+                        Ok(bytes) => util::client_subnet(&bytes),
+                        Err(_) => Value::nothing(Span::unknown()),
+                    },
+                    EdnsOption::Unknown(code, val) => {
+                        let mut option = record![
                             "code" => Value::int(*code as i64, Span::unknown()),
-                            "data" => util::string_or_binary(val.clone()),
-                        ],
-                        Span::unknown(),
-                    ),
-                    _ => todo!(),
+                            "data" => Value::string(data_encoding::HEXLOWER.encode(val), Span::unknown()),
+                        ];
+
+                        for (col, decoded) in decode_option(*code, val) {
+                            option.push(col, decoded);
+                        }
+
+                        Value::record(option, Span::unknown())
+                    }
+                    // anything newer is shown as code and hex, like unknown
+                    // options
+                    option => match Vec::<u8>::try_from(option) {
+                        Ok(val) => Value::record(
+                            record![
+                                "code" => Value::int(u16::from(EdnsCode::from(option)) as i64, Span::unknown()),
+                                "data" => Value::string(data_encoding::HEXLOWER.encode(&val), Span::unknown()),
+                            ],
+                            Span::unknown(),
+                        ),
+                        Err(_) => Value::nothing(Span::unknown()),
+                    },
                 };
 
                 (code, option)
@@ -1039,6 +1625,61 @@ impl<'o> Opt<'o> {
     }
 }
 
+/// Decodes the options hickory leaves as bytes, for the columns to show
+/// alongside their code and hex data. Options that are too short for what
+/// they should hold are left as they are.
+fn decode_option(code: u16, data: &[u8]) -> Vec<(&'static str, Value)> {
+    let int = |val: u64| Value::int(val as i64, Span::unknown());
+
+    match (code, data) {
+        (constants::edns::NSID, id) => vec![("text", util::string_or_binary(id))],
+        (constants::edns::EXPIRE, [a, b, c, d]) => vec![(
+            "expire",
+            util::sec_to_duration(u32::from_be_bytes([*a, *b, *c, *d])),
+        )],
+        (constants::edns::COOKIE, cookie) if cookie.len() >= 8 => vec![
+            ("client", Value::binary(&cookie[..8], Span::unknown())),
+            (
+                "server",
+                match &cookie[8..] {
+                    [] => Value::nothing(Span::unknown()),
+                    server => Value::binary(server, Span::unknown()),
+                },
+            ),
+        ],
+        // the timeout is in units of 100 milliseconds
+        (constants::edns::KEEPALIVE, [a, b]) => vec![(
+            "timeout",
+            util::std_to_duration(std::time::Duration::from_millis(
+                u64::from(u16::from_be_bytes([*a, *b])) * 100,
+            )),
+        )],
+        (constants::edns::PADDING, padding) => vec![("length", int(padding.len() as u64))],
+        (constants::edns::EDE, [a, b, text @ ..]) => {
+            let info_code = u16::from_be_bytes([*a, *b]);
+
+            vec![
+                ("info_code", int(info_code.into())),
+                (
+                    "purpose",
+                    match util::ede_purpose(info_code) {
+                        Some(purpose) => Value::string(purpose, Span::unknown()),
+                        None => Value::nothing(Span::unknown()),
+                    },
+                ),
+                (
+                    "extra_text",
+                    match text {
+                        [] => Value::nothing(Span::unknown()),
+                        text => Value::string(String::from_utf8_lossy(text), Span::unknown()),
+                    },
+                ),
+            ]
+        }
+        _ => Vec::new(),
+    }
+}
+
 pub struct RType(pub(crate) hickory_proto::rr::RecordType);
 
 impl TryFrom<&Value> for RType {
@@ -1051,22 +1692,29 @@ impl TryFrom<&Value> for RType {
         };
 
         match value {
-            Value::String { .. } => Ok(RType(
-                RecordType::from_str(&value.as_str().unwrap().to_uppercase())
-                    .map_err(|err| qtype_err(err, value.span()))?,
-            )),
-            Value::Int { val, .. } => {
-                let rtype = RecordType::from(*val as u16);
-
-                if let RecordType::Unknown(r) = rtype {
-                    return Err(LabeledError::new("invalid record type").with_label(
-                        format!("Error parsing record type: unknown code: {}", r),
-                        value.span(),
-                    ));
+            Value::String { .. } => {
+                let rtype = value.as_str().unwrap().to_uppercase();
+
+                // RFC 3597 names types without a mnemonic TYPEnnn
+                if let Some(code) = rtype
+                    .strip_prefix("TYPE")
+                    .and_then(|code| code.parse::<u16>().ok())
+                {
+                    return Ok(RType(RecordType::from(code)));
                 }
 
-                Ok(RType(rtype))
+                Ok(RType(
+                    RecordType::from_str(&rtype).map_err(|err| qtype_err(err, value.span()))?,
+                ))
             }
+            Value::Int { val, .. } => Ok(RType(RecordType::from(u16::try_from(*val).map_err(
+                |err| {
+                    LabeledError::new("invalid record type").with_label(
+                        format!("record type code should be between 0 and 65535: {err}"),
+                        value.span(),
+                    )
+                },
+            )?))),
             value => Err(LabeledError::new("invalid record type").with_label(
                 "Invalid type for record type argument. Must be either string or int.",
                 value.span(),
@@ -1091,7 +1739,14 @@ impl TryFrom<Value> for DNSClass {
                 hickory_proto::rr::DNSClass::from_str(&value.as_str().unwrap().to_uppercase())
                     .map_err(|err| class_err(err, value.span()))?,
             ),
-            Value::Int { val, .. } => DNSClass(hickory_proto::rr::DNSClass::from(val as u16)),
+            Value::Int { val, .. } => DNSClass(hickory_proto::rr::DNSClass::from(
+                u16::try_from(val).map_err(|err| {
+                    LabeledError::new("invalid DNS class").with_label(
+                        format!("class code should be between 0 and 65535: {err}"),
+                        value.span(),
+                    )
+                })?,
+            )),
             value => {
                 return Err(LabeledError::new("invalid DNS class").with_label(
                     "Invalid type for class type argument. Must be either string or int.",
@@ -1104,6 +1759,83 @@ impl TryFrom<Value> for DNSClass {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RCode(pub(crate) ResponseCode);
+
+impl RCode {
+    /// The conventional mnemonic for the response code, e.g. `NXDOMAIN`, as
+    /// opposed to the prose description given by its `Display` impl.
+    pub fn mnemonic(&self) -> String {
+        match self.0 {
+            ResponseCode::NoError => "NOERROR".into(),
+            ResponseCode::FormErr => "FORMERR".into(),
+            ResponseCode::ServFail => "SERVFAIL".into(),
+            ResponseCode::NXDomain => "NXDOMAIN".into(),
+            ResponseCode::NotImp => "NOTIMP".into(),
+            ResponseCode::Refused => "REFUSED".into(),
+            ResponseCode::YXDomain => "YXDOMAIN".into(),
+            ResponseCode::YXRRSet => "YXRRSET".into(),
+            ResponseCode::NXRRSet => "NXRRSET".into(),
+            ResponseCode::NotAuth => "NOTAUTH".into(),
+            ResponseCode::NotZone => "NOTZONE".into(),
+            ResponseCode::BADVERS => "BADVERS".into(),
+            ResponseCode::BADSIG => "BADSIG".into(),
+            ResponseCode::BADKEY => "BADKEY".into(),
+            ResponseCode::BADTIME => "BADTIME".into(),
+            ResponseCode::BADMODE => "BADMODE".into(),
+            ResponseCode::BADNAME => "BADNAME".into(),
+            ResponseCode::BADALG => "BADALG".into(),
+            ResponseCode::BADTRUNC => "BADTRUNC".into(),
+            ResponseCode::BADCOOKIE => "BADCOOKIE".into(),
+            ResponseCode::Unknown(code) => format!("RCODE{code}"),
+        }
+    }
+
+    pub fn into_value(self) -> Value {
+        code_record(self.mnemonic(), u16::from(self.0) as i64)
+    }
+}
+
+impl TryFrom<&Value> for RCode {
+    type Error = LabeledError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String { val, .. } => {
+                let upper = val.to_uppercase();
+
+                // accept the mnemonic as well as the prose description, e.g.
+                // "No Error"
+                (0..=u16::from(ResponseCode::BADCOOKIE))
+                    .map(|code| RCode(<ResponseCode as From<u16>>::from(code)))
+                    .find(|rcode| {
+                        rcode.mnemonic() == upper || rcode.0.to_str().to_uppercase() == upper
+                    })
+                    .or_else(|| {
+                        upper
+                            .strip_prefix("RCODE")
+                            .and_then(|code| code.parse::<u16>().ok())
+                            .map(|code| RCode(<ResponseCode as From<u16>>::from(code)))
+                    })
+                    .ok_or_else(|| {
+                        LabeledError::new("invalid response code")
+                            .with_label(format!("Unknown response code: {val}"), value.span())
+                    })
+            }
+            Value::Int { val, .. } => u16::try_from(*val)
+                .map(|code| RCode(<ResponseCode as From<u16>>::from(code)))
+                .map_err(|err| {
+                    LabeledError::new("invalid response code")
+                        .with_label(format!("Invalid response code: {err}"), value.span())
+                }),
+            value => Err(LabeledError::new("invalid response code").with_label(
+                "Invalid type for response code argument. Must be either string or int.",
+                value.span(),
+            )),
+        }
+    }
+}
+
 pub struct Protocol(pub(crate) hickory_resolver::config::Protocol);
 
 impl TryFrom<Value> for Protocol {
@@ -1134,7 +1866,7 @@ impl TryFrom<Value> for Protocol {
     }
 }
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub enum DnssecMode {
     None,
     Strict,
@@ -1159,17 +1891,604 @@ impl TryFrom<Value> for DnssecMode {
                     ));
                 }
             }),
+            // plain on/off, where on validates whenever records are signed
+            Value::Bool { val: true, .. } => Ok(DnssecMode::Opportunistic),
+            Value::Bool { val: false, .. } => Ok(DnssecMode::None),
             _ => Err(LabeledError::new("invalid input")
-                .with_label("Input must be a string", value.span())),
+                .with_label("Input must be a string or bool", value.span())),
+        }
+    }
+}
+
+impl std::fmt::Display for DnssecMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DnssecMode::None => "none",
+            DnssecMode::Strict => "strict",
+            DnssecMode::Opportunistic => "opportunistic",
+        })
+    }
+}
+
+impl std::str::FromStr for DnssecMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(DnssecMode::None),
+            "strict" => Ok(DnssecMode::Strict),
+            "opportunistic" => Ok(DnssecMode::Opportunistic),
+            _ => Err(format!("unknown DNSSEC mode {s}")),
+        }
+    }
+}
+
+/// The layout of the records that responses are returned as. Each version
+/// only changes what is listed here, so that scripts written against one
+/// keep working as the default moves on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OutputSchema {
+    /// The columns of 3.0.7: messages have only header, question, answer,
+    /// authority, additional, edns, and size, a message without a question
+    /// has an empty record for it, records have no raw column, and A and
+    /// AAAA rdata is the address as a string. Columns that opt-in flags add,
+    /// and how values are formatted, are as in v2.
+    V1,
+    /// The current columns, with A and AAAA rdata as a record of the
+    /// address, its family, and its reverse name.
+    #[default]
+    V2,
+}
+
+impl TryFrom<Value> for OutputSchema {
+    type Error = LabeledError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let span = value.span();
+        match value.as_str()?.to_lowercase().as_str() {
+            "v1" | "1" => Ok(OutputSchema::V1),
+            "v2" | "2" => Ok(OutputSchema::V2),
+            _ => Err(LabeledError::new("invalid output schema")
+                .with_label("Invalid output schema. Must be one of: v1, v2", span)),
         }
     }
 }
 
 pub mod util {
-    use std::time::Duration;
+    use std::{net::IpAddr, time::Duration};
 
     use chrono::TimeZone;
-    use nu_protocol::{LabeledError, Span, Value};
+    use hickory_proto::serialize::binary::BinEncodable;
+    use hickory_resolver::Name;
+    use nu_protocol::{record, LabeledError, Span, Value};
+
+    /// For ANY queries, whether the server declined to answer them in full,
+    /// which per [RFC 8482](https://www.rfc-editor.org/rfc/rfc8482#section-4.2)
+    /// it signals by answering with just an HINFO record whose CPU is
+    /// "RFC8482". `None` for any other query type.
+    pub fn minimal_any(message: &hickory_proto::op::Message) -> Option<bool> {
+        let query = message.queries().first()?;
+
+        if query.query_type() != hickory_proto::rr::RecordType::ANY {
+            return None;
+        }
+
+        let answers = message.answers();
+
+        Some(
+            !answers.is_empty()
+                && answers.iter().all(|record| {
+                    matches!(
+                        record.data(),
+                        Some(hickory_proto::rr::RData::HINFO(hinfo))
+                            if hinfo.cpu().eq_ignore_ascii_case(b"RFC8482")
+                    )
+                }),
+        )
+    }
+
+    /// Decodes an EDNS client subnet option (RFC 7871 section 6) from its wire
+    /// format, since hickory doesn't expose its fields.
+    pub fn client_subnet(bytes: &[u8]) -> Value {
+        let [family_hi, family_lo, source, scope, addr @ ..] = bytes else {
+            return Value::nothing(Span::unknown());
+        };
+
+        let address = match u16::from_be_bytes([*family_hi, *family_lo]) {
+            1 => {
+                let mut octets = [0; 4];
+                let len = addr.len().min(4);
+                octets[..len].copy_from_slice(&addr[..len]);
+                std::net::IpAddr::from(octets)
+            }
+            2 => {
+                let mut octets = [0; 16];
+                let len = addr.len().min(16);
+                octets[..len].copy_from_slice(&addr[..len]);
+                std::net::IpAddr::from(octets)
+            }
+            _ => return Value::nothing(Span::unknown()),
+        };
+
+        Value::record(
+            record![
+                "address"      => Value::string(format!("{address}/{source}"), Span::unknown()),
+                "scope_prefix" => Value::int(*scope as i64, Span::unknown()),
+            ],
+            Span::unknown(),
+        )
+    }
+
+    /// Encodes a record in the canonical form of [RFC 4034 section
+    /// 6.2](https://www.rfc-editor.org/rfc/rfc4034#section-6.2): no name
+    /// compression, and the owner name, along with the names in the RDATA of
+    /// the types listed there, in lowercase. The TTL is kept as received.
+    pub fn canonical_wire(
+        record: &hickory_proto::rr::Record,
+    ) -> Result<Vec<u8>, hickory_proto::error::ProtoError> {
+        use hickory_proto::serialize::binary::{BinEncoder, EncodeMode};
+
+        let mut record = record.clone();
+        record.set_name(record.name().to_lowercase());
+
+        let mut buf = Vec::new();
+        let mut encoder = BinEncoder::with_mode(&mut buf, EncodeMode::Signing);
+        encoder.set_canonical_names(true);
+        record.emit(&mut encoder)?;
+
+        Ok(buf)
+    }
+
+    pub fn digest_type_name(digest_type: hickory_proto::rr::dnssec::DigestType) -> &'static str {
+        use hickory_proto::rr::dnssec::DigestType;
+
+        match digest_type {
+            DigestType::SHA1 => "SHA-1",
+            DigestType::SHA256 => "SHA-256",
+            DigestType::GOSTR34_11_94 => "GOST R 34.11-94",
+            DigestType::SHA384 => "SHA-384",
+            DigestType::SHA512 => "SHA-512",
+            DigestType::ED25519 => "ED25519",
+            _ => "unknown",
+        }
+    }
+
+    /// What an extended DNS error info code means, per the IANA registry
+    /// started by [RFC 8914](https://www.rfc-editor.org/rfc/rfc8914#section-5.2).
+    pub fn ede_purpose(info_code: u16) -> Option<&'static str> {
+        Some(match info_code {
+            0 => "Other Error",
+            1 => "Unsupported DNSKEY Algorithm",
+            2 => "Unsupported DS Digest Type",
+            3 => "Stale Answer",
+            4 => "Forged Answer",
+            5 => "DNSSEC Indeterminate",
+            6 => "DNSSEC Bogus",
+            7 => "Signature Expired",
+            8 => "Signature Not Yet Valid",
+            9 => "DNSKEY Missing",
+            10 => "RRSIGs Missing",
+            11 => "No Zone Key Bit Set",
+            12 => "NSEC Missing",
+            13 => "Cached Error",
+            14 => "Not Ready",
+            15 => "Blocked",
+            16 => "Censored",
+            17 => "Filtered",
+            18 => "Prohibited",
+            19 => "Stale NXDomain Answer",
+            20 => "Not Authoritative",
+            21 => "Not Supported",
+            22 => "No Reachable Authority",
+            23 => "Network Error",
+            24 => "Invalid Data",
+            25 => "Signature Expired before Valid",
+            26 => "Too Early",
+            27 => "Unsupported NSEC3 Iterations Value",
+            28 => "Unable to conform to policy",
+            29 => "Synthesized",
+            30 => "Invalid Query Type",
+            _ => return None,
+        })
+    }
+
+    /// Formats bytes like `xxd`: an offset, 16 bytes in hex, and the same
+    /// bytes as ASCII with anything unprintable shown as a dot.
+    pub fn hexdump(bytes: &[u8]) -> String {
+        bytes
+            .chunks(16)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let hex: Vec<String> = chunk
+                    .chunks(2)
+                    .map(|pair| data_encoding::HEXLOWER.encode(pair))
+                    .collect();
+                let ascii: String = chunk
+                    .iter()
+                    .map(|b| match b {
+                        0x20..=0x7e => *b as char,
+                        _ => '.',
+                    })
+                    .collect();
+
+                format!("{:08x}: {:<40}  {}\n", i * 16, hex.join(" "), ascii)
+            })
+            .collect()
+    }
+
+    /// Randomizes the case of the letters in a name, per
+    /// [draft-vixie-dnsext-dns0x20](https://datatracker.ietf.org/doc/html/draft-vixie-dnsext-dns0x20-00).
+    /// A server that echoes the question back as is will preserve it, which a
+    /// spoofer guessing at the question would have to match too.
+    pub fn randomize_case(name: &Name) -> Name {
+        let labels = name.iter().map(|label| {
+            label
+                .iter()
+                .map(|byte| match rand::random() {
+                    true => byte.to_ascii_uppercase(),
+                    false => byte.to_ascii_lowercase(),
+                })
+                .collect::<Vec<u8>>()
+        });
+
+        match Name::from_labels(labels) {
+            Ok(mut randomized) => {
+                randomized.set_fqdn(name.is_fqdn());
+                randomized
+            }
+            Err(_) => name.clone(),
+        }
+    }
+
+    /// Turns the RNAME of a SOA record into the email address it stands for:
+    /// the first label is the mailbox, and may itself contain dots, escaped
+    /// in presentation format, e.g. `john\.doe.example.com.` for
+    /// `john.doe@example.com`.
+    pub fn rname_to_email(rname: &Name) -> Option<String> {
+        let mut labels = rname.iter();
+        let mailbox = String::from_utf8_lossy(labels.next()?);
+        let domain: Vec<_> = labels.map(String::from_utf8_lossy).collect();
+
+        if domain.is_empty() {
+            return None;
+        }
+
+        Some(format!("{mailbox}@{}", domain.join(".")))
+    }
+
+    /// Characters that have to be escaped with a backslash in presentation
+    /// format, besides those that are escaped as `\DDD`.
+    fn is_special(byte: u8) -> bool {
+        matches!(
+            byte,
+            b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' | b' '
+        )
+    }
+
+    /// Whether a name or character-string would need escaping to be shown in
+    /// presentation format.
+    pub fn needs_escaping(bytes: &[u8], in_name: bool) -> bool {
+        bytes.iter().any(|byte| match byte {
+            b' ' if !in_name => false,
+            b'\\' | b'"' => true,
+            byte if in_name && is_special(*byte) => true,
+            0x21..=0x7e => false,
+            _ => true,
+        })
+    }
+
+    /// Writes bytes as in [RFC 1035](https://www.rfc-editor.org/rfc/rfc1035#section-5.1)
+    /// presentation format: printable ASCII as is, characters with a special
+    /// meaning escaped with a backslash, and anything else as `\DDD` in
+    /// decimal. In names, dots within a label are special too; in
+    /// character-strings, which are quoted, spaces aren't.
+    pub fn escape_bytes(bytes: &[u8], in_name: bool) -> String {
+        let mut escaped = String::with_capacity(bytes.len());
+
+        for byte in bytes {
+            match byte {
+                b' ' if !in_name => escaped.push(' '),
+                b'\\' | b'"' => {
+                    escaped.push('\\');
+                    escaped.push(*byte as char);
+                }
+                byte if in_name && is_special(*byte) => {
+                    escaped.push('\\');
+                    escaped.push(*byte as char);
+                }
+                0x21..=0x7e => escaped.push(*byte as char),
+                byte => escaped.push_str(&format!("\\{byte:03}")),
+            }
+        }
+
+        escaped
+    }
+
+    /// Shows a name in presentation format, with each label escaped as need
+    /// be. IDNA labels (`xn--`) that are otherwise plain are still shown in
+    /// Unicode, as before. The root name is always shown as `.`, but other
+    /// names only end in one if `root_dot` is set.
+    pub fn escape_name(name: &Name, root_dot: bool) -> String {
+        if name.is_root() {
+            return ".".into();
+        }
+
+        let labels: Vec<String> = name
+            .iter()
+            .map(|label| {
+                if needs_escaping(label, true) {
+                    escape_bytes(label, true)
+                } else {
+                    hickory_proto::rr::domain::Label::from_raw_bytes(label)
+                        .map(|label| label.to_utf8())
+                        .unwrap_or_else(|_| escape_bytes(label, true))
+                }
+            })
+            .collect();
+
+        match root_dot {
+            true => format!("{}.", labels.join(".")),
+            false => labels.join("."),
+        }
+    }
+
+    /// Parses a name in presentation format, taking the escapes of
+    /// [`escape_name`] as raw bytes. Names without escapes are parsed as
+    /// UTF-8, so that IDNs are converted to punycode.
+    pub fn parse_name(s: &str) -> Result<Name, String> {
+        if !s.contains('\\') {
+            return Name::from_utf8(s).map_err(|err| err.to_string());
+        }
+
+        let (labels, fqdn) = split_labels(s)?;
+        let mut name = Name::from_labels(labels).map_err(|err| err.to_string())?;
+        name.set_fqdn(fqdn);
+        Ok(name)
+    }
+
+    /// Splits a name in presentation format into its labels, undoing any
+    /// escapes, without checking them against any limits. Also returns
+    /// whether the name ended with the root dot.
+    pub fn split_labels(s: &str) -> Result<(Vec<Vec<u8>>, bool), String> {
+        let mut labels: Vec<Vec<u8>> = vec![Vec::new()];
+        let mut chars = s.chars().peekable();
+        let mut fqdn = false;
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => match chars.next() {
+                    Some(digit) if digit.is_ascii_digit() => {
+                        let mut code = String::from(digit);
+                        for _ in 0..2 {
+                            match chars.next() {
+                                Some(digit) if digit.is_ascii_digit() => code.push(digit),
+                                _ => return Err(format!("incomplete \\DDD escape in {s}")),
+                            }
+                        }
+                        let byte = code
+                            .parse::<u8>()
+                            .map_err(|_| format!("escape \\{code} is out of range in {s}"))?;
+                        labels.last_mut().unwrap().push(byte);
+                    }
+                    Some(ch) if ch.is_ascii() => labels.last_mut().unwrap().push(ch as u8),
+                    Some(ch) => return Err(format!("can't escape non-ASCII {ch} in {s}")),
+                    None => return Err(format!("trailing backslash in {s}")),
+                },
+                '.' if chars.peek().is_none() => fqdn = true,
+                '.' => labels.push(Vec::new()),
+                ch if ch.is_ascii() => labels.last_mut().unwrap().push(ch as u8),
+                ch => return Err(format!("non-ASCII {ch} in escaped name {s}")),
+            }
+        }
+
+        // the root name is just the dot
+        if s == "." {
+            labels.clear();
+        }
+
+        Ok((labels, fqdn))
+    }
+
+    pub fn name_needs_escaping(name: &Name) -> bool {
+        name.iter().any(|label| needs_escaping(label, true))
+    }
+
+    /// Whether any of the names or character-strings in the rdata would need
+    /// escaping, so that its raw bytes are worth keeping.
+    pub fn rdata_needs_escaping(rdata: &hickory_proto::rr::RData) -> bool {
+        use hickory_proto::rr::RData;
+
+        match rdata {
+            RData::CNAME(name) => name_needs_escaping(name),
+            RData::HINFO(hinfo) => {
+                needs_escaping(hinfo.cpu(), false) || needs_escaping(hinfo.os(), false)
+            }
+            RData::MX(mx) => name_needs_escaping(mx.exchange()),
+            RData::NAPTR(naptr) => {
+                needs_escaping(naptr.flags(), false)
+                    || needs_escaping(naptr.services(), false)
+                    || needs_escaping(naptr.regexp(), false)
+                    || name_needs_escaping(naptr.replacement())
+            }
+            RData::NS(name) => name_needs_escaping(name),
+            RData::PTR(name) => name_needs_escaping(name),
+            RData::SOA(soa) => name_needs_escaping(soa.mname()) || name_needs_escaping(soa.rname()),
+            RData::SRV(srv) => name_needs_escaping(srv.target()),
+            RData::HTTPS(hickory_proto::rr::rdata::HTTPS(svcb)) | RData::SVCB(svcb) => {
+                name_needs_escaping(svcb.target_name())
+            }
+            RData::TXT(txt) => txt.iter().any(|data| needs_escaping(data, false)),
+            _ => false,
+        }
+    }
+
+    /// Shows a character-string, like the strings of a TXT record, in
+    /// presentation format, without the surrounding quotes.
+    pub fn escape_text(bytes: &[u8]) -> Value {
+        Value::string(escape_bytes(bytes, false), Span::unknown())
+    }
+
+    /// Orders records canonically: by name, then type, then rdata compared
+    /// as in wire format, so that output is stable even when servers rotate
+    /// the order of RRsets.
+    pub fn sort_records(records: &mut [hickory_proto::rr::Record]) {
+        records.sort_by_cached_key(|record| {
+            (
+                record.name().to_lowercase(),
+                u16::from(record.record_type()),
+                record
+                    .data()
+                    .and_then(|rdata| rdata.to_bytes().ok())
+                    .unwrap_or_default(),
+            )
+        });
+    }
+
+    /// Removes records that are repeated in the same section, keeping the
+    /// first. TTLs are not compared.
+    pub fn dedup_records(records: &mut Vec<hickory_proto::rr::Record>) {
+        let mut seen = Vec::with_capacity(records.len());
+
+        records.retain(|record| {
+            let key = (
+                record.name().to_lowercase(),
+                record.record_type(),
+                record.dns_class(),
+                record.data().cloned(),
+            );
+
+            if seen.contains(&key) {
+                false
+            } else {
+                seen.push(key);
+                true
+            }
+        });
+    }
+
+    /// Splits records into sets that share a name, type, and class, in the
+    /// order each set first appears.
+    pub fn group_rrsets(
+        records: Vec<hickory_proto::rr::Record>,
+    ) -> Vec<Vec<hickory_proto::rr::Record>> {
+        let mut rrsets: Vec<Vec<hickory_proto::rr::Record>> = Vec::new();
+
+        for record in records {
+            match rrsets.iter_mut().find(|rrset| {
+                // names compare case-insensitively
+                rrset[0].name() == record.name()
+                    && rrset[0].record_type() == record.record_type()
+                    && rrset[0].dns_class() == record.dns_class()
+            }) {
+                Some(rrset) => rrset.push(record),
+                None => rrsets.push(vec![record]),
+            }
+        }
+
+        rrsets
+    }
+
+    /// Gets a column from a record, treating empty cells, e.g. from a CSV
+    /// file, as if the column were not there.
+    pub fn column(record: &Value, name: &str) -> Option<Value> {
+        match record.get_data_by_key(name)? {
+            Value::Nothing { .. } => None,
+            Value::String { val, .. } if val.is_empty() => None,
+            val => Some(val),
+        }
+    }
+
+    /// An address as rdata: the address itself, its family, and the name to
+    /// look up for a reverse (PTR) lookup of it.
+    pub fn address_value(addr: IpAddr) -> Value {
+        let family = match addr {
+            IpAddr::V4(_) => "ipv4",
+            IpAddr::V6(_) => "ipv6",
+        };
+
+        Value::record(
+            record![
+                "ip"           => Value::string(addr.to_string(), Span::unknown()),
+                "family"       => Value::string(family, Span::unknown()),
+                "reverse_name" => Value::string(Name::from(addr).to_ascii(), Span::unknown()),
+            ],
+            Span::unknown(),
+        )
+    }
+
+    /// The mnemonic of a record type, or `TYPEnnn` for types without one, as
+    /// in [RFC 3597](https://www.rfc-editor.org/rfc/rfc3597#section-5).
+    pub fn rtype_mnemonic(rtype: hickory_proto::rr::RecordType) -> String {
+        match rtype {
+            hickory_proto::rr::RecordType::Unknown(code) => format!("TYPE{code}"),
+            rtype => rtype.to_string(),
+        }
+    }
+
+    /// Formats RDATA in the generic `\# length hex` presentation format of
+    /// [RFC 3597](https://www.rfc-editor.org/rfc/rfc3597#section-5).
+    pub fn generic_rdata(bytes: &[u8]) -> String {
+        match bytes {
+            [] => "\\# 0".into(),
+            bytes => format!(
+                "\\# {} {}",
+                bytes.len(),
+                data_encoding::HEXUPPER.encode(bytes)
+            ),
+        }
+    }
+
+    /// Parses RDATA given in the generic `\# length hex` format, with the hex
+    /// possibly split up by whitespace. Returns `None` if it isn't in that
+    /// format. Known types are decoded from the wire format as usual, so the
+    /// generic format works for any type.
+    pub fn parse_generic_rdata(
+        rtype: hickory_proto::rr::RecordType,
+        s: &str,
+    ) -> Option<Result<hickory_proto::rr::RData, String>> {
+        let mut tokens = s.split_whitespace();
+        if tokens.next() != Some("\\#") {
+            return None;
+        }
+
+        let parse = || {
+            let len: u16 = tokens
+                .next()
+                .ok_or("missing RDATA length after \\#")?
+                .parse()
+                .map_err(|err| format!("invalid RDATA length: {err}"))?;
+            let bytes = data_encoding::HEXLOWER_PERMISSIVE
+                .decode(tokens.collect::<String>().as_bytes())
+                .map_err(|err| format!("invalid hex in RDATA: {err}"))?;
+
+            if bytes.len() != usize::from(len) {
+                return Err(format!(
+                    "RDATA length is {len}, but {} bytes were given",
+                    bytes.len()
+                ));
+            }
+
+            match rtype {
+                hickory_proto::rr::RecordType::Unknown(_) => {
+                    Ok(hickory_proto::rr::RData::Unknown {
+                        code: rtype,
+                        rdata: hickory_proto::rr::rdata::NULL::with(bytes),
+                    })
+                }
+                rtype => {
+                    let mut decoder = hickory_proto::serialize::binary::BinDecoder::new(&bytes);
+                    hickory_proto::rr::RData::read(
+                        &mut decoder,
+                        rtype,
+                        hickory_proto::serialize::binary::Restrict::new(len),
+                    )
+                    .map_err(|err| format!("invalid {rtype} RDATA: {err}"))
+                }
+            }
+        };
+
+        Some(parse())
+    }
 
     pub fn string_or_binary<V>(bytes: V) -> Value
     where
@@ -1188,6 +2507,171 @@ pub mod util {
         )
     }
 
+    pub fn std_to_duration(duration: Duration) -> Value {
+        Value::duration(duration.as_nanos() as i64, Span::unknown())
+    }
+
+    /// Leaves out the columns of records, at any depth, that have nothing in
+    /// them or are empty lists or records, for `--compact`.
+    pub fn compact(value: Value) -> Value {
+        let span = value.span();
+
+        match value {
+            Value::Record { val, .. } => Value::record(
+                val.into_owned()
+                    .into_iter()
+                    .map(|(col, val)| (col, compact(val)))
+                    .filter(|(_, val)| match val {
+                        Value::Nothing { .. } => false,
+                        Value::List { vals, .. } => !vals.is_empty(),
+                        Value::Record { val, .. } => !val.is_empty(),
+                        _ => true,
+                    })
+                    .collect(),
+                span,
+            ),
+            Value::List { vals, .. } => Value::list(vals.into_iter().map(compact).collect(), span),
+            value => value,
+        }
+    }
+
+    /// Makes every column of a record a scalar, for formats like CSV that
+    /// have no room for anything else: nested records become columns of
+    /// their own named by their path, as in `header.truncated`, and lists
+    /// become their items joined with the separator.
+    pub fn scalar(value: Value, separator: &str) -> Value {
+        let span = value.span();
+
+        match value {
+            Value::Record { val, .. } => {
+                let mut flat = nu_protocol::Record::new();
+                flatten_into(&mut flat, None, val.into_owned(), separator);
+                Value::record(flat, span)
+            }
+            value => scalar_item(value, separator),
+        }
+    }
+
+    fn flatten_into(
+        flat: &mut nu_protocol::Record,
+        prefix: Option<&str>,
+        record: nu_protocol::Record,
+        separator: &str,
+    ) {
+        for (col, val) in record {
+            let col = match prefix {
+                Some(prefix) => format!("{prefix}.{col}"),
+                None => col,
+            };
+
+            match val {
+                Value::Record { val, .. } => {
+                    flatten_into(flat, Some(&col), val.into_owned(), separator)
+                }
+                val => {
+                    flat.push(col, scalar_item(val, separator));
+                }
+            }
+        }
+    }
+
+    /// A value as a scalar: a list as its items joined, a record as its
+    /// columns as `key=value` pairs, and anything else as it is.
+    fn scalar_item(value: Value, separator: &str) -> Value {
+        let span = value.span();
+        let text = |value: Value| match scalar_item(value, separator) {
+            Value::String { val, .. } => val,
+            value => value.to_abbreviated_string(&nu_protocol::Config::default()),
+        };
+
+        match value {
+            Value::List { vals, .. } => Value::string(
+                vals.into_iter()
+                    .map(text)
+                    .collect::<Vec<_>>()
+                    .join(separator),
+                span,
+            ),
+            Value::Record { val, .. } => {
+                let mut flat = nu_protocol::Record::new();
+                flatten_into(&mut flat, None, val.into_owned(), separator);
+                Value::string(
+                    flat.into_iter()
+                        .map(|(col, val)| format!("{col}={}", text(val)))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    span,
+                )
+            }
+            value => value,
+        }
+    }
+
+    /// A record as a line of a zone file: its name, TTL, class, type, and
+    /// rdata.
+    pub fn presentation_line(record: &hickory_proto::rr::Record, config: &super::Config) -> String {
+        format!(
+            "{} {} {} {} {}",
+            config.display_name(record.name()),
+            record.ttl(),
+            record.dns_class(),
+            rtype_mnemonic(record.record_type()),
+            record.data().map_or(String::new(), |data| data.to_string())
+        )
+    }
+
+    /// Counts latencies into buckets whose bounds go up in steps of 1, 2, and
+    /// 5 from 100µs, so that both a local resolver and one across the world
+    /// get a useful resolution. Every bucket from the fastest latency's to
+    /// the slowest's is listed, even if empty, so the rows chart evenly.
+    pub fn latency_histogram(latencies: &[Duration]) -> Value {
+        let bounds: Vec<Duration> = (0..6)
+            .flat_map(|exp| {
+                [1, 2, 5].map(|step| Duration::from_micros(100 * step * 10u64.pow(exp)))
+            })
+            .collect();
+
+        let mut counts = vec![0i64; bounds.len() + 1];
+        for latency in latencies {
+            let bucket = bounds.partition_point(|bound| bound <= latency);
+            counts[bucket] += 1;
+        }
+
+        let (Some(first), Some(last)) = (
+            counts.iter().position(|count| *count > 0),
+            counts.iter().rposition(|count| *count > 0),
+        ) else {
+            return Value::list(vec![], Span::unknown());
+        };
+
+        Value::list(
+            (first..=last)
+                .map(|bucket| {
+                    let bound = |bucket: Option<usize>| {
+                        bucket
+                            .and_then(|bucket| bounds.get(bucket))
+                            .map_or(Value::nothing(Span::unknown()), |bound| {
+                                std_to_duration(*bound)
+                            })
+                    };
+
+                    Value::record(
+                        record![
+                            "from"  => match bucket {
+                                0 => std_to_duration(Duration::ZERO),
+                                bucket => bound(Some(bucket - 1)),
+                            },
+                            "to"    => bound(Some(bucket)),
+                            "count" => Value::int(counts[bucket], Span::unknown()),
+                        ],
+                        Span::unknown(),
+                    )
+                })
+                .collect(),
+            Span::unknown(),
+        )
+    }
+
     pub fn sec_to_date<U: Into<i64>>(sec: U, input_span: Span) -> Result<Value, LabeledError> {
         let secs = sec.into();
         let datetime = match chrono::Utc.timestamp_opt(secs, 0) {
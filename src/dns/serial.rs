@@ -0,0 +1,118 @@
+//! SOA serial numbers, which wrap around and so have to be compared with
+//! [RFC 1982](https://www.rfc-editor.org/rfc/rfc1982) serial number
+//! arithmetic rather than as plain integers.
+
+use std::{cmp::Ordering, str::FromStr};
+
+use chrono::TimeZone;
+
+/// Half the serial number space: serials this far apart can't be ordered.
+const HALF: u32 = 1 << 31;
+
+/// Orders two serials, or returns `None` if they are exactly half the space
+/// apart, where RFC 1982 leaves the order undefined.
+pub fn compare(a: u32, b: u32) -> Option<Ordering> {
+    match b.wrapping_sub(a) {
+        0 => Some(Ordering::Equal),
+        HALF => None,
+        diff if diff < HALF => Some(Ordering::Less),
+        _ => Some(Ordering::Greater),
+    }
+}
+
+/// How far `b` is ahead of `a`, negative if it is behind.
+pub fn distance(a: u32, b: u32) -> i64 {
+    i64::from(b.wrapping_sub(a) as i32)
+}
+
+/// How the next serial is chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One more than the current serial.
+    Increment,
+    /// Today's date with a two digit counter, as in `2024013100`.
+    Date,
+    /// The current time in seconds since the epoch.
+    Unixtime,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "increment" => Ok(Self::Increment),
+            "date" => Ok(Self::Date),
+            "unixtime" => Ok(Self::Unixtime),
+            _ => Err(format!(
+                "unknown serial format {s}; should be increment, date, or unixtime"
+            )),
+        }
+    }
+}
+
+/// The serial that should follow `serial` at the time `now`, in seconds
+/// since the epoch. If the format's serial for now wouldn't be ahead of the
+/// current one, say because the counter for the day has been used up, the
+/// current serial is just incremented, so the result is always ahead.
+pub fn next(serial: u32, format: Format, now: i64) -> u32 {
+    let candidate = match format {
+        Format::Increment => None,
+        Format::Date => chrono::Utc
+            .timestamp_opt(now, 0)
+            .single()
+            .and_then(|date| date.format("%Y%m%d00").to_string().parse().ok()),
+        Format::Unixtime => u32::try_from(now).ok(),
+    };
+
+    match candidate {
+        Some(candidate) if compare(serial, candidate) == Some(Ordering::Less) => candidate,
+        _ => serial.wrapping_add(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_wraps_around() {
+        assert_eq!(compare(1, 2), Some(Ordering::Less));
+        assert_eq!(compare(2, 1), Some(Ordering::Greater));
+        assert_eq!(compare(7, 7), Some(Ordering::Equal));
+
+        // RFC 1982 section 3.2: the largest serial is just before 0
+        assert_eq!(compare(u32::MAX, 0), Some(Ordering::Less));
+        assert_eq!(compare(0, u32::MAX), Some(Ordering::Greater));
+        assert_eq!(compare(u32::MAX - 10, 10), Some(Ordering::Less));
+
+        // serials half the space apart have no order
+        assert_eq!(compare(0, 1 << 31), None);
+        assert_eq!(compare(1 << 31, 0), None);
+        assert_eq!(compare(0, (1 << 31) - 1), Some(Ordering::Less));
+        assert_eq!(compare(0, (1 << 31) + 1), Some(Ordering::Greater));
+    }
+
+    #[test]
+    fn distance_wraps_around() {
+        assert_eq!(distance(1, 5), 4);
+        assert_eq!(distance(5, 1), -4);
+        assert_eq!(distance(u32::MAX, 1), 2);
+        assert_eq!(distance(1, u32::MAX), -2);
+    }
+
+    #[test]
+    fn next_is_always_ahead() {
+        // 2024-01-31T12:00:00Z
+        let now = 1_706_702_400;
+
+        assert_eq!(next(41, Format::Increment, now), 42);
+        assert_eq!(next(u32::MAX, Format::Increment, now), 0);
+
+        assert_eq!(next(1, Format::Date, now), 2024013100);
+        assert_eq!(next(2024013100, Format::Date, now), 2024013101);
+        assert_eq!(next(2024020100, Format::Date, now), 2024020101);
+
+        assert_eq!(next(1, Format::Unixtime, now), now as u32);
+    }
+}
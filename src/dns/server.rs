@@ -0,0 +1,265 @@
+//! A minimal authoritative server that answers from a fixed set of records,
+//! so that scripts and tests can run against deterministic data without
+//! touching the network.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use hickory_proto::{
+    op::{Edns, Message, MessageType, OpCode, ResponseCode},
+    rr::{Name, RData, Record, RecordType},
+    serialize::binary::BinEncodable,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+};
+use tokio_util::sync::CancellationToken;
+
+use super::constants;
+
+/// The payload size assumed for UDP clients that don't advertise one with
+/// EDNS.
+const CLASSIC_UDP_PAYLOAD: usize = 512;
+
+/// The records being served.
+#[derive(Debug)]
+pub struct Zone {
+    records: Vec<Record>,
+}
+
+impl Zone {
+    pub fn new(records: impl IntoIterator<Item = Record>) -> Self {
+        let records = records
+            .into_iter()
+            .map(|mut record| {
+                // names given without a trailing dot are taken to be fully
+                // qualified, as there is no origin to append
+                let mut name = record.name().clone();
+                name.set_fqdn(true);
+                record.set_name(name);
+                record
+            })
+            .collect();
+
+        Self { records }
+    }
+
+    /// Builds the response to a request, as an authoritative server would.
+    pub fn answer(&self, request: &Message) -> Message {
+        let mut response =
+            Message::error_msg(request.id(), request.op_code(), ResponseCode::NoError);
+        response
+            .set_message_type(MessageType::Response)
+            .set_recursion_desired(request.recursion_desired())
+            .set_checking_disabled(request.checking_disabled())
+            .add_queries(request.queries().to_vec());
+
+        if let Some(edns) = request.extensions() {
+            let mut resp_edns = Edns::new();
+            resp_edns
+                .set_max_payload(constants::config::default::EDNS_PAYLOAD)
+                .set_dnssec_ok(edns.dnssec_ok());
+            response.set_edns(resp_edns);
+        }
+
+        if request.op_code() != OpCode::Query {
+            response.set_response_code(ResponseCode::NotImp);
+            return response;
+        }
+
+        let [query] = request.queries() else {
+            response.set_response_code(ResponseCode::FormErr);
+            return response;
+        };
+
+        response.set_authoritative(true);
+
+        let qtype = query.query_type();
+        let mut target = query.name().clone();
+
+        // follow CNAMEs that stay within the served records
+        for _ in 0..constants::config::default::MAX_CNAME_CHAIN {
+            let cname = self
+                .records
+                .iter()
+                .filter(|record| {
+                    record.name() == &target && record.dns_class() == query.query_class()
+                })
+                .find(|record| record.record_type() == RecordType::CNAME);
+
+            match (cname, cname.and_then(|record| record.data())) {
+                (Some(record), Some(RData::CNAME(next))) if qtype != RecordType::CNAME => {
+                    response.add_answer(record.clone());
+                    target = next.0.clone();
+                }
+                _ => break,
+            }
+        }
+
+        let answers: Vec<Record> = self
+            .records
+            .iter()
+            .filter(|record| {
+                record.name() == &target
+                    && record.dns_class() == query.query_class()
+                    && (qtype == RecordType::ANY || record.record_type() == qtype)
+            })
+            .cloned()
+            .collect();
+
+        if answers.is_empty() {
+            // a name exists if it has records, or if any name below it does
+            let exists = self
+                .records
+                .iter()
+                .any(|record| target.zone_of(record.name()));
+
+            if !exists {
+                response.set_response_code(ResponseCode::NXDomain);
+            }
+
+            if let Some(soa) = self.soa_for(&target) {
+                response.add_name_server(soa.clone());
+            }
+        }
+
+        response.add_answers(answers);
+        response
+    }
+
+    /// The SOA record of the closest enclosing zone, if any is served.
+    fn soa_for(&self, name: &Name) -> Option<&Record> {
+        self.records
+            .iter()
+            .filter(|record| record.record_type() == RecordType::SOA)
+            .filter(|record| record.name().zone_of(name))
+            .max_by_key(|record| record.name().num_labels())
+    }
+
+    /// Answers a request in wire format. Requests that cannot be parsed at
+    /// all are dropped, since there is no ID to reply to. UDP responses that
+    /// are too large for the client are truncated.
+    fn answer_bytes(&self, request: &[u8], udp: bool) -> Option<Vec<u8>> {
+        let request = match Message::from_vec(request) {
+            Ok(request) => request,
+            Err(err) => {
+                tracing::debug!(serve.phase = "parse", serve.error = ?err);
+                return None;
+            }
+        };
+
+        let mut response = self.answer(&request);
+        let mut bytes = response.to_bytes().ok()?;
+
+        if udp {
+            let max_size = request
+                .extensions()
+                .as_ref()
+                .map_or(CLASSIC_UDP_PAYLOAD, |edns| {
+                    (edns.max_payload() as usize).max(CLASSIC_UDP_PAYLOAD)
+                });
+
+            if bytes.len() > max_size {
+                response.take_answers();
+                response.take_name_servers();
+                response.take_additionals();
+                response.set_truncated(true);
+                bytes = response.to_bytes().ok()?;
+            }
+        }
+
+        Some(bytes)
+    }
+}
+
+/// Serves the zone over UDP and TCP on the given sockets until cancelled.
+pub async fn serve(zone: Arc<Zone>, udp: UdpSocket, tcp: TcpListener, cancel: CancellationToken) {
+    futures_util::future::join(
+        serve_udp(zone.clone(), udp, cancel.clone()),
+        serve_tcp(zone, tcp, cancel),
+    )
+    .await;
+}
+
+async fn serve_udp(zone: Arc<Zone>, socket: UdpSocket, cancel: CancellationToken) {
+    let mut buf = vec![0; u16::MAX as usize];
+
+    loop {
+        let (len, peer) = match cancel.run_until_cancelled(socket.recv_from(&mut buf)).await {
+            None => return,
+            Some(Ok(recv)) => recv,
+            Some(Err(err)) => {
+                tracing::debug!(serve.phase = "recv", serve.error = ?err);
+                continue;
+            }
+        };
+
+        if let Some(response) = zone.answer_bytes(&buf[..len], true) {
+            if let Err(err) = socket.send_to(&response, peer).await {
+                tracing::debug!(serve.phase = "send", serve.peer = %peer, serve.error = ?err);
+            }
+        }
+    }
+}
+
+async fn serve_tcp(zone: Arc<Zone>, listener: TcpListener, cancel: CancellationToken) {
+    loop {
+        let (stream, peer) = match cancel.run_until_cancelled(listener.accept()).await {
+            None => return,
+            Some(Ok(accepted)) => accepted,
+            Some(Err(err)) => {
+                tracing::debug!(serve.phase = "accept", serve.error = ?err);
+                continue;
+            }
+        };
+
+        tokio::spawn({
+            let zone = zone.clone();
+            let cancel = cancel.clone();
+
+            async move {
+                if let Some(Err(err)) = cancel
+                    .run_until_cancelled(serve_tcp_conn(&zone, stream))
+                    .await
+                {
+                    tracing::debug!(serve.phase = "tcp", serve.peer = %peer, serve.error = ?err);
+                }
+            }
+        });
+    }
+}
+
+/// Answers length-prefixed messages on one connection until the client
+/// hangs up.
+async fn serve_tcp_conn(zone: &Zone, mut stream: TcpStream) -> std::io::Result<()> {
+    loop {
+        let len = match stream.read_u16().await {
+            Ok(len) => len,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        let mut request = vec![0; len as usize];
+        stream.read_exact(&mut request).await?;
+
+        let Some(response) = zone.answer_bytes(&request, false) else {
+            continue;
+        };
+
+        // responses larger than a TCP message can carry can't be sent at all
+        let Ok(response_len) = u16::try_from(response.len()) else {
+            continue;
+        };
+
+        stream.write_u16(response_len).await?;
+        stream.write_all(&response).await?;
+    }
+}
+
+/// Binds UDP and TCP sockets on the same address. If the port is 0, the OS
+/// picks one for UDP and TCP uses the same one.
+pub async fn bind(addr: SocketAddr) -> std::io::Result<(UdpSocket, TcpListener)> {
+    let udp = UdpSocket::bind(addr).await?;
+    let tcp = TcpListener::bind(udp.local_addr()?).await?;
+    Ok((udp, tcp))
+}
@@ -0,0 +1,209 @@
+//! The special-use domain names of the [IANA
+//! registry](https://www.iana.org/assignments/special-use-domain-names/)
+//! that change how a question about them should be answered, per [RFC
+//! 6761](https://www.rfc-editor.org/rfc/rfc6761#section-5) and the RFCs that
+//! added to it, along with the reverse zones of private and local addresses
+//! of [RFC 6303](https://www.rfc-editor.org/rfc/rfc6303#section-4). Names
+//! under most of them mean nothing to the public DNS, and a question about
+//! one that leaves the host or the local network is at best wasted on the
+//! root servers.
+
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use hickory_proto::{
+    op::{Message, MessageType, ResponseCode},
+    rr::{RData, Record, RecordType},
+};
+use hickory_resolver::Name;
+use nu_protocol::{record, Span, Value};
+
+/// The name a resolver answers with the encrypted resolvers it designates,
+/// for Discovery of Designated Resolvers (RFC 9462).
+pub const DDR_NAME: &str = "_dns.resolver.arpa.";
+
+/// How a question about a name under a special-use zone should be answered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handling {
+    /// Always with the loopback address, without asking any server.
+    Loopback,
+    /// Always with NXDOMAIN, without asking any server.
+    NxDomain,
+    /// By the resolver that is asked, from what it knows itself rather than
+    /// from any zone in the public DNS.
+    Resolver,
+    /// By the local network's own servers; the public DNS has nothing there.
+    Local,
+    /// With multicast DNS on the local link, rather than by any server.
+    Mdns,
+    /// Outside the DNS entirely, as by the Tor network.
+    NotDns,
+    /// Not at all, unless a test environment sets it up; the root has no
+    /// such zone.
+    Test,
+}
+
+impl fmt::Display for Handling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Loopback => "loopback",
+            Self::NxDomain => "nxdomain",
+            Self::Resolver => "resolver",
+            Self::Local => "local",
+            Self::Mdns => "mdns",
+            Self::NotDns => "not-dns",
+            Self::Test => "test",
+        })
+    }
+}
+
+/// A special-use zone and what is special about it.
+#[derive(Debug)]
+pub struct SpecialUse {
+    pub zone: &'static str,
+    pub rfc: u16,
+    pub handling: Handling,
+    pub reason: &'static str,
+}
+
+impl SpecialUse {
+    /// Whether a question about a name here is worth warning about: anything
+    /// but the names that resolvers are meant to answer themselves.
+    pub fn warns(&self) -> bool {
+        self.handling != Handling::Resolver
+    }
+
+    pub fn to_value(&self, span: Span) -> Value {
+        Value::record(
+            record![
+                "zone"     => Value::string(self.zone, span),
+                "rfc"      => Value::int(self.rfc as i64, span),
+                "handling" => Value::string(self.handling.to_string(), span),
+                "reason"   => Value::string(self.reason, span),
+            ],
+            span,
+        )
+    }
+}
+
+const PRIVATE_REVERSE: &str =
+    "reverse zone of private addresses; only the local network's servers can answer for it";
+
+macro_rules! special {
+    ($zone:literal, $rfc:literal, $handling:ident, $reason:expr) => {
+        SpecialUse {
+            zone: $zone,
+            rfc: $rfc,
+            handling: Handling::$handling,
+            reason: $reason,
+        }
+    };
+}
+
+const REGISTRY: &[SpecialUse] = &[
+    special!("localhost.", 6761, Loopback, "names the host itself, always at the loopback address"),
+    special!("invalid.", 6761, NxDomain, "guaranteed never to exist"),
+    special!("test.", 6761, Test, "reserved for testing; not delegated from the root"),
+    special!("local.", 6762, Mdns, "resolved with multicast DNS on the local link, not by unicast servers"),
+    special!("254.169.in-addr.arpa.", 6762, Mdns, "reverse zone of IPv4 link-local addresses, resolved with multicast DNS"),
+    special!("8.e.f.ip6.arpa.", 6762, Mdns, "reverse zone of IPv6 link-local addresses, resolved with multicast DNS"),
+    special!("9.e.f.ip6.arpa.", 6762, Mdns, "reverse zone of IPv6 link-local addresses, resolved with multicast DNS"),
+    special!("a.e.f.ip6.arpa.", 6762, Mdns, "reverse zone of IPv6 link-local addresses, resolved with multicast DNS"),
+    special!("b.e.f.ip6.arpa.", 6762, Mdns, "reverse zone of IPv6 link-local addresses, resolved with multicast DNS"),
+    special!("onion.", 7686, NotDns, "Tor onion services, resolved by the Tor network rather than the DNS"),
+    special!("alt.", 9476, NotDns, "for name systems other than the DNS; not delegated from the root"),
+    special!("home.arpa.", 8375, Local, "residential home networks; only the home network's servers can answer for it"),
+    special!("resolver.arpa.", 9462, Resolver, "answered by the resolver asked, about itself"),
+    special!("ipv4only.arpa.", 8880, Resolver, "has fixed addresses that DNS64 resolvers synthesize AAAA records from"),
+    special!("127.in-addr.arpa.", 6303, Loopback, "reverse zone of the IPv4 loopback addresses"),
+    special!("1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.ip6.arpa.", 6303, Loopback, "reverse name of the IPv6 loopback address"),
+    special!("10.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("16.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("17.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("18.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("19.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("20.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("21.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("22.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("23.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("24.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("25.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("26.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("27.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("28.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("29.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("30.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("31.172.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("168.192.in-addr.arpa.", 6303, Local, PRIVATE_REVERSE),
+    special!("d.f.ip6.arpa.", 6303, Local, "reverse zone of IPv6 unique local addresses; only the local network's servers can answer for it"),
+];
+
+/// The special-use zone a name is at or under, if any. Where zones nest, the
+/// closest one counts.
+pub fn special_use(name: &Name) -> Option<&'static SpecialUse> {
+    REGISTRY
+        .iter()
+        .filter(|special| {
+            Name::from_ascii(special.zone)
+                .expect("registry zones should parse")
+                .zone_of(name)
+        })
+        .max_by_key(|special| special.zone.len())
+}
+
+/// The warning that goes with a response about a name under a special-use
+/// zone, saying why a unicast query for it may be meaningless, or nothing.
+pub fn warning(name: &Name) -> Value {
+    match special_use(name).filter(|special| special.warns()) {
+        Some(special) => {
+            let mut warning = record!["kind" => Value::string("special-use", Span::unknown())];
+            if let Value::Record { val, .. } = special.to_value(Span::unknown()) {
+                warning.extend(val.into_owned());
+            }
+            Value::record(warning, Span::unknown())
+        }
+        None => Value::nothing(Span::unknown()),
+    }
+}
+
+/// The answer to a question that RFC 6761 says is never to be sent to a
+/// server at all: the loopback address for names under localhost., and
+/// NXDOMAIN for names under invalid.
+pub fn local_answer(request: &Message) -> Option<Message> {
+    let query = request.queries().first()?;
+    let special = special_use(query.name())
+        .filter(|special| matches!(special.zone, "localhost." | "invalid."))?;
+
+    let mut response = Message::new();
+    response
+        .set_id(request.id())
+        .set_message_type(MessageType::Response)
+        .set_op_code(request.op_code())
+        .set_recursion_desired(request.recursion_desired())
+        .set_recursion_available(true)
+        .set_authoritative(true)
+        .add_query(query.clone());
+
+    match special.handling {
+        Handling::NxDomain => {
+            response.set_response_code(ResponseCode::NXDomain);
+        }
+        _ => {
+            let rdata = match query.query_type() {
+                RecordType::A => Some(RData::A(Ipv4Addr::LOCALHOST.into())),
+                RecordType::AAAA => Some(RData::AAAA(Ipv6Addr::LOCALHOST.into())),
+                _ => None,
+            };
+
+            if let Some(rdata) = rdata {
+                let mut record = Record::from_rdata(query.name().clone(), 0, rdata);
+                record.set_dns_class(query.query_class());
+                response.add_answer(record);
+            }
+        }
+    }
+
+    Some(response)
+}
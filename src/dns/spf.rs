@@ -0,0 +1,322 @@
+//! The parts of SPF ([RFC 7208](https://www.rfc-editor.org/rfc/rfc7208))
+//! that flattening a record takes: reading its terms, and folding the
+//! networks it ends up listing into as few prefixes as cover them.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The most DNS lookups that evaluating a record can take before it is a
+/// permanent error (RFC 7208 section 4.6.4).
+pub const LOOKUP_LIMIT: usize = 10;
+
+/// The longest a character-string in a TXT record can be.
+pub const STRING_LIMIT: usize = 255;
+
+/// How long a record can be and still leave its response, with the header
+/// and question, under the 512 bytes of a plain UDP response, which RFC 7208
+/// section 3.4 asks records to fit in.
+pub const UDP_LIMIT: usize = 450;
+
+/// One term of a record: a mechanism, with its qualifier, or a modifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Term {
+    Mechanism {
+        qualifier: char,
+        name: String,
+        /// What follows the name, from the `:` or `/` on.
+        arg: String,
+    },
+    Modifier {
+        name: String,
+        value: String,
+    },
+}
+
+impl Term {
+    /// Whether the term takes a DNS lookup, counting toward [`LOOKUP_LIMIT`].
+    pub fn looks_up(&self) -> bool {
+        match self {
+            Self::Mechanism { name, .. } => {
+                matches!(name.as_str(), "include" | "a" | "mx" | "ptr" | "exists")
+            }
+            Self::Modifier { name, .. } => name == "redirect",
+        }
+    }
+
+    /// The domain given to a mechanism, as in `include:example.com` or
+    /// `a:example.com/24`.
+    pub fn domain(&self) -> Option<&str> {
+        match self {
+            Self::Mechanism { arg, .. } => arg
+                .strip_prefix(':')
+                .map(|arg| arg.split('/').next().unwrap_or_default())
+                .filter(|domain| !domain.is_empty()),
+            Self::Modifier { .. } => None,
+        }
+    }
+
+    /// The IPv4 and IPv6 prefix lengths given to an `a` or `mx` mechanism,
+    /// as in `a/24//64`, defaulting to single addresses.
+    pub fn dual_cidr(&self) -> Result<(u8, u8), String> {
+        let Self::Mechanism { arg, .. } = self else {
+            return Ok((32, 128));
+        };
+        let cidr = arg.find('/').map_or("", |idx| &arg[idx..]);
+        let (v4, v6) = match cidr.split_once("//") {
+            Some((v4, v6)) => (v4.strip_prefix('/').unwrap_or(v4), Some(v6)),
+            None => (cidr.strip_prefix('/').unwrap_or(cidr), None),
+        };
+
+        let parse = |len: &str, max: u8| match len {
+            "" => Ok(max),
+            len => len
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max)
+                .ok_or_else(|| format!("invalid prefix length in {self}")),
+        };
+
+        Ok((parse(v4, 32)?, parse(v6.unwrap_or_default(), 128)?))
+    }
+
+    /// Whether the term has a macro (RFC 7208 section 7) that stands for the
+    /// domain whose record it is in, and so means something else anywhere
+    /// else. The explanation of `exp` doesn't count, since only the top
+    /// record's is ever used.
+    pub fn names_own_domain(&self) -> bool {
+        let text = match self {
+            Self::Mechanism { arg, .. } => arg,
+            Self::Modifier { name, value } if name == "redirect" => value,
+            Self::Modifier { .. } => return false,
+        };
+
+        let text = text.to_ascii_lowercase();
+        text.contains("%{d") || text.contains("%{o")
+    }
+}
+
+impl std::fmt::Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mechanism {
+                qualifier,
+                name,
+                arg,
+            } => match qualifier {
+                '+' => write!(f, "{name}{arg}"),
+                qualifier => write!(f, "{qualifier}{name}{arg}"),
+            },
+            Self::Modifier { name, value } => write!(f, "{name}={value}"),
+        }
+    }
+}
+
+/// Whether a TXT record is an SPF record: `v=spf1`, then a space or nothing.
+pub fn is_spf(text: &str) -> bool {
+    text.get(..6)
+        .is_some_and(|version| version.eq_ignore_ascii_case("v=spf1"))
+        && text[6..].chars().next().is_none_or(|c| c == ' ')
+}
+
+/// The terms of an SPF record, after its version.
+pub fn parse(text: &str) -> Vec<Term> {
+    text.split_whitespace()
+        .skip(1)
+        .map(|term| {
+            let name_end = term.find([':', '/', '=']).unwrap_or(term.len());
+            match term[name_end..].strip_prefix('=') {
+                Some(value) => Term::Modifier {
+                    name: term[..name_end].to_ascii_lowercase(),
+                    value: value.into(),
+                },
+                None => {
+                    let (qualifier, name) = match term.chars().next() {
+                        Some(qualifier @ ('+' | '-' | '~' | '?')) => {
+                            (qualifier, &term[1..name_end])
+                        }
+                        _ => ('+', &term[..name_end]),
+                    };
+                    Term::Mechanism {
+                        qualifier,
+                        name: name.to_ascii_lowercase(),
+                        arg: term[name_end..].into(),
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Folds networks, as addresses with their prefix lengths in a space of
+/// `bits` bits, into the fewest that cover exactly the same addresses:
+/// networks inside others are dropped, and pairs of halves are joined.
+fn aggregate_bits(mut nets: Vec<(u128, u8)>, bits: u8) -> Vec<(u128, u8)> {
+    let mask = |len: u8| match len {
+        0 => 0,
+        len => (u128::MAX >> (128 - bits)) & (u128::MAX << (bits - len)),
+    };
+
+    for (addr, len) in &mut nets {
+        *addr &= mask(*len);
+    }
+    nets.sort();
+
+    let mut folded: Vec<(u128, u8)> = Vec::new();
+    for (addr, len) in nets {
+        if let Some((last, last_len)) = folded.last() {
+            if *last_len <= len && addr & mask(*last_len) == *last {
+                continue;
+            }
+        }
+        folded.push((addr, len));
+
+        // networks come in order, so a network's other half, if it is there,
+        // is the one just before it
+        while let [.., (first, first_len), (second, second_len)] = folded[..] {
+            if first_len != second_len || first_len == 0 {
+                break;
+            }
+            let parent = first_len - 1;
+            if first & mask(parent) != second & mask(parent) {
+                break;
+            }
+            folded.truncate(folded.len() - 2);
+            folded.push((first & mask(parent), parent));
+        }
+    }
+
+    folded
+}
+
+pub fn aggregate_v4(nets: &[(Ipv4Addr, u8)]) -> Vec<(Ipv4Addr, u8)> {
+    aggregate_bits(
+        nets.iter()
+            .map(|(addr, len)| (u32::from(*addr) as u128, *len))
+            .collect(),
+        32,
+    )
+    .into_iter()
+    .map(|(addr, len)| (Ipv4Addr::from(addr as u32), len))
+    .collect()
+}
+
+pub fn aggregate_v6(nets: &[(Ipv6Addr, u8)]) -> Vec<(Ipv6Addr, u8)> {
+    aggregate_bits(
+        nets.iter()
+            .map(|(addr, len)| (u128::from(*addr), *len))
+            .collect(),
+        128,
+    )
+    .into_iter()
+    .map(|(addr, len)| (Ipv6Addr::from(addr), len))
+    .collect()
+}
+
+/// Splits a record into the character-strings of a TXT record, each no
+/// longer than [`STRING_LIMIT`]. Receivers join them back without anything
+/// in between (RFC 7208 section 3.3).
+pub fn split_strings(text: &str) -> Vec<String> {
+    text.as_bytes()
+        .chunks(STRING_LIMIT)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v4(nets: &[(&str, u8)]) -> Vec<(Ipv4Addr, u8)> {
+        nets.iter()
+            .map(|(addr, len)| (addr.parse().unwrap(), *len))
+            .collect()
+    }
+
+    fn v6(nets: &[(&str, u8)]) -> Vec<(Ipv6Addr, u8)> {
+        nets.iter()
+            .map(|(addr, len)| (addr.parse().unwrap(), *len))
+            .collect()
+    }
+
+    #[test]
+    fn aggregate_networks_v4() {
+        // halves join, and networks inside others are dropped
+        assert_eq!(
+            aggregate_v4(&v4(&[
+                ("192.0.2.128", 25),
+                ("192.0.2.0", 25),
+                ("192.0.2.77", 32),
+                ("192.0.3.0", 24),
+            ])),
+            v4(&[("192.0.2.0", 23)]),
+        );
+
+        // joining cascades, and host bits below the prefix are ignored
+        assert_eq!(
+            aggregate_v4(&v4(&[
+                ("10.0.0.0", 32),
+                ("10.0.0.1", 32),
+                ("10.0.0.2", 31),
+                ("10.0.0.7", 30),
+            ])),
+            v4(&[("10.0.0.0", 29)]),
+        );
+
+        // neighbours that aren't halves of the same network stay apart
+        assert_eq!(
+            aggregate_v4(&v4(&[("192.0.2.0", 24), ("192.0.1.0", 24)])),
+            v4(&[("192.0.1.0", 24), ("192.0.2.0", 24)]),
+        );
+
+        assert_eq!(
+            aggregate_v4(&v4(&[("0.0.0.0", 1), ("128.0.0.0", 1)])),
+            v4(&[("0.0.0.0", 0)]),
+        );
+    }
+
+    #[test]
+    fn aggregate_networks_v6() {
+        assert_eq!(
+            aggregate_v6(&v6(&[
+                ("2001:db8::", 33),
+                ("2001:db8:8000::", 33),
+                ("2001:db8:1234::1", 128),
+            ])),
+            v6(&[("2001:db8::", 32)]),
+        );
+        assert_eq!(
+            aggregate_v6(&v6(&[("2001:db8::", 128), ("2001:db8::2", 128)])),
+            v6(&[("2001:db8::", 128), ("2001:db8::2", 128)]),
+        );
+    }
+
+    #[test]
+    fn parse_terms() {
+        assert!(is_spf("v=spf1 -all"));
+        assert!(is_spf("V=SPF1"));
+        assert!(!is_spf("v=spf10 -all"));
+
+        let terms = parse(
+            "v=spf1 ip4:192.0.2.0/24 a/24//64 -include:_spf.example.com redirect=example.net",
+        );
+        assert_eq!(terms.len(), 4);
+        assert_eq!(terms[1].dual_cidr(), Ok((24, 64)));
+        assert_eq!(terms[2].domain(), Some("_spf.example.com"));
+        assert_eq!(terms[2].to_string(), "-include:_spf.example.com");
+        assert_eq!(
+            terms[3],
+            Term::Modifier {
+                name: "redirect".into(),
+                value: "example.net".into(),
+            },
+        );
+        assert_eq!(terms.iter().filter(|term| term.looks_up()).count(), 3);
+    }
+
+    #[test]
+    fn split_long_records() {
+        let record = format!("v=spf1 {}-all", "ip4:192.0.2.1 ".repeat(30));
+        let strings = split_strings(&record);
+        assert!(strings.iter().all(|string| string.len() <= STRING_LIMIT));
+        assert_eq!(strings.concat(), record);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, net::SocketAddr, sync::Mutex, time::Duration};
+
+use hickory_proto::op::ResponseCode;
+use nu_protocol::{record, Span, Value};
+
+use super::serde::{util, RCode};
+
+/// Counters for everything sent since the plugin started, or since they were
+/// last reset.
+#[derive(Debug, Default)]
+pub struct Stats {
+    counters: Mutex<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    queries: u64,
+    responses: u64,
+    timeouts: u64,
+    errors: u64,
+    retries: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    rcodes: BTreeMap<u16, u64>,
+    // there will only ever be a handful of servers, so a linear scan is fine
+    servers: Vec<(SocketAddr, ServerCounters)>,
+}
+
+#[derive(Debug, Default)]
+struct ServerCounters {
+    queries: u64,
+    responses: u64,
+    timeouts: u64,
+    errors: u64,
+    rtt: Duration,
+}
+
+/// How an exchange with a server ended.
+pub enum Outcome {
+    Response {
+        rcode: ResponseCode,
+        size: usize,
+        rtt: Duration,
+    },
+    Timeout,
+    Error,
+}
+
+impl Counters {
+    fn server(&mut self, server: SocketAddr) -> &mut ServerCounters {
+        let idx = match self.servers.iter().position(|(addr, _)| *addr == server) {
+            Some(idx) => idx,
+            None => {
+                self.servers.push((server, ServerCounters::default()));
+                self.servers.len() - 1
+            }
+        };
+
+        &mut self.servers[idx].1
+    }
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts one query sent to a server, of the given size, and how it went.
+    pub fn record(&self, server: SocketAddr, sent: usize, outcome: Outcome) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.queries += 1;
+        counters.bytes_sent += sent as u64;
+
+        match outcome {
+            Outcome::Response { rcode, size, rtt } => {
+                counters.responses += 1;
+                counters.bytes_received += size as u64;
+                *counters.rcodes.entry(rcode.into()).or_default() += 1;
+
+                let server = counters.server(server);
+                server.queries += 1;
+                server.responses += 1;
+                server.rtt += rtt;
+            }
+            Outcome::Timeout => {
+                counters.timeouts += 1;
+
+                let server = counters.server(server);
+                server.queries += 1;
+                server.timeouts += 1;
+            }
+            Outcome::Error => {
+                counters.errors += 1;
+
+                let server = counters.server(server);
+                server.queries += 1;
+                server.errors += 1;
+            }
+        }
+    }
+
+    /// Counts a query that had to be asked again, e.g. over TCP after a
+    /// truncated UDP response.
+    pub fn record_retry(&self) {
+        self.counters.lock().unwrap().retries += 1;
+    }
+
+    pub fn reset(&self) {
+        *self.counters.lock().unwrap() = Counters::default();
+    }
+
+    pub fn to_value(&self, span: Span) -> Value {
+        let counters = self.counters.lock().unwrap();
+        let count = |n: u64| Value::int(n as i64, Span::unknown());
+
+        let rcodes = counters
+            .rcodes
+            .iter()
+            .map(|(rcode, n)| {
+                (
+                    RCode(<ResponseCode as From<u16>>::from(*rcode)).mnemonic(),
+                    count(*n),
+                )
+            })
+            .collect();
+
+        let servers = counters
+            .servers
+            .iter()
+            .map(|(addr, server)| {
+                let avg_rtt = match server.responses {
+                    0 => Value::nothing(Span::unknown()),
+                    n => util::std_to_duration(server.rtt / n as u32),
+                };
+
+                Value::record(
+                    record![
+                        "server"    => Value::string(addr.to_string(), Span::unknown()),
+                        "queries"   => count(server.queries),
+                        "responses" => count(server.responses),
+                        "timeouts"  => count(server.timeouts),
+                        "errors"    => count(server.errors),
+                        "avg_rtt"   => avg_rtt,
+                    ],
+                    Span::unknown(),
+                )
+            })
+            .collect();
+
+        Value::record(
+            record![
+                "queries"        => count(counters.queries),
+                "responses"      => count(counters.responses),
+                "timeouts"       => count(counters.timeouts),
+                "errors"         => count(counters.errors),
+                "retries"        => count(counters.retries),
+                "bytes_sent"     => Value::filesize(counters.bytes_sent as i64, Span::unknown()),
+                "bytes_received" => Value::filesize(counters.bytes_received as i64, Span::unknown()),
+                "rcodes"         => Value::record(rcodes, Span::unknown()),
+                "servers"        => Value::list(servers, Span::unknown()),
+            ],
+            span,
+        )
+    }
+}
@@ -0,0 +1,83 @@
+//! What carries messages to the nameserver. Normally that is one of hickory's
+//! network clients, but any [`Transport`] can be put in its place, such as
+//! [`MockTransport`] for testing without a network.
+
+use std::{future::Future, net::SocketAddr, pin::Pin, sync::Mutex};
+
+use hickory_proto::{
+    error::{ProtoError, ProtoErrorKind},
+    op::Message,
+    rr::{Name, RecordType},
+};
+
+pub type TransportResponse = Pin<Box<dyn Future<Output = Result<Vec<u8>, ProtoError>> + Send>>;
+
+/// Sends requests to nameservers and returns their responses, both in wire
+/// format.
+pub trait Transport: Send + Sync {
+    fn exchange(&self, server: SocketAddr, request: Vec<u8>) -> TransportResponse;
+}
+
+/// A transport that replays canned wire responses instead of talking to a
+/// nameserver. Queries nothing was canned for time out.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    responses: Mutex<Vec<(Name, RecordType, Vec<u8>)>>,
+    requests: Mutex<Vec<(SocketAddr, Vec<u8>)>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers queries for the name and type with the given response. The
+    /// response's ID is rewritten to match each request. Responding again to
+    /// the same question replaces the previous response.
+    pub fn respond(&self, name: Name, qtype: RecordType, response: Vec<u8>) {
+        let mut responses = self.responses.lock().unwrap();
+        responses.retain(|(n, t, _)| !(*n == name && *t == qtype));
+        responses.push((name, qtype, response));
+    }
+
+    /// Every request sent so far, with the server it was sent to, in the
+    /// order they were sent.
+    pub fn requests(&self) -> Vec<(SocketAddr, Vec<u8>)> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Transport for MockTransport {
+    fn exchange(&self, server: SocketAddr, request: Vec<u8>) -> TransportResponse {
+        self.requests
+            .lock()
+            .unwrap()
+            .push((server, request.clone()));
+
+        let response = Message::from_vec(&request).map(|message| {
+            let query = message.queries().first()?;
+
+            self.responses
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(name, qtype, _)| name == query.name() && *qtype == query.query_type())
+                .map(|(_, _, response)| {
+                    let mut response = response.clone();
+
+                    if response.len() >= 2 {
+                        response[..2].copy_from_slice(&message.id().to_be_bytes());
+                    }
+
+                    response
+                })
+        });
+
+        Box::pin(async move {
+            match response? {
+                Some(response) => Ok(response),
+                None => Err(ProtoErrorKind::Timeout.into()),
+            }
+        })
+    }
+}
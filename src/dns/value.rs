@@ -0,0 +1,152 @@
+//! A DNS name that keeps being one as it flows through a pipeline, rather
+//! than becoming a string that compares case-sensitively and has to be parsed
+//! again to get at its parts.
+
+use std::cmp::Ordering;
+
+use hickory_resolver::Name;
+use nu_protocol::{
+    ast::{Comparison, Operator},
+    CustomValue, ShellError, Span, Value,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{serde::util, special};
+
+/// A name kept as its raw labels, so that nothing about it is lost in
+/// transit between the plugin and the engine.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsName {
+    labels: Vec<Vec<u8>>,
+    fqdn: bool,
+}
+
+impl DnsName {
+    pub fn new(name: &Name) -> Self {
+        Self {
+            labels: name.iter().map(<[u8]>::to_vec).collect(),
+            fqdn: name.is_fqdn(),
+        }
+    }
+
+    pub fn into_value(self, span: Span) -> Value {
+        Value::custom(Box::new(self), span)
+    }
+
+    pub fn name(&self) -> Name {
+        let mut name =
+            Name::from_labels(self.labels.clone()).expect("labels came from a valid name");
+        name.set_fqdn(self.fqdn);
+        name
+    }
+
+    /// The name a value stands for, whether it's a [`DnsName`] or a string.
+    fn from_value(value: &Value) -> Option<Name> {
+        match value {
+            Value::Custom { val, .. } => val.as_any().downcast_ref::<DnsName>().map(DnsName::name),
+            Value::String { val, .. } => util::parse_name(val).ok(),
+            _ => None,
+        }
+    }
+}
+
+#[typetag::serde]
+impl CustomValue for DnsName {
+    fn clone_value(&self, span: Span) -> Value {
+        self.clone().into_value(span)
+    }
+
+    fn type_name(&self) -> String {
+        "DnsName".into()
+    }
+
+    fn to_base_value(&self, span: Span) -> Result<Value, ShellError> {
+        Ok(Value::string(
+            util::escape_name(&self.name(), self.fqdn),
+            span,
+        ))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn follow_path_string(
+        &self,
+        self_span: Span,
+        column_name: String,
+        path_span: Span,
+    ) -> Result<Value, ShellError> {
+        let name = self.name();
+
+        Ok(match column_name.as_str() {
+            "name" => self.to_base_value(path_span)?,
+            "ascii" => Value::string(name.to_ascii(), path_span),
+            "unicode" => Value::string(name.to_utf8(), path_span),
+            "parent" => match name.is_root() {
+                true => Value::nothing(path_span),
+                false => DnsName::new(&name.base_name()).into_value(path_span),
+            },
+            "labels" => Value::list(
+                self.labels
+                    .iter()
+                    .map(|label| Value::string(util::escape_bytes(label, true), path_span))
+                    .collect(),
+                path_span,
+            ),
+            "fqdn" => Value::bool(self.fqdn, path_span),
+            "depth" => Value::int(self.labels.len() as i64, path_span),
+            "special_use" => special::special_use(&name)
+                .map_or(Value::nothing(path_span), |special| {
+                    special.to_value(path_span)
+                }),
+            _ => {
+                return Err(ShellError::CantFindColumn {
+                    col_name: column_name,
+                    span: Some(path_span),
+                    src_span: self_span,
+                })
+            }
+        })
+    }
+
+    /// Orders names canonically (RFC 4034 section 6.1): by their labels from
+    /// the right, ignoring case.
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        DnsName::from_value(other).map(|other| self.name().cmp(&other))
+    }
+
+    /// Names are equal regardless of case or a trailing dot, and end with any
+    /// name they are under, as in `$name ends-with example.com`.
+    fn operation(
+        &self,
+        lhs_span: Span,
+        operator: Operator,
+        op: Span,
+        right: &Value,
+    ) -> Result<Value, ShellError> {
+        let Some(other) = DnsName::from_value(right) else {
+            return Err(ShellError::OperatorMismatch {
+                op_span: op,
+                lhs_ty: self.type_name(),
+                lhs_span,
+                rhs_ty: right.get_type().to_string(),
+                rhs_span: right.span(),
+            });
+        };
+
+        let name = self.name();
+        let result = match operator {
+            Operator::Comparison(Comparison::Equal) => name == other,
+            Operator::Comparison(Comparison::NotEqual) => name != other,
+            Operator::Comparison(Comparison::EndsWith) => other.zone_of(&name),
+            _ => return Err(ShellError::UnsupportedOperator { operator, span: op }),
+        };
+
+        Ok(Value::bool(result, op))
+    }
+}
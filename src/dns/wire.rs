@@ -0,0 +1,480 @@
+//! Where things are in a DNS message on the wire, which the parsed message
+//! doesn't keep: the offset of each name and record, and which names were
+//! compressed with pointers to earlier ones (RFC 1035 §4.1.4). Buggy
+//! middleboxes tend to get compression wrong, so this helps tell whose fault
+//! a garbled response is.
+
+use hickory_proto::{
+    error::ProtoError,
+    op::{Edns, Header, Message, Query},
+    rr::{rdata::NULL, DNSClass, Name, RData, Record, RecordType},
+    serialize::binary::{BinDecodable, BinDecoder},
+};
+use nu_protocol::{record, LabeledError, Span, Value};
+
+use super::{config::Config, serde};
+
+const HEADER_LEN: usize = 12;
+
+/// Follows at most this many pointers in one name, so a loop of pointers
+/// can't hang the decoder.
+const MAX_POINTERS: usize = 128;
+
+/// Where a name is, and how it was encoded.
+#[derive(Debug)]
+pub struct NameLayout {
+    pub offset: usize,
+    /// Bytes the name takes up where it is, up to and including the first
+    /// pointer, if any.
+    pub length: usize,
+    /// Offsets that pointers in the name pointed to, in the order they were
+    /// followed.
+    pub pointers: Vec<usize>,
+}
+
+/// Where a record is, and the names in it.
+#[derive(Debug)]
+pub struct RecordLayout {
+    pub offset: usize,
+    pub length: usize,
+    pub rtype: RecordType,
+    pub name: NameLayout,
+    pub rdata_offset: usize,
+    pub rdata_length: usize,
+    pub rdata_names: Vec<NameLayout>,
+}
+
+/// Where a question is.
+#[derive(Debug)]
+pub struct QueryLayout {
+    pub offset: usize,
+    pub name: NameLayout,
+}
+
+#[derive(Debug)]
+pub struct Layout {
+    pub queries: Vec<QueryLayout>,
+    pub answers: Vec<RecordLayout>,
+    pub name_servers: Vec<RecordLayout>,
+    /// Records of the additional section, leaving out the OPT, SIG, and TSIG
+    /// records that the parsed message keeps apart from it, so that these
+    /// line up with the parsed additional records.
+    pub additionals: Vec<RecordLayout>,
+}
+
+impl Layout {
+    /// Walks a message to find where everything is.
+    pub fn read(msg: &[u8]) -> Result<Self, String> {
+        if msg.len() < HEADER_LEN {
+            return Err(format!(
+                "message is {} bytes, shorter than a header",
+                msg.len()
+            ));
+        }
+
+        let count = |idx: usize| u16::from_be_bytes([msg[idx], msg[idx + 1]]);
+        let mut pos = HEADER_LEN;
+
+        let mut queries = Vec::new();
+        for _ in 0..count(4) {
+            let name = read_name(msg, pos)?;
+            let offset = pos;
+            pos = end_of(msg, offset + name.length, 4)?;
+            queries.push(QueryLayout { offset, name });
+        }
+
+        let mut read_section = |records: u16| {
+            (0..records)
+                .map(|_| {
+                    let record = read_record(msg, pos)?;
+                    pos += record.length;
+                    Ok(record)
+                })
+                .collect::<Result<Vec<_>, String>>()
+        };
+
+        let answers = read_section(count(6))?;
+        let name_servers = read_section(count(8))?;
+        let additionals = read_section(count(10))?
+            .into_iter()
+            .filter(|record| {
+                !matches!(
+                    record.rtype,
+                    RecordType::OPT | RecordType::SIG | RecordType::TSIG
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            queries,
+            answers,
+            name_servers,
+            additionals,
+        })
+    }
+}
+
+/// Checks that `len` more bytes are there after `pos`, and returns where
+/// they end.
+fn end_of(msg: &[u8], pos: usize, len: usize) -> Result<usize, String> {
+    match pos + len <= msg.len() {
+        true => Ok(pos + len),
+        false => Err(format!("message ends early, at byte {}", msg.len())),
+    }
+}
+
+fn read_name(msg: &[u8], offset: usize) -> Result<NameLayout, String> {
+    let mut pos = offset;
+    let mut pointers = Vec::new();
+    let mut length = None;
+
+    loop {
+        let len = *msg
+            .get(pos)
+            .ok_or_else(|| format!("name at byte {offset} runs past the end of the message"))?;
+
+        match len & 0xc0 {
+            0xc0 => {
+                let low = *msg.get(pos + 1).ok_or_else(|| {
+                    format!("name at byte {offset} runs past the end of the message")
+                })?;
+                let target = usize::from(u16::from_be_bytes([len & 0x3f, low]));
+
+                length.get_or_insert_with(|| pos + 2 - offset);
+                pointers.push(target);
+
+                if pointers.len() > MAX_POINTERS {
+                    return Err(format!("pointers in name at byte {offset} loop"));
+                }
+
+                pos = target;
+            }
+            0x00 if len == 0 => {
+                length.get_or_insert_with(|| pos + 1 - offset);
+                break;
+            }
+            0x00 => pos += 1 + usize::from(len),
+            _ => {
+                return Err(format!(
+                    "name at byte {offset} has a label of unsupported type {:#04x}",
+                    len & 0xc0
+                ))
+            }
+        }
+    }
+
+    Ok(NameLayout {
+        offset,
+        length: length.unwrap_or_default(),
+        pointers,
+    })
+}
+
+fn read_record(msg: &[u8], offset: usize) -> Result<RecordLayout, String> {
+    let name = read_name(msg, offset)?;
+    let fixed = offset + name.length;
+    let rdata_offset = end_of(msg, fixed, 10)?;
+    let rtype = RecordType::from(u16::from_be_bytes([msg[fixed], msg[fixed + 1]]));
+    let rdata_length = usize::from(u16::from_be_bytes([msg[fixed + 8], msg[fixed + 9]]));
+    let end = end_of(msg, rdata_offset, rdata_length)?;
+
+    // the types whose rdata starts with, or is, names that may be compressed,
+    // and SRV, whose target mustn't be but sometimes is anyway
+    let rdata_names = match rtype {
+        RecordType::NS | RecordType::CNAME | RecordType::PTR => {
+            vec![read_name(msg, rdata_offset)?]
+        }
+        RecordType::MX => vec![read_name(msg, rdata_offset + 2)?],
+        RecordType::SRV => vec![read_name(msg, rdata_offset + 6)?],
+        RecordType::SOA => {
+            let mname = read_name(msg, rdata_offset)?;
+            let rname = read_name(msg, rdata_offset + mname.length)?;
+            vec![mname, rname]
+        }
+        _ => Vec::new(),
+    };
+
+    Ok(RecordLayout {
+        offset,
+        length: end - offset,
+        rtype,
+        name,
+        rdata_offset,
+        rdata_length,
+        rdata_names,
+    })
+}
+
+impl NameLayout {
+    pub fn into_value(self) -> Value {
+        Value::record(
+            record![
+                "offset"     => Value::int(self.offset as i64, Span::unknown()),
+                "length"     => Value::int(self.length as i64, Span::unknown()),
+                "compressed" => Value::bool(!self.pointers.is_empty(), Span::unknown()),
+                "pointers"   => Value::list(
+                    self.pointers
+                        .into_iter()
+                        .map(|pointer| Value::int(pointer as i64, Span::unknown()))
+                        .collect(),
+                    Span::unknown(),
+                ),
+            ],
+            Span::unknown(),
+        )
+    }
+}
+
+impl QueryLayout {
+    pub fn into_value(self) -> Value {
+        Value::record(
+            record![
+                "offset" => Value::int(self.offset as i64, Span::unknown()),
+                "name"   => self.name.into_value(),
+            ],
+            Span::unknown(),
+        )
+    }
+}
+
+impl RecordLayout {
+    pub fn into_value(self) -> Value {
+        Value::record(
+            record![
+                "offset"       => Value::int(self.offset as i64, Span::unknown()),
+                "length"       => Value::int(self.length as i64, Span::unknown()),
+                "name"         => self.name.into_value(),
+                "rdata_offset" => Value::int(self.rdata_offset as i64, Span::unknown()),
+                "rdata_length" => Value::int(self.rdata_length as i64, Span::unknown()),
+                "rdata_names"  => Value::list(
+                    self.rdata_names
+                        .into_iter()
+                        .map(NameLayout::into_value)
+                        .collect(),
+                    Span::unknown(),
+                ),
+            ],
+            Span::unknown(),
+        )
+    }
+}
+
+/// Something in a message that couldn't be parsed, found while salvaging
+/// what could be.
+#[derive(Debug)]
+pub struct Problem {
+    /// The message column of the section it is in.
+    pub section: &'static str,
+    /// Where in the section it is, counting records that were parsed.
+    pub index: usize,
+    pub error: String,
+    /// The record, if its name, type, class, and TTL could be read, with its
+    /// rdata kept as is.
+    pub record: Option<Record>,
+}
+
+/// A message parsed as far as possible, with what couldn't be parsed.
+#[derive(Debug)]
+pub struct Salvaged {
+    pub message: Message,
+    pub problems: Vec<Problem>,
+}
+
+/// Parses as much of a malformed message as possible. Records whose rdata
+/// doesn't parse are kept with their rdata as unknown bytes, and parsing
+/// stops at the first thing that can't be skipped over, like a broken name
+/// or the message ending before the header says it should. Only a message
+/// too short for a header can't be salvaged at all.
+pub fn salvage(msg: &[u8]) -> Result<Salvaged, String> {
+    let mut decoder = BinDecoder::new(msg);
+    let header = Header::read(&mut decoder).map_err(|err| format!("invalid header: {err}"))?;
+
+    let mut message = Message::new();
+    message.set_header(header);
+    let mut problems = Vec::new();
+
+    for index in 0..usize::from(header.query_count()) {
+        let offset = decoder.index();
+
+        match Query::read(&mut decoder) {
+            Ok(query) => {
+                message.add_query(query);
+            }
+            Err(err) => {
+                problems.push(Problem {
+                    section: "question",
+                    index,
+                    error: format!("at byte {offset}: {err}"),
+                    record: None,
+                });
+
+                return Ok(Salvaged { message, problems });
+            }
+        }
+    }
+
+    for (section, count) in [
+        ("answer", header.answer_count()),
+        ("authority", header.name_server_count()),
+        ("additional", header.additional_count()),
+    ] {
+        for index in 0..usize::from(count) {
+            let offset = decoder.index();
+
+            if decoder.is_empty() {
+                problems.push(Problem {
+                    section,
+                    index,
+                    error: format!(
+                        "the header says there are {count} {section} records, but the message ends after {index}"
+                    ),
+                    record: None,
+                });
+
+                return Ok(Salvaged { message, problems });
+            }
+
+            let record = match Record::read(&mut decoder) {
+                Ok(record) => record,
+                Err(err) => {
+                    let (record, end) = match read_unparsed(msg, offset) {
+                        Ok(unparsed) => unparsed,
+                        Err(cause) => {
+                            problems.push(Problem {
+                                section,
+                                index,
+                                error: format!("at byte {offset}: {cause}"),
+                                record: None,
+                            });
+
+                            return Ok(Salvaged { message, problems });
+                        }
+                    };
+
+                    problems.push(Problem {
+                        section,
+                        index,
+                        error: format!("at byte {offset}: {err}"),
+                        record: Some(record),
+                    });
+
+                    match end <= msg.len() {
+                        true => decoder = decoder.clone(end as u16),
+                        false => return Ok(Salvaged { message, problems }),
+                    }
+
+                    continue;
+                }
+            };
+
+            match section {
+                "answer" => message.add_answer(record),
+                "authority" => message.add_name_server(record),
+                _ if record.record_type() == RecordType::OPT => {
+                    message.set_edns(Edns::from(&record))
+                }
+                _ => message.add_additional(record),
+            };
+        }
+    }
+
+    Ok(Salvaged { message, problems })
+}
+
+/// Parses a response, salvaging what it can if it is malformed and
+/// `lenient` is set.
+pub fn parse_response(msg: &[u8], lenient: bool) -> Result<Message, ProtoError> {
+    match Message::from_vec(msg) {
+        Err(_) if lenient => salvage(msg)
+            .map(|salvaged| salvaged.message)
+            .map_err(ProtoError::from),
+        parsed => parsed,
+    }
+}
+
+/// What is wrong with a message, if it doesn't parse as is.
+pub fn problems(msg: &[u8]) -> Vec<Problem> {
+    match Message::from_vec(msg) {
+        Ok(_) => Vec::new(),
+        Err(_) => salvage(msg)
+            .map(|salvaged| salvaged.problems)
+            .unwrap_or_default(),
+    }
+}
+
+/// Reads a record whose rdata doesn't parse, keeping its rdata as unknown
+/// bytes, and returns where it ends. The rdata is cut short if the message
+/// is.
+fn read_unparsed(msg: &[u8], offset: usize) -> Result<(Record, usize), String> {
+    let mut decoder = BinDecoder::new(msg).clone(offset as u16);
+    let read_err = |err| format!("{err}");
+
+    let name = Name::read(&mut decoder).map_err(|err| format!("invalid name: {err}"))?;
+    let rtype = RecordType::from(decoder.read_u16().map_err(read_err)?.unverified());
+    let class = DNSClass::from(decoder.read_u16().map_err(read_err)?.unverified());
+    let ttl = decoder.read_u32().map_err(read_err)?.unverified();
+    let len = usize::from(decoder.read_u16().map_err(read_err)?.unverified());
+
+    let start = decoder.index();
+    let end = start + len;
+    let rdata = msg[start..end.min(msg.len())].to_vec();
+
+    let mut record = Record::from_rdata(
+        name,
+        ttl,
+        RData::Unknown {
+            code: rtype,
+            rdata: NULL::with(rdata),
+        },
+    );
+    record.set_dns_class(class);
+
+    Ok((record, end))
+}
+
+impl Problem {
+    /// Shows the problem as a row of its section, with an error column: the
+    /// record as far as it could be read, with its rdata in the generic
+    /// format, or just the error if not even that.
+    pub fn into_value(self, config: &Config) -> Result<Value, LabeledError> {
+        let error = Value::string(self.error, Span::unknown());
+
+        match self.record {
+            Some(record) => {
+                let mut row = serde::Record(record).into_value(config)?.into_record()?;
+                row.push("error", error);
+                Ok(Value::record(row, Span::unknown()))
+            }
+            None => Ok(Value::record(record! { "error" => error }, Span::unknown())),
+        }
+    }
+}
+
+/// Puts the problems found while salvaging a message into its rows where
+/// they belong, so that what went wrong shows up next to what didn't.
+pub fn insert_problems(
+    message: Value,
+    problems: Vec<Problem>,
+    config: &Config,
+) -> Result<Value, LabeledError> {
+    let span = message.span();
+    let mut message = message.into_record()?;
+
+    for problem in problems {
+        let section = problem.section;
+        let index = problem.index;
+
+        match message.get_mut(section) {
+            Some(Value::List { vals, .. }) => {
+                vals.insert(index.min(vals.len()), problem.into_value(config)?);
+            }
+            Some(Value::Record { val, .. }) => {
+                val.to_mut()
+                    .push("error", Value::string(problem.error, Span::unknown()));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Value::record(message, span))
+}
@@ -0,0 +1,289 @@
+//! Zone digests (ZONEMD, [RFC 8976](https://www.rfc-editor.org/rfc/rfc8976)),
+//! which hickory has no record type for, so they are handled here from their
+//! raw RDATA.
+
+use hickory_proto::{
+    rr::{dnssec::DigestType, DNSClass, Name, RData, Record, RecordType},
+    serialize::{
+        binary::{BinEncodable, BinEncoder},
+        txt::Parser,
+    },
+};
+
+use super::serde::util;
+
+/// The ZONEMD record type.
+pub const RECORD_TYPE: RecordType = RecordType::Unknown(63);
+
+/// The only digest scheme defined so far, which hashes the whole zone in one
+/// go.
+pub const SCHEME_SIMPLE: u8 = 1;
+
+/// Digests shorter than this are not valid (RFC 8976 section 2.2.4).
+const MIN_DIGEST_LEN: usize = 12;
+
+/// The RDATA of a ZONEMD record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZoneMd {
+    pub serial: u32,
+    pub scheme: u8,
+    pub algorithm: u8,
+    pub digest: Vec<u8>,
+}
+
+impl ZoneMd {
+    pub fn from_wire(rdata: &[u8]) -> Result<Self, String> {
+        match rdata {
+            [s0, s1, s2, s3, scheme, algorithm, digest @ ..] if digest.len() >= MIN_DIGEST_LEN => {
+                Ok(Self {
+                    serial: u32::from_be_bytes([*s0, *s1, *s2, *s3]),
+                    scheme: *scheme,
+                    algorithm: *algorithm,
+                    digest: digest.to_vec(),
+                })
+            }
+            _ => Err(format!("ZONEMD RDATA is too short ({} bytes)", rdata.len())),
+        }
+    }
+
+    /// Parses the RDATA in presentation format, either as `serial scheme
+    /// algorithm digest`, with the digest in hex and possibly split up, or in
+    /// the generic `\# length hex` format of RFC 3597.
+    pub fn from_presentation(tokens: &[&str]) -> Result<Self, String> {
+        let hex = |tokens: &[&str]| {
+            data_encoding::HEXLOWER_PERMISSIVE
+                .decode(tokens.concat().as_bytes())
+                .map_err(|err| format!("invalid hex in ZONEMD record: {err}"))
+        };
+
+        match tokens {
+            ["\\#", _, data @ ..] => Self::from_wire(&hex(data)?),
+            [serial, scheme, algorithm, digest @ ..] if !digest.is_empty() => {
+                let field =
+                    |name: &str, token: &str| format!("invalid {name} in ZONEMD record: {token}");
+
+                Ok(Self {
+                    serial: serial.parse().map_err(|_| field("serial", serial))?,
+                    scheme: scheme.parse().map_err(|_| field("scheme", scheme))?,
+                    algorithm: algorithm
+                        .parse()
+                        .map_err(|_| field("hash algorithm", algorithm))?,
+                    digest: hex(digest)?,
+                })
+            }
+            _ => {
+                Err("ZONEMD record should have a serial, scheme, hash algorithm, and digest".into())
+            }
+        }
+    }
+
+    pub fn from_record(record: &Record) -> Option<Result<Self, String>> {
+        match record.data() {
+            Some(RData::Unknown { code, rdata }) if *code == RECORD_TYPE => {
+                Some(Self::from_wire(rdata.anything()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn scheme_name(&self) -> String {
+        match self.scheme {
+            SCHEME_SIMPLE => "SIMPLE".into(),
+            scheme => scheme.to_string(),
+        }
+    }
+
+    pub fn algorithm_name(&self) -> String {
+        match self.digest_type() {
+            Some(digest_type) => util::digest_type_name(digest_type).into(),
+            None => self.algorithm.to_string(),
+        }
+    }
+
+    /// The hash the digest is made with, if it is one that is defined.
+    pub fn digest_type(&self) -> Option<DigestType> {
+        match self.algorithm {
+            1 => Some(DigestType::SHA384),
+            2 => Some(DigestType::SHA512),
+            _ => None,
+        }
+    }
+}
+
+/// Computes the digest of a zone with the SIMPLE scheme (RFC 8976 section
+/// 3.3): every record in canonical form and canonical order, duplicates
+/// removed, leaving out the apex ZONEMD records and the signatures over them.
+pub fn simple_digest(apex: &Name, records: &[Record], digest_type: DigestType) -> Option<Vec<u8>> {
+    let covers_zonemd = |record: &Record| match record.data() {
+        Some(RData::DNSSEC(hickory_proto::rr::dnssec::rdata::DNSSECRData::RRSIG(sig))) => {
+            sig.type_covered() == RECORD_TYPE
+        }
+        _ => false,
+    };
+
+    let mut canonical: Vec<(Name, DNSClass, u16, Vec<u8>, u32)> = records
+        .iter()
+        .filter(|record| {
+            !(record.name() == apex
+                && (record.record_type() == RECORD_TYPE || covers_zonemd(record)))
+        })
+        .map(|record| {
+            let mut rdata = Vec::new();
+            let mut encoder = BinEncoder::new(&mut rdata);
+            encoder.set_canonical_names(true);
+
+            if let Some(data) = record.data() {
+                data.emit(&mut encoder).ok()?;
+            }
+
+            Some((
+                record.name().to_lowercase(),
+                record.dns_class(),
+                u16::from(record.record_type()),
+                rdata,
+                record.ttl(),
+            ))
+        })
+        .collect::<Option<_>>()?;
+
+    canonical
+        .sort_by(|a, b| (&a.0, u16::from(a.1), a.2, &a.3).cmp(&(&b.0, u16::from(b.1), b.2, &b.3)));
+    canonical.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1 && a.2 == b.2 && a.3 == b.3);
+
+    let mut wire = Vec::new();
+    let mut encoder = BinEncoder::new(&mut wire);
+    encoder.set_canonical_names(true);
+
+    for (name, class, rtype, rdata, ttl) in &canonical {
+        name.emit_as_canonical(&mut encoder, true).ok()?;
+        encoder.emit_u16(*rtype).ok()?;
+        class.emit(&mut encoder).ok()?;
+        encoder.emit_u32(*ttl).ok()?;
+        encoder.emit_u16(u16::try_from(rdata.len()).ok()?).ok()?;
+        encoder.emit_vec(rdata).ok()?;
+    }
+
+    let digest = digest_type.digest_all(&[&wire]).ok()?;
+    Some(digest.as_ref().to_vec())
+}
+
+/// What [`extract`] takes out of a zone file.
+pub struct Extracted {
+    /// The zone file without its ZONEMD records.
+    pub rest: String,
+    pub zonemds: Vec<ZoneMd>,
+    /// The TTL the SOA record was given, which hickory's parser replaces with
+    /// the SOA's expire time.
+    pub soa_ttl: Option<u32>,
+}
+
+/// Takes the ZONEMD records out of the text of a zone file, since hickory's
+/// zone file parser doesn't know the type. They are taken to be at the apex,
+/// which is the only place they mean anything.
+pub fn extract(contents: &str) -> Result<Extracted, String> {
+    let mut extracted = Extracted {
+        rest: String::with_capacity(contents.len()),
+        zonemds: Vec::new(),
+        soa_ttl: None,
+    };
+
+    let mut default_ttl = None;
+    let mut entry = String::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut depth = 0usize;
+
+    for line in contents.lines() {
+        entry.push_str(line);
+        entry.push('\n');
+
+        let mut code = String::with_capacity(line.len());
+        let mut quoted = false;
+        let mut escaped = false;
+
+        for ch in line.chars() {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = !quoted,
+                ';' if !quoted => break,
+                '(' if !quoted => {
+                    depth += 1;
+                    code.push(' ');
+                    continue;
+                }
+                ')' if !quoted => {
+                    depth = depth.saturating_sub(1);
+                    code.push(' ');
+                    continue;
+                }
+                _ => {}
+            }
+
+            code.push(ch);
+        }
+
+        tokens.extend(code.split_whitespace().map(String::from));
+
+        if depth > 0 {
+            continue;
+        }
+
+        // the type is the first field after the owner name, if there is one,
+        // and the optional TTL and class
+        let skip = usize::from(!entry.starts_with(char::is_whitespace));
+        let rtype = tokens
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .find(|(_, token)| !is_ttl(token) && !is_class(token));
+        let ttl = |idx: usize| {
+            tokens[skip.min(idx)..idx]
+                .iter()
+                .find(|token| is_ttl(token))
+                .and_then(|token| Parser::parse_time(token).ok())
+        };
+
+        match rtype {
+            _ if tokens
+                .first()
+                .is_some_and(|token| token.eq_ignore_ascii_case("$TTL")) =>
+            {
+                default_ttl = tokens
+                    .get(1)
+                    .and_then(|token| Parser::parse_time(token).ok());
+                extracted.rest.push_str(&entry);
+            }
+            Some((idx, rtype))
+                if rtype.eq_ignore_ascii_case("ZONEMD") || rtype.eq_ignore_ascii_case("TYPE63") =>
+            {
+                let rdata: Vec<&str> = tokens[idx + 1..].iter().map(String::as_str).collect();
+                extracted.zonemds.push(ZoneMd::from_presentation(&rdata)?);
+            }
+            Some((idx, rtype)) if rtype.eq_ignore_ascii_case("SOA") => {
+                extracted.soa_ttl = ttl(idx).or(default_ttl);
+                extracted.rest.push_str(&entry);
+            }
+            _ => extracted.rest.push_str(&entry),
+        }
+
+        entry.clear();
+        tokens.clear();
+    }
+
+    extracted.rest.push_str(&entry);
+
+    Ok(extracted)
+}
+
+fn is_ttl(token: &str) -> bool {
+    token.starts_with(|ch: char| ch.is_ascii_digit())
+}
+
+fn is_class(token: &str) -> bool {
+    ["IN", "CH", "HS", "CS", "NONE", "ANY"]
+        .iter()
+        .any(|class| token.eq_ignore_ascii_case(class))
+        || token
+            .get(..5)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("CLASS"))
+}
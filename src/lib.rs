@@ -1,3 +1,7 @@
 mod dns;
 
-pub use dns::Dns;
+pub use dns::{
+    error::DnsError,
+    transport::{MockTransport, Transport},
+    Dns,
+};
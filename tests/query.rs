@@ -0,0 +1,1284 @@
+use std::{str::FromStr, sync::Arc};
+
+use hickory_proto::{
+    op::{Edns, Message, MessageType, Query, ResponseCode},
+    rr::{
+        rdata::{
+            self,
+            opt::{EdnsCode, EdnsOption},
+        },
+        Name, RData, Record, RecordType,
+    },
+};
+use nu_plugin_dns::{Dns, MockTransport};
+use nu_protocol::{record, Value};
+
+/// A response for example.com. A with one address, exactly as it would come
+/// off the wire.
+const EXAMPLE_COM_A: &[u8] = &[
+    0x00, 0x00, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, // header
+    0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o', b'm', 0x00, // qname
+    0x00, 0x01, 0x00, 0x01, // qtype, qclass
+    0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, // name, type, class, ttl
+    0x00, 0x04, 0x5d, 0xb8, 0xd8, 0x22, // rdata
+];
+
+fn name(name: &str) -> Name {
+    Name::from_str(name).unwrap()
+}
+
+fn flags(extra: nu_protocol::Record) -> Value {
+    let mut flags = record! {
+        "server" => Value::test_string("192.0.2.53"),
+        "type"   => Value::test_string("A"),
+    };
+
+    for (col, val) in extra {
+        flags.insert(col, val);
+    }
+
+    Value::test_record(flags)
+}
+
+fn response(qname: &str, qtype: RecordType, rcode: ResponseCode, answers: Vec<Record>) -> Vec<u8> {
+    let mut message = Message::new();
+    message
+        .set_message_type(MessageType::Response)
+        .set_response_code(rcode)
+        .add_query(Query::query(name(qname), qtype))
+        .add_answers(answers);
+
+    message.to_vec().unwrap()
+}
+
+fn soa(zone: &str) -> Record {
+    Record::from_rdata(
+        name(zone),
+        3600,
+        RData::SOA(rdata::SOA::new(
+            name("ns.example.com."),
+            name("hostmaster.example.com."),
+            1,
+            7200,
+            3600,
+            1209600,
+            300,
+        )),
+    )
+}
+
+fn get<'v>(value: &'v Value, path: &[&str]) -> &'v Value {
+    path.iter().fold(value, |value, col| {
+        value
+            .as_record()
+            .unwrap()
+            .get(col)
+            .unwrap_or_else(|| panic!("no column {col}"))
+    })
+}
+
+fn only(value: &Value) -> &Value {
+    let list = value.as_list().unwrap();
+    assert_eq!(list.len(), 1, "expected exactly one value: {value:?}");
+    &list[0]
+}
+
+#[test]
+fn canned_wire_response() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    let resp = dns
+        .query(Value::test_string("example.com"), flags(record!()))
+        .unwrap();
+    let resp = only(&resp);
+
+    assert_eq!(get(resp, &["rcode", "name"]).as_str().unwrap(), "NOERROR");
+
+    let answer = only(get(resp, &["answer"]));
+    assert_eq!(get(answer, &["name"]).as_str().unwrap(), "example.com.");
+    assert_eq!(get(answer, &["type"]).as_str().unwrap(), "A");
+    assert_eq!(
+        get(answer, &["rdata", "ip"]).as_str().unwrap(),
+        "93.184.216.34"
+    );
+    assert_eq!(get(answer, &["rdata", "family"]).as_str().unwrap(), "ipv4");
+    assert_eq!(
+        get(answer, &["rdata", "reverse_name"]).as_str().unwrap(),
+        "34.216.184.93.in-addr.arpa."
+    );
+    assert!(get(answer, &["raw"]).is_nothing());
+    assert_eq!(
+        get(answer, &["ttl"]).as_duration().unwrap(),
+        3600 * 1_000_000_000
+    );
+}
+
+#[test]
+fn request_goes_to_configured_server() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    dns.query(Value::test_string("example.com"), flags(record!()))
+        .unwrap();
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+
+    let (server, request) = &requests[0];
+    assert_eq!(server.to_string(), "192.0.2.53:53");
+
+    let request = Message::from_vec(request).unwrap();
+    assert_eq!(request.message_type(), MessageType::Query);
+    assert!(request.recursion_desired());
+    assert_eq!(request.queries()[0].name(), &name("example.com."));
+    assert_eq!(request.queries()[0].query_type(), RecordType::A);
+}
+
+#[test]
+fn one_response_per_type() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+    mock.respond(
+        name("example.com."),
+        RecordType::AAAA,
+        response(
+            "example.com.",
+            RecordType::AAAA,
+            ResponseCode::NoError,
+            vec![],
+        ),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! {
+                "type" => Value::test_list(vec![Value::test_string("A"), Value::test_string("AAAA")]),
+            }),
+        )
+        .unwrap();
+
+    let types: Vec<_> = resp
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|resp| {
+            get(resp, &["question", "type"])
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+
+    assert_eq!(types.len(), 2);
+    assert!(types.contains(&"A".to_string()));
+    assert!(types.contains(&"AAAA".to_string()));
+}
+
+#[test]
+fn nxdomain_is_described() {
+    let mut message = Message::new();
+    message
+        .set_message_type(MessageType::Response)
+        .set_response_code(ResponseCode::NXDomain)
+        .add_query(Query::query(name("nope.example.com."), RecordType::A))
+        .add_name_server(soa("example.com."));
+
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("nope.example.com."),
+        RecordType::A,
+        message.to_vec().unwrap(),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(Value::test_string("nope.example.com"), flags(record!()))
+        .unwrap();
+    let resp = only(&resp);
+
+    assert_eq!(get(resp, &["rcode", "name"]).as_str().unwrap(), "NXDOMAIN");
+    assert_eq!(
+        get(resp, &["negative", "kind"]).as_str().unwrap(),
+        "NXDOMAIN"
+    );
+    assert_eq!(
+        get(resp, &["negative", "soa", "name"]).as_str().unwrap(),
+        "example.com."
+    );
+    assert_eq!(
+        get(resp, &["negative_ttl"]).as_duration().unwrap(),
+        300 * 1_000_000_000
+    );
+}
+
+#[test]
+fn fail_on_nxdomain() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("nope.example.com."),
+        RecordType::A,
+        response(
+            "nope.example.com.",
+            RecordType::A,
+            ResponseCode::NXDomain,
+            vec![],
+        ),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let err = dns
+        .query(
+            Value::test_string("nope.example.com"),
+            flags(record! { "fail-on-nxdomain" => Value::test_bool(true) }),
+        )
+        .unwrap_err();
+
+    assert_eq!(err.code.as_deref(), Some("dns::nxdomain"));
+}
+
+#[test]
+fn no_response_times_out() {
+    let dns = Dns::with_transport(Arc::new(MockTransport::new()));
+    let err = dns
+        .query(Value::test_string("example.com"), flags(record!()))
+        .unwrap_err();
+
+    assert_eq!(err.code.as_deref(), Some("dns::timeout"));
+}
+
+#[test]
+fn record_input_overrides_type() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        RecordType::MX,
+        response(
+            "example.com.",
+            RecordType::MX,
+            ResponseCode::NoError,
+            vec![Record::from_rdata(
+                name("example.com."),
+                300,
+                RData::MX(rdata::MX::new(10, name("mail.example.com."))),
+            )],
+        ),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_list(vec![Value::test_record(record! {
+                "name" => Value::test_string("example.com"),
+                "type" => Value::test_string("MX"),
+            })]),
+            flags(record!()),
+        )
+        .unwrap();
+
+    let answer = only(get(only(&resp), &["answer"]));
+    assert_eq!(get(answer, &["type"]).as_str().unwrap(), "MX");
+    assert_eq!(
+        get(answer, &["rdata", "exchange"]).as_str().unwrap(),
+        "mail.example.com."
+    );
+}
+
+#[test]
+fn attempts() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        RecordType::A,
+        response(
+            "example.com.",
+            RecordType::A,
+            ResponseCode::ServFail,
+            vec![],
+        ),
+    );
+
+    let dns = Dns::with_transport(mock.clone());
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "attempts" => Value::test_int(3) }),
+        )
+        .unwrap();
+
+    assert_eq!(
+        get(only(&resp), &["rcode", "name"]).as_str().unwrap(),
+        "SERVFAIL"
+    );
+    assert_eq!(mock.requests().len(), 3);
+}
+
+#[test]
+fn deadline() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "deadline" => Value::test_duration(0) }),
+        )
+        .unwrap();
+    let resp = only(&resp);
+
+    assert!(get(resp, &["timeout"]).as_bool().unwrap());
+    assert_eq!(
+        get(resp, &["question", "name"]).as_str().unwrap(),
+        "example.com."
+    );
+    assert!(get(resp, &["answer"]).as_list().unwrap().is_empty());
+    assert!(get(resp, &["rcode"]).is_nothing());
+    assert!(mock.requests().is_empty());
+}
+
+#[test]
+fn nodata_has_empty_sections() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        RecordType::A,
+        response("example.com.", RecordType::A, ResponseCode::NoError, vec![]),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(Value::test_string("example.com"), flags(record! {}))
+        .unwrap();
+    let resp = only(&resp);
+
+    for section in ["answer", "authority", "additional"] {
+        assert!(get(resp, &[section]).as_list().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn compact() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "compact" => Value::test_bool(true) }),
+        )
+        .unwrap();
+    let resp = only(&resp).as_record().unwrap();
+
+    assert!(resp.contains("answer"));
+    for col in ["authority", "additional", "edns", "negative"] {
+        assert!(!resp.contains(col), "{col} should be left out");
+    }
+}
+
+#[test]
+fn no_cache() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    let query = |flags: Value| {
+        let resp = dns.query(Value::test_string("example.com"), flags).unwrap();
+        get(only(&resp), &["cached"]).as_bool().unwrap()
+    };
+
+    assert!(!query(flags(record! { "cache" => Value::test_bool(true) })));
+    assert!(query(flags(record! { "cache" => Value::test_bool(true) })));
+    assert!(!query(flags(record! {
+        "cache"    => Value::test_bool(true),
+        "no-cache" => Value::test_bool(true),
+    })));
+    assert_eq!(mock.requests().len(), 2);
+}
+
+#[test]
+fn cache_keeps_dnssec_modes_apart() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    let query = |dnssec: &str| {
+        let resp = dns
+            .query(
+                Value::test_string("example.com"),
+                flags(record! {
+                    "cache"  => Value::test_bool(true),
+                    "dnssec" => Value::test_string(dnssec),
+                }),
+            )
+            .unwrap();
+        get(only(&resp), &["cached"]).as_bool().unwrap()
+    };
+
+    assert!(!query("none"));
+    assert!(query("none"));
+    assert!(!query("strict"));
+    assert!(query("strict"));
+    assert_eq!(mock.requests().len(), 2);
+}
+
+#[test]
+fn group_rrsets() {
+    let a = |owner: &str, ttl: u32, addr: [u8; 4]| {
+        Record::from_rdata(
+            name(owner),
+            ttl,
+            RData::A(rdata::A::from(std::net::Ipv4Addr::from(addr))),
+        )
+    };
+
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        RecordType::A,
+        response(
+            "example.com.",
+            RecordType::A,
+            ResponseCode::NoError,
+            vec![
+                a("example.com.", 300, [192, 0, 2, 1]),
+                a("www.example.com.", 300, [192, 0, 2, 3]),
+                a("EXAMPLE.com.", 60, [192, 0, 2, 2]),
+            ],
+        ),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "group-rrsets" => Value::test_bool(true) }),
+        )
+        .unwrap();
+
+    let answer = get(only(&resp), &["answer"]).as_list().unwrap();
+    assert_eq!(answer.len(), 2);
+
+    let apex = &answer[0];
+    assert_eq!(get(apex, &["name"]).as_str().unwrap(), "example.com.");
+    assert_eq!(get(apex, &["type"]).as_str().unwrap(), "A");
+    assert_eq!(
+        get(apex, &["ttl"]).as_duration().unwrap(),
+        60 * 1_000_000_000
+    );
+    let addrs: Vec<_> = get(apex, &["records"])
+        .as_list()
+        .unwrap()
+        .iter()
+        .map(|rdata| get(rdata, &["ip"]).as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(addrs, ["192.0.2.1", "192.0.2.2"]);
+
+    assert_eq!(get(&answer[1], &["records"]).as_list().unwrap().len(), 1);
+}
+
+#[test]
+fn group_rrsets_with_wire() {
+    let a = |ttl: u32, addr: [u8; 4]| {
+        Record::from_rdata(
+            name("example.com."),
+            ttl,
+            RData::A(rdata::A::from(std::net::Ipv4Addr::from(addr))),
+        )
+    };
+
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        RecordType::A,
+        response(
+            "example.com.",
+            RecordType::A,
+            ResponseCode::NoError,
+            vec![a(300, [192, 0, 2, 1]), a(60, [192, 0, 2, 2])],
+        ),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! {
+                "group-rrsets" => Value::test_bool(true),
+                "with-wire" => Value::test_bool(true),
+            }),
+        )
+        .unwrap();
+    let rrset = only(get(only(&resp), &["answer"]));
+
+    // the set as a whole has no single wire format
+    let cols = rrset.as_record().unwrap();
+    assert!(!cols.contains("wire") && !cols.contains("sha256"));
+
+    let records = get(rrset, &["records"]).as_list().unwrap();
+    assert_eq!(records.len(), 2);
+    for (record, (ttl, last)) in records.iter().zip([(300u32, 1u8), (60, 2)]) {
+        assert_eq!(
+            get(record, &["rdata", "ip"]).as_str().unwrap(),
+            format!("192.0.2.{last}")
+        );
+
+        let wire = get(record, &["wire"]).as_binary().unwrap();
+        assert!(wire.ends_with(&[&ttl.to_be_bytes()[..], &[0, 4, 192, 0, 2, last]].concat()));
+        assert_eq!(
+            get(record, &["sha256"]).as_str().unwrap(),
+            data_encoding::HEXLOWER.encode(
+                hickory_proto::rr::dnssec::DigestType::SHA256
+                    .hash(wire)
+                    .unwrap()
+                    .as_ref()
+            )
+        );
+    }
+}
+
+#[test]
+fn rcode_filter() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+    mock.respond(
+        name("nope.example.com."),
+        RecordType::A,
+        response(
+            "nope.example.com.",
+            RecordType::A,
+            ResponseCode::NXDomain,
+            vec![],
+        ),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_list(vec![
+                Value::test_string("example.com"),
+                Value::test_string("nope.example.com"),
+            ]),
+            flags(record! { "rcode-filter" => Value::test_string("NXDOMAIN") }),
+        )
+        .unwrap();
+
+    assert_eq!(
+        get(only(&resp), &["question", "name"]).as_str().unwrap(),
+        "nope.example.com."
+    );
+}
+
+#[test]
+fn edns_opt() {
+    let mut message = Message::new();
+    let mut edns = Edns::new();
+    edns.options_mut()
+        .insert(EdnsOption::Unknown(65001, vec![0xbe, 0xef]));
+    message
+        .set_message_type(MessageType::Response)
+        .add_query(Query::query(name("example.com."), RecordType::A))
+        .set_edns(edns);
+
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        RecordType::A,
+        message.to_vec().unwrap(),
+    );
+
+    let dns = Dns::with_transport(mock.clone());
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "edns-opt" => Value::test_string("65001:BEEF") }),
+        )
+        .unwrap();
+
+    let (_, request) = &mock.requests()[0];
+    let request = Message::from_vec(request).unwrap();
+    assert_eq!(
+        request
+            .extensions()
+            .as_ref()
+            .unwrap()
+            .options()
+            .get(EdnsCode::Unknown(65001)),
+        Some(&EdnsOption::Unknown(65001, vec![0xbe, 0xef]))
+    );
+
+    let opt = get(only(&resp), &["edns", "opts", "unknown(65001)"]);
+    assert_eq!(get(opt, &["code"]).as_int().unwrap(), 65001);
+    assert_eq!(get(opt, &["data"]).as_str().unwrap(), "beef");
+}
+
+#[test]
+fn edns_extended_error() {
+    let mut message = Message::new();
+    let mut edns = Edns::new();
+    edns.set_max_payload(1232).set_dnssec_ok(true);
+    let mut ede = vec![0x00, 0x12];
+    ede.extend_from_slice(b"denied by policy");
+    edns.options_mut().insert(EdnsOption::Unknown(15, ede));
+    message
+        .set_message_type(MessageType::Response)
+        .set_response_code(ResponseCode::Refused)
+        .add_query(Query::query(name("example.com."), RecordType::A))
+        .set_edns(edns);
+
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        RecordType::A,
+        message.to_vec().unwrap(),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(Value::test_string("example.com"), flags(record! {}))
+        .unwrap();
+
+    let edns = get(only(&resp), &["edns"]);
+    assert_eq!(
+        get(edns, &["max_payload"]).as_filesize().unwrap().get(),
+        1232
+    );
+    assert!(get(edns, &["dnssec_ok"]).as_bool().unwrap());
+
+    let ede = get(edns, &["opts", "EDE"]);
+    assert_eq!(get(ede, &["info_code"]).as_int().unwrap(), 18);
+    assert_eq!(get(ede, &["purpose"]).as_str().unwrap(), "Prohibited");
+    assert_eq!(
+        get(ede, &["extra_text"]).as_str().unwrap(),
+        "denied by policy"
+    );
+}
+
+#[test]
+fn dnssec_ok() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    dns.query(
+        Value::test_string("example.com"),
+        flags(record! { "dnssec-ok" => Value::test_bool(true) }),
+    )
+    .unwrap();
+
+    let (_, request) = &mock.requests()[0];
+    let request = Message::from_vec(request).unwrap();
+    assert!(request.extensions().as_ref().unwrap().dnssec_ok());
+
+    let err = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! {
+                "dnssec-ok" => Value::test_bool(true),
+                "dnssec" => Value::test_string("strict"),
+            }),
+        )
+        .unwrap_err();
+    assert_eq!(err.msg, "invalid config combination");
+}
+
+#[test]
+fn checking_disabled() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    dns.query(
+        Value::test_string("example.com"),
+        flags(record! { "cd" => Value::test_bool(true) }),
+    )
+    .unwrap();
+    dns.query(Value::test_string("example.com"), flags(record! {}))
+        .unwrap();
+
+    let requests = mock.requests();
+    assert!(Message::from_vec(&requests[0].1)
+        .unwrap()
+        .checking_disabled());
+    assert!(!Message::from_vec(&requests[1].1)
+        .unwrap()
+        .checking_disabled());
+}
+
+#[test]
+fn scoped_server() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    for server in ["fe80::1%1", "[fe80::1%1]:53"] {
+        dns.query(
+            Value::test_string("example.com"),
+            flags(record! { "server" => Value::test_string(server) }),
+        )
+        .unwrap();
+    }
+
+    for (server, _) in mock.requests() {
+        assert_eq!(server.to_string(), "[fe80::1%1]:53");
+    }
+
+    let err = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "server" => Value::test_string("192.0.2.53%1") }),
+        )
+        .unwrap_err();
+    assert_eq!(err.msg, "invalid server");
+}
+
+#[test]
+fn address_family() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock);
+    dns.query(
+        Value::test_string("example.com"),
+        flags(record! { "ipv4" => Value::test_bool(true) }),
+    )
+    .unwrap();
+
+    for flags in [
+        flags(record! { "ipv6" => Value::test_bool(true) }),
+        flags(record! {
+            "ipv4" => Value::test_bool(true),
+            "ipv6" => Value::test_bool(true),
+        }),
+    ] {
+        let err = dns
+            .query(Value::test_string("example.com"), flags)
+            .unwrap_err();
+        assert_eq!(err.msg, "invalid config combination");
+    }
+}
+
+#[test]
+fn server_host_name() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! {
+                "server" => Value::test_string("localhost:5353"),
+                "ipv4" => Value::test_bool(true),
+            }),
+        )
+        .unwrap();
+
+    assert_eq!(mock.requests()[0].0.to_string(), "127.0.0.1:5353");
+    assert_eq!(
+        get(only(&resp), &["server"]).as_str().unwrap(),
+        "127.0.0.1:5353"
+    );
+}
+
+#[test]
+fn minimal_any() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        RecordType::ANY,
+        response(
+            "example.com.",
+            RecordType::ANY,
+            ResponseCode::NoError,
+            vec![Record::from_rdata(
+                name("example.com."),
+                3600,
+                RData::HINFO(rdata::HINFO::new("RFC8482".into(), String::new())),
+            )],
+        ),
+    );
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "type" => Value::test_string("ANY") }),
+        )
+        .unwrap();
+    assert!(get(only(&resp), &["minimal_any"]).as_bool().unwrap());
+
+    let resp = dns
+        .query(Value::test_string("example.com"), flags(record!()))
+        .unwrap();
+    assert!(get(only(&resp), &["minimal_any"]).is_nothing());
+}
+
+#[test]
+fn unknown_type_generic_rdata() {
+    let rtype = RecordType::Unknown(65280);
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        rtype,
+        response(
+            "example.com.",
+            rtype,
+            ResponseCode::NoError,
+            vec![Record::from_rdata(
+                name("example.com."),
+                3600,
+                RData::Unknown {
+                    code: rtype,
+                    rdata: rdata::NULL::with(vec![0x0a, 0x00, 0x00, 0x01]),
+                },
+            )],
+        ),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "type" => Value::test_string("type65280") }),
+        )
+        .unwrap();
+
+    let answer = only(get(only(&resp), &["answer"]));
+    assert_eq!(get(answer, &["type"]).as_str().unwrap(), "TYPE65280");
+    assert_eq!(get(answer, &["rdata"]).as_str().unwrap(), "\\# 4 0A000001");
+}
+
+#[test]
+fn presentation_escaping() {
+    let owner = Name::from_labels(vec![&b"a.b"[..], b"example", b"com"])
+        .unwrap()
+        .append_domain(&Name::root())
+        .unwrap();
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("example.com."),
+        RecordType::TXT,
+        response(
+            "example.com.",
+            RecordType::TXT,
+            ResponseCode::NoError,
+            vec![Record::from_rdata(
+                owner,
+                3600,
+                RData::TXT(rdata::TXT::from_bytes(vec![b"say \"hi\"", &[0xff]])),
+            )],
+        ),
+    );
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "type" => Value::test_string("TXT") }),
+        )
+        .unwrap();
+
+    let answer = only(get(only(&resp), &["answer"]));
+    assert_eq!(
+        get(answer, &["name"]).as_str().unwrap(),
+        "a\\.b.example.com."
+    );
+
+    let txt = get(answer, &["rdata"]).as_list().unwrap();
+    assert_eq!(txt[0].as_str().unwrap(), "say \\\"hi\\\"");
+    assert_eq!(txt[1].as_str().unwrap(), "\\255");
+
+    let raw_name = get(answer, &["raw", "name"]).as_list().unwrap();
+    assert_eq!(raw_name[0].as_binary().unwrap(), b"a.b");
+}
+
+#[test]
+fn no_root_dot() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com."),
+            flags(record! { "no-root-dot" => Value::test_bool(true) }),
+        )
+        .unwrap();
+    let resp = only(&resp);
+
+    assert_eq!(
+        get(resp, &["question", "name"]).as_str().unwrap(),
+        "example.com"
+    );
+    assert_eq!(
+        get(only(get(resp, &["answer"])), &["name"])
+            .as_str()
+            .unwrap(),
+        "example.com"
+    );
+}
+
+#[test]
+fn lenient_salvages_malformed_response() {
+    let malformed = [
+        &[
+            0x00, 0x00, 0x81, 0x80, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+        ][..], // header, two answers
+        &EXAMPLE_COM_A[12..29], // question
+        &[0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10], // name, type, class, ttl
+        &[0x00, 0x03, 0x01, 0x02, 0x03], // three bytes of A rdata
+    ]
+    .concat();
+
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, malformed);
+
+    let dns = Dns::with_transport(mock);
+    assert!(dns
+        .query(Value::test_string("example.com"), flags(record!()))
+        .is_err());
+
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "lenient" => Value::test_bool(true) }),
+        )
+        .unwrap();
+
+    let answer = get(only(&resp), &["answer"]).as_list().unwrap();
+    assert_eq!(answer.len(), 2);
+    assert_eq!(get(&answer[0], &["type"]).as_str().unwrap(), "A");
+    assert_eq!(
+        get(&answer[0], &["rdata"]).as_str().unwrap(),
+        "\\# 3 010203"
+    );
+    assert!(get(&answer[0], &["error"]).as_str().is_ok());
+    assert!(get(&answer[1], &["error"])
+        .as_str()
+        .unwrap()
+        .contains("ends after 1"));
+}
+
+#[test]
+fn lenient_rejects_strict_dnssec() {
+    let dns = Dns::with_transport(Arc::new(MockTransport::new()));
+    let err = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! {
+                "lenient" => Value::test_bool(true),
+                "dnssec" => Value::test_string("strict"),
+            }),
+        )
+        .unwrap_err();
+    assert_eq!(err.msg, "invalid config combination");
+}
+
+#[test]
+fn lenient_rejects_encrypted_protocols() {
+    let dns = Dns::with_transport(Arc::new(MockTransport::new()));
+    for protocol in ["tls", "https", "quic"] {
+        let err = dns
+            .query(
+                Value::test_string("example.com"),
+                flags(record! {
+                    "lenient" => Value::test_bool(true),
+                    "protocol" => Value::test_string(protocol),
+                    "dns-name" => Value::test_string("dns.example"),
+                }),
+            )
+            .unwrap_err();
+        assert_eq!(err.msg, "invalid config combination", "{protocol}");
+    }
+}
+
+#[test]
+fn with_question() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "with-question" => Value::test_bool(true) }),
+        )
+        .unwrap();
+
+    let answer = only(get(only(&resp), &["answer"]));
+    assert_eq!(
+        get(answer, &["question", "name"]).as_str().unwrap(),
+        "example.com."
+    );
+    assert_eq!(get(answer, &["question", "type"]).as_str().unwrap(), "A");
+}
+
+#[test]
+fn log_file() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let path = std::env::temp_dir().join(format!("nu_plugin_dns-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let dns = Dns::with_transport(mock);
+    for _ in 0..2 {
+        dns.query(
+            Value::test_string("example.com"),
+            flags(record! { "log-file" => Value::test_string(path.to_string_lossy()) }),
+        )
+        .unwrap();
+    }
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let lines: Vec<_> = log.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with('{') && lines[0].ends_with('}'));
+    assert!(lines[0].contains(r#""server":"192.0.2.53:53""#));
+    assert!(lines[0].contains(r#""qname":"example.com.""#));
+    assert!(lines[0].contains(r#""qtype":"A""#));
+    assert!(lines[0].contains(r#""rcode":"NOERROR""#));
+    assert!(lines[0].contains("93.184.216.34"));
+    assert!(lines[0].contains(r#""error":null"#));
+}
+
+#[test]
+fn special_use_answered_locally() {
+    let mock = Arc::new(MockTransport::new());
+    let dns = Dns::with_transport(mock.clone());
+
+    let resp = dns
+        .query(Value::test_string("app.localhost."), flags(record!()))
+        .unwrap();
+    let resp = only(&resp);
+    assert_eq!(get(resp, &["rcode", "name"]).as_str().unwrap(), "NOERROR");
+    assert_eq!(
+        get(only(get(resp, &["answer"])), &["rdata", "ip"])
+            .as_str()
+            .unwrap(),
+        "127.0.0.1"
+    );
+    assert!(get(resp, &["server"]).is_nothing());
+
+    let resp = dns
+        .query(Value::test_string("nothing.invalid."), flags(record!()))
+        .unwrap();
+    assert_eq!(
+        get(only(&resp), &["rcode", "name"]).as_str().unwrap(),
+        "NXDOMAIN"
+    );
+
+    assert!(mock.requests().is_empty());
+}
+
+#[test]
+fn special_use_warning() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("printer.local."),
+        RecordType::A,
+        response(
+            "printer.local.",
+            RecordType::A,
+            ResponseCode::NXDomain,
+            vec![],
+        ),
+    );
+
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock.clone());
+    let resp = dns
+        .query(Value::test_string("printer.local."), flags(record!()))
+        .unwrap();
+    let warning = get(only(&resp), &["warning"]);
+    assert_eq!(get(warning, &["kind"]).as_str().unwrap(), "special-use");
+    assert_eq!(get(warning, &["zone"]).as_str().unwrap(), "local.");
+    assert_eq!(get(warning, &["handling"]).as_str().unwrap(), "mdns");
+
+    let resp = dns
+        .query(Value::test_string("example.com."), flags(record!()))
+        .unwrap();
+    assert!(get(only(&resp), &["warning"]).is_nothing());
+
+    let err = dns
+        .query(
+            Value::test_string("printer.local."),
+            flags(record! { "strict-special-use" => Value::test_bool(true) }),
+        )
+        .unwrap_err();
+    assert_eq!(err.code.as_deref(), Some("dns::special_use"));
+    assert_eq!(mock.requests().len(), 2);
+}
+
+#[test]
+fn errors_as_values() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(
+        name("broken.example.com."),
+        RecordType::A,
+        response(
+            "broken.example.com.",
+            RecordType::A,
+            ResponseCode::ServFail,
+            vec![],
+        ),
+    );
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_list(vec![
+                Value::test_string("example.com"),
+                Value::test_string("broken.example.com"),
+                Value::test_string("silent.example.com"),
+            ]),
+            flags(record! {
+                "errors-as-values" => Value::test_bool(true),
+                "attempts"         => Value::test_int(2),
+            }),
+        )
+        .unwrap();
+    let rows = resp.as_list().unwrap();
+    assert_eq!(rows.len(), 3);
+    let row = |qname: &str| {
+        rows.iter()
+            .find(|row| get(row, &["question", "name"]).as_str().unwrap() == qname)
+            .unwrap()
+    };
+
+    assert!(get(row("example.com."), &["error"]).is_nothing());
+
+    let servfail = get(row("broken.example.com."), &["error"]);
+    assert_eq!(get(servfail, &["kind"]).as_str().unwrap(), "servfail");
+    assert_eq!(get(servfail, &["attempts"]).as_int().unwrap(), 2);
+
+    let silent = row("silent.example.com.");
+    let timeout = get(silent, &["error"]);
+    assert_eq!(get(timeout, &["kind"]).as_str().unwrap(), "timeout");
+    assert_eq!(get(timeout, &["attempts"]).as_int().unwrap(), 2);
+    assert!(get(silent, &["rcode"]).is_nothing());
+}
+
+#[test]
+fn output_schema() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "output-schema" => Value::test_string("v1") }),
+        )
+        .unwrap();
+    let message = only(&resp);
+    let columns = |value: &Value| {
+        value
+            .as_record()
+            .unwrap()
+            .columns()
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        columns(message),
+        [
+            "header",
+            "question",
+            "answer",
+            "authority",
+            "additional",
+            "edns",
+            "size"
+        ]
+    );
+    assert_eq!(
+        columns(get(message, &["question"])),
+        ["name", "type", "class"]
+    );
+    assert!(get(message, &["authority"]).as_list().unwrap().is_empty());
+    assert!(get(message, &["edns"]).is_nothing());
+
+    let answer = only(get(message, &["answer"]));
+    assert_eq!(columns(answer), ["name", "type", "class", "ttl", "rdata"]);
+    assert_eq!(get(answer, &["rdata"]).as_str().unwrap(), "93.184.216.34");
+
+    let err = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "output-schema" => Value::test_string("v9") }),
+        )
+        .unwrap_err();
+    assert_eq!(err.msg, "invalid output schema");
+}
+
+#[test]
+fn scalar() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "scalar" => Value::test_bool(true) }),
+        )
+        .unwrap();
+    let resp = only(&resp);
+
+    for (col, val) in resp.as_record().unwrap().iter() {
+        assert!(
+            !matches!(val, Value::List { .. } | Value::Record { .. }),
+            "{col} is not a scalar"
+        );
+    }
+    assert_eq!(
+        get(resp, &["answer"]).as_str().unwrap(),
+        "example.com. 3600 IN A 93.184.216.34"
+    );
+    assert_eq!(get(resp, &["rcode.name"]).as_str().unwrap(), "NOERROR");
+}
+
+#[test]
+fn with_wire() {
+    let mock = Arc::new(MockTransport::new());
+    mock.respond(name("example.com."), RecordType::A, EXAMPLE_COM_A.to_vec());
+
+    let dns = Dns::with_transport(mock);
+    let resp = dns
+        .query(
+            Value::test_string("example.com"),
+            flags(record! { "with-wire" => Value::test_bool(true) }),
+        )
+        .unwrap();
+    let answer = only(get(only(&resp), &["answer"]));
+
+    // the owner name is written out in full, where the response compressed it
+    assert_eq!(
+        get(answer, &["wire"]).as_binary().unwrap(),
+        [
+            &[0x07][..],
+            b"example",
+            &[0x03],
+            b"com",
+            &[0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10],
+            &[0x00, 0x04, 0x5d, 0xb8, 0xd8, 0x22],
+        ]
+        .concat()
+    );
+    assert_eq!(
+        get(answer, &["sha256"]).as_str().unwrap(),
+        "2c6c7e68d890a45732d8f8152e0bdd447cbde026a5e8da2c691bdaeaa7e4930c"
+    );
+}